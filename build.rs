@@ -0,0 +1,81 @@
+//! Injects build metadata served by `GET /version`.
+//!
+//! - `WOS_GIT_COMMIT`: the `GIT_COMMIT` environment variable if set, else
+//!   `git rev-parse --short=12 HEAD`, else `unknown`.
+//! - `WOS_BUILD_UNIX_TIME`: `SOURCE_DATE_EPOCH` if set (reproducible builds),
+//!   else the time this script ran.
+//! - `WOS_WHISPER_RS_SYS_VERSION`: the locked `whisper-rs-sys` version, which
+//!   pins the bundled whisper.cpp sources.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let manifest_dir = Path::new(&manifest_dir);
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for watched in [".git/HEAD", ".git/index", "Cargo.lock"] {
+        if manifest_dir.join(watched).exists() {
+            println!("cargo:rerun-if-changed={watched}");
+        }
+    }
+
+    println!(
+        "cargo:rustc-env=WOS_GIT_COMMIT={}",
+        git_commit(manifest_dir)
+    );
+    println!("cargo:rustc-env=WOS_BUILD_UNIX_TIME={}", build_unix_time());
+    println!(
+        "cargo:rustc-env=WOS_WHISPER_RS_SYS_VERSION={}",
+        locked_version(&manifest_dir.join("Cargo.lock"), "whisper-rs-sys")
+            .unwrap_or_else(|| "unknown".to_string())
+    );
+}
+
+fn git_commit(manifest_dir: &Path) -> String {
+    if let Ok(commit) = std::env::var("GIT_COMMIT") {
+        if !commit.trim().is_empty() {
+            return commit.trim().to_string();
+        }
+    }
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .current_dir(manifest_dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn build_unix_time() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0)
+        })
+}
+
+/// Finds `package`'s version in a `Cargo.lock` without a TOML parser.
+fn locked_version(lock_path: &Path, package: &str) -> Option<String> {
+    let lock = std::fs::read_to_string(lock_path).ok()?;
+    let name_line = format!("name = \"{package}\"");
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == name_line {
+            let version = lines.next()?.trim().strip_prefix("version = \"")?;
+            return version.strip_suffix('"').map(ToOwned::to_owned);
+        }
+    }
+    None
+}
@@ -8,6 +8,8 @@ ACCELERATION="${WHISPER_ACCELERATION:-metal}"
 # Auto-detect platform for feature flags
 if [[ "$OSTYPE" == "darwin"* ]]; then
     FEATURES="--features metal"
+elif [[ "$OSTYPE" == "linux-gnu"* && "$ACCELERATION" == "rocm" ]]; then
+    FEATURES="--features rocm"
 elif [[ "$OSTYPE" == "linux-gnu"* ]]; then
     FEATURES="--features cuda"
 else
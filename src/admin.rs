@@ -0,0 +1,308 @@
+//! Administrative endpoints, enabled by setting `ADMIN_API_KEY`.
+//!
+//! `POST /admin/models/reload` replaces the default model without a restart.
+//! The body names either a model file (`{"model": "/models/ggml-medium.bin"}`)
+//! or a size (`{"size": "medium"}`), which is resolved and downloaded like
+//! `WHISPER_MODEL_SIZE` at startup. The new model is loaded (and self-tested
+//! when `STARTUP_SELF_TEST` is enabled) alongside the old one, then swapped in:
+//! requests already running finish on the old model, and it is freed once
+//! the last of them completes. A failed load leaves the old model in place.
+//! With `WHISPER_CPU_WORKERS` set, the CPU-only copy is reloaded as well.
+//!
+//! `POST /admin/purge` deletes stored job results, queued uploads, archived
+//! transcripts, and debug traces for an API key or time range (see `purge`).
+//!
+//! `GET /admin/traces/{trace_id}` returns the debug trace of a request sent
+//! with `debug_trace=true` (see `debug_trace`).
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::body::Bytes;
+use axum::extract::{Path as UrlPath, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::api::{bearer_token, ActiveModel, AppState};
+use crate::config::{AppConfig, WhisperModelSize};
+use crate::error::AppError;
+use crate::jobs::{new_id, unix_secs};
+use crate::model_registry::load_cpu_pool;
+use crate::model_store::DownloadProgress;
+use crate::purge::{open_audit_log, write_audit_entry, PurgeFilter};
+use crate::self_test;
+
+/// Body of `POST /admin/models/reload`.
+#[derive(Debug, Deserialize)]
+struct ReloadRequest {
+    /// Path of a model file to load.
+    model: Option<String>,
+    /// Model size to resolve and download, such as `medium` or `large-v3`.
+    size: Option<String>,
+}
+
+/// Loads a new default model and swaps it in (`POST /admin/models/reload`).
+pub async fn reload_model(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    require_admin(&state.cfg, &headers)?;
+    let req: ReloadRequest = serde_json::from_slice(&body).map_err(|err| {
+        AppError::invalid_request(format!("invalid JSON body: {err}"), None, None)
+    })?;
+    let cfg = reload_config(&state.cfg, req)?;
+
+    let _reloading = state
+        .model_reload
+        .try_lock()
+        .map_err(|_| AppError::conflict("a model reload is already in progress"))?;
+    let started = Instant::now();
+    let loader = state.model_loader;
+    let (cfg, backend, cpu_backend) = tokio::task::spawn_blocking(move || {
+        let backend = loader(cfg.clone(), &DownloadProgress::default())?;
+        let cpu_backend = load_cpu_pool(&cfg, loader)?;
+        Ok::<_, AppError>((cfg, backend, cpu_backend))
+    })
+    .await
+    .map_err(|err| AppError::internal(format!("model reload task failed: {err}")))??;
+    if state.cfg.startup_self_test {
+        self_test::run(backend.as_ref()).await?;
+    }
+
+    let mut model = ActiveModel::from_config(&cfg, backend);
+    model.cpu_backend = cpu_backend;
+    let previous = state.active_model();
+    state.swap_model(model.clone());
+    info!(
+        previous = %previous.path,
+        model = %model.path,
+        load_ms = started.elapsed().as_millis() as u64,
+        "default model reloaded"
+    );
+    Ok(Json(json!({
+        "status": "reloaded",
+        "model": model_json(&model),
+        "previous_model": model_json(&previous),
+        "load_ms": started.elapsed().as_millis() as u64,
+    })))
+}
+
+/// Body of `POST /admin/purge`.
+#[derive(Debug, Deserialize)]
+struct PurgeRequest {
+    #[serde(flatten)]
+    filter: PurgeFilter,
+    /// Count what would be deleted without deleting it.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Deletes stored data for an API key or time range (`POST /admin/purge`).
+///
+/// Every purge, including a dry run or one that fails part way, is recorded in
+/// the log and in `PURGE_AUDIT_LOG` when set.
+pub async fn purge_artifacts(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    require_admin(&state.cfg, &headers)?;
+    let req: PurgeRequest = serde_json::from_slice(&body).map_err(|err| {
+        AppError::invalid_request(format!("invalid JSON body: {err}"), None, None)
+    })?;
+    let (filter, dry_run) = (req.filter, req.dry_run);
+    if filter.is_empty() {
+        return Err(AppError::invalid_request(
+            "set at least one of api_key, from, or to",
+            None,
+            Some("invalid_purge_request"),
+        ));
+    }
+    if filter
+        .from
+        .zip(filter.to)
+        .is_some_and(|(from, to)| from >= to)
+    {
+        return Err(AppError::invalid_request(
+            "from must be earlier than to",
+            Some("from"),
+            Some("invalid_purge_request"),
+        ));
+    }
+    let mut audit_log = match state.cfg.purge_audit_log.as_deref() {
+        Some(path) => Some(open_audit_log(Path::new(path)).map_err(|err| {
+            AppError::internal(format!("failed to open PURGE_AUDIT_LOG {path:?}: {err}"))
+        })?),
+        None => None,
+    };
+
+    let job_ids = state.jobs.purge(&filter, dry_run);
+    let spooled_jobs = state
+        .job_spool
+        .as_ref()
+        .map_or(0, |spool| spool.purge(&job_ids, dry_run));
+    let (archived, traces) = {
+        let state = Arc::clone(&state);
+        let filter = filter.clone();
+        tokio::task::spawn_blocking(move || {
+            let archived = state
+                .archive
+                .as_ref()
+                .map_or(Ok(0), |archive| archive.purge(&filter, dry_run));
+            let traces = state
+                .debug_traces
+                .as_ref()
+                .map_or(Ok(0), |traces| traces.purge(&filter, dry_run));
+            (archived, traces)
+        })
+        .await
+        .map_err(|err| AppError::internal(format!("purge task failed: {err}")))?
+    };
+
+    let mut entry = json!({
+        "id": new_id("purge_"),
+        "object": "purge",
+        "created_at": unix_secs(),
+        "filter": filter,
+        "dry_run": dry_run,
+        "jobs": job_ids.len(),
+        "spooled_jobs": spooled_jobs,
+        "archived_transcripts": archived.as_ref().ok(),
+        "debug_traces": traces.as_ref().ok(),
+    });
+    let failure = match (&archived, &traces) {
+        (Err(err), _) => Some(format!("failed to purge TRANSCRIPT_ARCHIVE_DIR: {err}")),
+        (_, Err(err)) => Some(format!("failed to purge DEBUG_TRACE_DIR: {err}")),
+        _ => None,
+    };
+    if let Some(failure) = &failure {
+        entry["error"] = json!(failure);
+    }
+    info!(
+        id = %entry["id"].as_str().unwrap_or_default(),
+        api_key = ?filter.api_key,
+        from = ?filter.from,
+        to = ?filter.to,
+        dry_run,
+        jobs = job_ids.len(),
+        spooled_jobs,
+        archived_transcripts = ?archived.as_ref().ok(),
+        debug_traces = ?traces.as_ref().ok(),
+        "stored data purged"
+    );
+    if let Some(log) = &mut audit_log {
+        if let Err(err) = write_audit_entry(log, &entry) {
+            warn!(error = %err, "failed to write purge audit entry");
+            return Err(AppError::internal(format!(
+                "purge ran but its audit entry could not be written: {err}"
+            )));
+        }
+    }
+    if let Some(failure) = failure {
+        return Err(AppError::internal(failure));
+    }
+    Ok(Json(entry))
+}
+
+/// Returns a stored debug trace (`GET /admin/traces/{trace_id}`).
+pub async fn get_debug_trace(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    UrlPath(trace_id): UrlPath<String>,
+) -> Result<Json<Value>, AppError> {
+    require_admin(&state.cfg, &headers)?;
+    if state.debug_traces.is_none() {
+        return Err(AppError::not_found(
+            "debug traces are disabled; set DEBUG_TRACE_DIR to enable them",
+            None,
+        ));
+    }
+    let trace = {
+        let state = Arc::clone(&state);
+        let trace_id = trace_id.clone();
+        tokio::task::spawn_blocking(move || {
+            state
+                .debug_traces
+                .as_ref()
+                .map_or(Ok(None), |traces| traces.get(&trace_id))
+        })
+        .await
+        .map_err(|err| AppError::internal(format!("debug trace task failed: {err}")))?
+    };
+    match trace {
+        Ok(Some(trace)) => Ok(Json(trace)),
+        Ok(None) => Err(AppError::not_found(
+            format!("no debug trace with id {trace_id:?}"),
+            Some("trace_id"),
+        )),
+        Err(err) => Err(AppError::internal(format!(
+            "failed to read debug trace {trace_id:?}: {err}"
+        ))),
+    }
+}
+
+/// Rejects requests without the admin token; `404` when admin endpoints are disabled.
+fn require_admin(cfg: &AppConfig, headers: &HeaderMap) -> Result<(), AppError> {
+    let Some(admin_key) = cfg.admin_api_key.as_deref() else {
+        return Err(AppError::not_found(
+            "admin endpoints are disabled; set ADMIN_API_KEY to enable them",
+            None,
+        ));
+    };
+    if bearer_token(headers)? != admin_key {
+        return Err(AppError::unauthorized("invalid admin token"));
+    }
+    Ok(())
+}
+
+/// Derives the configuration for the requested model from the running one.
+fn reload_config(base: &AppConfig, req: ReloadRequest) -> Result<AppConfig, AppError> {
+    let mut cfg = base.clone();
+    match (req.model, req.size) {
+        (Some(model), None) => {
+            if !Path::new(&model).is_file() {
+                return Err(AppError::invalid_request(
+                    format!("model file {model:?} does not exist"),
+                    Some("model"),
+                    Some("invalid_model"),
+                ));
+            }
+            cfg.whisper_model = model;
+            cfg.whisper_model_explicit = true;
+        }
+        (None, Some(size)) => {
+            let size = WhisperModelSize::from_str(&size, true).map_err(|_| {
+                AppError::invalid_request(
+                    format!("unknown model size {size:?}"),
+                    Some("size"),
+                    Some("invalid_size"),
+                )
+            })?;
+            cfg.use_model_size(size);
+        }
+        _ => {
+            return Err(AppError::invalid_request(
+                "set exactly one of model or size",
+                None,
+                Some("invalid_reload_request"),
+            ))
+        }
+    }
+    Ok(cfg)
+}
+
+fn model_json(model: &ActiveModel) -> Value {
+    json!({
+        "path": model.path,
+        "size": model
+            .size
+            .and_then(|size| size.to_possible_value())
+            .map(|value| value.get_name().to_string()),
+    })
+}
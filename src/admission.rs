@@ -0,0 +1,258 @@
+//! Queue positions and start-time estimates for requests waiting for a worker.
+//!
+//! Each worker pool has a [`QueueTracker`]. A request that finds every worker
+//! busy takes a [`QueueTicket`] until it is admitted or gives up; tickets are
+//! numbered in arrival order, which is also the order the admission semaphore
+//! serves waiters in, so a ticket's position is the number of older tickets
+//! plus one. Start times are estimated from a moving average of recent
+//! inference times and are omitted until one has finished.
+//!
+//! Trackers also remember how long the queue has stayed at each length, which
+//! `/health/ready` uses to report sustained overload.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+
+/// Weight of the newest inference time in the moving average, out of 8.
+const AVERAGE_WEIGHT: u64 = 2;
+
+/// Tickets in the queue and when it last grew to each length.
+#[derive(Debug, Default)]
+struct Waiting {
+    tickets: BTreeSet<u64>,
+    /// `reached[n]` is when the queue grew to `n + 1` tickets; popped as it shrinks,
+    /// so the queue has held at least `n + 1` tickets ever since.
+    reached: Vec<Instant>,
+}
+
+/// Waiting requests of one worker pool.
+#[derive(Debug)]
+pub struct QueueTracker {
+    workers: usize,
+    next_ticket: AtomicU64,
+    waiting: Mutex<Waiting>,
+    /// Moving average of inference time in milliseconds; `0` until one finishes.
+    average_ms: AtomicU64,
+}
+
+impl QueueTracker {
+    /// Creates a tracker for a pool of `workers` inference workers.
+    pub fn new(workers: usize) -> Arc<Self> {
+        Arc::new(Self {
+            workers: workers.max(1),
+            next_ticket: AtomicU64::new(0),
+            waiting: Mutex::new(Waiting::default()),
+            average_ms: AtomicU64::new(0),
+        })
+    }
+
+    /// Enters the queue behind every request already waiting.
+    pub fn join(self: &Arc<Self>) -> QueueTicket {
+        let id = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut waiting) = self.waiting.lock() {
+            waiting.tickets.insert(id);
+            waiting.reached.push(Instant::now());
+        }
+        QueueTicket {
+            spot: QueueSpot {
+                tracker: Arc::clone(self),
+                id,
+            },
+        }
+    }
+
+    /// Number of requests currently waiting.
+    pub fn waiting(&self) -> usize {
+        self.waiting
+            .lock()
+            .map_or(0, |waiting| waiting.tickets.len())
+    }
+
+    /// How long at least `depth` requests have been waiting without a break;
+    /// `None` while fewer are waiting.
+    pub fn saturated_for(&self, depth: usize) -> Option<Duration> {
+        let waiting = self.waiting.lock().ok()?;
+        let reached = waiting.reached.get(depth.max(1) - 1)?;
+        Some(reached.elapsed())
+    }
+
+    /// Status a request arriving now would get.
+    pub fn next_status(&self) -> QueueStatus {
+        self.status_at(self.waiting() + 1)
+    }
+
+    /// Folds a finished inference into the start-time estimates.
+    pub fn record_inference(&self, elapsed: Duration) {
+        let sample = (elapsed.as_millis() as u64).max(1);
+        let _ = self
+            .average_ms
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                Some(match average {
+                    0 => sample,
+                    _ => (average * (8 - AVERAGE_WEIGHT) + sample * AVERAGE_WEIGHT) / 8,
+                })
+            });
+    }
+
+    /// Estimates when the request at 1-based `position` starts.
+    ///
+    /// Every worker is busy, so the first `workers` waiters start when a
+    /// running request finishes (half an inference away on average) and each
+    /// further round of `workers` waiters one inference later.
+    fn status_at(&self, position: usize) -> QueueStatus {
+        let average_ms = self.average_ms.load(Ordering::Relaxed);
+        let rounds = ((position - 1) / self.workers) as u64;
+        QueueStatus {
+            position,
+            estimated_start_secs: (average_ms > 0)
+                .then(|| (average_ms * (2 * rounds + 1) / 2 + 999) / 1000),
+        }
+    }
+}
+
+/// Workers and queue of a model served by a pool of its own (`WHISPER_MODEL_WORKERS`).
+#[derive(Debug)]
+pub struct PoolAdmission {
+    /// Inference workers in the pool.
+    pub workers: usize,
+    /// One permit per worker.
+    pub slots: Arc<Semaphore>,
+    pub queue: Arc<QueueTracker>,
+    /// Waiting requests beyond which new requests for the model are rejected.
+    pub max_depth: Option<usize>,
+}
+
+impl PoolAdmission {
+    /// Creates an idle pool of `workers` workers.
+    pub fn new(workers: usize, max_depth: Option<usize>) -> Self {
+        Self {
+            workers,
+            slots: Arc::new(Semaphore::new(workers)),
+            queue: QueueTracker::new(workers),
+            max_depth,
+        }
+    }
+}
+
+/// A request's place in the queue; leaves the queue when dropped.
+#[derive(Debug)]
+pub struct QueueTicket {
+    spot: QueueSpot,
+}
+
+impl QueueTicket {
+    /// Handle for looking up this ticket's position from elsewhere.
+    pub fn spot(&self) -> QueueSpot {
+        self.spot.clone()
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        if let Ok(mut waiting) = self.spot.tracker.waiting.lock() {
+            if waiting.tickets.remove(&self.spot.id) {
+                waiting.reached.pop();
+            }
+        }
+    }
+}
+
+/// Read-only handle to a [`QueueTicket`]'s place in the queue.
+#[derive(Debug, Clone)]
+pub struct QueueSpot {
+    tracker: Arc<QueueTracker>,
+    id: u64,
+}
+
+impl QueueSpot {
+    /// Current position and estimate; `None` once the request stopped waiting.
+    pub fn status(&self) -> Option<QueueStatus> {
+        let position = {
+            let waiting = self.tracker.waiting.lock().ok()?;
+            if !waiting.tickets.contains(&self.id) {
+                return None;
+            }
+            waiting.tickets.range(..self.id).count() + 1
+        };
+        Some(self.tracker.status_at(position))
+    }
+}
+
+/// Where a waiting request stands.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct QueueStatus {
+    /// 1-based place in line; `position - 1` requests are ahead.
+    pub position: usize,
+    /// Rough seconds until a worker frees up for this request.
+    pub estimated_start_secs: Option<u64>,
+}
+
+/// Adds `queue_position` and `estimated_start_secs` to a response body.
+pub fn insert_queue_status(body: &mut Value, status: QueueStatus) {
+    body["queue_position"] = json!(status.position);
+    if let Some(secs) = status.estimated_start_secs {
+        body["estimated_start_secs"] = json!(secs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_follow_arrival_order_and_free_up_on_drop() {
+        let tracker = QueueTracker::new(2);
+        let first = tracker.join();
+        let second = tracker.join();
+        let third = tracker.join();
+        assert_eq!(third.spot().status().expect("waiting").position, 3);
+        assert_eq!(
+            third.spot().status().expect("waiting").estimated_start_secs,
+            None
+        );
+
+        let spot = second.spot();
+        drop(first);
+        assert_eq!(spot.status().expect("waiting").position, 1);
+        assert_eq!(third.spot().status().expect("waiting").position, 2);
+        drop(second);
+        assert_eq!(spot.status(), None);
+        assert_eq!(tracker.next_status().position, 2);
+    }
+
+    #[test]
+    fn saturation_restarts_when_the_queue_drops_below_depth() {
+        let tracker = QueueTracker::new(1);
+        let first = tracker.join();
+        let second = tracker.join();
+        assert!(tracker.saturated_for(2).is_some());
+        assert!(tracker.saturated_for(3).is_none());
+
+        std::thread::sleep(Duration::from_millis(20));
+        drop(first);
+        assert!(tracker.saturated_for(2).is_none());
+        let _third = tracker.join();
+        let since_third = tracker.saturated_for(2).expect("saturated");
+        let since_second = tracker.saturated_for(1).expect("saturated");
+        assert!(since_third < Duration::from_millis(20));
+        assert!(since_second >= Duration::from_millis(20));
+        drop(second);
+    }
+
+    #[test]
+    fn estimates_use_average_inference_time_per_worker_round() {
+        let tracker = QueueTracker::new(2);
+        tracker.record_inference(Duration::from_secs(4));
+        assert_eq!(tracker.status_at(1).estimated_start_secs, Some(2));
+        assert_eq!(tracker.status_at(2).estimated_start_secs, Some(2));
+        assert_eq!(tracker.status_at(3).estimated_start_secs, Some(6));
+
+        tracker.record_inference(Duration::from_secs(12));
+        assert_eq!(tracker.status_at(1).estimated_start_secs, Some(3));
+    }
+}
@@ -3,54 +3,446 @@
 //! This module owns request parsing, authentication, input validation, and
 //! response formatting while delegating inference to a backend implementation.
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use axum::extract::{DefaultBodyLimit, Multipart, State};
-use axum::http::{header, HeaderMap};
+use axum::body::Body;
+use axum::extract::multipart::Field;
+use axum::extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, RawQuery, Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{Json, Router};
+use clap::ValueEnum;
+use futures_util::stream::FuturesUnordered;
+use futures_util::{stream, StreamExt};
 use serde_json::json;
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+use tracing::{debug, warn};
 
-use crate::audio::{decode_to_mono_16khz_f32, validate_extension};
-use crate::backend::{TaskKind, TranscribeRequest, Transcriber};
-use crate::config::AppConfig;
+use crate::admin::{get_debug_trace, purge_artifacts, reload_model};
+use crate::admission::{insert_queue_status, QueueSpot, QueueStatus, QueueTracker};
+use crate::archive::TranscriptArchive;
+use crate::audio::{
+    clipped_fraction, is_silent, trim_silence, Downmix, ResampleQuality, SpeechMap, VadParams,
+};
+use crate::backend::whisper_rs;
+use crate::backend::{
+    Capabilities, NoSpeechReason, TaskKind, TranscribeRequest, Transcriber, TranscriptResult,
+    TranscriptSegment, TranscriptWarning, Usage,
+};
+use crate::build_info;
+use crate::chunking::{ChunkParams, Stitcher};
+use crate::config::{
+    parse_decoders, parse_max_tokens_per_segment, ApiKey, AppConfig, Compute, KeyDefaults,
+    WhisperModelSize,
+};
+use crate::debug_trace::{self, DebugTraces};
+use crate::demo::demo_page;
+use crate::denoise;
 use crate::error::AppError;
-use crate::formats::{segments_to_srt, segments_to_vtt, ResponseFormat};
+use crate::formats::{
+    compression_ratio, merge_segments, sanitize_timestamps, segments_to_srt, segments_to_vtt,
+    CueWriter, FrameRate, ResponseFormat, SubtitleTiming, TextNormalization,
+};
+use crate::job_spool::{JobSpool, SpooledFile, SpooledJob};
+use crate::jobs::{new_id, unix_secs, JobStore};
+use crate::memory_budget::{
+    estimated_samples, request_memory_cost, MemoryBudget, MemoryReservation,
+};
+use crate::metrics::{render_worker_gauges, Metrics, WorkerPool};
+use crate::model_registry::{load_model, parse_on_demand_model_id, ModelLoader, OnDemandModels};
+use crate::openapi;
+use crate::pagination::{ContinuationStore, Overflow};
+use crate::presets::Preset;
+use crate::punctuation::guess_punctuation;
+use crate::rate_limit::{enforce_rate_limit, RateLimiter};
+use crate::request_id::{self, assign_request_id, REQUEST_ID_HEADER};
+use crate::rtp::RtpRouter;
+use crate::sniff::resolve_extension;
+use crate::streaming::audio_stream;
+#[cfg(feature = "tts")]
+use crate::tts::{pcm16_to_wav, PiperTts};
+use crate::upload::{multipart_error, read_upload, upload_too_large, UploadBody};
 
 /// Human-readable service name returned by health endpoints.
 pub const APP_NAME: &str = "whisper-openai-server";
-/// Service version string returned by health endpoints.
-pub const APP_VERSION: &str = "0.1.0";
-/// Maximum accepted multipart request body size for audio uploads.
-pub const MULTIPART_BODY_LIMIT_BYTES: usize = 25 * 1024 * 1024;
+/// Request body allowance on top of `MAX_UPLOAD_BYTES` for part headers and boundaries.
+const MULTIPART_FRAMING_ALLOWANCE_BYTES: usize = 64 * 1024;
+/// Response header carrying the no-speech reason when a transcript is empty by design.
+pub const NO_SPEECH_HEADER: &str = "x-no-speech";
+/// Response header echoing the `metadata` form field for non-JSON formats.
+pub const CLIENT_METADATA_HEADER: &str = "x-client-metadata";
+/// Maximum size in bytes of the opaque `metadata` form field.
+pub const MAX_METADATA_BYTES: usize = 4096;
+/// Response header carrying the language detected (or forced) for a transcription.
+pub const DETECTED_LANGUAGE_HEADER: &str = "x-detected-language";
+/// Response header naming the debug trace stored for a `debug_trace=true` request.
+pub const DEBUG_TRACE_ID_HEADER: &str = "x-debug-trace-id";
+/// `Retry-After` seconds suggested when the inference queue is full.
+const QUEUE_FULL_RETRY_AFTER_SECS: u64 = 5;
 
 /// Shared state injected into all route handlers.
 pub struct AppState {
     /// Runtime configuration loaded at startup.
     pub cfg: AppConfig,
-    /// Active inference backend implementation.
+    /// Default model, replaced by `POST /admin/models/reload`.
+    active_model: RwLock<ActiveModel>,
+    /// Held while `POST /admin/models/reload` loads a model.
+    pub model_reload: tokio::sync::Mutex<()>,
+    /// Loads the models requested through `POST /admin/models/reload`.
+    pub(crate) model_loader: ModelLoader,
+    /// Request and inference metrics recorder.
+    pub metrics: Metrics,
+    /// Lazily loaded `whisper-<size>` backends, when `WHISPER_ON_DEMAND_MODELS` is enabled.
+    pub on_demand_models: Option<OnDemandModels>,
+    /// Backends loaded from `WHISPER_EXTRA_MODELS`, keyed by model id.
+    pub extra_models: HashMap<String, Arc<dyn Transcriber>>,
+    /// Admission queue bounding concurrent inference to the worker count.
+    pub inference_slots: Arc<Semaphore>,
+    /// Admission queue of the CPU-only pool, when `WHISPER_CPU_WORKERS` is set.
+    pub cpu_inference_slots: Option<Arc<Semaphore>>,
+    /// Requests currently waiting in the admission queue.
+    pub queued_requests: AtomicUsize,
+    /// Queue positions of requests waiting for a default worker.
+    pub queue: Arc<QueueTracker>,
+    /// Queue positions of requests waiting for a CPU-only worker.
+    pub cpu_queue: Option<Arc<QueueTracker>>,
+    /// Budget for decoded request audio, when `MEMORY_BUDGET_MB` is set.
+    pub memory_budget: Option<MemoryBudget>,
+    /// Caps audio uploads whose bodies are being read at the same time.
+    pub upload_slots: Semaphore,
+    /// Per-client counters, when `RATE_LIMIT_*` or per-key limits are configured.
+    pub rate_limiter: Option<RateLimiter>,
+    /// Asynchronous jobs submitted with `async=true`.
+    pub jobs: JobStore,
+    /// On-disk copy of queued async jobs, when `ASYNC_JOB_DIR` is set.
+    pub job_spool: Option<JobSpool>,
+    /// Remaining pages of transcripts split by `max_chars`.
+    pub continuations: ContinuationStore,
+    /// Archive of completed transcripts, when `TRANSCRIPT_ARCHIVE_DIR` is set.
+    pub archive: Option<TranscriptArchive>,
+    /// Traces of requests sent with `debug_trace=true`, when `DEBUG_TRACE_DIR` is set.
+    pub debug_traces: Option<DebugTraces>,
+    /// Routes RTP packets to streaming sessions, when `RTP_PORT` is set.
+    pub rtp: Option<Arc<RtpRouter>>,
+    /// Speech synthesizer backing `/v1/audio/speech`.
+    #[cfg(feature = "tts")]
+    pub tts: PiperTts,
+}
+
+/// The default model's backend and where it was loaded from.
+#[derive(Clone)]
+pub struct ActiveModel {
     pub backend: Arc<dyn Transcriber>,
+    /// Model file on disk.
+    pub path: String,
+    /// Size the model was resolved from; `None` for an explicit `WHISPER_MODEL` file.
+    pub size: Option<WhisperModelSize>,
+    /// CPU-only copy serving `compute=cpu` requests, when `WHISPER_CPU_WORKERS` is set.
+    pub cpu_backend: Option<Arc<dyn Transcriber>>,
+}
+
+impl ActiveModel {
+    /// Describes the model configured by `cfg`, served by `backend`.
+    pub fn from_config(cfg: &AppConfig, backend: Arc<dyn Transcriber>) -> Self {
+        Self {
+            backend,
+            path: cfg.whisper_model.clone(),
+            size: (!cfg.whisper_model_explicit).then_some(cfg.whisper_model_size),
+            cpu_backend: None,
+        }
+    }
+
+    /// Returns the backend for `compute`; without a CPU pool every request
+    /// runs on the default workers.
+    pub fn backend_for(&self, compute: Option<Compute>) -> Arc<dyn Transcriber> {
+        match (compute, &self.cpu_backend) {
+            (Some(Compute::Cpu), Some(cpu)) => Arc::clone(cpu),
+            _ => Arc::clone(&self.backend),
+        }
+    }
 }
 
 impl AppState {
     /// Constructs shared handler state.
     pub fn new(cfg: AppConfig, backend: Arc<dyn Transcriber>) -> Self {
-        Self { cfg, backend }
+        Self {
+            active_model: RwLock::new(ActiveModel::from_config(&cfg, backend)),
+            model_reload: tokio::sync::Mutex::new(()),
+            model_loader: load_model,
+            #[cfg(feature = "tts")]
+            tts: PiperTts::new(&cfg),
+            metrics: Metrics::new(&cfg),
+            on_demand_models: cfg
+                .whisper_on_demand_models
+                .then(|| OnDemandModels::new(&cfg)),
+            extra_models: HashMap::new(),
+            inference_slots: Arc::new(Semaphore::new(cfg.whisper_parallelism)),
+            cpu_inference_slots: cfg
+                .whisper_cpu_workers
+                .map(|workers| Arc::new(Semaphore::new(workers))),
+            queued_requests: AtomicUsize::new(0),
+            queue: QueueTracker::new(cfg.whisper_parallelism),
+            cpu_queue: cfg.whisper_cpu_workers.map(QueueTracker::new),
+            memory_budget: cfg.memory_budget_mb.map(MemoryBudget::new),
+            upload_slots: Semaphore::new(cfg.max_concurrent_uploads),
+            rate_limiter: (cfg.rate_limit_requests_per_minute.is_some()
+                || cfg.rate_limit_concurrent_requests.is_some()
+                || cfg.api_keys.iter().any(|key| {
+                    key.requests_per_minute.is_some() || key.concurrent_requests.is_some()
+                }))
+            .then(RateLimiter::default),
+            jobs: {
+                let jobs = JobStore::new(cfg.async_job_retention).with_byte_caps(
+                    cfg.async_job_max_total_bytes,
+                    cfg.async_job_max_bytes_per_key,
+                );
+                match cfg.async_job_storage.clone() {
+                    Some(storage) => jobs.with_storage(storage, cfg.storage_cipher.clone()),
+                    None => jobs,
+                }
+            },
+            job_spool: cfg
+                .async_job_storage
+                .clone()
+                .map(|storage| JobSpool::new(storage, cfg.storage_cipher.clone())),
+            continuations: ContinuationStore::new(cfg.async_job_retention),
+            archive: cfg
+                .transcript_archive_storage
+                .clone()
+                .map(|storage| TranscriptArchive::new(storage, cfg.storage_cipher.clone())),
+            debug_traces: cfg
+                .debug_trace_storage
+                .clone()
+                .map(|storage| DebugTraces::new(storage, cfg.storage_cipher.clone())),
+            rtp: cfg.rtp_port.map(|_| Arc::new(RtpRouter::default())),
+            cfg,
+        }
+    }
+
+    /// Serves `compute=cpu` requests for the default model from `backend`.
+    pub fn with_cpu_pool(mut self, backend: Option<Arc<dyn Transcriber>>) -> Self {
+        match self.active_model.get_mut() {
+            Ok(active) => active.cpu_backend = backend,
+            Err(poisoned) => poisoned.into_inner().cpu_backend = backend,
+        }
+        self
+    }
+
+    /// Serves `models` alongside the default model under their ids.
+    pub fn with_extra_models(mut self, models: HashMap<String, Arc<dyn Transcriber>>) -> Self {
+        self.extra_models = models;
+        self
+    }
+
+    /// Admission queue and queue tracker of the worker pool `backend` runs on.
+    fn admission<'a>(
+        &'a self,
+        backend: &'a dyn Transcriber,
+    ) -> (&'a Arc<Semaphore>, &'a Arc<QueueTracker>) {
+        if let Some(pool) = backend.dedicated_pool() {
+            return (&pool.slots, &pool.queue);
+        }
+        match (&self.cpu_inference_slots, &self.cpu_queue) {
+            (Some(slots), Some(queue)) if backend.uses_cpu_pool() => (slots, queue),
+            _ => (&self.inference_slots, &self.queue),
+        }
+    }
+
+    /// Extra and loaded on-demand models with their ids, sorted by id.
+    fn loaded_models(&self) -> Vec<(String, Arc<dyn Transcriber>)> {
+        let mut models = self
+            .extra_models
+            .iter()
+            .map(|(id, backend)| (id.clone(), Arc::clone(backend)))
+            .chain(self.on_demand_models.iter().flat_map(OnDemandModels::ready))
+            .collect::<Vec<_>>();
+        models.sort_by(|a, b| a.0.cmp(&b.0));
+        models
+    }
+
+    /// Returns the current default model.
+    pub fn active_model(&self) -> ActiveModel {
+        match self.active_model.read() {
+            Ok(active) => active.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the default model; requests already running keep the old one.
+    pub fn swap_model(&self, model: ActiveModel) {
+        match self.active_model.write() {
+            Ok(mut active) => *active = model,
+            Err(poisoned) => *poisoned.into_inner() = model,
+        }
     }
 }
 
 /// Builds the Axum router for all public endpoints.
+///
+/// Rate limits apply to the `/v1` API only, so health probes are never throttled.
+/// The CORS layer sits outside authentication so browser preflights succeed.
 pub fn build_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/", get(root))
-        .route("/health", get(health))
+    let cors = cors_layer(&state.cfg);
+    let router = Router::new()
         .route("/v1", get(v1))
         .route("/v1/models", get(list_models))
         .route("/v1/audio/transcriptions", post(audio_transcriptions))
         .route("/v1/audio/translations", post(audio_translations))
-        .layer(DefaultBodyLimit::max(MULTIPART_BODY_LIMIT_BYTES))
-        .with_state(state)
+        .route("/v1/audio/language", post(audio_language))
+        .route("/v1/audio/stream", get(audio_stream))
+        .route("/v1/audio/jobs/:id", get(audio_job))
+        .route("/v1/audio/continuations/:token", get(audio_continuation));
+
+    #[cfg(feature = "tts")]
+    let router = router.route("/v1/audio/speech", post(audio_speech));
+
+    let router = router
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            enforce_rate_limit,
+        ))
+        .route("/", get(root))
+        .route("/health", get(health))
+        .route("/version", get(version))
+        .route("/openapi.json", get(openapi_spec))
+        .route("/demo", get(demo_page))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/admin/models/reload", post(reload_model))
+        .route("/admin/purge", post(purge_artifacts))
+        .route("/admin/traces/:id", get(get_debug_trace))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            identify_api_key,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            record_request_metrics,
+        ))
+        .layer(DefaultBodyLimit::max(multipart_body_limit(&state.cfg)))
+        .layer(middleware::from_fn(assign_request_id))
+        .with_state(state);
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+/// Builds the CORS layer from `CORS_ALLOWED_*`, or `None` when no origins are allowed.
+fn cors_layer(cfg: &AppConfig) -> Option<CorsLayer> {
+    let origins = cfg
+        .cors_allowed_origins
+        .iter()
+        .filter(|origin| !origin.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+    if origins.is_empty() {
+        return None;
+    }
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins)
+    };
+    let allow_headers = if cfg.cors_allowed_headers.iter().any(|name| name == "*") {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(
+            cfg.cors_allowed_headers
+                .iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok()),
+        )
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_headers(allow_headers)
+            .allow_methods(cfg.cors_allowed_methods.clone())
+            .expose_headers([
+                HeaderName::from_static(NO_SPEECH_HEADER),
+                HeaderName::from_static(CLIENT_METADATA_HEADER),
+                HeaderName::from_static(DETECTED_LANGUAGE_HEADER),
+                HeaderName::from_static(DEBUG_TRACE_ID_HEADER),
+                HeaderName::from_static(REQUEST_ID_HEADER),
+                header::RETRY_AFTER,
+            ])
+            .max_age(Duration::from_secs(600)),
+    )
+}
+
+/// Largest request body accepted: the audio limit plus room for the form fields.
+fn multipart_body_limit(cfg: &AppConfig) -> usize {
+    cfg.max_upload_bytes
+        .saturating_add(cfg.multipart_max_fields * cfg.multipart_max_field_bytes)
+        .saturating_add(MULTIPART_FRAMING_ALLOWANCE_BYTES)
+}
+
+/// Rejects requests whose declared `Content-Length` cannot fit the upload limit.
+fn check_content_length(cfg: &AppConfig, headers: &HeaderMap) -> Result<(), AppError> {
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match declared {
+        Some(len) if len > multipart_body_limit(cfg) as u64 => {
+            Err(upload_too_large(cfg.max_upload_bytes))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Label of the API key that authenticated a request.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey(pub String);
+
+/// Attaches the authenticating key's label to the request and its `request` log span.
+///
+/// Requests without a valid key pass through unchanged; handlers still enforce
+/// authentication through [`require_auth`].
+async fn identify_api_key(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Ok(Some(api_key)) = require_auth(&state.cfg, req.headers()) else {
+        return next.run(req).await;
+    };
+    tracing::Span::current().record("api_key", api_key.label.as_str());
+    req.extensions_mut()
+        .insert(AuthenticatedKey(api_key.label.clone()));
+    next.run(req).await
+}
+
+/// Records per-endpoint request counts and latencies.
+async fn record_request_metrics(
+    State(state): State<Arc<AppState>>,
+    matched_path: MatchedPath,
+    req: Request,
+    next: Next,
+) -> Response {
+    let endpoint = matched_path.as_str().to_owned();
+    let started = Instant::now();
+    let res = next.run(req).await;
+    state.metrics.incr(
+        "requests",
+        &[("endpoint", &endpoint), ("status", res.status().as_str())],
+    );
+    state.metrics.timing(
+        "request_duration",
+        started.elapsed(),
+        &[("endpoint", &endpoint)],
+    );
+    res
 }
 
 /// Root status endpoint (`GET /`).
@@ -62,7 +454,7 @@ pub async fn root(
     Ok(Json(json!({
         "status": "ok",
         "name": APP_NAME,
-        "version": APP_VERSION,
+        "version": build_info::VERSION,
         "model": state.cfg.api_model_alias,
     })))
 }
@@ -75,6 +467,128 @@ pub async fn health(
     root(State(state), headers).await
 }
 
+/// Liveness probe (`GET /health/live`): the process is up and serving HTTP.
+pub async fn health_live() -> Json<serde_json::Value> {
+    Json(json!({"status": "ok"}))
+}
+
+/// Readiness probe (`GET /health/ready`) reporting model, worker, and queue state.
+///
+/// Until the default model is loaded (and has passed the startup self-test),
+/// `startup` answers this route with `503`; once this handler serves it, the
+/// model is `ready`, or `reloading` while `POST /admin/models/reload` loads a
+/// replacement and the current one keeps serving.
+/// On-demand models still loading are listed without affecting readiness.
+/// With `READY_OVERLOAD_QUEUE_DEPTH` set, a queue held at that length for
+/// `READY_OVERLOAD_SECS` answers `503` so load balancers route around this
+/// replica; requests already accepted keep being served.
+pub async fn health_ready(State(state): State<Arc<AppState>>) -> Response {
+    let workers = state.cfg.whisper_parallelism;
+    let busy = workers.saturating_sub(state.inference_slots.available_permits());
+    let overloaded_for = state.cfg.ready_overload_depth.and_then(|depth| {
+        state
+            .queue
+            .saturated_for(depth)
+            .filter(|held| *held >= state.cfg.ready_overload_after)
+    });
+    let model_status = match state.model_reload.try_lock() {
+        Ok(_) => "ready",
+        Err(_) => "reloading",
+    };
+    let mut body = json!({
+        "status": if overloaded_for.is_some() { "overloaded" } else { "ready" },
+        "model": {"id": state.cfg.api_model_alias, "status": model_status},
+        "workers": {"total": workers, "busy": busy},
+        "queue": {
+            "waiting": state.queued_requests.load(Ordering::Relaxed),
+            "max_depth": state.cfg.max_queue_depth,
+        },
+    });
+    if let (Some(total), Some(slots)) = (state.cfg.whisper_cpu_workers, &state.cpu_inference_slots)
+    {
+        let busy = total.saturating_sub(slots.available_permits());
+        body["cpu_workers"] = json!({"total": total, "busy": busy});
+    }
+    if let Some(models) = &state.on_demand_models {
+        body["on_demand_models"] = json!(models.status());
+    }
+    let dedicated = state
+        .loaded_models()
+        .into_iter()
+        .filter_map(|(id, backend)| {
+            let pool = backend.dedicated_pool()?;
+            Some(json!({
+                "id": id,
+                "workers": {
+                    "total": pool.workers,
+                    "busy": pool.workers.saturating_sub(pool.slots.available_permits()),
+                },
+                "queue": {"waiting": pool.queue.waiting(), "max_depth": pool.max_depth},
+            }))
+        })
+        .collect::<Vec<_>>();
+    if !dedicated.is_empty() {
+        body["model_workers"] = json!(dedicated);
+    }
+    match overloaded_for {
+        Some(held) => {
+            body["queue"]["overloaded_secs"] = json!(held.as_secs());
+            (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+        }
+        None => Json(body).into_response(),
+    }
+}
+
+/// Prometheus scrape endpoint (`GET /metrics`) with what each inference worker
+/// is running, so stuck requests show up before they time out.
+///
+/// Served only with `PROMETHEUS_METRICS=true`. Unlike `/health`, it never calls
+/// [`require_auth`], so it answers without a bearer token even when API keys are
+/// configured; `identify_api_key` runs on this route too but only labels
+/// requests that carry a valid key and rejects none.
+pub async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    if !state.cfg.prometheus_metrics {
+        return Err(AppError::not_found(
+            "the metrics endpoint is disabled; set PROMETHEUS_METRICS=true",
+            None,
+        ));
+    }
+    let active = state.active_model();
+    let mut pools = vec![WorkerPool {
+        model: state.cfg.api_model_alias.clone(),
+        pool: "default",
+        workers: active.backend.worker_activity(),
+    }];
+    if let Some(cpu) = &active.cpu_backend {
+        pools.push(WorkerPool {
+            model: state.cfg.api_model_alias.clone(),
+            pool: "cpu",
+            workers: cpu.worker_activity(),
+        });
+    }
+    pools.extend(
+        state
+            .loaded_models()
+            .into_iter()
+            .map(|(model, backend)| WorkerPool {
+                model,
+                pool: match backend.dedicated_pool() {
+                    Some(_) => "dedicated",
+                    None => "default",
+                },
+                workers: backend.worker_activity(),
+            }),
+    );
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        render_worker_gauges(&pools),
+    )
+        .into_response())
+}
+
 /// API root status endpoint (`GET /v1`).
 pub async fn v1(
     State(state): State<Arc<AppState>>,
@@ -83,460 +597,5924 @@ pub async fn v1(
     root(State(state), headers).await
 }
 
+/// Build and dependency versions for deployment audits (`GET /version`).
+pub async fn version(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_auth(&state.cfg, &headers)?;
+    let mut body = build_info::version_json(whisper_rs::system_info());
+    body["name"] = json!(APP_NAME);
+    Ok(Json(body))
+}
+
+/// OpenAPI description of the API for client generators (`GET /openapi.json`).
+///
+/// Served without authentication, like `/health`; it describes the API but
+/// exposes no data.
+pub async fn openapi_spec(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(openapi::spec(&state.cfg))
+}
+
 /// Lists accepted model identifiers (`GET /v1/models`).
 pub async fn list_models(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_auth(&state.cfg, &headers)?;
-    let data = state
+    let mut data = state
         .cfg
         .accepted_model_ids()
         .into_iter()
-        .map(|id| json!({"id": id, "object": "model", "owned_by": "local", "permission": []}))
+        .map(|id| {
+            let capabilities = model_capabilities(&state, &id).to_json();
+            json!({
+                "id": id,
+                "object": "model",
+                "owned_by": "local",
+                "permission": [],
+                "capabilities": capabilities,
+            })
+        })
         .collect::<Vec<_>>();
+    data.extend(state.cfg.model_aliases.iter().map(|(alias, target)| {
+        json!({
+            "id": alias,
+            "object": "model",
+            "owned_by": "local",
+            "permission": [],
+            "alias_for": target,
+            "capabilities": model_capabilities(&state, target).to_json(),
+        })
+    }));
 
     Ok(Json(json!({"object": "list", "data": data})))
 }
 
+/// Capabilities of the backend serving `model`, without loading on-demand models.
+///
+/// On-demand sizes run on the same backend as the default model.
+fn model_capabilities(state: &AppState, model: &str) -> Capabilities {
+    match state.extra_models.get(state.cfg.resolve_model_alias(model)) {
+        Some(backend) => backend.capabilities(),
+        None => state.active_model().backend_for(None).capabilities(),
+    }
+}
+
 /// Handles speech-to-text transcription requests (`POST /v1/audio/transcriptions`).
 pub async fn audio_transcriptions(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
+    RawQuery(query): RawQuery,
     multipart: Multipart,
 ) -> Result<Response, AppError> {
-    handle_audio_request(state, headers, multipart, TaskKind::Transcribe).await
+    handle_audio_request(state, headers, query, multipart, TaskKind::Transcribe).await
 }
 
 /// Handles speech-to-English translation requests (`POST /v1/audio/translations`).
 pub async fn audio_translations(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
+    RawQuery(query): RawQuery,
     multipart: Multipart,
 ) -> Result<Response, AppError> {
-    handle_audio_request(state, headers, multipart, TaskKind::Translate).await
+    handle_audio_request(state, headers, query, multipart, TaskKind::Translate).await
 }
 
-struct AudioForm {
+/// Maximum accepted `input` length for speech synthesis, matching OpenAI.
+#[cfg(feature = "tts")]
+pub const MAX_SPEECH_INPUT_CHARS: usize = 4096;
+
+/// JSON body accepted by `POST /v1/audio/speech`.
+#[cfg(feature = "tts")]
+#[derive(Debug, serde::Deserialize)]
+struct SpeechRequest {
+    model: String,
+    input: String,
+    voice: String,
+    response_format: Option<String>,
+    speed: Option<f32>,
+}
+
+/// Synthesizes speech from text (`POST /v1/audio/speech`).
+///
+/// OpenAI voice names are accepted for compatibility and all map to the
+/// configured piper voice. Only `wav` and `pcm` output formats are supported.
+#[cfg(feature = "tts")]
+pub async fn audio_speech(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, AppError> {
+    require_auth(&state.cfg, &headers)?;
+
+    let req: SpeechRequest = serde_json::from_slice(&body).map_err(|err| {
+        AppError::invalid_request(format!("invalid JSON body: {err}"), None, None)
+    })?;
+    if !matches!(req.model.as_str(), "tts-1" | "tts-1-hd") {
+        return Err(AppError::invalid_request(
+            format!(
+                "unsupported model={:?}; accepted models: tts-1,tts-1-hd",
+                req.model
+            ),
+            Some("model"),
+            Some("invalid_model"),
+        ));
+    }
+    if req.voice.trim().is_empty() {
+        return Err(AppError::invalid_request(
+            "voice must not be empty",
+            Some("voice"),
+            Some("invalid_voice"),
+        ));
+    }
+    let input = req.input.trim();
+    if input.is_empty() || input.chars().count() > MAX_SPEECH_INPUT_CHARS {
+        return Err(AppError::invalid_request(
+            format!("input must contain between 1 and {MAX_SPEECH_INPUT_CHARS} characters"),
+            Some("input"),
+            Some("invalid_input"),
+        ));
+    }
+    let speed = req.speed.unwrap_or(1.0);
+    if !speed.is_finite() || !(0.25..=4.0).contains(&speed) {
+        return Err(AppError::invalid_request(
+            format!("invalid speed={speed}; expected a value in range [0.25, 4.0]"),
+            Some("speed"),
+            Some("invalid_speed"),
+        ));
+    }
+    let format = req.response_format.as_deref().unwrap_or("wav");
+    if !matches!(format, "wav" | "pcm") {
+        return Err(AppError::invalid_request(
+            format!("unsupported response_format={format:?}; expected one of wav,pcm"),
+            Some("response_format"),
+            Some("invalid_response_format"),
+        ));
+    }
+
+    let audio = state.tts.synthesize(input, speed).await?;
+    if format == "pcm" {
+        return Ok(([(header::CONTENT_TYPE, "audio/pcm")], audio.pcm_s16le).into_response());
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "audio/wav")],
+        pcm16_to_wav(&audio.pcm_s16le, audio.sample_rate),
+    )
+        .into_response())
+}
+
+struct UploadedFile {
+    filename: String,
     extension: String,
-    bytes: Vec<u8>,
+    body: UploadBody,
+}
+
+struct AudioForm {
+    /// Uploaded `file` parts in request order; more than one makes a batch request.
+    files: Vec<UploadedFile>,
     model: String,
     language: Option<String>,
     prompt: Option<String>,
     response_format: ResponseFormat,
     temperature: Option<f32>,
+    max_tokens_per_segment: Option<u32>,
+    beam_size: Option<u32>,
+    best_of: Option<u32>,
+    stream: bool,
+    /// Whether choppy segments are merged into sentences after inference.
+    merge_segments: bool,
+    /// Whether missing punctuation and casing are guessed from pauses in
+    /// English transcripts.
+    heuristic_punctuation: bool,
+    /// Whether long silences are cut out of the audio before inference.
+    vad_filter: bool,
+    /// Silence length and padding used by the VAD filter.
+    vad_params: VadParams,
+    /// Resampler converting the upload to 16 kHz.
+    resample_quality: ResampleQuality,
+    /// Whether background noise is suppressed with RNNoise after decoding.
+    denoise: bool,
+    /// How multichannel uploads are reduced to mono, from the `channel` field.
+    downmix: Downmix,
+    /// Opaque client value echoed in responses and stored with async jobs.
+    metadata: Option<String>,
+    /// Label of the authenticating API key, stored with jobs and archived transcripts.
+    api_key: Option<String>,
+    /// Worker pool from the `compute` field, replaced by the API key's policy when set.
+    compute: Option<Compute>,
+    /// Whether token log-probabilities were requested with `include[]=logprobs`.
+    include_logprobs: bool,
+    /// Cue time format of subtitles, from `timecode`, `frame_rate`, and `drop_frame`.
+    subtitle_timing: SubtitleTiming,
+    /// Whether word timings were requested with `timestamp_granularities[]=word`.
+    word_timestamps: bool,
+    /// Seconds of audio inspected by `POST /v1/audio/language`, from `duration`.
+    detect_secs: Option<f32>,
+    /// Maximum transcript characters per JSON response, from `max_chars`.
+    max_chars: Option<usize>,
+    overflow: Overflow,
+    /// Whether a debug trace of the request is stored, from `debug_trace`.
+    debug_trace: bool,
+    /// When a synchronous request times out (`REQUEST_TIMEOUT_SECS`); `None` for async jobs.
+    deadline: Option<Instant>,
+}
+
+impl AudioForm {
+    /// How the transcript is rendered for the client.
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            format: self.response_format,
+            include_logprobs: self.include_logprobs,
+            subtitle_timing: self.subtitle_timing,
+            word_timestamps: self.word_timestamps,
+        }
+    }
+
+    /// The decode options recorded in a debug trace, after presets and
+    /// server defaults have been applied.
+    fn trace_parameters(&self) -> serde_json::Value {
+        json!({
+            "language": self.language,
+            "prompt": self.prompt,
+            "response_format": self.response_format.to_string(),
+            "temperature": self.temperature,
+            "max_tokens_per_segment": self.max_tokens_per_segment,
+            "beam_size": self.beam_size,
+            "best_of": self.best_of,
+            "merge_segments": self.merge_segments,
+            "heuristic_punctuation": self.heuristic_punctuation,
+            "vad_filter": self.vad_filter,
+            "vad_min_silence_ms": self.vad_params.min_silence_ms,
+            "vad_padding_ms": self.vad_params.padding_ms,
+            "resample_quality": self.resample_quality.as_str(),
+            "denoise": self.denoise,
+            "channel": match self.downmix {
+                Downmix::Average => json!("average"),
+                Downmix::Channel(channel) => json!(channel),
+            },
+            "compute": self.compute.map(Compute::as_str),
+        })
+    }
 }
 
 async fn handle_audio_request(
     state: Arc<AppState>,
     headers: HeaderMap,
+    query: Option<String>,
     mut multipart: Multipart,
     task: TaskKind,
 ) -> Result<Response, AppError> {
-    require_auth(&state.cfg, &headers)?;
-
-    let form = parse_audio_form(&mut multipart).await?;
-    validate_requested_model(&state.cfg, &form.model)?;
-
-    let decode_bytes = form.bytes;
-    let extension_hint = form.extension;
-    let audio_16khz_mono_f32 = tokio::task::spawn_blocking(move || {
-        decode_to_mono_16khz_f32(&decode_bytes, &extension_hint)
-    })
-    .await
-    .map_err(|err| AppError::internal(format!("audio decode task failed: {err}")))??;
-
-    let request = TranscribeRequest {
-        task,
-        audio_16khz_mono_f32,
-        language: form.language,
-        prompt: form.prompt,
-        temperature: form.temperature,
-    };
-
-    let result = state.backend.transcribe(request).await?;
-
-    match form.response_format {
-        ResponseFormat::Json => Ok(Json(json!({"text": result.text})).into_response()),
-        ResponseFormat::Text => Ok((
-            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
-            result.text,
-        )
-            .into_response()),
-        ResponseFormat::Srt => Ok((
-            [(header::CONTENT_TYPE, "application/x-subrip; charset=utf-8")],
-            segments_to_srt(&result.segments),
-        )
-            .into_response()),
-        ResponseFormat::Vtt => Ok((
-            [(header::CONTENT_TYPE, "text/vtt; charset=utf-8")],
-            segments_to_vtt(&result.segments),
-        )
-            .into_response()),
-        ResponseFormat::VerboseJson => {
-            let language = result.language.unwrap_or_else(|| "unknown".to_string());
-            let segments = result
-                .segments
-                .into_iter()
-                .enumerate()
-                .map(|(idx, seg)| {
-                    json!({
-                        "id": idx,
-                        "start": seg.start_secs,
-                        "end": seg.end_secs,
-                        "text": seg.text,
-                    })
-                })
-                .collect::<Vec<_>>();
+    let api_key = require_auth(&state.cfg, &headers)?;
 
-            Ok(Json(json!({
-                "task": task.as_str(),
-                "language": language,
-                "text": result.text,
-                "segments": segments,
-            }))
-            .into_response())
+    let async_job = parse_async_query(query.as_deref())?;
+    let form = read_audio_form(&state, &headers, api_key, &mut multipart).await?;
+    if form.stream {
+        if async_job {
+            return Err(AppError::invalid_request(
+                "stream=true cannot be combined with async=true",
+                Some("stream"),
+                Some("invalid_stream"),
+            ));
         }
+        validate_stream_request(form.response_format)?;
     }
-}
+    if form.files.len() > 1 {
+        validate_batch_request(&form)?;
+    }
+    if form.max_chars.is_some() {
+        validate_max_chars_request(&form, async_job)?;
+    }
+    if form.debug_trace {
+        validate_debug_trace_request(&state, &form, async_job)?;
+    }
+    let backend = resolve_backend(&state, &form.model, form.compute)?;
+    validate_capabilities(&state, task, &form, &backend.capabilities())?;
+    check_queue_depth(&state, Some(backend.as_ref()))?;
 
-/// Parses and validates multipart form fields for audio endpoints.
-async fn parse_audio_form(multipart: &mut Multipart) -> Result<AudioForm, AppError> {
-    let mut file_name: Option<String> = None;
-    let mut file_bytes: Option<Vec<u8>> = None;
-    let mut model = "whisper-1".to_string();
-    let mut language: Option<String> = None;
-    let mut prompt: Option<String> = None;
-    let mut response_format = ResponseFormat::Json;
-    let mut temperature: Option<f32> = None;
+    if async_job {
+        return submit_job(state, backend, task, form).await;
+    }
+    let mut form = form;
+    form.deadline = state
+        .cfg
+        .request_timeout
+        .map(|timeout| Instant::now() + timeout);
+    if form.files.len() > 1 {
+        let body =
+            transcribe_batch(&state, backend, task, form, state.cfg.max_queue_wait, &()).await;
+        return Ok(Json(body).into_response());
+    }
+    if form.debug_trace {
+        return transcribe_traced(state, backend, task, form).await;
+    }
+    transcribe_file(state, backend, task, form).await
+}
 
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|err| AppError::bad_multipart(format!("invalid multipart body: {err}")))?
+/// Transcribes the single file of a synchronous request and renders the response.
+async fn transcribe_file(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    mut form: AudioForm,
+) -> Result<Response, AppError> {
+    let stream = form.stream;
+    let vad = form.vad_filter.then_some(form.vad_params);
+    let file = form.files.remove(0);
+    let (request, memory) = within_deadline(
+        form.deadline,
+        prepare_request(&state, task, &form, file, state.cfg.max_queue_wait, None),
+    )
+    .await?;
+    if stream
+        && matches!(
+            form.response_format,
+            ResponseFormat::Srt | ResponseFormat::Vtt
+        )
     {
-        let Some(name) = field.name().map(ToOwned::to_owned) else {
-            continue;
-        };
+        return stream_subtitles(state, backend, task, request, memory, vad, form).await;
+    }
+    if stream {
+        return stream_transcription(state, backend, task, request, memory, vad, &form).await;
+    }
 
-        match name.as_str() {
-            "file" => {
-                let filename = field
-                    .file_name()
-                    .map(ToOwned::to_owned)
-                    .ok_or_else(|| AppError::bad_multipart("file field is missing filename"))?;
-                let bytes = field.bytes().await.map_err(|err| {
-                    AppError::bad_multipart(format!("failed to read file bytes: {err}"))
-                })?;
-                file_name = Some(filename);
-                file_bytes = Some(bytes.to_vec());
+    let result = within_deadline(
+        form.deadline,
+        run_transcription(
+            &state,
+            backend.as_ref(),
+            request,
+            vad,
+            state.cfg.max_queue_wait,
+            &(),
+        ),
+    )
+    .await?;
+    let postprocess_started = Instant::now();
+    let result = postprocess_transcript(state.cfg.text_normalization, &form, result);
+    debug_trace::stage("postprocess", postprocess_started.elapsed());
+    debug_trace::result(&result);
+    let metadata = form.metadata.as_deref();
+    archive_transcript(
+        &state,
+        None,
+        task,
+        form.render_options(),
+        metadata,
+        form.api_key.as_deref(),
+        &result,
+    );
+    let no_speech = result.no_speech;
+    let detected_language = result.language.clone();
+    let rendered = render_transcript(task, result, form.render_options());
+    let mut response = match rendered {
+        RenderedTranscript::Json(mut body) => {
+            insert_metadata(&mut body, metadata);
+            if let Some(max_chars) = form.max_chars {
+                body = state.continuations.limit(body, max_chars, form.overflow);
             }
-            "model" => {
-                model = field
-                    .text()
-                    .await
-                    .map_err(|err| AppError::bad_multipart(format!("invalid model field: {err}")))?
-                    .trim()
-                    .to_string();
-            }
-            "language" => {
-                language = Some(
-                    field
-                        .text()
-                        .await
-                        .map_err(|err| {
-                            AppError::bad_multipart(format!("invalid language field: {err}"))
-                        })?
-                        .trim()
-                        .to_string(),
-                )
-                .filter(|v| !v.is_empty());
-            }
-            "prompt" => {
-                prompt = Some(
-                    field
-                        .text()
-                        .await
-                        .map_err(|err| {
-                            AppError::bad_multipart(format!("invalid prompt field: {err}"))
-                        })?
-                        .trim()
-                        .to_string(),
-                )
-                .filter(|v| !v.is_empty());
+            RenderedTranscript::Json(body).into_response()
+        }
+        rendered => {
+            let mut response = rendered.into_response();
+            if let Some(value) = metadata.and_then(|raw| HeaderValue::from_str(raw).ok()) {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(CLIENT_METADATA_HEADER), value);
             }
-            "response_format" => {
-                let raw = field
-                    .text()
-                    .await
-                    .map_err(|err| {
-                        AppError::bad_multipart(format!("invalid response_format field: {err}"))
-                    })?
-                    .trim()
-                    .to_string();
-                response_format = ResponseFormat::parse(&raw)?;
-            }
-            "temperature" => {
-                let raw = field
-                    .text()
-                    .await
-                    .map_err(|err| {
-                        AppError::bad_multipart(format!("invalid temperature field: {err}"))
-                    })?
-                    .trim()
-                    .to_string();
-
-                if !raw.is_empty() {
-                    let value = raw.parse::<f32>().map_err(|_| {
-                        AppError::invalid_request(
-                            format!("invalid temperature={raw:?}; expected float"),
-                            Some("temperature"),
-                            Some("invalid_temperature"),
-                        )
-                    })?;
-                    if !value.is_finite() {
-                        return Err(AppError::invalid_request(
-                            format!("invalid temperature={raw:?}; expected a finite float"),
-                            Some("temperature"),
-                            Some("invalid_temperature"),
-                        ));
-                    }
-                    if !(0.0..=1.0).contains(&value) {
-                        return Err(AppError::invalid_request(
-                            format!(
-                                "invalid temperature={raw:?}; expected a value in range [0.0, 1.0]"
-                            ),
-                            Some("temperature"),
-                            Some("invalid_temperature"),
-                        ));
-                    }
-                    temperature = Some(value);
-                }
+            response
+        }
+    };
+
+    if let Some(value) = detected_language.and_then(|lang| HeaderValue::from_str(&lang).ok()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(DETECTED_LANGUAGE_HEADER), value);
+    }
+    if let Some(reason) = no_speech {
+        response.headers_mut().insert(
+            HeaderName::from_static(NO_SPEECH_HEADER),
+            HeaderValue::from_static(reason.as_str()),
+        );
+    }
+    Ok(response)
+}
+
+/// Runs [`transcribe_file`] while collecting a debug trace, and stores the
+/// trace under a new trace id before responding.
+///
+/// The id is generated here rather than taken from `x-request-id`, which
+/// clients choose, so one request cannot replace another's trace. It is
+/// returned in the `x-debug-trace-id` header, on errors too.
+async fn transcribe_traced(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    form: AudioForm,
+) -> Result<Response, AppError> {
+    let started = Instant::now();
+    let request_id = request_id::current().unwrap_or_else(|| new_id("req_"));
+    let file = &form.files[0];
+    let request = json!({
+        "id": request_id,
+        "created_at": unix_secs(),
+        "task": task.as_str(),
+        "model": form.model,
+        "api_key": form.api_key,
+        "file": {
+            "filename": file.filename,
+            "extension": file.extension,
+            "bytes": file.body.len(),
+        },
+    });
+    let parameters = form.trace_parameters();
+    let (response, trace) =
+        debug_trace::capture(transcribe_file(Arc::clone(&state), backend, task, form)).await;
+    let outcome = response.as_ref().map(|_| ()).map_err(ToString::to_string);
+    let trace = trace.into_json(request, parameters, started.elapsed(), outcome);
+    let trace_id = new_id("trace_");
+    let saved = match &state.debug_traces {
+        Some(traces) => traces.save(&trace_id, &trace).await,
+        None => Ok(()),
+    };
+    let mut response = response.unwrap_or_else(IntoResponse::into_response);
+    match saved {
+        Ok(()) => {
+            if let Ok(value) = HeaderValue::from_str(&trace_id) {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(DEBUG_TRACE_ID_HEADER), value);
             }
-            _ => {}
+        }
+        Err(err) => {
+            warn!(request_id = %request_id, error = %err, "failed to store debug trace");
+            state.metrics.incr("debug_trace_failures", &[]);
         }
     }
+    Ok(response)
+}
 
-    let filename = file_name.ok_or_else(|| {
-        AppError::invalid_request("missing required multipart field: file", Some("file"), None)
+/// Reads an audio request's form while holding an upload slot, filling in the
+/// API key's request defaults and applying its `compute` policy.
+async fn read_audio_form(
+    state: &AppState,
+    headers: &HeaderMap,
+    api_key: Option<&ApiKey>,
+    multipart: &mut Multipart,
+) -> Result<AudioForm, AppError> {
+    // With dedicated pools the model named in the form decides which queue
+    // applies, so the check waits until the form has been read.
+    if state.cfg.model_workers.is_empty() {
+        check_queue_depth(state, None)?;
+    }
+    check_content_length(&state.cfg, headers)?;
+    let upload = state.upload_slots.try_acquire().map_err(|_| {
+        state.metrics.incr("uploads_rejected", &[]);
+        AppError::overloaded(format!(
+            "server is reading {} uploads already; retry later",
+            state.cfg.max_concurrent_uploads
+        ))
     })?;
-    let extension = validate_extension(&filename)?;
-    let bytes = file_bytes
-        .ok_or_else(|| AppError::invalid_request("missing file content", Some("file"), None))?;
-    if bytes.is_empty() {
+    let defaults = api_key.map(|key| &key.defaults);
+    let mut form = parse_audio_form(&state.cfg, defaults, multipart).await?;
+    drop(upload);
+    if let Some(compute) = api_key.and_then(|key| key.compute) {
+        form.compute = Some(compute);
+    }
+    form.api_key = api_key.map(|key| key.label.clone());
+    Ok(form)
+}
+
+/// Most audio, in seconds, whisper's language detector looks at.
+const LANGUAGE_DETECTION_MAX_SECS: f32 = 30.0;
+
+/// Detects the spoken language without transcribing (`POST /v1/audio/language`).
+///
+/// Only the first `duration` seconds (at most 30) are inspected.
+pub async fn audio_language(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let api_key = require_auth(&state.cfg, &headers)?;
+    let mut form = read_audio_form(&state, &headers, api_key, &mut multipart).await?;
+    if form.files.len() > 1 {
         return Err(AppError::invalid_request(
-            "uploaded file is empty",
+            "language detection takes a single file",
             Some("file"),
-            Some("empty_file"),
+            None,
         ));
     }
-
-    if model.is_empty() {
+    if form.stream {
         return Err(AppError::invalid_request(
-            "model must not be empty",
-            Some("model"),
-            Some("invalid_model"),
+            "stream=true is only supported for transcriptions",
+            Some("stream"),
+            Some("invalid_stream"),
         ));
     }
+    let backend = resolve_backend(&state, &form.model, form.compute)?;
+    form.deadline = state
+        .cfg
+        .request_timeout
+        .map(|timeout| Instant::now() + timeout);
+    let file = form.files.remove(0);
+    let mut body =
+        within_deadline(form.deadline, detect_language(&state, backend, &form, file)).await?;
+    insert_metadata(&mut body, form.metadata.as_deref());
+    Ok(Json(body).into_response())
+}
 
-    Ok(AudioForm {
-        extension,
-        bytes,
-        model,
-        language,
-        prompt,
-        response_format,
-        temperature,
-    })
+/// Decodes the first `detect_secs` seconds of `file` and runs language detection on them.
+///
+/// Only that window is decoded and budgeted for, so `MAX_AUDIO_SECONDS` does
+/// not apply.
+async fn detect_language(
+    state: &AppState,
+    backend: Arc<dyn Transcriber>,
+    form: &AudioForm,
+    file: UploadedFile,
+) -> Result<serde_json::Value, AppError> {
+    let window_secs = form.detect_secs.unwrap_or(LANGUAGE_DETECTION_MAX_SECS);
+    let (request, _memory) = prepare_request(
+        state,
+        TaskKind::Transcribe,
+        form,
+        file,
+        state.cfg.max_queue_wait,
+        Some((f64::from(window_secs) * 16_000.0) as usize),
+    )
+    .await?;
+    let audio_secs = audio_duration_secs(&request);
+    if is_silent(&request.audio_16khz_mono_f32) {
+        let mut body = json!({"language": null});
+        insert_no_speech(&mut body, Some(NoSpeechReason::SilentAudio));
+        insert_usage(&mut body, Some(usage(audio_secs, Duration::ZERO)));
+        return Ok(body);
+    }
+
+    let _slot = acquire_inference_slot(state, backend.as_ref()).await?;
+    let started = Instant::now();
+    let ranked = backend
+        .detect_language(request.audio_16khz_mono_f32)
+        .await?;
+    let elapsed = started.elapsed();
+    state
+        .metrics
+        .timing("inference_duration", elapsed, &[("task", "language")]);
+
+    let (language, probability) = ranked
+        .first()
+        .map(|(language, probability)| (language.clone(), *probability))
+        .unzip();
+    let mut body = json!({
+        "language": language,
+        "probability": probability,
+        "language_probabilities": ranked
+            .into_iter()
+            .map(|(language, probability)| json!({"language": language, "probability": probability}))
+            .collect::<Vec<_>>(),
+    });
+    insert_usage(&mut body, Some(usage(audio_secs, elapsed)));
+    Ok(body)
 }
 
-/// Verifies that the requested model id is supported by current configuration.
-fn validate_requested_model(cfg: &AppConfig, requested_model: &str) -> Result<(), AppError> {
-    if cfg
-        .accepted_model_ids()
-        .iter()
-        .any(|id| id == requested_model)
-    {
-        return Ok(());
+/// Reserves memory for one uploaded file, decodes it, and builds the backend request.
+///
+/// An estimate is reserved before decoding, so concurrent decodes count
+/// against the budget, and resized to the decoded audio afterwards. Both
+/// wait up to `max_queue_wait` (or indefinitely); the reservation must be
+/// held until inference finishes. With `max_samples` set, only the start of
+/// the audio is decoded and `MAX_AUDIO_SECONDS` is not checked.
+async fn prepare_request(
+    state: &AppState,
+    task: TaskKind,
+    form: &AudioForm,
+    file: UploadedFile,
+    max_queue_wait: Option<Duration>,
+    max_samples: Option<usize>,
+) -> Result<(TranscribeRequest, MemoryReservation), AppError> {
+    let upload_bytes = file.body.resident_bytes();
+    let mut memory = match &state.memory_budget {
+        Some(budget) => {
+            let samples = estimated_samples(file.body.len(), &file.extension)
+                .min(max_samples.unwrap_or(usize::MAX));
+            let cost = request_memory_cost(upload_bytes, samples);
+            budget
+                .reserve_estimate(cost, max_queue_wait)
+                .await
+                .map_err(|err| {
+                    state.metrics.incr("memory_budget_rejections", &[]);
+                    err
+                })?
+        }
+        None => MemoryReservation::unlimited(),
+    };
+    let body = file.body;
+    let extension_hint = file.extension;
+    let decode_started = Instant::now();
+    let ffmpeg_bin = state.cfg.ffmpeg_bin.clone();
+    let quality = form.resample_quality;
+    let downmix = form.downmix;
+    let denoise = form.denoise;
+    let max_duration = state
+        .cfg
+        .max_audio_duration
+        .filter(|_| max_samples.is_none());
+    let (decoded, denoise_elapsed) = tokio::task::spawn_blocking(move || {
+        let mut decoded = body.decode(
+            &extension_hint,
+            ffmpeg_bin.as_deref(),
+            quality,
+            downmix,
+            max_samples,
+        )?;
+        // Checked before denoising so over-long audio is not processed further.
+        check_audio_duration(decoded.samples.len(), max_duration)?;
+        let denoise_elapsed = denoise.then(|| {
+            let started = Instant::now();
+            decoded.samples = denoise::denoise(&decoded.samples);
+            started.elapsed()
+        });
+        Ok::<_, AppError>((decoded, denoise_elapsed))
+    })
+    .await
+    .map_err(|err| AppError::internal(format!("audio decode task failed: {err}")))??;
+    let denoise_elapsed = denoise_elapsed.unwrap_or_default();
+    let decode_elapsed = decode_started.elapsed().saturating_sub(denoise_elapsed);
+    state.metrics.timing("decode_duration", decode_elapsed, &[]);
+    debug_trace::stage("decode", decode_elapsed);
+    if denoise {
+        state
+            .metrics
+            .timing("denoise_duration", denoise_elapsed, &[]);
+        debug_trace::stage("denoise", denoise_elapsed);
+    }
+    if let Some(elapsed) = decoded.resample_elapsed {
+        state.metrics.timing(
+            "resample_duration",
+            elapsed,
+            &[("quality", quality.as_str())],
+        );
+        // Part of `decode`, reported separately to show the resampler's share.
+        debug_trace::stage("resample", elapsed);
+    }
+    debug_trace::event(
+        "decoded",
+        json!({"audio_secs": decoded.samples.len() as f64 / 16_000.0}),
+    );
+    let audio_16khz_mono_f32 = decoded.samples;
+
+    if let Some(budget) = &state.memory_budget {
+        let cost = request_memory_cost(upload_bytes, audio_16khz_mono_f32.len());
+        budget
+            .resize(&mut memory, cost, max_queue_wait)
+            .await
+            .map_err(|err| {
+                state.metrics.incr("memory_budget_rejections", &[]);
+                err
+            })?;
+    }
+
+    let request = TranscribeRequest {
+        task,
+        audio_16khz_mono_f32,
+        language: form.language.clone(),
+        prompt: form.prompt.clone(),
+        temperature: form.temperature,
+        max_tokens_per_segment: form
+            .max_tokens_per_segment
+            .or(state.cfg.whisper_max_tokens_per_segment),
+        beam_size: form.beam_size.or(state.cfg.whisper_beam_size),
+        best_of: form.best_of.or(state.cfg.whisper_best_of),
+        // Only `verbose_json` lists the probabilities.
+        language_probs: form.response_format == ResponseFormat::VerboseJson,
+        deadline: form.deadline,
+    };
+    Ok((request, memory))
+}
+
+/// Fails `work` with a `504` once `deadline` passes.
+///
+/// Dropping `work` stops waiting on decoding and the worker queue; inference
+/// itself is aborted by the backend through `TranscribeRequest::deadline`.
+async fn within_deadline<T>(
+    deadline: Option<Instant>,
+    work: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let Some(deadline) = deadline else {
+        return work.await;
+    };
+    tokio::time::timeout_at(deadline.into(), work)
+        .await
+        .unwrap_or_else(|_| Err(request_timed_out()))
+}
+
+/// `504` error for requests that exceed `REQUEST_TIMEOUT_SECS`.
+fn request_timed_out() -> AppError {
+    AppError::timeout("request exceeded REQUEST_TIMEOUT_SECS before transcription finished")
+}
+
+/// Runs inference on a free worker, skipping it entirely for silent audio.
+///
+/// With `vad` set, long silences are cut before inference and segment times
+/// mapped back onto the original audio. Audio longer than `CHUNK_SECS` is
+/// transcribed in windows. `hooks` follow the request through the admission
+/// queue.
+async fn run_transcription(
+    state: &AppState,
+    backend: &dyn Transcriber,
+    mut request: TranscribeRequest,
+    vad: Option<VadParams>,
+    max_queue_wait: Option<Duration>,
+    hooks: &dyn AdmissionHooks,
+) -> Result<TranscriptResult, AppError> {
+    let audio_secs = audio_duration_secs(&request);
+    if is_silent(&request.audio_16khz_mono_f32) {
+        debug_trace::event("silent_audio", json!({"skipped_inference": true}));
+        let mut result = TranscriptResult::no_speech(request.language, NoSpeechReason::SilentAudio);
+        result.usage = Some(usage(audio_secs, Duration::ZERO));
+        return Ok(result);
+    }
+    let audio_warnings = audio_warnings(&request);
+    let max_tokens_per_segment = request.max_tokens_per_segment;
+    let speech_map = apply_vad(&mut request, vad);
+    let inference_secs = audio_duration_secs(&request);
+    let hooks = &TimelineHooks {
+        hooks,
+        admission: true,
+        offset_secs: 0.0,
+        speech_map: speech_map.as_ref(),
+    };
+    let windows = state.cfg.chunking.and_then(|params| {
+        let windows = params.plan(request.audio_16khz_mono_f32.len())?;
+        Some((params, windows))
+    });
+    let (mut result, inference_time) = match windows {
+        Some((params, windows)) => {
+            transcribe_windows(
+                state,
+                backend,
+                request,
+                params,
+                windows,
+                max_queue_wait,
+                hooks,
+            )
+            .await?
+        }
+        None => infer(state, backend, request, max_queue_wait, hooks).await?,
+    };
+    correct_timestamps(&mut result, inference_secs);
+    if let Some(map) = &speech_map {
+        restore_segment_times(&mut result.segments, map);
+    }
+    add_warnings(&mut result, audio_warnings, max_tokens_per_segment);
+    result.usage = Some(usage(audio_secs, inference_time));
+    Ok(result)
+}
+
+/// Transcribes `request` on a free worker; returns the result and inference time.
+async fn infer(
+    state: &AppState,
+    backend: &dyn Transcriber,
+    request: TranscribeRequest,
+    max_queue_wait: Option<Duration>,
+    hooks: &dyn AdmissionHooks,
+) -> Result<(TranscriptResult, Duration), AppError> {
+    let _slot = acquire_inference_slot_within(state, backend, max_queue_wait, hooks).await?;
+    infer_on_slot(state, backend, request, hooks).await
+}
+
+/// Transcribes `request` on the worker slot the caller already holds.
+async fn infer_on_slot(
+    state: &AppState,
+    backend: &dyn Transcriber,
+    request: TranscribeRequest,
+    hooks: &dyn AdmissionHooks,
+) -> Result<(TranscriptResult, Duration), AppError> {
+    let task = request.task;
+    let inference_started = Instant::now();
+    let result = if hooks.wants_segments() {
+        let (segment_tx, mut segment_rx) = mpsc::unbounded_channel();
+        let forward = async {
+            while let Some(segment) = segment_rx.recv().await {
+                hooks.segment(segment);
+            }
+        };
+        tokio::join!(backend.transcribe_stream(request, segment_tx), forward).0?
+    } else {
+        backend.transcribe(request).await?
+    };
+    let inference_time = inference_started.elapsed();
+    debug_trace::stage("inference", inference_time);
+    state.admission(backend).1.record_inference(inference_time);
+    state.metrics.timing(
+        "inference_duration",
+        inference_time,
+        &[("task", task.as_str())],
+    );
+    Ok((result, inference_time))
+}
+
+/// Transcribes long audio window by window and stitches the results.
+///
+/// Every window waits for a worker of its own, so other requests are admitted
+/// between windows instead of waiting for the whole recording. Sequential
+/// windows reuse the language detected in the first; with `params.parallel`
+/// all windows queue at once, and each copies its slice of the audio only
+/// once it has a worker. `hooks` follow the first window, and the returned
+/// time is the inference time of all windows together.
+async fn transcribe_windows(
+    state: &AppState,
+    backend: &dyn Transcriber,
+    mut request: TranscribeRequest,
+    params: ChunkParams,
+    windows: Vec<Range<usize>>,
+    max_queue_wait: Option<Duration>,
+    hooks: &dyn AdmissionHooks,
+) -> Result<(TranscriptResult, Duration), AppError> {
+    debug!(
+        windows = windows.len(),
+        parallel = params.parallel,
+        "transcribing long audio in windows"
+    );
+    debug_trace::event(
+        "chunking",
+        json!({"windows": windows.len(), "parallel": params.parallel}),
+    );
+    let audio = std::mem::take(&mut request.audio_16khz_mono_f32);
+    let window_request = |range: &Range<usize>, language: Option<String>| TranscribeRequest {
+        audio_16khz_mono_f32: audio[range.clone()].to_vec(),
+        language,
+        ..request.clone()
+    };
+    let window_hooks = |idx: usize, range: &Range<usize>| TimelineHooks {
+        hooks,
+        admission: idx == 0,
+        offset_secs: range.start as f64 / 16_000.0,
+        speech_map: None,
+    };
+
+    let mut stitcher = Stitcher::new(windows.clone());
+    let mut inference_time = Duration::ZERO;
+    let mut join = |idx: usize, (mut result, elapsed): (TranscriptResult, Duration)| {
+        inference_time += elapsed;
+        correct_timestamps(&mut result, windows[idx].len() as f64 / 16_000.0);
+        let stitched = stitcher.push(idx, result).to_vec();
+        if !stitched.is_empty() {
+            hooks.stitched(stitcher.language(), &stitched);
+        }
+    };
+
+    if params.parallel {
+        let mut runs = windows
+            .iter()
+            .enumerate()
+            .map(|(idx, range)| {
+                let hooks = window_hooks(idx, range);
+                let language = request.language.clone();
+                let window_request = &window_request;
+                async move {
+                    let outcome = async {
+                        let _slot =
+                            acquire_inference_slot_within(state, backend, max_queue_wait, &hooks)
+                                .await?;
+                        let request = window_request(range, language);
+                        infer_on_slot(state, backend, request, &hooks).await
+                    };
+                    (idx, outcome.await)
+                }
+            })
+            .collect::<FuturesUnordered<_>>();
+        while let Some((idx, outcome)) = runs.next().await {
+            join(idx, outcome?);
+        }
+    } else {
+        let mut language = request.language.clone();
+        for (idx, range) in windows.iter().enumerate() {
+            let request = window_request(range, language.clone());
+            let hooks = window_hooks(idx, range);
+            let outcome = infer(state, backend, request, max_queue_wait, &hooks).await?;
+            language = language.or_else(|| outcome.0.language.clone());
+            join(idx, outcome);
+        }
+    }
+    Ok((stitcher.finish(), inference_time))
+}
+
+/// Cuts long silences out of the request audio when the VAD filter is on.
+///
+/// Returns the map back to the original timeline, or `None` when nothing was cut.
+fn apply_vad(request: &mut TranscribeRequest, vad: Option<VadParams>) -> Option<SpeechMap> {
+    let map = trim_silence(&mut request.audio_16khz_mono_f32, vad?)?;
+    debug!(
+        removed_secs = map.removed_secs(),
+        "vad filter removed silence before inference"
+    );
+    debug_trace::event("vad", json!({"removed_secs": map.removed_secs()}));
+    Some(map)
+}
+
+/// Moves segment times from VAD-trimmed audio back onto the original recording.
+fn restore_segment_times(segments: &mut [TranscriptSegment], map: &SpeechMap) {
+    for segment in segments {
+        segment.start_secs = map.original_secs(segment.start_secs, false);
+        segment.end_secs = map.original_secs(segment.end_secs, true);
+        for word in &mut segment.words {
+            word.start_secs = map.original_secs(word.start_secs, false);
+            word.end_secs = map.original_secs(word.end_secs, true);
+        }
+    }
+}
+
+/// Builds the `usage` reported for `audio_secs` of audio processed in `elapsed`.
+fn usage(audio_secs: f64, elapsed: Duration) -> Usage {
+    Usage {
+        audio_secs,
+        processing_ms: elapsed.as_millis() as u64,
     }
+}
 
+/// Rejects decoded audio of `samples` 16 kHz samples that runs past `max_duration`.
+fn check_audio_duration(samples: usize, max_duration: Option<Duration>) -> Result<(), AppError> {
+    let Some(max_duration) = max_duration else {
+        return Ok(());
+    };
+    let secs = samples as f64 / 16_000.0;
+    if secs <= max_duration.as_secs_f64() {
+        return Ok(());
+    }
     Err(AppError::invalid_request(
         format!(
-            "unsupported model={requested_model:?}; accepted models: {}",
-            cfg.accepted_model_ids().join(",")
+            "audio is {secs:.1} seconds long; the maximum is {} seconds",
+            max_duration.as_secs()
         ),
-        Some("model"),
-        Some("invalid_model"),
+        Some("file"),
+        Some("audio_too_long"),
     ))
 }
 
-/// Enforces optional bearer-token authentication.
-fn require_auth(cfg: &AppConfig, headers: &HeaderMap) -> Result<(), AppError> {
-    let Some(expected_api_key) = cfg.api_key.as_deref() else {
-        return Ok(());
+/// Share of full-scale samples above which the audio is reported as clipped.
+const CLIPPED_WARNING_FRACTION: f64 = 0.001;
+/// Probability of the detected language below which detection is reported as uncertain.
+const LOW_LANGUAGE_CONFIDENCE: f32 = 0.5;
+
+/// Quality caveats visible in the request audio itself, found before inference.
+fn audio_warnings(request: &TranscribeRequest) -> Vec<TranscriptWarning> {
+    let clipped = clipped_fraction(&request.audio_16khz_mono_f32);
+    if clipped > CLIPPED_WARNING_FRACTION {
+        return vec![TranscriptWarning::new(
+            "audio_clipped",
+            format!(
+                "{:.1}% of the audio is at full scale; clipping distorts speech and can cause misrecognitions",
+                clipped * 100.0
+            ),
+        )];
+    }
+    Vec::new()
+}
+
+/// Adds `audio` warnings and those visible in the finished transcript to `result`.
+fn add_warnings(
+    result: &mut TranscriptResult,
+    audio: Vec<TranscriptWarning>,
+    max_tokens_per_segment: Option<u32>,
+) {
+    result.warnings.splice(0..0, audio);
+    if let Some((language, probability)) = result.language_probs.first() {
+        if *probability < LOW_LANGUAGE_CONFIDENCE {
+            result.warnings.push(TranscriptWarning::new(
+                "low_language_confidence",
+                format!(
+                    "detected language {language:?} with probability {probability:.2}; set `language` if it is wrong"
+                ),
+            ));
+        }
+    }
+    if let Some(limit) = max_tokens_per_segment {
+        let truncated = result
+            .segments
+            .iter()
+            .filter(|seg| seg.token_count >= limit as usize)
+            .count();
+        if truncated > 0 {
+            result.warnings.push(TranscriptWarning::new(
+                "segments_truncated",
+                format!(
+                    "{truncated} segment(s) reached max_tokens_per_segment={limit} and may be cut short"
+                ),
+            ));
+        }
+    }
+}
+
+/// Length of the request audio in seconds.
+fn audio_duration_secs(request: &TranscribeRequest) -> f64 {
+    request.audio_16khz_mono_f32.len() as f64 / 16_000.0
+}
+
+/// Repairs impossible segment timings before the transcript is formatted.
+fn correct_timestamps(result: &mut TranscriptResult, audio_secs: f64) {
+    let corrected = sanitize_timestamps(&mut result.segments, audio_secs);
+    if corrected > 0 {
+        debug!(
+            corrected,
+            audio_secs, "corrected impossible segment timestamps"
+        );
+    }
+}
+
+/// Applies the server's text normalization and the request's post-processing
+/// options to a finished transcript.
+///
+/// Heuristic punctuation runs after normalization, only on English
+/// transcripts, and rebuilds `text` from the corrected segments; merging then
+/// uses the guessed sentence ends.
+fn postprocess_transcript(
+    text: TextNormalization,
+    form: &AudioForm,
+    mut result: TranscriptResult,
+) -> TranscriptResult {
+    text.apply(&mut result);
+    let english = result.language.as_deref().map_or(true, |lang| lang == "en");
+    if form.heuristic_punctuation && english && !result.segments.is_empty() {
+        guess_punctuation(&mut result.segments);
+        result.text = text.join_segments(&result.segments);
+    }
+    if form.merge_segments {
+        result.segments = merge_segments(result.segments);
+    }
+    result
+}
+
+/// Transcript formatted for a `response_format`, independent of how it is delivered.
+enum RenderedTranscript {
+    Json(serde_json::Value),
+    Text {
+        content_type: &'static str,
+        body: String,
+    },
+}
+
+impl RenderedTranscript {
+    /// Converts the transcript into the `result` value of an async job.
+    fn into_job_result(self) -> serde_json::Value {
+        match self {
+            Self::Json(body) => body,
+            Self::Text { body, .. } => serde_json::Value::String(body),
+        }
+    }
+}
+
+impl IntoResponse for RenderedTranscript {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Json(body) => Json(body).into_response(),
+            Self::Text { content_type, body } => {
+                ([(header::CONTENT_TYPE, content_type)], body).into_response()
+            }
+        }
+    }
+}
+
+/// How a transcript is rendered for the client.
+#[derive(Debug, Clone, Copy)]
+struct RenderOptions {
+    format: ResponseFormat,
+    /// Adds OpenAI-style `logprobs` to `json` output.
+    include_logprobs: bool,
+    /// Cue time format of `srt` and `vtt` output.
+    subtitle_timing: SubtitleTiming,
+    /// Adds a top-level `words` list to `verbose_json` output.
+    word_timestamps: bool,
+}
+
+impl RenderOptions {
+    /// Plain rendering in `format`.
+    fn new(format: ResponseFormat) -> Self {
+        Self {
+            format,
+            include_logprobs: false,
+            subtitle_timing: SubtitleTiming::Millis,
+            word_timestamps: false,
+        }
+    }
+}
+
+fn render_transcript(
+    task: TaskKind,
+    mut result: TranscriptResult,
+    options: RenderOptions,
+) -> RenderedTranscript {
+    let warnings = std::mem::take(&mut result.warnings);
+    let no_speech = result.no_speech;
+    let avg_logprob = result.avg_logprob();
+    let usage = result.usage;
+    let logprobs = options
+        .include_logprobs
+        .then(|| logprobs_json(&result.segments));
+    let words = options
+        .word_timestamps
+        .then(|| words_json(&result.segments));
+    match options.format {
+        ResponseFormat::Json => {
+            let mut body = json!({"text": result.text});
+            if let Some(language) = result.language {
+                body["language"] = json!(language);
+            }
+            insert_confidence(&mut body, avg_logprob);
+            insert_no_speech(&mut body, no_speech);
+            insert_usage(&mut body, usage);
+            insert_warnings(&mut body, &warnings);
+            if let Some(logprobs) = logprobs {
+                body["logprobs"] = logprobs;
+            }
+            RenderedTranscript::Json(body)
+        }
+        ResponseFormat::Text => RenderedTranscript::Text {
+            content_type: "text/plain; charset=utf-8",
+            body: result.text,
+        },
+        ResponseFormat::Srt => RenderedTranscript::Text {
+            content_type: "application/x-subrip; charset=utf-8",
+            body: segments_to_srt(&result.segments, options.subtitle_timing),
+        },
+        ResponseFormat::Vtt => RenderedTranscript::Text {
+            content_type: "text/vtt; charset=utf-8",
+            body: segments_to_vtt(&result.segments, options.subtitle_timing),
+        },
+        ResponseFormat::VerboseJson => {
+            let language = result.language.unwrap_or_else(|| "unknown".to_string());
+            let segments = result
+                .segments
+                .into_iter()
+                .enumerate()
+                .map(|(idx, seg)| {
+                    // No `seek`: whisper.cpp does not report which 30 s window a segment came from.
+                    let mut segment = json!({
+                        "id": idx,
+                        "start": seg.start_secs,
+                        "end": seg.end_secs,
+                        "text": seg.text,
+                        "tokens": seg.tokens,
+                        "compression_ratio": compression_ratio(&seg.text),
+                    });
+                    if let Some(avg_logprob) = seg.avg_logprob {
+                        segment["avg_logprob"] = json!(avg_logprob);
+                    }
+                    if let Some(no_speech_prob) = seg.no_speech_prob {
+                        segment["no_speech_prob"] = json!(no_speech_prob);
+                    }
+                    segment
+                })
+                .collect::<Vec<_>>();
+
+            let mut body = json!({
+                "task": task.as_str(),
+                "language": language,
+                "text": result.text,
+                "segments": segments,
+            });
+            insert_confidence(&mut body, avg_logprob);
+            insert_no_speech(&mut body, no_speech);
+            insert_usage(&mut body, usage);
+            insert_warnings(&mut body, &warnings);
+            if !result.language_probs.is_empty() {
+                body["language_probabilities"] = result
+                    .language_probs
+                    .into_iter()
+                    .map(|(language, probability)| {
+                        json!({"language": language, "probability": probability})
+                    })
+                    .collect();
+            }
+            if let Some(words) = words {
+                body["words"] = words;
+            }
+            if let Some(logprobs) = logprobs {
+                body["logprobs"] = logprobs;
+            }
+            RenderedTranscript::Json(body)
+        }
+    }
+}
+
+/// OpenAI-style `words` for `timestamp_granularities[]=word`: every word of
+/// the transcript in order, with its start and end in seconds.
+fn words_json(segments: &[TranscriptSegment]) -> serde_json::Value {
+    segments
+        .iter()
+        .flat_map(|seg| &seg.words)
+        .map(|word| {
+            json!({
+                "word": word.word,
+                "start": word.start_secs,
+                "end": word.end_secs,
+            })
+        })
+        .collect()
+}
+
+/// OpenAI-style token `logprobs` for `include[]=logprobs`: text tokens in order,
+/// each with its log-probability and UTF-8 bytes.
+fn logprobs_json(segments: &[TranscriptSegment]) -> serde_json::Value {
+    segments
+        .iter()
+        .flat_map(|seg| &seg.token_logprobs)
+        .map(|token| {
+            json!({
+                "token": token.token,
+                "logprob": token.logprob,
+                "bytes": token.token.as_bytes(),
+            })
+        })
+        .collect()
+}
+
+/// Registers an async job, runs it in the background, and returns `202 Accepted`.
+///
+/// With `ASYNC_JOB_DIR` set, the job is persisted before it is acknowledged.
+async fn submit_job(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    form: AudioForm,
+) -> Result<Response, AppError> {
+    let id = state
+        .jobs
+        .create(task, form.metadata.clone(), form.api_key.clone())?;
+    let mut job = state
+        .jobs
+        .get(&id)
+        .ok_or_else(|| AppError::internal("async job disappeared after creation"))?;
+    let (slots, queue) = state.admission(backend.as_ref());
+    if slots.available_permits() == 0 {
+        insert_queue_status(&mut job, queue.next_status());
+    }
+
+    if let Some(spool) = &state.job_spool {
+        let created_at = job["created_at"].as_u64().unwrap_or_else(unix_secs);
+        let bodies = form.files.iter().map(|file| &file.body).collect::<Vec<_>>();
+        if let Err(err) = spool
+            .save(&spooled_job(&id, task, created_at, &form), &bodies)
+            .await
+        {
+            spool.remove(&id);
+            let message = format!("failed to persist async job: {err}");
+            state.jobs.fail(&id, AppError::internal(message.clone()));
+            return Err(AppError::internal(message));
+        }
+    }
+    spawn_job(state, backend, task, form, id);
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+/// Runs an async job in the background, then drops its spooled copy.
+///
+/// Jobs wait for a worker without the `MAX_QUEUE_WAIT_SECS` limit, since the
+/// client is not holding a connection open.
+fn spawn_job(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    form: AudioForm,
+    job_id: String,
+) {
+    tokio::spawn(async move {
+        run_job(&state, backend, task, form, &job_id).await;
+        if let Some(spool) = &state.job_spool {
+            spool.remove(&job_id);
+        }
+    });
+}
+
+async fn run_job(
+    state: &Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    mut form: AudioForm,
+    job_id: &str,
+) {
+    let hooks = JobHooks {
+        state,
+        job_id,
+        segments: form.files.len() == 1,
     };
+    if form.files.len() > 1 {
+        let body = transcribe_batch(state, backend, task, form, None, &hooks).await;
+        state.jobs.complete(job_id, None, body);
+        return;
+    }
 
-    let Some(raw) = headers.get(header::AUTHORIZATION) else {
-        return Err(AppError::unauthorized("missing bearer token"));
+    let file = form.files.remove(0);
+    let outcome = match prepare_request(state, task, &form, file, None, None).await {
+        Ok((request, _memory)) => {
+            let vad = form.vad_filter.then_some(form.vad_params);
+            run_transcription(state, backend.as_ref(), request, vad, None, &hooks).await
+        }
+        Err(err) => Err(err),
     };
+    match outcome {
+        Ok(result) => {
+            let result = postprocess_transcript(state.cfg.text_normalization, &form, result);
+            if !hooks.purged() {
+                archive_transcript(
+                    state,
+                    Some(job_id),
+                    task,
+                    form.render_options(),
+                    form.metadata.as_deref(),
+                    form.api_key.as_deref(),
+                    &result,
+                );
+            }
+            let language = result.language.clone();
+            let rendered = render_transcript(task, result, form.render_options());
+            state
+                .jobs
+                .complete(job_id, language, rendered.into_job_result());
+        }
+        Err(err) => {
+            state.metrics.incr("async_job_failures", &[]);
+            state.jobs.fail(job_id, err);
+        }
+    }
+}
 
-    let value = raw
-        .to_str()
-        .map_err(|_| AppError::unauthorized("invalid authorization header"))?;
+/// Manifest persisted for a queued job.
+fn spooled_job(id: &str, task: TaskKind, created_at: u64, form: &AudioForm) -> SpooledJob {
+    SpooledJob {
+        id: id.to_string(),
+        task: task.as_str().to_string(),
+        created_at,
+        model: form.model.clone(),
+        language: form.language.clone(),
+        prompt: form.prompt.clone(),
+        response_format: form.response_format.to_string(),
+        temperature: form.temperature,
+        max_tokens_per_segment: form.max_tokens_per_segment,
+        beam_size: form.beam_size,
+        best_of: form.best_of,
+        merge_segments: form.merge_segments,
+        heuristic_punctuation: form.heuristic_punctuation,
+        vad_filter: form.vad_filter,
+        vad_min_silence_ms: Some(form.vad_params.min_silence_ms),
+        vad_padding_ms: Some(form.vad_params.padding_ms),
+        resample_quality: Some(form.resample_quality.as_str().to_string()),
+        denoise: form.denoise,
+        channel: match form.downmix {
+            Downmix::Channel(channel) => Some(channel),
+            Downmix::Average => None,
+        },
+        metadata: form.metadata.clone(),
+        compute: form.compute.map(|compute| compute.as_str().to_string()),
+        include_logprobs: form.include_logprobs,
+        word_timestamps: form.word_timestamps,
+        frame_rate: match form.subtitle_timing {
+            SubtitleTiming::Smpte { rate, .. } => Some(rate.as_str().to_string()),
+            SubtitleTiming::Millis => None,
+        },
+        drop_frame: matches!(
+            form.subtitle_timing,
+            SubtitleTiming::Smpte {
+                drop_frame: true,
+                ..
+            }
+        ),
+        api_key: form.api_key.clone(),
+        files: form
+            .files
+            .iter()
+            .map(|file| SpooledFile {
+                filename: file.filename.clone(),
+                extension: file.extension.clone(),
+            })
+            .collect(),
+    }
+}
 
-    let mut parts = value.split_whitespace();
-    let scheme = parts
-        .next()
-        .ok_or_else(|| AppError::unauthorized("missing bearer token"))?;
-    let token = parts
-        .next()
-        .filter(|v| !v.is_empty())
-        .ok_or_else(|| AppError::unauthorized("missing bearer token"))?;
-    if parts.next().is_some() || !scheme.eq_ignore_ascii_case("bearer") {
-        return Err(AppError::unauthorized("missing bearer token"));
+/// Resubmits the jobs left queued in `ASYNC_JOB_DIR` by a previous run.
+///
+/// Returns how many jobs were resumed; jobs whose model is no longer
+/// configured are marked `failed`.
+pub fn resume_spooled_jobs(state: &Arc<AppState>) -> Result<usize, AppError> {
+    let Some(spool) = &state.job_spool else {
+        return Ok(0);
+    };
+    let queued = spool
+        .load_queued()
+        .map_err(|err| AppError::internal(format!("failed to read ASYNC_JOB_DIR: {err}")))?;
+    let mut resumed = 0;
+    for (job, bodies) in queued {
+        let task = match job.task.as_str() {
+            "translate" => TaskKind::Translate,
+            _ => TaskKind::Transcribe,
+        };
+        state.jobs.restore(
+            &job.id,
+            task,
+            job.created_at,
+            job.metadata.clone(),
+            job.api_key.clone(),
+        );
+        let form = AudioForm {
+            files: job
+                .files
+                .into_iter()
+                .zip(bodies)
+                .map(|(file, body)| UploadedFile {
+                    filename: file.filename,
+                    extension: file.extension,
+                    body,
+                })
+                .collect(),
+            model: job.model,
+            language: job.language,
+            prompt: job.prompt,
+            response_format: ResponseFormat::parse(&job.response_format)
+                .unwrap_or(ResponseFormat::Json),
+            temperature: job.temperature,
+            max_tokens_per_segment: job.max_tokens_per_segment,
+            beam_size: job.beam_size,
+            best_of: job.best_of,
+            stream: false,
+            merge_segments: job.merge_segments,
+            heuristic_punctuation: job.heuristic_punctuation,
+            vad_filter: job.vad_filter,
+            vad_params: VadParams {
+                min_silence_ms: job
+                    .vad_min_silence_ms
+                    .unwrap_or(state.cfg.vad_params.min_silence_ms),
+                padding_ms: job
+                    .vad_padding_ms
+                    .unwrap_or(state.cfg.vad_params.padding_ms),
+            },
+            resample_quality: job
+                .resample_quality
+                .and_then(|quality| ResampleQuality::from_str(&quality, true).ok())
+                .unwrap_or(state.cfg.resample_quality),
+            denoise: job.denoise && denoise::AVAILABLE,
+            downmix: job.channel.map_or(Downmix::Average, Downmix::Channel),
+            metadata: job.metadata,
+            api_key: job.api_key,
+            compute: job
+                .compute
+                .and_then(|compute| Compute::from_str(&compute, true).ok()),
+            include_logprobs: job.include_logprobs,
+            subtitle_timing: job
+                .frame_rate
+                .as_deref()
+                .and_then(|rate| FrameRate::parse(rate).ok())
+                .map_or(SubtitleTiming::Millis, |rate| SubtitleTiming::Smpte {
+                    rate,
+                    drop_frame: job.drop_frame,
+                }),
+            word_timestamps: job.word_timestamps,
+            detect_secs: None,
+            max_chars: None,
+            overflow: Overflow::default(),
+            debug_trace: false,
+            deadline: None,
+        };
+        match resolve_backend(state, &form.model, form.compute) {
+            Ok(backend) => {
+                spawn_job(Arc::clone(state), backend, task, form, job.id);
+                resumed += 1;
+            }
+            Err(err) => {
+                spool.remove(&job.id);
+                state.jobs.fail(&job.id, err);
+            }
+        }
     }
+    Ok(resumed)
+}
 
-    if token != expected_api_key {
-        return Err(AppError::unauthorized("invalid token"));
+/// Rejects batch (multi-file) requests for formats that cannot be combined into a list.
+fn validate_batch_request(form: &AudioForm) -> Result<(), AppError> {
+    if form.stream {
+        return Err(AppError::invalid_request(
+            "stream=true supports a single file per request",
+            Some("stream"),
+            Some("invalid_stream"),
+        ));
+    }
+    if !matches!(
+        form.response_format,
+        ResponseFormat::Json | ResponseFormat::VerboseJson
+    ) {
+        return Err(AppError::invalid_request(
+            format!(
+                "response_format={} is not supported with multiple files; use json or verbose_json",
+                form.response_format
+            ),
+            Some("response_format"),
+            Some("invalid_response_format"),
+        ));
     }
+    Ok(())
+}
 
+/// Rejects parameters the model's backend cannot honour rather than ignoring them.
+fn validate_capabilities(
+    state: &AppState,
+    task: TaskKind,
+    form: &AudioForm,
+    capabilities: &Capabilities,
+) -> Result<(), AppError> {
+    if form.word_timestamps && !capabilities.word_timestamps {
+        return Err(unsupported_by_model(
+            state,
+            &form.model,
+            "word timestamps",
+            "timestamp_granularities[]",
+            |supported| supported.word_timestamps,
+        ));
+    }
+    if form.stream && !capabilities.streaming {
+        return Err(unsupported_by_model(
+            state,
+            &form.model,
+            "streaming",
+            "stream",
+            |supported| supported.streaming,
+        ));
+    }
+    if task == TaskKind::Translate && !capabilities.translation_targets.contains(&"en") {
+        return Err(unsupported_by_model(
+            state,
+            &form.model,
+            "translation into English",
+            "model",
+            |supported| supported.translation_targets.contains(&"en"),
+        ));
+    }
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use std::sync::Arc;
+/// `501` error for a parameter the model's backend does not support, naming
+/// the served models whose capabilities pass `supports`.
+pub(crate) fn unsupported_by_model(
+    state: &AppState,
+    model: &str,
+    feature: &str,
+    param: &str,
+    supports: impl Fn(&Capabilities) -> bool,
+) -> AppError {
+    let supported_models = state
+        .cfg
+        .accepted_model_ids()
+        .into_iter()
+        .filter(|id| supports(&model_capabilities(state, id)))
+        .collect::<Vec<_>>();
+    let hint = if supported_models.is_empty() {
+        "no served model does".to_string()
+    } else {
+        format!("use one of: {}", supported_models.join(", "))
+    };
+    AppError::not_implemented(
+        format!("model={model:?} does not support {feature}; {hint}"),
+        param,
+        supported_models,
+    )
+}
 
-    use async_trait::async_trait;
-    use axum::body::{to_bytes, Body};
-    use axum::http::{Request, StatusCode};
-    use serde_json::Value;
-    use tower::ServiceExt;
+/// Checks that `max_chars` is used with a single-file, synchronous JSON response.
+fn validate_max_chars_request(form: &AudioForm, async_job: bool) -> Result<(), AppError> {
+    let supported = !form.stream
+        && !async_job
+        && form.files.len() == 1
+        && matches!(
+            form.response_format,
+            ResponseFormat::Json | ResponseFormat::VerboseJson
+        );
+    if supported {
+        return Ok(());
+    }
+    Err(AppError::invalid_request(
+        "max_chars requires a single file, response_format json or verbose_json, and no stream or async",
+        Some("max_chars"),
+        Some("invalid_max_chars"),
+    ))
+}
+
+/// Rejects `debug_trace=true` unless `DEBUG_TRACE_DIR` is set and the request is
+/// a synchronous, single-file request without `stream`.
+fn validate_debug_trace_request(
+    state: &AppState,
+    form: &AudioForm,
+    async_job: bool,
+) -> Result<(), AppError> {
+    if state.debug_traces.is_none() {
+        return Err(AppError::invalid_request(
+            "debug_trace requires DEBUG_TRACE_DIR to be set on the server",
+            Some("debug_trace"),
+            Some("debug_trace_unavailable"),
+        ));
+    }
+    if form.stream || async_job || form.files.len() > 1 {
+        return Err(AppError::invalid_request(
+            "debug_trace requires a single file and no stream or async",
+            Some("debug_trace"),
+            Some("invalid_debug_trace"),
+        ));
+    }
+    Ok(())
+}
+
+/// Transcribes every uploaded file, sharing the worker pool, and returns a result list.
+///
+/// Each entry is the file's usual JSON body plus `index` and `filename`; a file
+/// that fails carries an `error` object instead so the rest of the batch still
+/// succeeds.
+async fn transcribe_batch(
+    state: &Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    mut form: AudioForm,
+    max_queue_wait: Option<Duration>,
+    hooks: &dyn AdmissionHooks,
+) -> serde_json::Value {
+    let files = std::mem::take(&mut form.files);
+    let entries = files.into_iter().enumerate().map(|(index, file)| {
+        let (form, backend) = (&form, &backend);
+        async move {
+            let filename = file.filename.clone();
+            let outcome = within_deadline(form.deadline, async {
+                let (request, _memory) =
+                    prepare_request(state, task, form, file, max_queue_wait, None).await?;
+                let vad = form.vad_filter.then_some(form.vad_params);
+                run_transcription(state, backend.as_ref(), request, vad, max_queue_wait, hooks)
+                    .await
+            })
+            .await;
+            let mut entry = match outcome {
+                Ok(result) => {
+                    let result = postprocess_transcript(state.cfg.text_normalization, form, result);
+                    if !hooks.purged() {
+                        archive_transcript(
+                            state,
+                            None,
+                            task,
+                            form.render_options(),
+                            form.metadata.as_deref(),
+                            form.api_key.as_deref(),
+                            &result,
+                        );
+                    }
+                    render_transcript(task, result, form.render_options()).into_job_result()
+                }
+                Err(err) => json!({"error": err.into_json()["error"].take()}),
+            };
+            entry["index"] = json!(index);
+            entry["filename"] = json!(filename);
+            entry
+        }
+    });
+    let data = futures_util::future::join_all(entries).await;
+    let mut body = json!({"object": "list", "data": data});
+    insert_metadata(&mut body, form.metadata.as_deref());
+    body
+}
+
+/// Writes a completed transcript to `TRANSCRIPT_ARCHIVE_DIR` in the background.
+///
+/// `api_key` is the label of the requesting key, recorded for purges. Archive
+/// failures are logged and counted but never fail the request.
+fn archive_transcript(
+    state: &Arc<AppState>,
+    id: Option<&str>,
+    task: TaskKind,
+    options: RenderOptions,
+    metadata: Option<&str>,
+    api_key: Option<&str>,
+    result: &TranscriptResult,
+) {
+    if state.archive.is_none() {
+        return;
+    }
+    let id = id.map_or_else(|| new_id("tr_"), ToOwned::to_owned);
+    let mut json = render_transcript(
+        task,
+        result.clone(),
+        RenderOptions::new(ResponseFormat::VerboseJson),
+    )
+    .into_job_result();
+    insert_metadata(&mut json, metadata);
+    if let Some(api_key) = api_key {
+        json["api_key"] = json!(api_key);
+    }
+    let options = RenderOptions {
+        include_logprobs: false,
+        ..options
+    };
+    let format = options.format;
+    let rendered = match render_transcript(task, result.clone(), options) {
+        RenderedTranscript::Text { body, .. } => Some(body),
+        RenderedTranscript::Json(_) => None,
+    };
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        let Some(archive) = &state.archive else {
+            return;
+        };
+        if let Err(err) = archive
+            .save(&id, unix_secs(), &json, format, rendered.as_deref())
+            .await
+        {
+            warn!(id = %id, error = %err, "failed to archive transcript");
+            state.metrics.incr("archive_failures", &[]);
+        }
+    });
+}
+
+/// Reports status and, once finished, the result of an async job (`GET /v1/audio/jobs/{id}`).
+pub async fn audio_job(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let api_key = require_auth(&state.cfg, &headers)?;
+    state
+        .jobs
+        .get_for(&id, api_key.map(|key| key.label.as_str()))
+        .map(Json)
+        .ok_or_else(|| AppError::not_found(format!("no job found with id {id:?}"), Some("id")))
+}
+
+/// Returns the next page of a transcript split by `max_chars`
+/// (`GET /v1/audio/continuations/{token}`).
+pub async fn audio_continuation(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_auth(&state.cfg, &headers)?;
+    state.continuations.get(&token).map(Json).ok_or_else(|| {
+        AppError::not_found(
+            format!("no transcript page found for continuation token {token:?}"),
+            Some("token"),
+        )
+    })
+}
+
+/// Reads the `async` flag from the request query string.
+fn parse_async_query(query: Option<&str>) -> Result<bool, AppError> {
+    let raw = query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .find(|(key, _)| *key == "async")
+        .map(|(_, value)| value);
+    match raw {
+        None | Some("") => Ok(false),
+        Some(raw) => parse_bool_param(raw, "async"),
+    }
+}
+
+/// Parses a `true`/`false` request parameter.
+fn parse_bool_param(raw: &str, param: &str) -> Result<bool, AppError> {
+    match raw.to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(AppError::invalid_request(
+            format!("invalid {param}={raw:?}; expected true or false"),
+            Some(param),
+            Some(&format!("invalid_{param}")),
+        )),
+    }
+}
+
+/// Rejects `stream=true` for `verbose_json`.
+fn validate_stream_request(format: ResponseFormat) -> Result<(), AppError> {
+    if format == ResponseFormat::VerboseJson {
+        return Err(AppError::invalid_request(
+            "stream=true requires response_format json, text, srt, or vtt",
+            Some("stream"),
+            Some("invalid_stream"),
+        ));
+    }
+    Ok(())
+}
+
+/// Streams a transcription or translation as Server-Sent Events.
+///
+/// Emits one `transcript.text.delta` event per decoded segment (translated
+/// into English for translations) and finishes with
+/// `transcript.text.done` carrying the authoritative full result (or an `error`
+/// event if inference fails after the stream has started). While every worker
+/// is busy, `transcript.queued` events report the queue position instead.
+async fn stream_transcription(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    mut request: TranscribeRequest,
+    memory: MemoryReservation,
+    vad: Option<VadParams>,
+    form: &AudioForm,
+) -> Result<Response, AppError> {
+    let metadata = form.metadata.clone();
+    let include_logprobs = form.include_logprobs;
+    let api_key = form.api_key.clone();
+    let (segment_tx, segment_rx) = mpsc::unbounded_channel();
+    let (done_tx, done_rx) = oneshot::channel();
+    let (spot_tx, spot_rx) = oneshot::channel();
+    let mut speech_map = None;
+    if is_silent(&request.audio_16khz_mono_f32) {
+        let audio_secs = audio_duration_secs(&request);
+        let mut result = TranscriptResult::no_speech(request.language, NoSpeechReason::SilentAudio);
+        result.usage = Some(usage(audio_secs, Duration::ZERO));
+        archive_transcript(
+            &state,
+            None,
+            task,
+            RenderOptions::new(ResponseFormat::Json),
+            metadata.as_deref(),
+            api_key.as_deref(),
+            &result,
+        );
+        let _ = done_tx.send(Ok(result));
+    } else {
+        check_queue_depth(&state, Some(backend.as_ref()))?;
+        let metadata = metadata.clone();
+        let audio_secs = audio_duration_secs(&request);
+        let audio_warnings = audio_warnings(&request);
+        let max_tokens_per_segment = request.max_tokens_per_segment;
+        speech_map = apply_vad(&mut request, vad).map(Arc::new);
+        let inference_secs = audio_duration_secs(&request);
+        let speech_map = speech_map.clone();
+        tokio::spawn(async move {
+            let _memory = memory;
+            let hooks = StreamHooks(std::sync::Mutex::new(Some(spot_tx)));
+            let slot = acquire_inference_slot_within(
+                &state,
+                backend.as_ref(),
+                state.cfg.max_queue_wait,
+                &hooks,
+            )
+            .await;
+            drop(hooks);
+            let _slot = match slot {
+                Ok(slot) => slot,
+                Err(err) => {
+                    let _ = done_tx.send(Err(err));
+                    return;
+                }
+            };
+            let inference_started = Instant::now();
+            let mut result = backend.transcribe_stream(request, segment_tx).await;
+            let inference_time = inference_started.elapsed();
+            state
+                .admission(backend.as_ref())
+                .1
+                .record_inference(inference_time);
+            if let Ok(result) = &mut result {
+                correct_timestamps(result, inference_secs);
+                if let Some(map) = &speech_map {
+                    restore_segment_times(&mut result.segments, map);
+                }
+                state.cfg.text_normalization.apply(result);
+                add_warnings(result, audio_warnings, max_tokens_per_segment);
+                result.usage = Some(usage(audio_secs, inference_time));
+            }
+            state.metrics.timing(
+                "inference_duration",
+                inference_time,
+                &[("task", task.as_str())],
+            );
+            if let Ok(result) = &result {
+                archive_transcript(
+                    &state,
+                    None,
+                    task,
+                    RenderOptions::new(ResponseFormat::Json),
+                    metadata.as_deref(),
+                    api_key.as_deref(),
+                    result,
+                );
+            }
+            let _ = done_tx.send(result);
+        });
+    }
+
+    let queued = stream::once(spot_rx)
+        .filter_map(|spot| async move { spot.ok() })
+        .flat_map(queued_events);
+    let deltas = stream::unfold(segment_rx, |mut rx| async move {
+        rx.recv().await.map(|segment| (segment, rx))
+    })
+    .enumerate()
+    .map(move |(idx, mut segment)| {
+        if let Some(map) = &speech_map {
+            restore_segment_times(std::slice::from_mut(&mut segment), map);
+        }
+        delta_event(idx, segment)
+    });
+    let done = stream::once(async move {
+        match done_rx.await {
+            Ok(Ok(result)) => done_event(result, metadata.as_deref(), include_logprobs),
+            Ok(Err(err)) => error_event(err),
+            Err(_) => error_event(AppError::internal(
+                "transcription task ended without a result",
+            )),
+        }
+    });
+
+    let events = queued.chain(deltas).chain(done).map(Ok::<_, Infallible>);
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Streams `srt` or `vtt` subtitles, writing cues as chunking windows finish.
+///
+/// With `CHUNK_SECS` set, the cues of each window are sent once every earlier
+/// window is done, postprocessed on their own; the remaining cues follow when
+/// transcription finishes. Errors before the first cue are answered as usual;
+/// later ones abort the body.
+async fn stream_subtitles(
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    task: TaskKind,
+    request: TranscribeRequest,
+    memory: MemoryReservation,
+    vad: Option<VadParams>,
+    form: AudioForm,
+) -> Result<Response, AppError> {
+    let text_normalization = state.cfg.text_normalization;
+    let metadata = form.metadata.clone();
+    let (content_type, mut writer) = match form.response_format {
+        ResponseFormat::Vtt => (
+            "text/vtt; charset=utf-8",
+            CueWriter::vtt(form.subtitle_timing),
+        ),
+        _ => (
+            "application/x-subrip; charset=utf-8",
+            CueWriter::srt(form.subtitle_timing),
+        ),
+    };
+    let form = Arc::new(form);
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let transcribing = Arc::clone(&form);
+    tokio::spawn(async move {
+        let _memory = memory;
+        let form = transcribing;
+        let hooks = SubtitleHooks(event_tx.clone());
+        let result = within_deadline(
+            form.deadline,
+            run_transcription(
+                &state,
+                backend.as_ref(),
+                request,
+                vad,
+                state.cfg.max_queue_wait,
+                &hooks,
+            ),
+        )
+        .await
+        .map(|result| postprocess_transcript(state.cfg.text_normalization, &form, result));
+        if let Ok(result) = &result {
+            archive_transcript(
+                &state,
+                None,
+                task,
+                form.render_options(),
+                form.metadata.as_deref(),
+                form.api_key.as_deref(),
+                result,
+            );
+        }
+        let _ = event_tx.send(SubtitleEvent::Done(result));
+    });
+
+    let first = match event_rx.recv().await {
+        Some(SubtitleEvent::Done(Err(err))) => return Err(err),
+        Some(event) => event,
+        None => {
+            return Err(AppError::internal(
+                "transcription task ended without a result",
+            ))
+        }
+    };
+    let rest = (!matches!(first, SubtitleEvent::Done(_))).then_some(event_rx);
+    let events = stream::once(async { first }).chain(stream::unfold(rest, |rx| async move {
+        let mut rx = rx?;
+        match rx.recv().await {
+            Some(event) => {
+                let rest = (!matches!(event, SubtitleEvent::Done(_))).then_some(rx);
+                Some((event, rest))
+            }
+            None => {
+                let err = AppError::internal("transcription task ended without a result");
+                Some((SubtitleEvent::Done(Err(err)), None))
+            }
+        }
+    }));
+    let header = writer.header();
+    let mut sent = 0;
+    let cues = events.map(move |event| match event {
+        SubtitleEvent::Stitched(language, segments) => {
+            let batch = TranscriptResult {
+                text: String::new(),
+                language,
+                segments,
+                no_speech: None,
+                language_probs: Vec::new(),
+                usage: None,
+                warnings: Vec::new(),
+            };
+            let batch = postprocess_transcript(text_normalization, &form, batch);
+            sent += batch.segments.len();
+            Ok(writer.write(&batch.segments))
+        }
+        SubtitleEvent::Done(Ok(result)) => {
+            let rest = result.segments.get(sent..).unwrap_or_default();
+            Ok(writer.write(rest) + writer.finish())
+        }
+        SubtitleEvent::Done(Err(err)) => Err(err),
+    });
+    let body = stream::once(async move { Ok(header.to_string()) }).chain(cues);
+
+    let mut response = (
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(body),
+    )
+        .into_response();
+    if let Some(value) = metadata.and_then(|raw| HeaderValue::from_str(&raw).ok()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(CLIENT_METADATA_HEADER), value);
+    }
+    Ok(response)
+}
+
+/// Progress of a streamed `srt` or `vtt` transcription.
+enum SubtitleEvent {
+    /// Stitched segments no later window can change, with the transcript's language.
+    Stitched(Option<String>, Vec<TranscriptSegment>),
+    /// The postprocessed transcript, or why transcription failed.
+    Done(Result<TranscriptResult, AppError>),
+}
+
+/// Hands stitched segments to a streamed subtitle body.
+struct SubtitleHooks(mpsc::UnboundedSender<SubtitleEvent>);
+
+impl AdmissionHooks for SubtitleHooks {
+    fn stitched(&self, language: Option<&str>, segments: &[TranscriptSegment]) {
+        let language = language.map(ToOwned::to_owned);
+        let _ = self
+            .0
+            .send(SubtitleEvent::Stitched(language, segments.to_vec()));
+    }
+}
+
+/// Hands a streamed request's queue spot to its event stream.
+struct StreamHooks(std::sync::Mutex<Option<oneshot::Sender<QueueSpot>>>);
+
+impl AdmissionHooks for StreamHooks {
+    fn queued(&self, spot: QueueSpot) {
+        if let Some(tx) = self.0.lock().ok().and_then(|mut tx| tx.take()) {
+            let _ = tx.send(spot);
+        }
+    }
+}
+
+/// How often a queued stream checks whether its position changed.
+const QUEUE_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Emits a `transcript.queued` event whenever the queue status at `spot`
+/// changes, ending once the request is admitted.
+fn queued_events(spot: QueueSpot) -> impl futures_util::Stream<Item = Event> {
+    stream::unfold((spot, None), |(spot, last)| async move {
+        loop {
+            let status = spot.status()?;
+            if last != Some(status) {
+                return Some((queued_event(status), (spot, Some(status))));
+            }
+            tokio::time::sleep(QUEUE_EVENT_INTERVAL).await;
+        }
+    })
+}
+
+fn queued_event(status: QueueStatus) -> Event {
+    let mut payload = json!({"type": "transcript.queued"});
+    insert_queue_status(&mut payload, status);
+    Event::default().data(payload.to_string())
+}
+
+fn delta_event(idx: usize, segment: TranscriptSegment) -> Event {
+    let delta = if idx == 0 {
+        segment.text.clone()
+    } else {
+        format!(" {}", segment.text)
+    };
+    let payload = json!({
+        "type": "transcript.text.delta",
+        "delta": delta,
+        "segment": {
+            "id": idx,
+            "start": segment.start_secs,
+            "end": segment.end_secs,
+            "text": segment.text,
+        },
+    });
+    Event::default().data(payload.to_string())
+}
+
+fn done_event(result: TranscriptResult, metadata: Option<&str>, include_logprobs: bool) -> Event {
+    let avg_logprob = result.avg_logprob();
+    let logprobs = include_logprobs.then(|| logprobs_json(&result.segments));
+    let mut payload = json!({"type": "transcript.text.done", "text": result.text});
+    if let Some(language) = result.language {
+        payload["language"] = json!(language);
+    }
+    insert_confidence(&mut payload, avg_logprob);
+    insert_no_speech(&mut payload, result.no_speech);
+    insert_usage(&mut payload, result.usage);
+    insert_warnings(&mut payload, &result.warnings);
+    insert_metadata(&mut payload, metadata);
+    if let Some(logprobs) = logprobs {
+        payload["logprobs"] = logprobs;
+    }
+    Event::default().data(payload.to_string())
+}
+
+fn error_event(err: AppError) -> Event {
+    let mut payload = err.into_json();
+    payload["type"] = json!("error");
+    Event::default().data(payload.to_string())
+}
+
+/// Adds transcript-level `avg_logprob` and `confidence` fields when available.
+///
+/// `confidence` is the geometric-mean token probability, `exp(avg_logprob)`.
+fn insert_confidence(body: &mut serde_json::Value, avg_logprob: Option<f64>) {
+    if let Some(avg_logprob) = avg_logprob {
+        body["avg_logprob"] = json!(avg_logprob);
+        body["confidence"] = json!((avg_logprob.exp() * 10_000.0).round() / 10_000.0);
+    }
+}
+
+/// Marks an intentionally empty transcript with `no_speech` and its reason.
+fn insert_no_speech(body: &mut serde_json::Value, no_speech: Option<NoSpeechReason>) {
+    if let Some(reason) = no_speech {
+        body["no_speech"] = json!(true);
+        body["no_speech_reason"] = json!(reason.as_str());
+    }
+}
+
+/// Adds an OpenAI-style `usage` block metering audio seconds and processing time.
+fn insert_usage(body: &mut serde_json::Value, usage: Option<Usage>) {
+    if let Some(usage) = usage {
+        body["usage"] = json!({
+            "type": "duration",
+            "seconds": (usage.audio_secs * 1000.0).round() / 1000.0,
+            "processing_ms": usage.processing_ms,
+        });
+    }
+}
+
+/// Lists quality caveats as `warnings`, omitted when there are none.
+fn insert_warnings(body: &mut serde_json::Value, warnings: &[TranscriptWarning]) {
+    if !warnings.is_empty() {
+        body["warnings"] = warnings
+            .iter()
+            .map(|warning| json!({"code": warning.code, "message": warning.message}))
+            .collect();
+    }
+}
+
+/// Echoes the client's opaque `metadata` form field.
+fn insert_metadata(body: &mut serde_json::Value, metadata: Option<&str>) {
+    if let Some(metadata) = metadata {
+        body["metadata"] = json!(metadata);
+    }
+}
+
+/// Waits for a free inference worker, honoring `MAX_QUEUE_WAIT_SECS`.
+pub(crate) async fn acquire_inference_slot(
+    state: &AppState,
+    backend: &dyn Transcriber,
+) -> Result<OwnedSemaphorePermit, AppError> {
+    check_queue_depth(state, Some(backend))?;
+    acquire_inference_slot_within(state, backend, state.cfg.max_queue_wait, &()).await
+}
+
+/// Rejects new work with `429` when every worker is busy and `MAX_QUEUE_DEPTH`
+/// requests are already waiting, instead of growing the queue without bound.
+///
+/// A model with a pool of its own is checked against its own workers and
+/// queue limit; `None` checks the default workers.
+fn check_queue_depth(state: &AppState, backend: Option<&dyn Transcriber>) -> Result<(), AppError> {
+    let (slots, waiting, max_depth, workers) =
+        match backend.and_then(|backend| backend.dedicated_pool()) {
+            Some(pool) => (
+                &pool.slots,
+                pool.queue.waiting(),
+                pool.max_depth,
+                pool.workers,
+            ),
+            None => (
+                &state.inference_slots,
+                state.queued_requests.load(Ordering::Relaxed),
+                state.cfg.max_queue_depth,
+                state.cfg.whisper_parallelism,
+            ),
+        };
+    let Some(max_depth) = max_depth else {
+        return Ok(());
+    };
+    if slots.available_permits() == 0 && waiting >= max_depth {
+        state.metrics.incr("queue_full_rejections", &[]);
+        return Err(AppError::queue_full(
+            format!(
+                "{waiting} requests are already waiting for {workers} busy inference workers; retry later"
+            ),
+            QUEUE_FULL_RETRY_AFTER_SECS,
+        ));
+    }
+    Ok(())
+}
+
+/// Waits up to `max_wait` (or indefinitely) for a free worker of `backend`'s pool.
+///
+/// A request that has to wait takes a place in the pool's [`QueueTracker`],
+/// reported to `hooks` so its position can be shown to the client.
+async fn acquire_inference_slot_within(
+    state: &AppState,
+    backend: &dyn Transcriber,
+    max_wait: Option<Duration>,
+    hooks: &dyn AdmissionHooks,
+) -> Result<OwnedSemaphorePermit, AppError> {
+    let queued_at = Instant::now();
+    // Dedicated pools count their waiters in their own queue tracker.
+    let _queued = backend.dedicated_pool().is_none().then(|| {
+        state.queued_requests.fetch_add(1, Ordering::Relaxed);
+        QueuedRequest(&state.queued_requests)
+    });
+    let (slots, queue) = state.admission(backend);
+    if let Ok(permit) = Arc::clone(slots).try_acquire_owned() {
+        state.metrics.timing("queue_wait", queued_at.elapsed(), &[]);
+        debug_trace::stage("queue_wait", queued_at.elapsed());
+        hooks.admitted();
+        return Ok(permit);
+    }
+    let ticket = queue.join();
+    hooks.queued(ticket.spot());
+    let acquire = Arc::clone(slots).acquire_owned();
+    let permit = match max_wait {
+        Some(max_wait) => match tokio::time::timeout(max_wait, acquire).await {
+            Ok(permit) => permit,
+            Err(_) => {
+                state.metrics.incr("queue_timeouts", &[]);
+                return Err(AppError::overloaded(format!(
+                    "request waited more than {}s for a free inference worker; retry later",
+                    max_wait.as_secs_f64()
+                )));
+            }
+        },
+        None => acquire.await,
+    }
+    .map_err(|_| AppError::internal("inference admission queue closed"))?;
+    drop(ticket);
+    state.metrics.timing("queue_wait", queued_at.elapsed(), &[]);
+    debug_trace::stage("queue_wait", queued_at.elapsed());
+    hooks.admitted();
+    Ok(permit)
+}
+
+/// Notified as a request moves through the admission queue.
+trait AdmissionHooks: Sync {
+    /// The request found every worker busy and is waiting at `spot`.
+    fn queued(&self, _spot: QueueSpot) {}
+
+    /// The request acquired an inference worker.
+    fn admitted(&self) {}
+
+    /// Whether the request's data was purged while it ran, so its result is not archived.
+    fn purged(&self) -> bool {
+        false
+    }
+
+    /// Whether inference should report segments to [`AdmissionHooks::segment`].
+    fn wants_segments(&self) -> bool {
+        false
+    }
+
+    /// A segment was decoded, timed on the original recording; fallback
+    /// passes may still revise it.
+    fn segment(&self, _segment: TranscriptSegment) {}
+
+    /// Chunking stitched these segments, in `language`, and no later window
+    /// can change them; they are timed on the original recording.
+    fn stitched(&self, _language: Option<&str>, _segments: &[TranscriptSegment]) {}
+}
+
+impl AdmissionHooks for () {}
+
+/// Moves the segments reported to `hooks` onto the original recording's
+/// timeline: past the start of a chunking window, then out of VAD-trimmed time.
+struct TimelineHooks<'a> {
+    hooks: &'a dyn AdmissionHooks,
+    /// Whether queue and admission events are passed on; only a request's
+    /// first window reports them.
+    admission: bool,
+    offset_secs: f64,
+    speech_map: Option<&'a SpeechMap>,
+}
+
+impl AdmissionHooks for TimelineHooks<'_> {
+    fn queued(&self, spot: QueueSpot) {
+        if self.admission {
+            self.hooks.queued(spot);
+        }
+    }
+
+    fn admitted(&self) {
+        if self.admission {
+            self.hooks.admitted();
+        }
+    }
+
+    fn purged(&self) -> bool {
+        self.hooks.purged()
+    }
+
+    fn wants_segments(&self) -> bool {
+        self.hooks.wants_segments()
+    }
+
+    fn segment(&self, mut segment: TranscriptSegment) {
+        segment.start_secs += self.offset_secs;
+        segment.end_secs += self.offset_secs;
+        for word in &mut segment.words {
+            word.start_secs += self.offset_secs;
+            word.end_secs += self.offset_secs;
+        }
+        if let Some(map) = self.speech_map {
+            restore_segment_times(std::slice::from_mut(&mut segment), map);
+        }
+        self.hooks.segment(segment);
+    }
+
+    /// Stitched segments are already placed past their window's start.
+    fn stitched(&self, language: Option<&str>, segments: &[TranscriptSegment]) {
+        match self.speech_map {
+            Some(map) => {
+                let mut segments = segments.to_vec();
+                restore_segment_times(&mut segments, map);
+                self.hooks.stitched(language, &segments);
+            }
+            None => self.hooks.stitched(language, segments),
+        }
+    }
+}
+
+/// Shows an async job's queue position, marks it running once admitted, and
+/// records the segments of a single-file job as they are decoded.
+struct JobHooks<'a> {
+    state: &'a AppState,
+    job_id: &'a str,
+    /// Whether decoded segments are recorded; batch jobs only report results.
+    segments: bool,
+}
+
+impl AdmissionHooks for JobHooks<'_> {
+    fn queued(&self, spot: QueueSpot) {
+        self.state.jobs.set_queue_spot(self.job_id, spot);
+    }
+
+    fn admitted(&self) {
+        self.state.jobs.set_running(self.job_id);
+        if let Some(spool) = &self.state.job_spool {
+            spool.mark_started(self.job_id);
+        }
+    }
+
+    fn purged(&self) -> bool {
+        !self.state.jobs.contains(self.job_id)
+    }
+
+    fn wants_segments(&self) -> bool {
+        self.segments
+    }
+
+    fn segment(&self, segment: TranscriptSegment) {
+        self.state.jobs.add_segment(self.job_id, &segment);
+    }
+}
+
+/// Removes a request from `queued_requests` when it stops waiting.
+struct QueuedRequest<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedRequest<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Parses and validates multipart form fields for audio endpoints.
+///
+/// `defaults` are the requesting key's values for omitted fields, which take
+/// the place of the server defaults but lose to anything the client sends.
+async fn parse_audio_form(
+    cfg: &AppConfig,
+    defaults: Option<&KeyDefaults>,
+    multipart: &mut Multipart,
+) -> Result<AudioForm, AppError> {
+    let mut files = Vec::new();
+    let mut model = "whisper-1".to_string();
+    let mut language: Option<String> = None;
+    let mut source_language: Option<String> = None;
+    let mut prompt: Option<String> = None;
+    let mut response_format: Option<ResponseFormat> = None;
+    let mut temperature: Option<f32> = None;
+    let mut max_tokens_per_segment: Option<u32> = None;
+    let mut beam_size: Option<u32> = None;
+    let mut best_of: Option<u32> = None;
+    let mut stream = false;
+    let mut merge_segments: Option<bool> = None;
+    let mut heuristic_punctuation: Option<bool> = None;
+    let mut vad_filter: Option<bool> = None;
+    let mut resample_quality: Option<ResampleQuality> = None;
+    let mut denoise: Option<bool> = None;
+    let mut preset: Option<String> = None;
+    let mut downmix = Downmix::Average;
+    let mut metadata: Option<String> = None;
+    let mut compute: Option<Compute> = None;
+    let mut include_logprobs = false;
+    let mut timecode: Option<String> = None;
+    let mut frame_rate: Option<FrameRate> = None;
+    let mut drop_frame = false;
+    let mut word_timestamps = false;
+    let mut detect_secs: Option<f32> = None;
+    let mut max_chars: Option<usize> = None;
+    let mut overflow = Overflow::default();
+    let mut debug_trace = false;
+
+    let mut part_count = 0usize;
+    let mut field_count = 0usize;
+    let mut upload_bytes = 0usize;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| multipart_error("invalid multipart body", err, cfg))?
+    {
+        part_count += 1;
+        if part_count > cfg.multipart_max_parts {
+            return Err(AppError::bad_multipart(format!(
+                "multipart body has more than {} parts",
+                cfg.multipart_max_parts
+            )));
+        }
+        let Some(name) = field.name().map(ToOwned::to_owned) else {
+            continue;
+        };
+
+        if name == "file" {
+            let filename = field
+                .file_name()
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| AppError::bad_multipart("file field is missing filename"))?;
+            let (body, head) = read_upload(field, cfg, upload_bytes).await?;
+            upload_bytes += body.len();
+            if body.is_empty() {
+                return Err(AppError::invalid_request(
+                    "uploaded file is empty",
+                    Some("file"),
+                    Some("empty_file"),
+                ));
+            }
+            let extension = resolve_extension(&filename, &head, cfg.ffmpeg_bin.is_some())?;
+            files.push(UploadedFile {
+                filename,
+                extension,
+                body,
+            });
+            continue;
+        }
+
+        field_count += 1;
+        if field_count > cfg.multipart_max_fields {
+            return Err(AppError::bad_multipart(format!(
+                "multipart body has more than {} form fields",
+                cfg.multipart_max_fields
+            )));
+        }
+        // Unknown fields are read too so oversized values are rejected rather than skipped.
+        let raw = read_text_field(field, &name, cfg.multipart_max_field_bytes).await?;
+
+        match name.as_str() {
+            "model" => model = raw,
+            "language" => language = Some(raw).filter(|v| !v.is_empty()),
+            "source_language" => source_language = Some(raw).filter(|v| !v.is_empty()),
+            "prompt" => prompt = Some(raw).filter(|v| !v.is_empty()),
+            "response_format" => response_format = Some(ResponseFormat::parse(&raw)?),
+            "temperature" if !raw.is_empty() => {
+                let value = raw.parse::<f32>().map_err(|_| {
+                    AppError::invalid_request(
+                        format!("invalid temperature={raw:?}; expected float"),
+                        Some("temperature"),
+                        Some("invalid_temperature"),
+                    )
+                })?;
+                if !value.is_finite() {
+                    return Err(AppError::invalid_request(
+                        format!("invalid temperature={raw:?}; expected a finite float"),
+                        Some("temperature"),
+                        Some("invalid_temperature"),
+                    ));
+                }
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(AppError::invalid_request(
+                        format!(
+                            "invalid temperature={raw:?}; expected a value in range [0.0, 1.0]"
+                        ),
+                        Some("temperature"),
+                        Some("invalid_temperature"),
+                    ));
+                }
+                temperature = Some(value);
+            }
+            "max_tokens_per_segment" if !raw.is_empty() => {
+                let value = parse_max_tokens_per_segment(&raw).map_err(|expected| {
+                    AppError::invalid_request(
+                        format!("invalid max_tokens_per_segment={raw:?}; {expected}"),
+                        Some("max_tokens_per_segment"),
+                        Some("invalid_max_tokens_per_segment"),
+                    )
+                })?;
+                max_tokens_per_segment = Some(value);
+            }
+            "beam_size" if !raw.is_empty() => {
+                let value = parse_decoders(&raw).map_err(|expected| {
+                    AppError::invalid_request(
+                        format!("invalid beam_size={raw:?}; {expected}"),
+                        Some("beam_size"),
+                        Some("invalid_beam_size"),
+                    )
+                })?;
+                beam_size = Some(value);
+            }
+            "best_of" if !raw.is_empty() => {
+                let value = parse_decoders(&raw).map_err(|expected| {
+                    AppError::invalid_request(
+                        format!("invalid best_of={raw:?}; {expected}"),
+                        Some("best_of"),
+                        Some("invalid_best_of"),
+                    )
+                })?;
+                best_of = Some(value);
+            }
+            "stream" if !raw.is_empty() => stream = parse_bool_param(&raw, "stream")?,
+            "merge_segments" if !raw.is_empty() => {
+                merge_segments = Some(parse_bool_param(&raw, "merge_segments")?);
+            }
+            "heuristic_punctuation" | "restore_punctuation" if !raw.is_empty() => {
+                heuristic_punctuation = Some(parse_bool_param(&raw, "heuristic_punctuation")?);
+            }
+            "vad_filter" if !raw.is_empty() => {
+                vad_filter = Some(parse_bool_param(&raw, "vad_filter")?);
+            }
+            "metadata" if raw.len() > MAX_METADATA_BYTES => {
+                return Err(AppError::invalid_request(
+                    format!("metadata exceeds {MAX_METADATA_BYTES} bytes"),
+                    Some("metadata"),
+                    Some("invalid_metadata"),
+                ));
+            }
+            "metadata" => metadata = Some(raw).filter(|v| !v.is_empty()),
+            "max_chars" if !raw.is_empty() => {
+                let value = raw
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| {
+                        AppError::invalid_request(
+                            format!("invalid max_chars={raw:?}; expected a positive integer"),
+                            Some("max_chars"),
+                            Some("invalid_max_chars"),
+                        )
+                    })?;
+                max_chars = Some(value);
+            }
+            "overflow" if !raw.is_empty() => overflow = Overflow::parse(&raw)?,
+            "debug_trace" if !raw.is_empty() => {
+                debug_trace = parse_bool_param(&raw, "debug_trace")?;
+            }
+            "compute" if !raw.is_empty() => compute = Some(parse_compute(&raw)?),
+            "resample_quality" if !raw.is_empty() => {
+                resample_quality = Some(parse_resample_quality(&raw)?);
+            }
+            "channel" if !raw.is_empty() => downmix = parse_channel(&raw)?,
+            "denoise" if !raw.is_empty() => denoise = Some(parse_bool_param(&raw, "denoise")?),
+            "preset" if !raw.is_empty() => preset = Some(raw),
+            "include[]" | "include" if !raw.is_empty() => {
+                if raw != "logprobs" {
+                    return Err(AppError::invalid_request(
+                        format!("invalid include={raw:?}; expected logprobs"),
+                        Some("include[]"),
+                        Some("invalid_include"),
+                    ));
+                }
+                include_logprobs = true;
+            }
+            "timecode" if !raw.is_empty() => timecode = Some(raw),
+            "frame_rate" if !raw.is_empty() => frame_rate = Some(FrameRate::parse(&raw)?),
+            "drop_frame" if !raw.is_empty() => drop_frame = parse_bool_param(&raw, "drop_frame")?,
+            "timestamp_granularities[]" | "timestamp_granularities" if !raw.is_empty() => {
+                match raw.as_str() {
+                    "segment" => {}
+                    "word" => word_timestamps = true,
+                    _ => {
+                        return Err(AppError::invalid_request(
+                            format!(
+                                "invalid timestamp_granularities={raw:?}; expected segment or word"
+                            ),
+                            Some("timestamp_granularities[]"),
+                            Some("invalid_timestamp_granularities"),
+                        ))
+                    }
+                }
+            }
+            "duration" if !raw.is_empty() => {
+                let value = raw
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| *v > 0.0 && *v <= LANGUAGE_DETECTION_MAX_SECS)
+                    .ok_or_else(|| {
+                        AppError::invalid_request(
+                            format!(
+                                "invalid duration={raw:?}; expected seconds in range (0, {LANGUAGE_DETECTION_MAX_SECS}]"
+                            ),
+                            Some("duration"),
+                            Some("invalid_duration"),
+                        )
+                    })?;
+                detect_secs = Some(value);
+            }
+            _ => {}
+        }
+    }
+
+    if files.is_empty() {
+        return Err(AppError::invalid_request(
+            "missing required multipart field: file",
+            Some("file"),
+            None,
+        ));
+    }
+
+    if model.is_empty() {
+        return Err(AppError::invalid_request(
+            "model must not be empty",
+            Some("model"),
+            Some("invalid_model"),
+        ));
+    }
+
+    let defaults = defaults.cloned().unwrap_or_default();
+    let response_format = response_format
+        .or(defaults.response_format)
+        .unwrap_or(ResponseFormat::Json);
+    if include_logprobs
+        && !matches!(
+            response_format,
+            ResponseFormat::Json | ResponseFormat::VerboseJson
+        )
+    {
+        return Err(AppError::invalid_request(
+            "include[]=logprobs requires response_format json or verbose_json",
+            Some("include[]"),
+            Some("invalid_include"),
+        ));
+    }
+    if word_timestamps && response_format != ResponseFormat::VerboseJson {
+        return Err(AppError::invalid_request(
+            "timestamp_granularities[]=word requires response_format verbose_json",
+            Some("timestamp_granularities[]"),
+            Some("invalid_timestamp_granularities"),
+        ));
+    }
+    let subtitle_timing =
+        parse_subtitle_timing(timecode.as_deref(), frame_rate, drop_frame, response_format)?;
+    let language = resolve_source_language(language, source_language)?.or(defaults.language);
+
+    if denoise == Some(true) && !denoise::AVAILABLE {
+        return Err(denoise::unavailable());
+    }
+
+    // Explicit fields win over the preset, and the preset over server defaults.
+    let preset = match preset.or(defaults.preset) {
+        Some(name) => cfg.presets.get(&name).cloned().ok_or_else(|| {
+            AppError::invalid_request(
+                format!(
+                    "unknown preset={name:?}; expected one of {}",
+                    cfg.presets
+                        .keys()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Some("preset"),
+                Some("invalid_preset"),
+            )
+        })?,
+        None => Preset::default(),
+    };
+    let denoise = denoise.or(preset.denoise).unwrap_or(cfg.denoise);
+
+    Ok(AudioForm {
+        files,
+        model,
+        language,
+        prompt,
+        response_format,
+        temperature: temperature.or(preset.temperature),
+        max_tokens_per_segment: max_tokens_per_segment.or(preset.max_tokens_per_segment),
+        beam_size: beam_size.or(preset.beam_size),
+        best_of: best_of.or(preset.best_of),
+        stream,
+        merge_segments: merge_segments
+            .or(preset.merge_segments)
+            .unwrap_or(cfg.merge_segments),
+        heuristic_punctuation: heuristic_punctuation
+            .or(preset.heuristic_punctuation)
+            .unwrap_or(cfg.heuristic_punctuation),
+        vad_filter: vad_filter.or(preset.vad_filter).unwrap_or(cfg.vad_filter),
+        vad_params: VadParams {
+            min_silence_ms: preset
+                .vad_min_silence_ms
+                .unwrap_or(cfg.vad_params.min_silence_ms),
+            padding_ms: preset.vad_padding_ms.unwrap_or(cfg.vad_params.padding_ms),
+        },
+        resample_quality: resample_quality
+            .or(preset.resample_quality)
+            .unwrap_or(cfg.resample_quality),
+        denoise,
+        downmix,
+        metadata,
+        api_key: None,
+        compute,
+        include_logprobs,
+        subtitle_timing,
+        word_timestamps,
+        detect_secs,
+        max_chars,
+        overflow,
+        debug_trace,
+        deadline: None,
+    })
+}
+
+/// Builds the subtitle cue timing from the `timecode`, `frame_rate`, and
+/// `drop_frame` fields; SMPTE timecodes need a frame rate and a subtitle format.
+fn parse_subtitle_timing(
+    timecode: Option<&str>,
+    frame_rate: Option<FrameRate>,
+    drop_frame: bool,
+    format: ResponseFormat,
+) -> Result<SubtitleTiming, AppError> {
+    let invalid = |message: String| {
+        AppError::invalid_request(message, Some("timecode"), Some("invalid_timecode"))
+    };
+    match timecode.unwrap_or("ms") {
+        "ms" if frame_rate.is_none() && !drop_frame => Ok(SubtitleTiming::Millis),
+        "ms" => Err(invalid(
+            "frame_rate and drop_frame require timecode=smpte".to_string(),
+        )),
+        "smpte" => {
+            if !matches!(format, ResponseFormat::Srt | ResponseFormat::Vtt) {
+                return Err(invalid(format!(
+                    "timecode=smpte requires response_format srt or vtt, not {format}"
+                )));
+            }
+            let rate = frame_rate
+                .ok_or_else(|| invalid("timecode=smpte requires frame_rate".to_string()))?;
+            SubtitleTiming::smpte(rate, drop_frame)
+        }
+        other => Err(invalid(format!(
+            "invalid timecode={other:?}; expected ms or smpte"
+        ))),
+    }
+}
+
+/// Parses the `compute` field (`cpu` or `gpu`).
+pub(crate) fn parse_compute(raw: &str) -> Result<Compute, AppError> {
+    Compute::from_str(raw, true).map_err(|_| {
+        AppError::invalid_request(
+            format!("invalid compute={raw:?}; expected cpu or gpu"),
+            Some("compute"),
+            Some("invalid_compute"),
+        )
+    })
+}
+
+/// Resolves the spoken-language hint from `language` and `source_language`.
+///
+/// Both name the language of the audio, for translations as well as
+/// transcriptions (the translation target is always English); either may be
+/// sent, but sending both with different values is rejected.
+pub(crate) fn resolve_source_language(
+    language: Option<String>,
+    source_language: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let language = language.filter(|lang| !lang.trim().is_empty());
+    let source_language = source_language.filter(|lang| !lang.trim().is_empty());
+    match (language, source_language) {
+        (Some(language), Some(source))
+            if !language.trim().eq_ignore_ascii_case(source.trim()) =>
+        {
+            Err(AppError::invalid_request(
+                format!(
+                    "language={language:?} and source_language={source:?} disagree; both name the spoken language, so send one"
+                ),
+                Some("source_language"),
+                Some("conflicting_language"),
+            ))
+        }
+        (language, source) => Ok(source.or(language)),
+    }
+}
+
+/// Parses the `resample_quality` field (`fast`, `balanced`, or `high`).
+fn parse_resample_quality(raw: &str) -> Result<ResampleQuality, AppError> {
+    ResampleQuality::from_str(raw, true).map_err(|_| {
+        AppError::invalid_request(
+            format!("invalid resample_quality={raw:?}; expected fast, balanced, or high"),
+            Some("resample_quality"),
+            Some("invalid_resample_quality"),
+        )
+    })
+}
+
+/// Parses the `channel` field: a channel number from 0, or `average`.
+fn parse_channel(raw: &str) -> Result<Downmix, AppError> {
+    if raw.eq_ignore_ascii_case("average") {
+        return Ok(Downmix::Average);
+    }
+    raw.parse().map(Downmix::Channel).map_err(|_| {
+        AppError::invalid_request(
+            format!("invalid channel={raw:?}; expected a channel number from 0 or average"),
+            Some("channel"),
+            Some("invalid_channel"),
+        )
+    })
+}
+
+/// Reads a multipart text field of at most `max_bytes` and trims surrounding whitespace.
+async fn read_text_field(
+    mut field: Field<'_>,
+    name: &str,
+    max_bytes: usize,
+) -> Result<String, AppError> {
+    let mut value = Vec::new();
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|err| AppError::bad_multipart(format!("invalid {name} field: {err}")))?
+    {
+        if value.len() + chunk.len() > max_bytes {
+            return Err(AppError::bad_multipart(format!(
+                "multipart field {name:?} exceeds {max_bytes} bytes"
+            )));
+        }
+        value.extend_from_slice(&chunk);
+    }
+    String::from_utf8(value)
+        .map(|value| value.trim().to_string())
+        .map_err(|_| AppError::bad_multipart(format!("invalid {name} field: not valid UTF-8")))
+}
+
+/// Resolves the backend serving `requested_model`, loading on-demand sizes when enabled.
+pub(crate) fn resolve_backend(
+    state: &AppState,
+    requested_model: &str,
+    compute: Option<Compute>,
+) -> Result<Arc<dyn Transcriber>, AppError> {
+    let requested_model = state.cfg.resolve_model_alias(requested_model);
+    if let Some(backend) = state.extra_models.get(requested_model) {
+        return Ok(Arc::clone(backend));
+    }
+    let active = state.active_model();
+    if let Some(models) = &state.on_demand_models {
+        if let Some(size) = parse_on_demand_model_id(requested_model) {
+            if active.size == Some(size) {
+                return Ok(active.backend_for(compute));
+            }
+            return models.get_or_load(requested_model, size);
+        }
+    }
+    validate_requested_model(&state.cfg, requested_model)?;
+    Ok(active.backend_for(compute))
+}
+
+/// Verifies that the requested model id is supported by current configuration.
+fn validate_requested_model(cfg: &AppConfig, requested_model: &str) -> Result<(), AppError> {
+    if cfg
+        .accepted_model_ids()
+        .iter()
+        .any(|id| id == requested_model)
+    {
+        return Ok(());
+    }
+
+    Err(AppError::invalid_request(
+        format!(
+            "unsupported model={requested_model:?}; accepted models: {}",
+            cfg.accepted_model_ids().join(",")
+        ),
+        Some("model"),
+        Some("invalid_model"),
+    ))
+}
+
+/// Enforces optional bearer-token authentication.
+///
+/// Returns the key that authenticated the request, or `None` when no keys are configured.
+pub(crate) fn require_auth<'a>(
+    cfg: &'a AppConfig,
+    headers: &HeaderMap,
+) -> Result<Option<&'a ApiKey>, AppError> {
+    if cfg.api_keys.is_empty() {
+        return Ok(None);
+    }
+
+    let token = bearer_token(headers)?;
+    cfg.api_keys
+        .iter()
+        .find(|api_key| api_key.key == token)
+        .map(Some)
+        .ok_or_else(|| AppError::unauthorized("invalid token"))
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Result<&str, AppError> {
+    let Some(raw) = headers.get(header::AUTHORIZATION) else {
+        return Err(AppError::unauthorized("missing bearer token"));
+    };
+
+    let value = raw
+        .to_str()
+        .map_err(|_| AppError::unauthorized("invalid authorization header"))?;
+
+    let mut parts = value.split_whitespace();
+    let scheme = parts
+        .next()
+        .ok_or_else(|| AppError::unauthorized("missing bearer token"))?;
+    let token = parts
+        .next()
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| AppError::unauthorized("missing bearer token"))?;
+    if parts.next().is_some() || !scheme.eq_ignore_ascii_case("bearer") {
+        return Err(AppError::unauthorized("missing bearer token"));
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::audio::{Downmix, ResampleQuality, VadParams};
+    use crate::backend::{
+        ActiveInference, Capabilities, CpuPool, DedicatedPool, TokenLogprob, TranscribeRequest,
+        Transcriber, TranscriptResult, TranscriptSegment, TranscriptWord,
+    };
+    use crate::chunking::ChunkParams;
+    use crate::config::{
+        AccelerationKind, ApiKey, AppConfig, BackendKind, Compute, DtwHeads, KeyDefaults,
+        ModelWorkers, WhisperModelSize,
+    };
+    use crate::denoise;
+    use crate::error::AppError;
+    use crate::formats::{SubtitleTiming, TextNormalization};
+    use crate::jobs::unix_secs;
+    use crate::model_registry::OnDemandModels;
+    use crate::presets::builtin_presets;
+    use crate::purge::PurgeFilter;
+    use crate::rate_limit::{enforce_rate_limit, HeldPermit};
+    use crate::storage::LocalStorage;
+
+    use super::{
+        build_router, resolve_source_language, resume_spooled_jobs, spooled_job, AppState,
+        AudioForm, Overflow, ResponseFormat, TaskKind, UploadBody, UploadedFile,
+        DEBUG_TRACE_ID_HEADER,
+    };
+
+    #[derive(Clone)]
+    struct MockBackend;
+
+    #[async_trait]
+    impl Transcriber for MockBackend {
+        async fn transcribe(&self, _req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            Ok(TranscriptResult {
+                text: "hello world".to_string(),
+                language: Some("en".to_string()),
+                segments: vec![TranscriptSegment {
+                    start_secs: 0.0,
+                    end_secs: 1.2,
+                    text: "hello world".to_string(),
+                    avg_logprob: Some(-0.25),
+                    token_count: 2,
+                    no_speech_prob: Some(0.125),
+                    tokens: vec![50364, 7751, 1002, 50424],
+                    token_logprobs: vec![
+                        TokenLogprob {
+                            token: "hello".to_string(),
+                            logprob: -0.125,
+                        },
+                        TokenLogprob {
+                            token: " world".to_string(),
+                            logprob: -0.375,
+                        },
+                    ],
+                    words: Vec::new(),
+                }],
+                no_speech: None,
+                language_probs: vec![("en".to_string(), 0.875), ("de".to_string(), 0.0625)],
+                usage: None,
+                warnings: Vec::new(),
+            })
+        }
+
+        async fn detect_language(
+            &self,
+            _audio_16khz_mono_f32: Vec<f32>,
+        ) -> Result<Vec<(String, f32)>, AppError> {
+            Ok(vec![("en".to_string(), 0.875), ("de".to_string(), 0.0625)])
+        }
+    }
+
+    fn test_cfg(api_key: Option<&str>) -> AppConfig {
+        AppConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8000,
+            rtp_port: None,
+            admin_api_key: None,
+            api_keys: api_key
+                .map(|key| ApiKey {
+                    key: key.to_string(),
+                    label: "default".to_string(),
+                    requests_per_minute: None,
+                    concurrent_requests: None,
+                    compute: None,
+                    defaults: KeyDefaults::default(),
+                })
+                .into_iter()
+                .collect(),
+            whisper_model: "dummy".to_string(),
+            whisper_model_explicit: true,
+            whisper_auto_download: false,
+            whisper_hf_repo: "ggerganov/whisper.cpp".to_string(),
+            whisper_hf_filename: "ggml-small.bin".to_string(),
+            whisper_cache_dir: "/tmp".to_string(),
+            hf_token: None,
+            aux_models: Vec::new(),
+            api_model_alias: "whisper-mlx".to_string(),
+            model_aliases: vec![("gpt-4o-transcribe".to_string(), "whisper-1".to_string())],
+            extra_models: Vec::new(),
+            backend_kind: BackendKind::WhisperRs,
+            acceleration_kind: AccelerationKind::Metal,
+            acceleration_explicit: false,
+            whisper_parallelism: 1,
+            whisper_on_demand_models: false,
+            whisper_cpu_workers: None,
+            model_workers: Vec::new(),
+            whisper_cpu_affinity: None,
+            worker_max_requests: None,
+            worker_max_age: None,
+            whisper_model_size: WhisperModelSize::Small,
+            whisper_max_tokens_per_segment: None,
+            whisper_beam_size: None,
+            whisper_best_of: None,
+            whisper_dtw: DtwHeads::Off,
+            whisper_max_initial_ts: 5.0,
+            merge_segments: false,
+            heuristic_punctuation: false,
+            text_normalization: TextNormalization::default(),
+            chunking: None,
+            vad_filter: false,
+            resample_quality: ResampleQuality::Fast,
+            denoise: false,
+            presets: builtin_presets(),
+            vad_params: VadParams {
+                min_silence_ms: 1000,
+                padding_ms: 200,
+            },
+            ffmpeg_bin: None,
+            startup_self_test: false,
+            max_queue_wait: None,
+            max_queue_depth: None,
+            ready_overload_depth: None,
+            ready_overload_after: std::time::Duration::from_secs(30),
+            request_timeout: None,
+            multipart_max_parts: 64,
+            multipart_max_fields: 32,
+            multipart_max_field_bytes: 64 * 1024,
+            max_connections: 1024,
+            max_concurrent_uploads: 16,
+            memory_budget_mb: None,
+            max_upload_bytes: 25 * 1024 * 1024,
+            max_audio_duration: None,
+            upload_spill_threshold_bytes: None,
+            upload_spill_dir: None,
+            rate_limit_requests_per_minute: None,
+            rate_limit_concurrent_requests: None,
+            cors_allowed_origins: Vec::new(),
+            cors_allowed_headers: vec!["authorization".into(), "content-type".into()],
+            cors_allowed_methods: vec![axum::http::Method::GET, axum::http::Method::POST],
+            demo_page: true,
+            prometheus_metrics: false,
+            http_header_timeout: std::time::Duration::from_secs(30),
+            http_read_timeout: std::time::Duration::from_secs(60),
+            http_write_timeout: std::time::Duration::from_secs(60),
+            http_idle_timeout: std::time::Duration::from_secs(120),
+            http_keepalive: true,
+            http2: true,
+            http2_max_concurrent_streams: 256,
+            http2_keepalive_interval: None,
+            http2_keepalive_timeout: std::time::Duration::from_secs(20),
+            tls: None,
+            async_job_retention: std::time::Duration::from_secs(3600),
+            async_job_max_total_bytes: None,
+            async_job_max_bytes_per_key: None,
+            async_job_storage: None,
+            transcript_archive_storage: None,
+            debug_trace_storage: None,
+            debug_trace_retention: None,
+            janitor_interval: std::time::Duration::from_secs(3600),
+            purge_audit_log: None,
+            storage_cipher: None,
+            statsd_addr: None,
+            statsd_prefix: "whisper_openai_server".to_string(),
+            statsd_tags: true,
+            #[cfg(feature = "tts")]
+            tts_piper_bin: "piper".to_string(),
+            #[cfg(feature = "tts")]
+            tts_voice: "en_US-lessac-medium".to_string(),
+            #[cfg(feature = "tts")]
+            tts_voice_model: "/tmp/en_US-lessac-medium.onnx".to_string(),
+            #[cfg(feature = "tts")]
+            tts_voices_dir: "/tmp".to_string(),
+            #[cfg(feature = "tts")]
+            tts_hf_repo: "rhasspy/piper-voices".to_string(),
+            command: None,
+        }
+    }
+
+    /// Builds a 16 kHz mono 16-bit PCM WAV file containing a 440 Hz tone.
+    fn tone_wav(samples: usize) -> Vec<u8> {
+        let mut wav = silent_wav(samples);
+        for idx in 0..samples {
+            let phase = idx as f32 * 440.0 * std::f32::consts::TAU / 16_000.0;
+            let sample = (phase.sin() * 8_000.0) as i16;
+            wav[44 + idx * 2..46 + idx * 2].copy_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+
+    /// Builds a 16 kHz mono 16-bit PCM WAV file containing silence.
+    fn silent_wav(samples: usize) -> Vec<u8> {
+        let data_len = (samples * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&16_000u32.to_le_bytes());
+        wav.extend_from_slice(&32_000u32.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        wav.resize(wav.len() + samples * 2, 0);
+        wav
+    }
+
+    /// Builds a multipart body with a `file` part followed by text fields.
+    fn multipart_body(
+        boundary: &str,
+        filename: &str,
+        file: &[u8],
+        fields: &[(&str, &str)],
+    ) -> Vec<u8> {
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(file);
+        body.extend_from_slice(b"\r\n");
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+        body
+    }
+
+    fn app(api_key: Option<&str>) -> axum::Router {
+        let state = Arc::new(AppState::new(test_cfg(api_key), Arc::new(MockBackend)));
+        build_router(state)
+    }
+
+    async fn parse_json_response(res: axum::response::Response) -> Value {
+        let bytes = to_bytes(res.into_body(), 1024 * 1024)
+            .await
+            .expect("body bytes");
+        serde_json::from_slice(&bytes).expect("json body")
+    }
+
+    #[tokio::test]
+    async fn models_requires_auth_when_api_key_set() {
+        let app = app(Some("secret"));
+
+        let req = Request::builder()
+            .uri("/v1/models")
+            .method("GET")
+            .body(Body::empty())
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["type"], "authentication_error");
+    }
+
+    #[tokio::test]
+    async fn models_lists_alias_and_whisper_1() {
+        let app = app(Some("secret"));
+
+        let req = Request::builder()
+            .uri("/v1/models")
+            .method("GET")
+            .header("Authorization", "Bearer secret")
+            .body(Body::empty())
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let payload = parse_json_response(res).await;
+        let ids = payload["data"]
+            .as_array()
+            .expect("array")
+            .iter()
+            .filter_map(|m| m["id"].as_str())
+            .collect::<Vec<_>>();
+
+        assert!(ids.contains(&"whisper-1"));
+        assert!(ids.contains(&"whisper-mlx"));
+        let alias = payload["data"]
+            .as_array()
+            .expect("array")
+            .iter()
+            .find(|m| m["id"] == "gpt-4o-transcribe")
+            .expect("alias listed");
+        assert_eq!(alias["alias_for"], "whisper-1");
+        assert_eq!(alias["capabilities"]["streaming"], true);
+        assert_eq!(alias["capabilities"]["word_timestamps"], false);
+        assert_eq!(
+            alias["capabilities"]["translation_targets"],
+            serde_json::json!(["en"])
+        );
+    }
+
+    /// A backend that neither streams nor translates.
+    struct TranscribeOnlyBackend;
+
+    #[async_trait]
+    impl Transcriber for TranscribeOnlyBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            MockBackend.transcribe(req).await
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                streaming: false,
+                translation_targets: Vec::new(),
+                ..Capabilities::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parameters_the_backend_cannot_honour_are_rejected() {
+        let mut cfg = test_cfg(None);
+        cfg.extra_models = vec![("final".to_string(), "large-v3".to_string())];
+        let extra: Arc<dyn Transcriber> = Arc::new(MockBackend);
+        let state = Arc::new(
+            AppState::new(cfg, Arc::new(TranscribeOnlyBackend))
+                .with_extra_models([("final".to_string(), extra)].into_iter().collect()),
+        );
+        let boundary = "X-BOUNDARY";
+        let cases = [
+            (
+                "/v1/audio/transcriptions",
+                &[
+                    ("timestamp_granularities[]", "word"),
+                    ("response_format", "verbose_json"),
+                ][..],
+                "timestamp_granularities[]",
+                serde_json::json!([]),
+            ),
+            (
+                "/v1/audio/transcriptions",
+                &[("stream", "true")][..],
+                "stream",
+                serde_json::json!(["final"]),
+            ),
+            (
+                "/v1/audio/translations",
+                &[("response_format", "json")][..],
+                "model",
+                serde_json::json!(["final"]),
+            ),
+        ];
+        for (uri, fields, param, supported_models) in cases {
+            let body = multipart_body(boundary, "a.wav", &tone_wav(16_000), fields);
+            let req = Request::builder()
+                .uri(uri)
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+            let res = build_router(Arc::clone(&state))
+                .oneshot(req)
+                .await
+                .expect("response");
+            assert_eq!(
+                res.status(),
+                StatusCode::NOT_IMPLEMENTED,
+                "{uri} {fields:?}"
+            );
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "unsupported_parameter");
+            assert_eq!(payload["error"]["param"], param);
+            assert_eq!(payload["error"]["supported_models"], supported_models);
+        }
+
+        let body = multipart_body(
+            boundary,
+            "a.wav",
+            &tone_wav(16_000),
+            &[("timestamp_granularities[]", "segment")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+        let res = build_router(state).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    /// A backend that times each word of the mock transcript.
+    struct WordTimingBackend;
+
+    #[async_trait]
+    impl Transcriber for WordTimingBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            let mut result = MockBackend.transcribe(req).await?;
+            result.segments[0].words = vec![
+                TranscriptWord {
+                    word: "hello".to_string(),
+                    start_secs: 0.0,
+                    end_secs: 0.5,
+                },
+                TranscriptWord {
+                    word: "world".to_string(),
+                    start_secs: 0.5,
+                    end_secs: 1.2,
+                },
+            ];
+            Ok(result)
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                word_timestamps: true,
+                ..MockBackend.capabilities()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn word_timestamps_are_listed_in_verbose_json() {
+        let state = Arc::new(AppState::new(test_cfg(None), Arc::new(WordTimingBackend)));
+        let boundary = "X-BOUNDARY";
+        let request = |format: &str| {
+            let body = multipart_body(
+                boundary,
+                "a.wav",
+                &tone_wav(16_000),
+                &[
+                    ("timestamp_granularities[]", "word"),
+                    ("response_format", format),
+                ],
+            );
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        let res = build_router(Arc::clone(&state))
+            .oneshot(request("verbose_json"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        // Like the segment, the last word is clamped to the one-second upload.
+        assert_eq!(
+            payload["words"],
+            serde_json::json!([
+                {"word": "hello", "start": 0.0, "end": 0.5},
+                {"word": "world", "start": 0.5, "end": 1.0},
+            ])
+        );
+
+        let res = build_router(state)
+            .oneshot(request("json"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_timestamp_granularities");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_accept_aliased_model_ids() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "gpt-4o-transcribe")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn containers_fall_back_to_ffmpeg_when_configured() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for ffmpeg: drains the upload and prints one second of loud PCM.
+        let dir = tempfile::tempdir().expect("tempdir");
+        let script = dir.path().join("ffmpeg");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\ncat >/dev/null\ni=0\nwhile [ $i -lt 16000 ]; do printf '\\000\\100'; i=$((i+1)); done\n",
+        )
+        .expect("write script");
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let boundary = "X-BOUNDARY";
+        let request = || {
+            let body = multipart_body(boundary, "clip.mkv", b"not really matroska", &[]);
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        let res = app(None).oneshot(request()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let mut cfg = test_cfg(None);
+        cfg.ffmpeg_bin = Some(script.to_string_lossy().into_owned());
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let res = app.oneshot(request()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["text"], "hello world");
+    }
+
+    #[tokio::test]
+    async fn openapi_spec_is_public_and_describes_audio_endpoints() {
+        let req = Request::builder()
+            .uri("/openapi.json")
+            .body(Body::empty())
+            .expect("request");
+
+        let res = app(Some("secret")).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let spec = parse_json_response(res).await;
+        assert_eq!(spec["openapi"], "3.1.0");
+        assert_eq!(spec["security"][0]["bearerAuth"], serde_json::json!([]));
+        let form = &spec["paths"]["/v1/audio/transcriptions"]["post"]["requestBody"]["content"]
+            ["multipart/form-data"]["schema"];
+        assert_eq!(form["properties"]["file"]["format"], "binary");
+        assert!(spec["paths"]["/v1/audio/translations"]["post"].is_object());
+        assert!(spec["paths"]["/v1/audio/language"]["post"].is_object());
+        assert!(spec["paths"]["/v1/models"]["get"].is_object());
+        assert!(spec["components"]["schemas"]["Error"].is_object());
+    }
+
+    #[tokio::test]
+    async fn demo_page_is_public_unless_disabled() {
+        let req = || {
+            Request::builder()
+                .uri("/demo")
+                .body(Body::empty())
+                .expect("request")
+        };
+        let res = app(Some("secret")).oneshot(req()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let content_type = res
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .expect("content type");
+        assert!(content_type.starts_with("text/html"));
+        let page = to_bytes(res.into_body(), 1024 * 1024)
+            .await
+            .expect("body bytes");
+        let page = String::from_utf8_lossy(&page);
+        assert!(page.contains("v1/audio/stream"));
+        assert!(page.contains("v1/audio/transcriptions"));
+
+        let mut cfg = test_cfg(None);
+        cfg.demo_page = false;
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let res = app.oneshot(req()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Backend whose first worker is partway through a request.
+    struct BusyBackend;
+
+    #[async_trait]
+    impl Transcriber for BusyBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            MockBackend.transcribe(req).await
+        }
+
+        fn worker_activity(&self) -> Vec<Option<ActiveInference>> {
+            vec![
+                Some(ActiveInference {
+                    elapsed: std::time::Duration::from_secs(90),
+                    progress_percent: 35,
+                }),
+                None,
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_report_worker_progress_when_enabled() {
+        let req = || {
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .expect("request")
+        };
+        let mut cfg = test_cfg(Some("secret"));
+        let disabled = build_router(Arc::new(AppState::new(cfg.clone(), Arc::new(BusyBackend))));
+        let res = disabled.oneshot(req()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        cfg.prometheus_metrics = true;
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(BusyBackend))));
+        let res = app.oneshot(req()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let content_type = res
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .expect("content type");
+        assert!(content_type.starts_with("text/plain; version=0.0.4"));
+        let body = to_bytes(res.into_body(), 1024 * 1024)
+            .await
+            .expect("body bytes");
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(
+            "whisper_worker_request_elapsed_seconds{model=\"whisper-mlx\",pool=\"default\",worker=\"0\"} 90\n"
+        ));
+        assert!(body.contains(
+            "whisper_worker_request_progress_percent{model=\"whisper-mlx\",pool=\"default\",worker=\"0\"} 35\n"
+        ));
+        assert!(body.contains(
+            "whisper_worker_busy{model=\"whisper-mlx\",pool=\"default\",worker=\"1\"} 0\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn request_ids_are_generated_or_propagated() {
+        let app = app(Some("secret"));
+        let req = Request::builder()
+            .uri("/v1/models")
+            .body(Body::empty())
+            .expect("request");
+        let res = app.clone().oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let id = res.headers()["x-request-id"]
+            .to_str()
+            .expect("id")
+            .to_string();
+        assert!(id.starts_with("req_"));
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["request_id"], id.as_str());
+
+        let req = Request::builder()
+            .uri("/health")
+            .header("X-Request-Id", "client-trace-42")
+            .body(Body::empty())
+            .expect("request");
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.headers()["x-request-id"], "client-trace-42");
+    }
+
+    #[tokio::test]
+    async fn health_probes_skip_auth_and_report_worker_state() {
+        let state = Arc::new(AppState::new(
+            test_cfg(Some("secret")),
+            Arc::new(MockBackend),
+        ));
+        let app = build_router(Arc::clone(&state));
+        let get = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .expect("request")
+        };
+
+        let res = app
+            .clone()
+            .oneshot(get("/health/live"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let _busy = state.inference_slots.acquire().await.expect("permit");
+        let res = app.oneshot(get("/health/ready")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["status"], "ready");
+        assert_eq!(payload["model"]["status"], "ready");
+        assert_eq!(payload["workers"]["busy"], 1);
+        assert_eq!(payload["queue"]["waiting"], 0);
+
+        let _reloading = state.model_reload.lock().await;
+        let res = build_router(Arc::clone(&state))
+            .oneshot(get("/health/ready"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["model"]["status"], "reloading");
+    }
+
+    #[tokio::test]
+    async fn sustained_queue_saturation_fails_readiness() {
+        let mut cfg = test_cfg(None);
+        cfg.ready_overload_depth = Some(1);
+        cfg.ready_overload_after = std::time::Duration::ZERO;
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let ready = || async {
+            build_router(Arc::clone(&state))
+                .oneshot(
+                    Request::builder()
+                        .uri("/health/ready")
+                        .body(Body::empty())
+                        .expect("request"),
+                )
+                .await
+                .expect("response")
+        };
+        assert_eq!(ready().await.status(), StatusCode::OK);
+
+        let waiting = state.queue.join();
+        let res = ready().await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["status"], "overloaded");
+        assert_eq!(payload["queue"]["overloaded_secs"], 0);
+
+        drop(waiting);
+        assert_eq!(ready().await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn models_accept_lowercase_bearer_scheme() {
+        let app = app(Some("secret"));
+
+        let req = Request::builder()
+            .uri("/v1/models")
+            .method("GET")
+            .header("Authorization", "bearer secret")
+            .body(Body::empty())
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_undecodable_mp4() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"bad.mp4\"\r\nContent-Type: video/mp4\r\n\r\nnot-a-real-media\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}--\r\n",
+            b = boundary
+        );
+
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "unsupported_media_type");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_validate_model_field() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nunknown-model\r\n--{b}--\r\n",
+            b = boundary
+        );
+
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_model");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_non_finite_temperature() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}\r\nContent-Disposition: form-data; name=\"temperature\"\r\n\r\nNaN\r\n--{b}--\r\n",
+            b = boundary
+        );
+
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_temperature");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_out_of_range_temperature() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}\r\nContent-Disposition: form-data; name=\"temperature\"\r\n\r\n1.5\r\n--{b}--\r\n",
+            b = boundary
+        );
+
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_temperature");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_out_of_range_max_tokens_per_segment() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}\r\nContent-Disposition: form-data; name=\"max_tokens_per_segment\"\r\n\r\n1000\r\n--{b}--\r\n",
+            b = boundary
+        );
+
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_max_tokens_per_segment");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_return_503_when_queue_wait_exceeded() {
+        let mut cfg = test_cfg(None);
+        cfg.max_queue_wait = Some(std::time::Duration::from_millis(20));
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let _busy = state.inference_slots.acquire().await.expect("permit");
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = build_router(Arc::clone(&state))
+            .oneshot(req)
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()["retry-after"], "5");
+
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "server_overloaded");
+    }
+
+    #[tokio::test]
+    async fn full_inference_queue_returns_429_with_retry_after() {
+        let mut cfg = test_cfg(None);
+        cfg.max_queue_depth = Some(1);
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let _busy = state.inference_slots.acquire().await.expect("permit");
+        state
+            .queued_requests
+            .store(1, std::sync::atomic::Ordering::Relaxed);
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = build_router(Arc::clone(&state))
+            .oneshot(req)
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            res.headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok()),
+            Some("5")
+        );
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "queue_full");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_report_detected_language_for_text_format() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("response_format", "text")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get("x-detected-language")
+                .and_then(|v| v.to_str().ok()),
+            Some("en")
+        );
+    }
+
+    #[tokio::test]
+    async fn transcriptions_json_includes_language() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["language"], "en");
+        assert_eq!(payload["avg_logprob"], -0.25);
+        assert_eq!(payload["confidence"], 0.7788);
+        assert!(payload.get("language_probabilities").is_none());
+    }
+
+    #[tokio::test]
+    async fn json_responses_list_quality_warnings() {
+        let boundary = "X-BOUNDARY";
+        let mut clipped = tone_wav(1600);
+        for idx in (0..1600).step_by(4) {
+            clipped[44 + idx * 2..46 + idx * 2].copy_from_slice(&i16::MAX.to_le_bytes());
+        }
+        let request = |wav: &[u8], fields: &[(&str, &str)]| {
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(boundary, "ok.wav", wav, fields)))
+                .expect("request")
+        };
+
+        let res = app(None)
+            .oneshot(request(&tone_wav(1600), &[("model", "whisper-1")]))
+            .await
+            .expect("response");
+        assert!(parse_json_response(res).await.get("warnings").is_none());
+
+        for format in ["json", "verbose_json"] {
+            let res = app(None)
+                .oneshot(request(
+                    &clipped,
+                    &[
+                        ("model", "whisper-1"),
+                        ("response_format", format),
+                        ("max_tokens_per_segment", "2"),
+                    ],
+                ))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::OK);
+            let payload = parse_json_response(res).await;
+            let codes = payload["warnings"]
+                .as_array()
+                .expect("warnings")
+                .iter()
+                .map(|warning| warning["code"].as_str().expect("code"))
+                .collect::<Vec<_>>();
+            assert_eq!(codes, ["audio_clipped", "segments_truncated"], "{format}");
+            assert!(payload["warnings"][0]["message"]
+                .as_str()
+                .is_some_and(|message| message.starts_with("25.0% of the audio")));
+        }
+    }
+
+    #[tokio::test]
+    async fn verbose_json_includes_segment_metadata_and_language_probabilities() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("response_format", "verbose_json")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        let segment = &payload["segments"][0];
+        assert!(segment.get("seek").is_none());
+        assert_eq!(
+            segment["tokens"],
+            serde_json::json!([50364, 7751, 1002, 50424])
+        );
+        assert_eq!(segment["avg_logprob"], -0.25);
+        assert_eq!(segment["no_speech_prob"], 0.125);
+        assert!(segment["compression_ratio"].as_f64().expect("ratio") > 0.0);
+        assert_eq!(
+            payload["language_probabilities"],
+            serde_json::json!([
+                {"language": "en", "probability": 0.875},
+                {"language": "de", "probability": 0.0625}
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn json_responses_report_usage() {
+        for format in ["json", "verbose_json"] {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(8000),
+                &[("response_format", format)],
+            );
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+
+            let res = app(None).oneshot(req).await.expect("response");
+            assert_eq!(res.status(), StatusCode::OK);
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["usage"]["type"], "duration", "{format}");
+            assert_eq!(payload["usage"]["seconds"], 0.5, "{format}");
+            assert!(payload["usage"]["processing_ms"].is_u64(), "{format}");
+        }
+    }
+
+    #[tokio::test]
+    async fn language_endpoint_detects_language_on_a_window() {
+        let send = |audio: Vec<u8>, fields: &[(&str, &str)]| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(boundary, "ok.wav", &audio, fields);
+            let req = Request::builder()
+                .uri("/v1/audio/language")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+            app(None).oneshot(req)
+        };
+
+        let res = send(tone_wav(8000), &[]).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["language"], "en");
+        assert_eq!(payload["probability"], 0.875);
+        assert_eq!(payload["language_probabilities"][1]["language"], "de");
+        assert_eq!(payload["usage"]["seconds"], 0.5);
+        assert!(payload.get("text").is_none());
+
+        let res = send(tone_wav(8000), &[("duration", "0.25")])
+            .await
+            .expect("response");
+        assert_eq!(parse_json_response(res).await["usage"]["seconds"], 0.25);
+
+        let res = send(tone_wav(8000), &[("duration", "45")])
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_duration");
+    }
+
+    #[tokio::test]
+    async fn language_detection_decodes_only_its_window_past_max_audio_seconds() {
+        let mut cfg = test_cfg(None);
+        cfg.max_audio_duration = Some(std::time::Duration::from_secs(1));
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let boundary = "X-BOUNDARY";
+        let request = |fields: &[(&str, &str)]| {
+            Request::builder()
+                .uri("/v1/audio/language")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(
+                    boundary,
+                    "long.wav",
+                    &tone_wav(48_000),
+                    fields,
+                )))
+                .expect("request")
+        };
+
+        let res = app.clone().oneshot(request(&[])).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["language"], "en");
+        assert_eq!(payload["usage"]["seconds"], 3.0);
+
+        let res = app
+            .oneshot(request(&[("duration", "0.5")]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(parse_json_response(res).await["usage"]["seconds"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn include_logprobs_returns_token_logprobs() {
+        let send = |fields: &[(&str, &str)]| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(boundary, "ok.wav", &tone_wav(8000), fields);
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+            app(None).oneshot(req)
+        };
+
+        let res = send(&[("include[]", "logprobs")]).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["logprobs"][0]["token"], "hello");
+        assert_eq!(payload["logprobs"][0]["logprob"], -0.125);
+        assert_eq!(payload["logprobs"][1]["token"], " world");
+        assert_eq!(payload["logprobs"][1]["bytes"][0], 32);
+
+        let res = send(&[]).await.expect("response");
+        assert!(parse_json_response(res).await.get("logprobs").is_none());
+
+        for fields in [
+            &[("include[]", "logprobs"), ("response_format", "srt")][..],
+            &[("include[]", "segments")][..],
+        ] {
+            let res = send(fields).await.expect("response");
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "invalid_include");
+        }
+    }
+
+    #[tokio::test]
+    async fn subtitles_render_smpte_timecodes() {
+        let send = |fields: &[(&str, &str)]| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(boundary, "ok.wav", &tone_wav(32_000), fields);
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+            app(None).oneshot(req)
+        };
+
+        let res = send(&[
+            ("response_format", "srt"),
+            ("timecode", "smpte"),
+            ("frame_rate", "29.97"),
+            ("drop_frame", "true"),
+        ])
+        .await
+        .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+        let body = String::from_utf8(bytes.to_vec()).expect("utf-8");
+        assert!(body.contains("00:00:00;00 --> 00:00:01;06"), "{body}");
+
+        for fields in [
+            &[("response_format", "srt"), ("frame_rate", "25")][..],
+            &[
+                ("response_format", "json"),
+                ("timecode", "smpte"),
+                ("frame_rate", "25"),
+            ][..],
+            &[("response_format", "vtt"), ("timecode", "smpte")][..],
+            &[
+                ("response_format", "vtt"),
+                ("timecode", "smpte"),
+                ("frame_rate", "25"),
+                ("drop_frame", "true"),
+            ][..],
+        ] {
+            let res = send(fields).await.expect("response");
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "invalid_timecode");
+        }
+    }
+
+    #[tokio::test]
+    async fn heuristic_punctuation_fixes_text_and_segments() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[
+                ("response_format", "verbose_json"),
+                ("heuristic_punctuation", "true"),
+            ],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["text"], "Hello world.");
+        assert_eq!(payload["segments"][0]["text"], "Hello world.");
+    }
+
+    #[tokio::test]
+    async fn resample_quality_is_chosen_per_request() {
+        // One second of 44.1 kHz audio.
+        let mut wav = silent_wav(44_100);
+        wav[24..28].copy_from_slice(&44_100u32.to_le_bytes());
+        wav[28..32].copy_from_slice(&88_200u32.to_le_bytes());
+        let transcribe = |quality: &str| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &wav,
+                &[
+                    ("response_format", "verbose_json"),
+                    ("resample_quality", quality),
+                ],
+            );
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        for quality in ["fast", "balanced", "High"] {
+            let res = app(None)
+                .oneshot(transcribe(quality))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::OK, "{quality}");
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["usage"]["seconds"], 1.0, "{quality}");
+        }
+
+        let res = app(None)
+            .oneshot(transcribe("best"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_resample_quality");
+    }
+
+    #[tokio::test]
+    async fn denoise_is_rejected_without_the_feature() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(16_000),
+            &[("denoise", "true")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        if denoise::AVAILABLE {
+            assert_eq!(res.status(), StatusCode::OK);
+        } else {
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "denoise_unavailable");
+            assert_eq!(payload["error"]["param"], "denoise");
+        }
+    }
+
+    #[tokio::test]
+    async fn uploads_are_identified_by_content_not_just_extension() {
+        let boundary = "X-BOUNDARY";
+        let request = |filename: &str, bytes: &[u8]| {
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(boundary, filename, bytes, &[])))
+                .expect("request")
+        };
+
+        let res = app(None)
+            .oneshot(request("audio.dat", &tone_wav(1600)))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app(None)
+            .oneshot(request("audio.wav", b"%PDF-1.7 not audio"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let payload = parse_json_response(res).await;
+        let message = payload["error"]["message"].as_str().expect("message");
+        assert!(
+            message.contains("is a PDF document, not audio"),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_selects_one_leg_of_stereo_audio() {
+        // One second of 16 kHz stereo: a silent left channel and a tone on the right.
+        let tone = tone_wav(16_000);
+        let mut wav = silent_wav(32_000);
+        wav[22..24].copy_from_slice(&2u16.to_le_bytes());
+        wav[28..32].copy_from_slice(&64_000u32.to_le_bytes());
+        wav[32..34].copy_from_slice(&4u16.to_le_bytes());
+        for idx in 0..16_000 {
+            let right = 44 + idx * 4 + 2;
+            wav[right..right + 2].copy_from_slice(&tone[44 + idx * 2..46 + idx * 2]);
+        }
+        let transcribe = |channel: &str| {
+            let boundary = "X-BOUNDARY";
+            let mut fields = vec![("response_format", "json")];
+            if !channel.is_empty() {
+                fields.push(("channel", channel));
+            }
+            let body = multipart_body(boundary, "call.wav", &wav, &fields);
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        for (channel, speech) in [("", true), ("average", true), ("0", false), ("1", true)] {
+            let res = app(None)
+                .oneshot(transcribe(channel))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::OK, "{channel}");
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["no_speech"].is_null(), speech, "{channel}");
+        }
+
+        for channel in ["2", "left"] {
+            let res = app(None)
+                .oneshot(transcribe(channel))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{channel}");
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "invalid_channel", "{channel}");
+            assert_eq!(payload["error"]["param"], "channel", "{channel}");
+        }
+    }
+
+    #[tokio::test]
+    async fn vad_filter_maps_segments_back_to_the_original_audio() {
+        // 2 s of silence, then 1 s of tone.
+        let mut wav = silent_wav(48_000);
+        wav[44 + 64_000..].copy_from_slice(&tone_wav(16_000)[44..]);
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &wav,
+            &[("response_format", "verbose_json"), ("vad_filter", "true")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        // The mock's 0.0-1.2 s segment starts 200 ms (rounded up to whole frames) before the tone.
+        let start = payload["segments"][0]["start"].as_f64().expect("start");
+        let end = payload["segments"][0]["end"].as_f64().expect("end");
+        assert!((start - 1.77).abs() < 1e-6, "start {start}");
+        assert!((end - 2.97).abs() < 1e-6, "end {end}");
+        assert_eq!(payload["usage"]["seconds"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn presets_fill_in_fields_the_request_leaves_unset() {
+        // 2 s of silence, then 1 s of tone.
+        let mut wav = silent_wav(48_000);
+        wav[44 + 64_000..].copy_from_slice(&tone_wav(16_000)[44..]);
+        let transcribe = |fields: &[(&str, &str)]| {
+            let boundary = "X-BOUNDARY";
+            let mut fields = fields.to_vec();
+            fields.push(("response_format", "verbose_json"));
+            let body = multipart_body(boundary, "ok.wav", &wav, &fields);
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+        let first_start =
+            |payload: &Value| payload["segments"][0]["start"].as_f64().expect("start");
+
+        // voicemail turns on the VAD filter and keeps 300 ms of padding, not VAD_PADDING_MS.
+        let res = app(None)
+            .oneshot(transcribe(&[("preset", "voicemail")]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let start = first_start(&parse_json_response(res).await);
+        assert!(start > 1.6 && start < 1.77, "start {start}");
+
+        let res = app(None)
+            .oneshot(transcribe(&[
+                ("vad_filter", "false"),
+                ("preset", "voicemail"),
+            ]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(first_start(&parse_json_response(res).await), 0.0);
+
+        let res = app(None)
+            .oneshot(transcribe(&[("preset", "podcast")]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_preset");
+        assert_eq!(payload["error"]["param"], "preset");
+    }
+
+    /// Backend that never finishes within a test's timeout.
+    struct StalledBackend;
+
+    #[async_trait]
+    impl Transcriber for StalledBackend {
+        async fn transcribe(&self, _req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            Err(AppError::backend("unreachable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_over_timeout_return_504() {
+        let mut cfg = test_cfg(None);
+        cfg.request_timeout = Some(std::time::Duration::from_millis(50));
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(StalledBackend))));
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "request_timeout");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_too_many_form_fields() {
+        let mut cfg = test_cfg(None);
+        cfg.multipart_max_fields = 2;
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("junk1", "a"), ("junk2", "b")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = build_router(state).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_multipart");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_oversized_form_field() {
+        let mut cfg = test_cfg(None);
+        cfg.multipart_max_field_bytes = 16;
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+
+        let boundary = "X-BOUNDARY";
+        let prompt = "x".repeat(17);
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("prompt", &prompt)],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = build_router(state).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_multipart");
+    }
+
+    #[tokio::test]
+    async fn on_demand_model_returns_503_until_loaded() {
+        let cfg = test_cfg(None);
+        let mut state = AppState::new(cfg.clone(), Arc::new(MockBackend));
+        state.on_demand_models = Some(OnDemandModels::with_loader(&cfg, |_, _| {
+            Ok(Arc::new(MockBackend) as Arc<dyn Transcriber>)
+        }));
+        let state = Arc::new(state);
+
+        let send = || {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("model", "whisper-large-v3")],
+            );
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+            build_router(Arc::clone(&state)).oneshot(req)
+        };
+
+        let res = send().await.expect("response");
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "model_loading");
+
+        let mut status = StatusCode::SERVICE_UNAVAILABLE;
+        for _ in 0..50 {
+            status = send().await.expect("response").status();
+            if status != StatusCode::SERVICE_UNAVAILABLE {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn silent_audio_returns_structured_no_speech_result() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "quiet.wav",
+            &silent_wav(1600),
+            &[("model", "whisper-1")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get("x-no-speech")
+                .and_then(|v| v.to_str().ok()),
+            Some("silent_audio")
+        );
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["text"], "");
+        assert_eq!(payload["no_speech"], true);
+        assert_eq!(payload["no_speech_reason"], "silent_audio");
+    }
+
+    #[tokio::test]
+    async fn transcriptions_stream_segments_as_server_sent_events() {
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("stream", "true")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok()),
+            Some("text/event-stream")
+        );
+        let body = to_bytes(res.into_body(), usize::MAX).await.expect("body");
+        let events = String::from_utf8(body.to_vec())
+            .expect("utf8")
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str::<Value>(data).expect("json event"))
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["type"], "transcript.text.delta");
+        assert_eq!(events[0]["delta"], "hello world");
+        assert_eq!(events[1]["type"], "transcript.text.done");
+        assert_eq!(events[1]["text"], "hello world");
+    }
+
+    #[tokio::test]
+    async fn translations_stream_segments_as_server_sent_events() {
+        let boundary = "X-BOUNDARY";
+        let request = |fields: &[(&str, &str)]| {
+            Request::builder()
+                .uri("/v1/audio/translations")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(
+                    boundary,
+                    "ok.wav",
+                    &tone_wav(1600),
+                    fields,
+                )))
+                .expect("request")
+        };
+
+        let res = app(None)
+            .oneshot(request(&[("model", "whisper-1"), ("stream", "true")]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.expect("body");
+        let events = String::from_utf8(body.to_vec())
+            .expect("utf8")
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str::<Value>(data).expect("json event"))
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["type"], "transcript.text.delta");
+        assert_eq!(events[1]["type"], "transcript.text.done");
+        assert_eq!(events[1]["text"], "hello world");
+
+        let res = app(None)
+            .oneshot(request(&[
+                ("model", "whisper-1"),
+                ("stream", "true"),
+                ("response_format", "verbose_json"),
+            ]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "invalid_stream");
+    }
+
+    #[tokio::test]
+    async fn translations_take_the_source_language_from_either_field() {
+        let some = |lang: &str| Some(lang.to_string());
+        assert_eq!(
+            resolve_source_language(some("es"), None).unwrap(),
+            some("es")
+        );
+        assert_eq!(
+            resolve_source_language(None, some("es")).unwrap(),
+            some("es")
+        );
+        assert_eq!(
+            resolve_source_language(some("ES"), some("es")).unwrap(),
+            some("es")
+        );
+        assert_eq!(resolve_source_language(some(" "), None).unwrap(), None);
+
+        let boundary = "X-BOUNDARY";
+        let req = Request::builder()
+            .uri("/v1/audio/translations")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("language", "en"), ("source_language", "es")],
+            )))
+            .expect("request");
+        let res = app(None).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "conflicting_language");
+        assert_eq!(payload["error"]["param"], "source_language");
+    }
+
+    #[tokio::test]
+    async fn async_transcription_returns_job_and_serves_result() {
+        let app = app(None);
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("response_format", "text")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions?async=true")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.clone().oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        let job = parse_json_response(res).await;
+        assert_eq!(job["object"], "audio.job");
+        let id = job["id"].as_str().expect("job id").to_string();
+
+        let mut status = Value::Null;
+        for _ in 0..50 {
+            let req = Request::builder()
+                .uri(format!("/v1/audio/jobs/{id}"))
+                .body(Body::empty())
+                .expect("request");
+            let res = app.clone().oneshot(req).await.expect("response");
+            assert_eq!(res.status(), StatusCode::OK);
+            status = parse_json_response(res).await;
+            if status["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(status["status"], "completed");
+        assert_eq!(status["result"], "hello world");
+        assert_eq!(status["language"], "en");
+
+        let req = Request::builder()
+            .uri("/v1/audio/jobs/job_missing")
+            .body(Body::empty())
+            .expect("request");
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Streams the mock's segment, then holds the worker until released.
+    struct GatedBackend(Arc<tokio::sync::Notify>);
+
+    #[async_trait]
+    impl Transcriber for GatedBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            MockBackend.transcribe(req).await
+        }
+
+        async fn transcribe_stream(
+            &self,
+            req: TranscribeRequest,
+            segments: tokio::sync::mpsc::UnboundedSender<TranscriptSegment>,
+        ) -> Result<TranscriptResult, AppError> {
+            let result = MockBackend.transcribe(req).await?;
+            let _ = segments.send(result.segments[0].clone());
+            self.0.notified().await;
+            Ok(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn running_jobs_show_the_segments_decoded_so_far() {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let backend = GatedBackend(Arc::clone(&release));
+        let app = build_router(Arc::new(AppState::new(test_cfg(None), Arc::new(backend))));
+        let boundary = "X-BOUNDARY";
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions?async=true")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[],
+            )))
+            .expect("request");
+        let res = app.clone().oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        let id = parse_json_response(res).await["id"]
+            .as_str()
+            .expect("job id")
+            .to_string();
+        let poll = |wanted: &'static str| {
+            let app = app.clone();
+            let uri = format!("/v1/audio/jobs/{id}");
+            async move {
+                let mut job = Value::Null;
+                for _ in 0..50 {
+                    let req = Request::builder()
+                        .uri(uri.as_str())
+                        .body(Body::empty())
+                        .expect("request");
+                    job = parse_json_response(app.clone().oneshot(req).await.expect("response"))
+                        .await;
+                    if job["status"] == wanted
+                        && (wanted != "running" || job["segments"].is_array())
+                    {
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+                job
+            }
+        };
+
+        let running = poll("running").await;
+        assert_eq!(running["status"], "running");
+        assert_eq!(running["segments"][0]["text"], "hello world");
+        assert_eq!(running["segments"][0]["start"], 0.0);
+
+        release.notify_one();
+        let completed = poll("completed").await;
+        assert_eq!(completed["status"], "completed");
+        assert!(completed.get("segments").is_none());
+        assert_eq!(completed["result"]["text"], "hello world");
+    }
+
+    #[tokio::test]
+    async fn queued_jobs_report_their_queue_position() {
+        let state = Arc::new(AppState::new(test_cfg(None), Arc::new(MockBackend)));
+        let workers = state.cfg.whisper_parallelism as u32;
+        let busy = Arc::clone(&state.inference_slots)
+            .acquire_many_owned(workers)
+            .await
+            .expect("permits");
+        let app = build_router(Arc::clone(&state));
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions?async=true")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = app.clone().oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        let job = parse_json_response(res).await;
+        assert_eq!(job["queue_position"], 1);
+        let id = job["id"].as_str().expect("job id").to_string();
+
+        let get_job = || {
+            let req = Request::builder()
+                .uri(format!("/v1/audio/jobs/{id}"))
+                .body(Body::empty())
+                .expect("request");
+            app.clone().oneshot(req)
+        };
+        let mut status = Value::Null;
+        for _ in 0..50 {
+            status = parse_json_response(get_job().await.expect("response")).await;
+            if status.get("queue_position").is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(status["status"], "queued");
+        assert_eq!(status["queue_position"], 1);
+
+        drop(busy);
+        for _ in 0..50 {
+            status = parse_json_response(get_job().await.expect("response")).await;
+            if status["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(status["status"], "completed");
+        assert!(status.get("queue_position").is_none());
+        assert_eq!(state.queue.waiting(), 0);
+    }
+
+    /// Records the audio length and language of every inference call.
+    #[derive(Default)]
+    struct RecordingBackend(std::sync::Mutex<Vec<(usize, Option<String>)>>);
+
+    #[async_trait]
+    impl Transcriber for RecordingBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            if let Ok(mut calls) = self.0.lock() {
+                calls.push((req.audio_16khz_mono_f32.len(), req.language.clone()));
+            }
+            MockBackend.transcribe(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn long_audio_is_transcribed_in_overlapping_windows() {
+        let mut cfg = test_cfg(None);
+        cfg.chunking = Some(ChunkParams {
+            window_secs: 60,
+            overlap_secs: 5,
+            parallel: false,
+        });
+        let backend = Arc::new(RecordingBackend::default());
+        let state = Arc::new(AppState::new(cfg, backend.clone()));
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "long.wav",
+            &tone_wav(100 * 16_000),
+            &[("response_format", "verbose_json")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+
+        let res = build_router(state).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        // The second window's copy of the mock segment falls before the cut at 57.5 s.
+        assert_eq!(payload["segments"].as_array().map(Vec::len), Some(1));
+        assert_eq!(payload["usage"]["seconds"], 100.0);
+        let calls = backend.0.lock().expect("calls").clone();
+        assert_eq!(
+            calls,
+            [(60 * 16_000, None), (45 * 16_000, Some("en".to_string()))]
+        );
+    }
+
+    /// Answers like [`MockBackend`] with the segment 10 s into the window,
+    /// its text naming the window's length so windows are told apart.
+    struct LateSegmentBackend;
+
+    #[async_trait]
+    impl Transcriber for LateSegmentBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            let samples = req.audio_16khz_mono_f32.len();
+            let mut result = MockBackend.transcribe(req).await?;
+            for segment in &mut result.segments {
+                segment.start_secs += 10.0;
+                segment.end_secs += 10.0;
+                segment.text = format!("window of {samples} samples");
+            }
+            Ok(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn segments_of_later_windows_report_no_seek() {
+        let mut cfg = test_cfg(None);
+        cfg.chunking = Some(ChunkParams {
+            window_secs: 60,
+            overlap_secs: 5,
+            parallel: false,
+        });
+        let state = Arc::new(AppState::new(cfg, Arc::new(LateSegmentBackend)));
+        let boundary = "X-BOUNDARY";
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(multipart_body(
+                boundary,
+                "long.wav",
+                &tone_wav(100 * 16_000),
+                &[("response_format", "verbose_json")],
+            )))
+            .expect("request");
+
+        let res = build_router(state).oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        let later = &payload["segments"][1];
+        assert_eq!(later["start"], 65.0);
+        assert!(later.get("seek").is_none());
+    }
+
+    #[tokio::test]
+    async fn streamed_subtitles_match_the_buffered_file() {
+        let mut cfg = test_cfg(None);
+        cfg.chunking = Some(ChunkParams {
+            window_secs: 60,
+            overlap_secs: 5,
+            parallel: true,
+        });
+        let state = Arc::new(AppState::new(cfg, Arc::new(RecordingBackend::default())));
+        let boundary = "X-BOUNDARY";
+        let audio = tone_wav(100 * 16_000);
+        let subtitles = |fields: &[(&str, &str)]| {
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(
+                    boundary, "long.wav", &audio, fields,
+                )))
+                .expect("request");
+            let router = build_router(Arc::clone(&state));
+            async move {
+                let res = router.oneshot(req).await.expect("response");
+                assert_eq!(res.status(), StatusCode::OK);
+                let content_type = res.headers()["content-type"].clone();
+                let body = to_bytes(res.into_body(), usize::MAX).await.expect("body");
+                (
+                    content_type,
+                    String::from_utf8(body.to_vec()).expect("utf8"),
+                )
+            }
+        };
+
+        for format in ["srt", "vtt"] {
+            let buffered = subtitles(&[("response_format", format)]).await;
+            let streamed = subtitles(&[("response_format", format), ("stream", "true")]).await;
+            assert_eq!(streamed, buffered);
+            assert!(streamed.1.contains(" --> "), "{}", streamed.1);
+        }
+    }
+
+    struct ReloadedBackend;
+
+    #[async_trait]
+    impl Transcriber for ReloadedBackend {
+        async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+            let mut result = MockBackend.transcribe(req).await?;
+            result.text = "reloaded".to_string();
+            Ok(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn admin_reload_swaps_default_model() {
+        fn load_reloaded(
+            _cfg: AppConfig,
+            _progress: &crate::model_store::DownloadProgress,
+        ) -> Result<Arc<dyn Transcriber>, AppError> {
+            Ok(Arc::new(ReloadedBackend))
+        }
+        let reload = |token: &str| {
+            Request::builder()
+                .uri("/admin/models/reload")
+                .method("POST")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::from(r#"{"size":"medium"}"#))
+                .expect("request")
+        };
+
+        let res = app(None).oneshot(reload("admin")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let mut cfg = test_cfg(None);
+        cfg.admin_api_key = Some("admin".to_string());
+        let mut state = AppState::new(cfg, Arc::new(MockBackend));
+        state.model_loader = load_reloaded;
+        let app = build_router(Arc::new(state));
+
+        let res = app.clone().oneshot(reload("nope")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = app
+            .clone()
+            .oneshot(reload("admin"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["model"]["size"], "medium");
+        assert_eq!(payload["previous_model"]["size"], Value::Null);
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("response_format", "text")],
+        );
+        let req = Request::builder()
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("request");
+        let res = app.oneshot(req).await.expect("response");
+        let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+        assert_eq!(&bytes[..], b"reloaded");
+    }
+
+    #[tokio::test]
+    async fn async_jobs_are_visible_only_to_the_key_that_submitted_them() {
+        let mut cfg = test_cfg(None);
+        cfg.api_keys = ["team-a", "team-b"]
+            .into_iter()
+            .map(|label| ApiKey {
+                key: format!("sk-{label}"),
+                label: label.to_string(),
+                requests_per_minute: None,
+                concurrent_requests: None,
+                compute: None,
+                defaults: KeyDefaults::default(),
+            })
+            .collect();
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let id = state
+            .jobs
+            .create(TaskKind::Transcribe, None, Some("team-a".to_string()))
+            .expect("create");
+        state
+            .jobs
+            .complete(&id, None, serde_json::json!({"text": "private"}));
+        let app = build_router(state);
+        let poll = |token: &str| {
+            let req = Request::builder()
+                .uri(format!("/v1/audio/jobs/{id}"))
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .expect("request");
+            app.clone().oneshot(req)
+        };
+
+        let res = poll("sk-team-a").await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(parse_json_response(res).await["result"]["text"], "private");
+
+        let res = poll("sk-team-b").await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["param"], "id");
+    }
+
+    #[tokio::test]
+    async fn purge_deletes_a_keys_jobs_and_transcripts_and_audits() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let audit_path = dir.path().join("purges.jsonl");
+        let mut cfg = test_cfg(None);
+        cfg.admin_api_key = Some("admin".to_string());
+        cfg.transcript_archive_storage =
+            Some(Arc::new(LocalStorage::new(dir.path().join("archive"))));
+        cfg.purge_audit_log = Some(audit_path.to_string_lossy().into());
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let mine = state
+            .jobs
+            .create(TaskKind::Transcribe, None, Some("team-a".to_string()))
+            .expect("create");
+        let theirs = state
+            .jobs
+            .create(TaskKind::Transcribe, None, Some("team-b".to_string()))
+            .expect("create");
+        let archive = state.archive.as_ref().expect("archive");
+        let json = serde_json::json!({"text": "hi", "api_key": "team-a"});
+        archive
+            .save("tr_1", unix_secs(), &json, ResponseFormat::Json, None)
+            .await
+            .expect("save");
+        let app = build_router(Arc::clone(&state));
+        let purge = |body: &str| {
+            Request::builder()
+                .uri("/admin/purge")
+                .method("POST")
+                .header("Authorization", "Bearer admin")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request")
+        };
+
+        let res = app.clone().oneshot(purge("{}")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = app
+            .clone()
+            .oneshot(purge(r#"{"api_key": "team-a", "dry_run": true}"#))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["jobs"], 1);
+        assert_eq!(payload["archived_transcripts"], 1);
+        assert!(state.jobs.contains(&mine));
+
+        let res = app
+            .oneshot(purge(r#"{"api_key": "team-a"}"#))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["object"], "purge");
+        assert_eq!(payload["filter"]["api_key"], "team-a");
+        assert_eq!(payload["archived_transcripts"], 1);
+        assert!(!state.jobs.contains(&mine));
+        assert!(state.jobs.contains(&theirs));
+        assert_eq!(
+            archive.purge(&PurgeFilter::default(), true).expect("count"),
+            0
+        );
+
+        let audit = std::fs::read_to_string(&audit_path).expect("audit log");
+        let entries = audit
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("entry"))
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["dry_run"], true);
+        assert_eq!(entries[1]["id"], payload["id"]);
+    }
 
-    use crate::backend::{TranscribeRequest, Transcriber, TranscriptResult, TranscriptSegment};
-    use crate::config::{AccelerationKind, AppConfig, BackendKind, WhisperModelSize};
-    use crate::error::AppError;
+    #[tokio::test]
+    async fn debug_traces_are_stored_under_server_ids_for_admins() {
+        let boundary = "X-BOUNDARY";
+        let transcribe = |fields: &[(&str, &str)]| {
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .header("x-request-id", "bad-transcript-1")
+                .body(Body::from(multipart_body(
+                    boundary,
+                    "ok.wav",
+                    &tone_wav(1600),
+                    fields,
+                )))
+                .expect("request")
+        };
+        let res = app(None)
+            .oneshot(transcribe(&[("debug_trace", "true")]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "debug_trace_unavailable");
 
-    use super::{build_router, AppState};
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut cfg = test_cfg(None);
+        cfg.admin_api_key = Some("admin".to_string());
+        cfg.debug_trace_storage = Some(Arc::new(LocalStorage::new(dir.path())));
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let res = app
+            .clone()
+            .oneshot(transcribe(&[
+                ("debug_trace", "true"),
+                ("preset", "voicemail"),
+            ]))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let trace_id = res.headers()[DEBUG_TRACE_ID_HEADER]
+            .to_str()
+            .expect("trace id")
+            .to_string();
+        assert!(trace_id.starts_with("trace_"), "{trace_id}");
 
-    #[derive(Clone)]
-    struct MockBackend;
+        let trace = |id: &str| {
+            Request::builder()
+                .uri(format!("/admin/traces/{id}"))
+                .header("Authorization", "Bearer admin")
+                .body(Body::empty())
+                .expect("request")
+        };
+        let res = app
+            .clone()
+            .oneshot(trace("bad-transcript-1"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = app
+            .clone()
+            .oneshot(trace(&trace_id))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["request"]["id"], "bad-transcript-1");
+        assert_eq!(payload["request"]["task"], "transcribe");
+        assert_eq!(payload["parameters"]["temperature"], 0.0);
+        assert_eq!(payload["parameters"]["vad_min_silence_ms"], 500);
+        let stages = payload["stages"]
+            .as_array()
+            .expect("stages")
+            .iter()
+            .map(|stage| stage["stage"].as_str().expect("name"))
+            .collect::<Vec<_>>();
+        assert_eq!(stages, ["decode", "queue_wait", "inference", "postprocess"]);
+        assert_eq!(payload["result"]["segments"]["count"], 1);
+        assert_eq!(payload["result"]["segments"]["tokens"], 2);
+        assert_eq!(payload["result"]["language"], "en");
+        assert!(payload.get("error").is_none());
 
-    #[async_trait]
-    impl Transcriber for MockBackend {
-        async fn transcribe(&self, _req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
-            Ok(TranscriptResult {
-                text: "hello world".to_string(),
-                language: Some("en".to_string()),
-                segments: vec![TranscriptSegment {
-                    start_secs: 0.0,
-                    end_secs: 1.2,
-                    text: "hello world".to_string(),
-                }],
-            })
-        }
+        let res = app.oneshot(trace("missing")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
     }
 
-    fn test_cfg(api_key: Option<&str>) -> AppConfig {
-        AppConfig {
-            host: "127.0.0.1".to_string(),
-            port: 8000,
-            api_key: api_key.map(ToOwned::to_owned),
-            whisper_model: "dummy".to_string(),
-            whisper_model_explicit: true,
-            whisper_auto_download: false,
-            whisper_hf_repo: "ggerganov/whisper.cpp".to_string(),
-            whisper_hf_filename: "ggml-small.bin".to_string(),
-            whisper_cache_dir: "/tmp".to_string(),
-            hf_token: None,
-            api_model_alias: "whisper-mlx".to_string(),
-            backend_kind: BackendKind::WhisperRs,
-            acceleration_kind: AccelerationKind::Metal,
-            acceleration_explicit: false,
-            whisper_parallelism: 1,
-            whisper_model_size: WhisperModelSize::Small,
+    #[tokio::test]
+    async fn compute_field_and_key_policy_route_to_cpu_pool() {
+        let mut cfg = test_cfg(None);
+        cfg.whisper_cpu_workers = Some(1);
+        cfg.api_keys = vec![
+            ApiKey {
+                key: "sk-batch".to_string(),
+                label: "batch".to_string(),
+                requests_per_minute: None,
+                concurrent_requests: None,
+                compute: Some(Compute::Cpu),
+                defaults: KeyDefaults::default(),
+            },
+            ApiKey {
+                key: "sk-live".to_string(),
+                label: "live".to_string(),
+                requests_per_minute: None,
+                concurrent_requests: None,
+                compute: None,
+                defaults: KeyDefaults::default(),
+            },
+        ];
+        let cpu: Arc<dyn Transcriber> = Arc::new(CpuPool(Arc::new(ReloadedBackend)));
+        let state = AppState::new(cfg, Arc::new(MockBackend)).with_cpu_pool(Some(cpu));
+        let app = build_router(Arc::new(state));
+
+        let transcribe = |token: &str, compute: &str| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("response_format", "text"), ("compute", compute)],
+            );
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header("Authorization", format!("Bearer {token}"))
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+        for (token, compute, expected) in [
+            ("sk-live", "cpu", "reloaded"),
+            ("sk-live", "gpu", "hello world"),
+            ("sk-live", "", "hello world"),
+            ("sk-batch", "gpu", "reloaded"),
+        ] {
+            let res = app
+                .clone()
+                .oneshot(transcribe(token, compute))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::OK);
+            let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+            assert_eq!(&bytes[..], expected.as_bytes(), "{token} compute={compute}");
         }
-    }
 
-    fn app(api_key: Option<&str>) -> axum::Router {
-        let state = Arc::new(AppState::new(test_cfg(api_key), Arc::new(MockBackend)));
-        build_router(state)
+        let res = app
+            .oneshot(transcribe("sk-live", "tpu"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
     }
 
-    async fn parse_json_response(res: axum::response::Response) -> Value {
-        let bytes = to_bytes(res.into_body(), 1024 * 1024)
+    #[tokio::test]
+    async fn key_defaults_fill_in_omitted_fields_only() {
+        let mut cfg = test_cfg(None);
+        cfg.api_keys = vec![ApiKey {
+            key: "sk-pbx".to_string(),
+            label: "pbx".to_string(),
+            requests_per_minute: None,
+            concurrent_requests: None,
+            compute: None,
+            defaults: KeyDefaults {
+                language: Some("de".to_string()),
+                preset: None,
+                response_format: Some(ResponseFormat::Text),
+            },
+        }];
+        let backend = Arc::new(RecordingBackend::default());
+        let app = build_router(Arc::new(AppState::new(cfg, backend.clone())));
+
+        let transcribe = |fields: &[(&str, &str)]| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), fields);
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header("Authorization", "Bearer sk-pbx")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+        let res = app
+            .clone()
+            .oneshot(transcribe(&[]))
             .await
-            .expect("body bytes");
-        serde_json::from_slice(&bytes).expect("json body")
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+        assert_eq!(&bytes[..], b"hello world");
+
+        let res = app
+            .oneshot(transcribe(&[
+                ("response_format", "json"),
+                ("language", "fr"),
+            ]))
+            .await
+            .expect("response");
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["text"], "hello world");
+
+        let languages = backend
+            .0
+            .lock()
+            .expect("calls")
+            .iter()
+            .map(|(_, language)| language.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [Some("de".to_string()), Some("fr".to_string())]);
     }
 
     #[tokio::test]
-    async fn models_requires_auth_when_api_key_set() {
-        let app = app(Some("secret"));
+    async fn extra_models_are_listed_and_selected_by_model_field() {
+        let mut cfg = test_cfg(None);
+        cfg.extra_models = vec![("final".to_string(), "large-v3".to_string())];
+        let extra: Arc<dyn Transcriber> = Arc::new(ReloadedBackend);
+        let state = AppState::new(cfg, Arc::new(MockBackend))
+            .with_extra_models([("final".to_string(), extra)].into_iter().collect());
+        let app = build_router(Arc::new(state));
 
         let req = Request::builder()
             .uri("/v1/models")
-            .method("GET")
             .body(Body::empty())
             .expect("request");
+        let payload = parse_json_response(app.clone().oneshot(req).await.expect("response")).await;
+        let ids: Vec<&str> = payload["data"]
+            .as_array()
+            .expect("data")
+            .iter()
+            .filter_map(|model| model["id"].as_str())
+            .collect();
+        assert!(ids.contains(&"final"));
 
-        let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let transcribe = |model: &str| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("response_format", "text"), ("model", model)],
+            );
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+        let res = app
+            .clone()
+            .oneshot(transcribe("final"))
+            .await
+            .expect("response");
+        let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+        assert_eq!(&bytes[..], b"reloaded");
 
-        let payload = parse_json_response(res).await;
-        assert_eq!(payload["error"]["type"], "authentication_error");
+        let res = app
+            .oneshot(transcribe("whisper-1"))
+            .await
+            .expect("response");
+        let bytes = to_bytes(res.into_body(), 1024).await.expect("body");
+        assert_eq!(&bytes[..], b"hello world");
     }
 
     #[tokio::test]
-    async fn models_lists_alias_and_whisper_1() {
-        let app = app(Some("secret"));
+    async fn dedicated_model_pools_have_their_own_workers_and_queue() {
+        let mut cfg = test_cfg(None);
+        cfg.max_queue_depth = Some(0);
+        cfg.extra_models = vec![("final".to_string(), "large-v3".to_string())];
+        let limits = ModelWorkers {
+            workers: 1,
+            max_queue_depth: None,
+        };
+        cfg.model_workers = vec![("final".to_string(), limits)];
+        let extra: Arc<dyn Transcriber> = Arc::new(DedicatedPool::new(
+            Arc::new(ReloadedBackend),
+            limits,
+            cfg.max_queue_depth,
+        ));
+        let state = Arc::new(
+            AppState::new(cfg, Arc::new(MockBackend)).with_extra_models(
+                [("final".to_string(), Arc::clone(&extra))]
+                    .into_iter()
+                    .collect(),
+            ),
+        );
+        let app = build_router(Arc::clone(&state));
+        let transcribe = |model: &str| {
+            let boundary = "X-BOUNDARY";
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("response_format", "text"), ("model", model)],
+            );
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request")
+        };
+
+        // Busy default workers do not hold up the dedicated model.
+        let busy_default = state.inference_slots.acquire().await.expect("permit");
+        let res = app
+            .clone()
+            .oneshot(transcribe("final"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app
+            .clone()
+            .oneshot(transcribe("whisper-1"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        drop(busy_default);
+
+        // ...and a busy dedicated worker does not hold up the default model.
+        let pool = extra.dedicated_pool().expect("dedicated");
+        let _busy_final = pool.slots.acquire().await.expect("permit");
+        let res = app
+            .clone()
+            .oneshot(transcribe("final"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let res = app
+            .clone()
+            .oneshot(transcribe("whisper-1"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
 
         let req = Request::builder()
-            .uri("/v1/models")
-            .method("GET")
-            .header("Authorization", "Bearer secret")
+            .uri("/health/ready")
             .body(Body::empty())
             .expect("request");
+        let payload = parse_json_response(app.oneshot(req).await.expect("response")).await;
+        assert_eq!(payload["model_workers"][0]["id"], "final");
+        assert_eq!(payload["model_workers"][0]["workers"]["busy"], 1);
+        assert_eq!(payload["model_workers"][0]["queue"]["max_depth"], 0);
+    }
 
-        let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
+    #[tokio::test]
+    async fn spooled_async_jobs_resume_after_restart() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut cfg = test_cfg(None);
+        cfg.async_job_storage = Some(Arc::new(LocalStorage::new(dir.path())));
 
-        let payload = parse_json_response(res).await;
-        let ids = payload["data"]
-            .as_array()
-            .expect("array")
-            .iter()
-            .filter_map(|m| m["id"].as_str())
-            .collect::<Vec<_>>();
+        let first = AppState::new(cfg.clone(), Arc::new(MockBackend));
+        let mut form = AudioForm {
+            files: vec![UploadedFile {
+                filename: "ok.wav".to_string(),
+                extension: "wav".to_string(),
+                body: UploadBody::Memory(tone_wav(1600)),
+            }],
+            model: "whisper-1".to_string(),
+            language: None,
+            prompt: None,
+            response_format: ResponseFormat::Text,
+            temperature: None,
+            max_tokens_per_segment: None,
+            beam_size: None,
+            best_of: None,
+            stream: false,
+            merge_segments: false,
+            heuristic_punctuation: false,
+            vad_filter: false,
+            vad_params: VadParams {
+                min_silence_ms: 500,
+                padding_ms: 300,
+            },
+            resample_quality: ResampleQuality::High,
+            denoise: false,
+            downmix: Downmix::Channel(0),
+            metadata: Some("rec-42".to_string()),
+            api_key: None,
+            compute: None,
+            include_logprobs: false,
+            subtitle_timing: SubtitleTiming::Millis,
+            word_timestamps: false,
+            detect_secs: None,
+            max_chars: None,
+            overflow: Overflow::default(),
+            debug_trace: false,
+            deadline: None,
+        };
+        let manifest = spooled_job("job_queued", TaskKind::Transcribe, 1_700_000_000, &form);
+        let bodies = form.files.iter().map(|file| &file.body).collect::<Vec<_>>();
+        first
+            .job_spool
+            .as_ref()
+            .expect("spool")
+            .save(&manifest, &bodies)
+            .await
+            .expect("save");
+        form.files.clear();
 
-        assert!(ids.contains(&"whisper-1"));
-        assert!(ids.contains(&"whisper-mlx"));
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        assert_eq!(resume_spooled_jobs(&state).expect("resume"), 1);
+        let mut job = Value::Null;
+        for _ in 0..50 {
+            job = state.jobs.get("job_queued").expect("job");
+            if job["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(job["status"], "completed");
+        assert_eq!(job["created_at"], 1_700_000_000);
+        assert_eq!(job["metadata"], "rec-42");
+        assert_eq!(job["result"], "hello world");
+        let stored = dir.path().join("job_queued");
+        assert!(!stored.join("job.json").exists());
+        assert!(!stored.join("file-0").exists());
+        assert!(stored.join("result.json").exists());
     }
 
     #[tokio::test]
-    async fn models_accept_lowercase_bearer_scheme() {
-        let app = app(Some("secret"));
-
+    async fn transcriptions_accept_multiple_files_as_batch() {
+        let boundary = "X-BOUNDARY";
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"bad.wav\"\r\n\r\nnot audio\r\n"
+        )
+        .into_bytes();
+        body.extend(multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]));
         let req = Request::builder()
-            .uri("/v1/models")
-            .method("GET")
-            .header("Authorization", "bearer secret")
-            .body(Body::empty())
+            .uri("/v1/audio/transcriptions")
+            .method("POST")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
             .expect("request");
 
-        let res = app.oneshot(req).await.expect("response");
+        let res = app(None).oneshot(req).await.expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["object"], "list");
+        let data = payload["data"].as_array().expect("data");
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0]["filename"], "bad.wav");
+        assert_eq!(data[0]["error"]["code"], "unsupported_media_type");
+        assert_eq!(data[1]["index"], 1);
+        assert_eq!(data[1]["text"], "hello world");
     }
 
     #[tokio::test]
-    async fn transcriptions_reject_mp4() {
-        let app = app(None);
+    async fn transcriptions_echo_bounded_metadata() {
         let boundary = "X-BOUNDARY";
-        let body = format!(
-            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"bad.mp4\"\r\nContent-Type: video/mp4\r\n\r\nnot-a-real-media\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}--\r\n",
-            b = boundary
-        );
+        let oversized = "x".repeat(super::MAX_METADATA_BYTES + 1);
+        for (metadata, expected) in [
+            ("rec-42", StatusCode::OK),
+            (oversized.as_str(), StatusCode::BAD_REQUEST),
+        ] {
+            let body = multipart_body(
+                boundary,
+                "ok.wav",
+                &tone_wav(1600),
+                &[("metadata", metadata)],
+            );
+            let req = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(body))
+                .expect("request");
+
+            let res = app(None).oneshot(req).await.expect("response");
+            assert_eq!(res.status(), expected);
+            let payload = parse_json_response(res).await;
+            if expected == StatusCode::OK {
+                assert_eq!(payload["metadata"], "rec-42");
+            } else {
+                assert_eq!(payload["error"]["code"], "invalid_metadata");
+            }
+        }
+    }
 
+    #[tokio::test]
+    async fn transcriptions_return_503_when_upload_slots_exhausted() {
+        let mut cfg = test_cfg(None);
+        cfg.max_concurrent_uploads = 1;
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        let _held = state.upload_slots.try_acquire().expect("upload slot");
+        let app = build_router(Arc::clone(&state));
+
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
         let req = Request::builder()
             .uri("/v1/audio/transcriptions")
             .method("POST")
@@ -548,21 +6526,82 @@ mod tests {
             .expect("request");
 
         let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
-
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
         let payload = parse_json_response(res).await;
-        assert_eq!(payload["error"]["code"], "unsupported_media_type");
+        assert_eq!(payload["error"]["code"], "server_overloaded");
     }
 
     #[tokio::test]
-    async fn transcriptions_validate_model_field() {
+    async fn api_keys_authenticate_independently_with_per_key_limits() {
+        let mut cfg = test_cfg(None);
+        cfg.api_keys = vec![
+            ApiKey {
+                key: "sk-alice".to_string(),
+                label: "alice".to_string(),
+                requests_per_minute: Some(1),
+                concurrent_requests: None,
+                compute: None,
+                defaults: KeyDefaults::default(),
+            },
+            ApiKey {
+                key: "sk-bob".to_string(),
+                label: "bob".to_string(),
+                requests_per_minute: None,
+                concurrent_requests: None,
+                compute: None,
+                defaults: KeyDefaults::default(),
+            },
+        ];
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let get = |token: &str| {
+            Request::builder()
+                .uri("/v1/models")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .expect("request")
+        };
+
+        let status = |res: axum::response::Response| res.status();
+        assert_eq!(
+            status(
+                app.clone()
+                    .oneshot(get("sk-alice"))
+                    .await
+                    .expect("response")
+            ),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(
+                app.clone()
+                    .oneshot(get("sk-alice"))
+                    .await
+                    .expect("response")
+            ),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        for _ in 0..3 {
+            assert_eq!(
+                status(app.clone().oneshot(get("sk-bob")).await.expect("response")),
+                StatusCode::OK
+            );
+        }
+        assert_eq!(
+            status(app.oneshot(get("sk-carol")).await.expect("response")),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn max_chars_splits_transcript_into_continuation_pages() {
         let app = app(None);
         let boundary = "X-BOUNDARY";
-        let body = format!(
-            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nunknown-model\r\n--{b}--\r\n",
-            b = boundary
+        let body = multipart_body(
+            boundary,
+            "ok.wav",
+            &tone_wav(1600),
+            &[("model", "whisper-1"), ("max_chars", "5")],
         );
-
         let req = Request::builder()
             .uri("/v1/audio/transcriptions")
             .method("POST")
@@ -573,22 +6612,105 @@ mod tests {
             .body(Body::from(body))
             .expect("request");
 
+        let res = app.clone().oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let first = parse_json_response(res).await;
+        assert_eq!(first["text"], "hello");
+        let token = first["continuation_token"].as_str().expect("token");
+
+        let req = Request::builder()
+            .uri(format!("/v1/audio/continuations/{token}"))
+            .body(Body::empty())
+            .expect("request");
         let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.status(), StatusCode::OK);
+        let second = parse_json_response(res).await;
+        assert_eq!(second["text"], "world");
+        assert_eq!(second["language"], "en");
+        assert!(second.get("continuation_token").is_none());
+    }
 
-        let payload = parse_json_response(res).await;
-        assert_eq!(payload["error"]["code"], "invalid_model");
+    #[tokio::test]
+    async fn uploads_over_max_upload_bytes_return_413() {
+        let mut cfg = test_cfg(None);
+        cfg.max_upload_bytes = 1000;
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let boundary = "X-BOUNDARY";
+        let request = |content_length: Option<&str>| {
+            let mut builder = Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                );
+            if let Some(len) = content_length {
+                builder = builder.header("Content-Length", len);
+            }
+            let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
+            builder.body(Body::from(body)).expect("request")
+        };
+
+        for content_length in [None, Some("1000000000")] {
+            let res = app
+                .clone()
+                .oneshot(request(content_length))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            let payload = parse_json_response(res).await;
+            assert_eq!(payload["error"]["code"], "request_too_large");
+        }
     }
 
     #[tokio::test]
-    async fn transcriptions_reject_non_finite_temperature() {
-        let app = app(None);
+    async fn audio_over_max_audio_seconds_is_rejected_after_decoding() {
+        let mut cfg = test_cfg(None);
+        cfg.max_audio_duration = Some(std::time::Duration::from_secs(1));
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
         let boundary = "X-BOUNDARY";
-        let body = format!(
-            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}\r\nContent-Disposition: form-data; name=\"temperature\"\r\n\r\nNaN\r\n--{b}--\r\n",
-            b = boundary
-        );
+        let request = |samples: usize| {
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("POST")
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={boundary}"),
+                )
+                .body(Body::from(multipart_body(
+                    boundary,
+                    "audio.wav",
+                    &tone_wav(samples),
+                    &[],
+                )))
+                .expect("request")
+        };
+
+        let res = app
+            .clone()
+            .oneshot(request(16_000))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app.oneshot(request(32_000)).await.expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload = parse_json_response(res).await;
+        assert_eq!(payload["error"]["code"], "audio_too_long");
+        assert_eq!(payload["error"]["param"], "file");
+    }
+
+    #[tokio::test]
+    async fn large_uploads_are_spooled_to_disk_and_removed() {
+        let spill_dir = std::env::temp_dir().join(format!("wos-spill-{}", std::process::id()));
+        std::fs::create_dir_all(&spill_dir).expect("spill dir");
+        let mut cfg = test_cfg(None);
+        cfg.upload_spill_threshold_bytes = Some(64);
+        cfg.upload_spill_dir = Some(spill_dir.to_string_lossy().into_owned());
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
 
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "ok.wav", &tone_wav(1600), &[]);
         let req = Request::builder()
             .uri("/v1/audio/transcriptions")
             .method("POST")
@@ -600,21 +6722,147 @@ mod tests {
             .expect("request");
 
         let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.status(), StatusCode::OK);
+        let leftover = std::fs::read_dir(&spill_dir).expect("read dir").count();
+        let _ = std::fs::remove_dir_all(&spill_dir);
+        assert_eq!(leftover, 0);
+    }
+
+    #[tokio::test]
+    async fn v1_requests_over_rate_limit_return_429() {
+        let mut cfg = test_cfg(Some("secret"));
+        cfg.rate_limit_requests_per_minute = Some(1);
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let get = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header("Authorization", "Bearer secret")
+                .body(Body::empty())
+                .expect("request")
+        };
 
+        let res = app
+            .clone()
+            .oneshot(get("/v1/models"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app
+            .clone()
+            .oneshot(get("/v1/models"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "60");
         let payload = parse_json_response(res).await;
-        assert_eq!(payload["error"]["code"], "invalid_temperature");
+        assert_eq!(payload["error"]["code"], "rate_limit_exceeded");
+
+        let res = app.oneshot(get("/health")).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
     }
 
     #[tokio::test]
-    async fn transcriptions_reject_out_of_range_temperature() {
-        let app = app(None);
-        let boundary = "X-BOUNDARY";
-        let body = format!(
-            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"ok.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF____WAVE\r\n--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n--{b}\r\nContent-Disposition: form-data; name=\"temperature\"\r\n\r\n1.5\r\n--{b}--\r\n",
-            b = boundary
+    async fn held_rate_limit_permits_outlive_the_response() {
+        let mut cfg = test_cfg(None);
+        cfg.rate_limit_concurrent_requests = Some(1);
+        let state = Arc::new(AppState::new(cfg, Arc::new(MockBackend)));
+        // Stands in for a WebSocket session that outlives its upgrade response.
+        let sessions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let open = Arc::clone(&sessions);
+        let app = axum::Router::new()
+            .route(
+                "/session",
+                axum::routing::get(
+                    move |axum::Extension(permit): axum::Extension<HeldPermit>| async move {
+                        open.lock().expect("lock").push(permit);
+                        StatusCode::OK
+                    },
+                ),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                state,
+                enforce_rate_limit,
+            ));
+        let get = || {
+            Request::builder()
+                .uri("/session")
+                .body(Body::empty())
+                .expect("request")
+        };
+
+        let res = app.clone().oneshot(get()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        to_bytes(res.into_body(), usize::MAX).await.expect("body");
+        let res = app.clone().oneshot(get()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        sessions.lock().expect("lock").clear();
+        let res = app.oneshot(get()).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_succeeds_without_auth_for_allowed_origins() {
+        let mut cfg = test_cfg(Some("secret"));
+        cfg.cors_allowed_origins = vec![axum::http::HeaderValue::from_static(
+            "https://app.example.com",
+        )];
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
+        let preflight = |origin: &'static str| {
+            Request::builder()
+                .uri("/v1/audio/transcriptions")
+                .method("OPTIONS")
+                .header("Origin", origin)
+                .header("Access-Control-Request-Method", "POST")
+                .header("Access-Control-Request-Headers", "authorization")
+                .body(Body::empty())
+                .expect("request")
+        };
+
+        let res = app
+            .clone()
+            .oneshot(preflight("https://app.example.com"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()["access-control-allow-origin"],
+            "https://app.example.com"
         );
+        assert!(res.headers()["access-control-allow-methods"]
+            .to_str()
+            .expect("methods")
+            .contains("POST"));
+
+        let res = app
+            .clone()
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .expect("response");
+        assert!(res.headers().get("access-control-allow-origin").is_none());
+
+        let req = Request::builder()
+            .uri("/v1/models")
+            .header("Origin", "https://app.example.com")
+            .header("Authorization", "Bearer secret")
+            .body(Body::empty())
+            .expect("request");
+        let res = app.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers()["access-control-expose-headers"]
+            .to_str()
+            .expect("expose")
+            .contains("x-detected-language"));
+    }
+
+    #[tokio::test]
+    async fn transcriptions_reject_audio_larger_than_memory_budget() {
+        let mut cfg = test_cfg(None);
+        cfg.memory_budget_mb = Some(1);
+        let app = build_router(Arc::new(AppState::new(cfg, Arc::new(MockBackend))));
 
+        let boundary = "X-BOUNDARY";
+        let body = multipart_body(boundary, "long.wav", &tone_wav(16_000 * 20), &[]);
         let req = Request::builder()
             .uri("/v1/audio/transcriptions")
             .method("POST")
@@ -626,9 +6874,8 @@ mod tests {
             .expect("request");
 
         let res = app.oneshot(req).await.expect("response");
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
         let payload = parse_json_response(res).await;
-        assert_eq!(payload["error"]["code"], "invalid_temperature");
+        assert_eq!(payload["error"]["code"], "request_too_large");
     }
 }
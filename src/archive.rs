@@ -0,0 +1,208 @@
+//! Archive of completed transcripts.
+//!
+//! When `TRANSCRIPT_ARCHIVE_DIR` is set, every completed transcription or
+//! translation is written to its storage (see `storage`) as
+//! `<YYYY-MM-DD>/<id>.json` (the `verbose_json` rendering) plus `<id>.txt`,
+//! `<id>.srt`, or `<id>.vtt` when the request asked for a text or subtitle
+//! format. With storage encryption configured, both values are sealed and
+//! keep their names. The JSON value records the label of the API key the
+//! request was made with, so `purge` can find a key's transcripts.
+
+use std::io;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::encryption::{is_sealed, StorageCipher};
+use crate::formats::ResponseFormat;
+use crate::purge::{parse_day, PurgeFilter};
+use crate::storage::Storage;
+
+/// Writes completed transcripts under date-partitioned keys.
+pub struct TranscriptArchive {
+    storage: Arc<dyn Storage>,
+    cipher: Option<StorageCipher>,
+}
+
+impl TranscriptArchive {
+    /// Creates an archive keeping transcripts in `storage`.
+    pub fn new(storage: Arc<dyn Storage>, cipher: Option<StorageCipher>) -> Self {
+        Self { storage, cipher }
+    }
+
+    /// Writes `json` and, for text formats, `rendered` under today's (UTC) date.
+    ///
+    /// Returns the key of the JSON value.
+    pub async fn save(
+        &self,
+        id: &str,
+        unix_secs: u64,
+        json: &Value,
+        format: ResponseFormat,
+        rendered: Option<&str>,
+    ) -> io::Result<String> {
+        let (year, month, day) = utc_date(unix_secs);
+        let stem = format!("{year:04}-{month:02}-{day:02}/{id}");
+        let body = serde_json::to_vec_pretty(json)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut values = vec![(format!("{stem}.json"), self.seal(body)?)];
+        if let (Some(extension), Some(rendered)) = (text_extension(format), rendered) {
+            values.push((
+                format!("{stem}.{extension}"),
+                self.seal(rendered.as_bytes().to_vec())?,
+            ));
+        }
+
+        let storage = Arc::clone(&self.storage);
+        tokio::task::spawn_blocking(move || {
+            for (key, value) in &values {
+                storage.put(key, value)?;
+            }
+            Ok(values.swap_remove(0).0)
+        })
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    }
+
+    /// Deletes the archived transcripts matching `filter` and returns how many matched.
+    ///
+    /// Transcripts are dated by when they were written. With `dry_run` set,
+    /// matches are only counted.
+    pub fn purge(&self, filter: &PurgeFilter, dry_run: bool) -> io::Result<usize> {
+        let mut purged = 0;
+        for object in self.storage.list("")? {
+            let Some(stem) = object.key.strip_suffix(".json") else {
+                continue;
+            };
+            let covered = stem
+                .split_once('/')
+                .and_then(|(day, _)| parse_day(day))
+                .is_some_and(|start| filter.covers_day(start));
+            if !covered {
+                continue;
+            }
+            let api_key = match &filter.api_key {
+                Some(_) => self.api_key_of(&object.key)?,
+                None => None,
+            };
+            if !filter.matches(api_key.as_deref(), object.modified) {
+                continue;
+            }
+            purged += 1;
+            if !dry_run {
+                for extension in ["json", "txt", "srt", "vtt"] {
+                    self.storage.delete(&format!("{stem}.{extension}"))?;
+                }
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Reads the API key label recorded in an archived JSON value.
+    fn api_key_of(&self, key: &str) -> io::Result<Option<String>> {
+        let Some(mut contents) = self.storage.get(key)? else {
+            return Ok(None);
+        };
+        if let (Some(cipher), true) = (&self.cipher, is_sealed(&contents)) {
+            contents = cipher.open(&contents)?;
+        }
+        Ok(serde_json::from_slice::<Value>(&contents)
+            .ok()
+            .and_then(|json| json["api_key"].as_str().map(ToOwned::to_owned)))
+    }
+
+    fn seal(&self, contents: Vec<u8>) -> io::Result<Vec<u8>> {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&contents),
+            None => Ok(contents),
+        }
+    }
+}
+
+/// File extension for formats archived alongside the JSON file.
+fn text_extension(format: ResponseFormat) -> Option<&'static str> {
+    match format {
+        ResponseFormat::Text => Some("txt"),
+        ResponseFormat::Srt => Some("srt"),
+        ResponseFormat::Vtt => Some("vtt"),
+        ResponseFormat::Json | ResponseFormat::VerboseJson => None,
+    }
+}
+
+/// Converts Unix seconds to a UTC `(year, month, day)` civil date.
+pub(crate) fn utc_date(unix_secs: u64) -> (i64, u32, u32) {
+    // Days-to-civil conversion from Howard Hinnant's date algorithms.
+    let days = (unix_secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    #[test]
+    fn unix_seconds_map_to_utc_dates() {
+        assert_eq!(utc_date(0), (1970, 1, 1));
+        assert_eq!(utc_date(951_782_400), (2000, 2, 29));
+        assert_eq!(utc_date(1_735_689_599), (2024, 12, 31));
+    }
+
+    #[tokio::test]
+    async fn archive_writes_json_and_subtitles_by_date() {
+        let dir = std::env::temp_dir().join(format!("wos-archive-{}", std::process::id()));
+        let archive = TranscriptArchive::new(Arc::new(LocalStorage::new(&dir)), None);
+        let json = serde_json::json!({"text": "hi"});
+
+        let key = archive
+            .save("tr_1", 0, &json, ResponseFormat::Srt, Some("1\n"))
+            .await
+            .expect("save");
+        assert_eq!(key, "1970-01-01/tr_1.json");
+        assert!(dir.join("1970-01-01").join("tr_1.srt").exists());
+
+        archive
+            .save("tr_2", 0, &json, ResponseFormat::Json, None)
+            .await
+            .expect("save");
+        assert!(!dir.join("1970-01-01").join("tr_2.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn purge_removes_one_keys_transcripts() {
+        let dir = std::env::temp_dir().join(format!("wos-archive-purge-{}", std::process::id()));
+        let archive = TranscriptArchive::new(Arc::new(LocalStorage::new(&dir)), None);
+        let today = crate::jobs::unix_secs();
+        for (id, key) in [("tr_a", "team-a"), ("tr_b", "team-b")] {
+            let json = serde_json::json!({"text": "hi", "api_key": key});
+            archive
+                .save(id, today, &json, ResponseFormat::Vtt, Some("WEBVTT\n"))
+                .await
+                .expect("save");
+        }
+        let filter = PurgeFilter {
+            api_key: Some("team-a".to_string()),
+            ..PurgeFilter::default()
+        };
+
+        assert_eq!(archive.purge(&filter, true).expect("dry run"), 1);
+        assert_eq!(archive.purge(&filter, false).expect("purge"), 1);
+        let (year, month, day) = utc_date(today);
+        let day_dir = dir.join(format!("{year:04}-{month:02}-{day:02}"));
+        assert!(!day_dir.join("tr_a.json").exists());
+        assert!(!day_dir.join("tr_a.vtt").exists());
+        assert!(day_dir.join("tr_b.vtt").exists());
+        assert_eq!(archive.purge(&filter, false).expect("purge"), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
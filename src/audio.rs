@@ -1,60 +1,362 @@
 //! Audio validation and decoding utilities.
 //!
 //! Uploaded files are decoded to 16 kHz mono PCM (`f32`) because that is the
-//! format expected by downstream Whisper inference in this project.
+//! format expected by downstream Whisper inference in this project. G.711
+//! telephony audio is decoded by `g711`, everything else by symphonia.
+//! Multichannel audio is reduced to mono by its [`Downmix`]: the average of
+//! all channels, or a single channel the request selected.
+//! Other sample rates are converted with the [`ResampleQuality`] the request
+//! chose: linear interpolation, or a windowed-sinc filter that removes the
+//! content above 8 kHz instead of folding it back into the speech band.
+//! Each packet is downmixed and resampled as soon as it is decoded, so only
+//! the 16 kHz result of a long recording is held in memory.
 
-use std::io::{Cursor, ErrorKind};
+use std::fs::File;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use serde::Deserialize;
 
 use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS};
 use symphonia::core::errors::Error as SymphoniaError;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::formats::{FormatOptions, Packet, Track};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 use symphonia::default::{get_codecs, get_probe};
 
+use tracing::warn;
+
 use crate::error::AppError;
+use crate::g711;
+#[cfg(feature = "opus")]
+use crate::opus::OpusDecoder;
 
 const TARGET_SAMPLE_RATE: u32 = 16_000;
+/// Peak amplitude (about -60 dBFS) below which decoded audio is treated as silence.
+const SILENCE_PEAK_THRESHOLD: f32 = 0.001;
+/// Magnitude at or above which a sample is counted as clipped (full scale).
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Length of a voice activity detection frame (30 ms at 16 kHz).
+const VAD_FRAME_SAMPLES: usize = 480;
+/// Frames this far above the recording's noise floor count as speech.
+const VAD_MARGIN_DB: f32 = 10.0;
+/// Bounds on the speech threshold, so clean recordings still keep quiet
+/// speech and noisy ones are left alone rather than trimmed into the speech.
+const VAD_MIN_THRESHOLD_DB: f32 = -60.0;
+const VAD_MAX_THRESHOLD_DB: f32 = -30.0;
+
+/// Error for Opus audio in a server built without the `opus` feature.
+#[cfg(not(feature = "opus"))]
+pub const OPUS_UNAVAILABLE: &str =
+    "Opus audio requires a server built with the `opus` feature; send pcm_s16le, WAV, FLAC, or MP3 instead";
 
 /// File extensions accepted by upload validation.
-pub const SUPPORTED_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "flac", "ogg", "webm"];
+pub const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "wav", "mp3", "mpga", "mpeg", "m4a", "mp4", "m4v", "mov", "flac", "ogg", "webm", "au", "ul",
+    "ulaw", "mulaw", "al", "alaw",
+];
 
 /// Validates and normalizes the file extension from an uploaded filename.
 ///
 /// Returns the lowercased extension without the leading dot.
 pub fn validate_extension(filename: &str) -> Result<String, AppError> {
+    let accepted = SUPPORTED_EXTENSIONS
+        .iter()
+        .map(|ext| format!(".{ext}"))
+        .collect::<Vec<_>>()
+        .join(",");
     let extension = filename
         .rsplit_once('.')
         .map(|(_, ext)| ext.trim().to_ascii_lowercase())
         .ok_or_else(|| {
-            AppError::unsupported_media_type(
-                "file must include an extension; accepted extensions: .wav,.mp3,.m4a,.flac,.ogg,.webm",
-            )
+            AppError::unsupported_media_type(format!(
+                "file must include an extension; accepted extensions: {accepted}"
+            ))
         })?;
 
-    if extension == "mp4" {
-        return Err(AppError::unsupported_media_type(
-            "unsupported file extension .mp4; accepted extensions: .wav,.mp3,.m4a,.flac,.ogg,.webm",
-        ));
-    }
-
     if !SUPPORTED_EXTENSIONS.iter().any(|ext| *ext == extension) {
         return Err(AppError::unsupported_media_type(format!(
-            "unsupported file extension .{extension}; accepted extensions: .wav,.mp3,.m4a,.flac,.ogg,.webm"
+            "unsupported file extension .{extension}; accepted extensions: {accepted}"
         )));
     }
 
     Ok(extension)
 }
 
+/// Resampler used to convert decoded audio to 16 kHz.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleQuality {
+    /// Linear interpolation: cheapest, but aliases content above 8 kHz.
+    #[default]
+    Fast,
+    /// Windowed sinc with 8 zero crossings per side.
+    Balanced,
+    /// Windowed sinc with 32 zero crossings per side and a sharper cutoff.
+    High,
+}
+
+impl ResampleQuality {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::Balanced => "balanced",
+            Self::High => "high",
+        }
+    }
+}
+
+/// How multichannel audio is reduced to mono.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Downmix {
+    /// The average of all channels.
+    #[default]
+    Average,
+    /// A single channel, numbered from 0.
+    Channel(usize),
+}
+
+impl Downmix {
+    /// Appends one mono sample per frame of `interleaved` audio to `mono`.
+    ///
+    /// Fails when the selected channel is not in the stream.
+    pub fn apply(
+        self,
+        interleaved: &[f32],
+        channels: usize,
+        mono: &mut Vec<f32>,
+    ) -> Result<(), AppError> {
+        self.check(channels)?;
+        if channels <= 1 {
+            mono.extend_from_slice(interleaved);
+            return Ok(());
+        }
+        let frames = interleaved.chunks_exact(channels);
+        match self {
+            Self::Average => {
+                let scale = 1.0 / channels as f32;
+                mono.extend(frames.map(|frame| frame.iter().sum::<f32>() * scale));
+            }
+            Self::Channel(channel) => mono.extend(frames.map(|frame| frame[channel])),
+        }
+        Ok(())
+    }
+
+    /// Checks that a selected channel exists in audio with `channels` channels.
+    pub fn check(self, channels: usize) -> Result<(), AppError> {
+        match self {
+            Self::Channel(channel) if channel >= channels.max(1) => {
+                Err(AppError::invalid_request(
+                    format!(
+                        "channel={channel} is out of range; the audio has {} channel(s), numbered from 0",
+                        channels.max(1)
+                    ),
+                    Some("channel"),
+                    Some("invalid_channel"),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Audio decoded to 16 kHz mono.
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    /// Time spent resampling; `None` when the audio was already 16 kHz.
+    pub resample_elapsed: Option<Duration>,
+}
+
 /// Decodes media bytes into normalized 16 kHz mono samples.
 ///
 /// `extension_hint` is used to improve container format probing.
 pub fn decode_to_mono_16khz_f32(bytes: &[u8], extension_hint: &str) -> Result<Vec<f32>, AppError> {
-    let cursor = Cursor::new(bytes.to_vec());
-    let mss = MediaSourceStream::new(Box::new(cursor), Default::default());
+    decode_bytes(
+        bytes.to_vec().into(),
+        extension_hint,
+        ResampleQuality::Fast,
+        Downmix::Average,
+        None,
+    )
+    .map(|decoded| decoded.samples)
+}
+
+/// Media bytes that decoders read in place, cheap to clone.
+#[derive(Clone)]
+pub struct SharedBytes(Arc<Vec<u8>>);
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(Arc::new(bytes))
+    }
+}
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Decodes media bytes, resampling with `quality` and reducing channels with `downmix`.
+///
+/// With `max_samples` set, decoding stops once that many 16 kHz samples are
+/// decoded and the rest of the media is never read.
+pub fn decode_bytes(
+    bytes: SharedBytes,
+    extension_hint: &str,
+    quality: ResampleQuality,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+) -> Result<DecodedAudio, AppError> {
+    decode_source(
+        Box::new(Cursor::new(bytes)),
+        extension_hint,
+        quality,
+        downmix,
+        max_samples,
+    )
+}
+
+/// Decodes an uploaded file spooled to disk, reading it incrementally.
+pub fn decode_file(
+    file: File,
+    extension_hint: &str,
+    quality: ResampleQuality,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+) -> Result<DecodedAudio, AppError> {
+    decode_source(
+        Box::new(file),
+        extension_hint,
+        quality,
+        downmix,
+        max_samples,
+    )
+}
+
+/// Decodes media from a seekable `reader` holding `len` bytes, such as a
+/// sealed upload being decrypted as it is read.
+pub fn decode_reader<R>(
+    reader: R,
+    len: u64,
+    extension_hint: &str,
+    quality: ResampleQuality,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+) -> Result<DecodedAudio, AppError>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let source = SizedReader { inner: reader, len };
+    decode_source(
+        Box::new(source),
+        extension_hint,
+        quality,
+        downmix,
+        max_samples,
+    )
+}
+
+/// A seekable reader of known length, exposed to symphonia as a media source.
+struct SizedReader<R> {
+    inner: R,
+    len: u64,
+}
+
+impl<R: Read> Read for SizedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for SizedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R: Read + Seek + Send + Sync> MediaSource for SizedReader<R> {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+/// Decodes media from a `source` whose bytes may still be arriving, passing
+/// its 16 kHz mono samples to `emit` as each packet is decoded.
+///
+/// Reads block on the source, so call off the async runtime.
+pub fn decode_incrementally(
+    source: Box<dyn MediaSource>,
+    extension_hint: &str,
+    emit: &mut dyn FnMut(Vec<f32>),
+) -> Result<(), AppError> {
+    let mut on_packet = |samples: &mut Vec<f32>| {
+        if !samples.is_empty() {
+            emit(std::mem::take(samples));
+        }
+    };
+    let decoded = decode_packets(
+        source,
+        extension_hint,
+        ResampleQuality::Fast,
+        Downmix::Average,
+        None,
+        &mut on_packet,
+    )?;
+    on_packet(&mut { decoded.samples });
+    Ok(())
+}
+
+fn decode_source(
+    source: Box<dyn MediaSource>,
+    extension_hint: &str,
+    quality: ResampleQuality,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+) -> Result<DecodedAudio, AppError> {
+    decode_packets(
+        source,
+        extension_hint,
+        quality,
+        downmix,
+        max_samples,
+        &mut |_| {},
+    )
+}
+
+/// Decodes `source`, handing the normalized samples to `on_packet` after
+/// each packet; whatever it leaves behind is returned at the end.
+///
+/// Stops reading once `max_samples` samples are held back for the result.
+fn decode_packets(
+    mut source: Box<dyn MediaSource>,
+    extension_hint: &str,
+    quality: ResampleQuality,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+    on_packet: &mut dyn FnMut(&mut Vec<f32>),
+) -> Result<DecodedAudio, AppError> {
+    if let Some((mut mono, sample_rate)) = g711::decode(&mut source, extension_hint, downmix)? {
+        let mut normalizer = Normalizer::new(quality, max_samples);
+        if let Some(max_samples) = max_samples {
+            // One extra source sample covers rounding in the resampler.
+            let source_samples =
+                max_samples as u64 * u64::from(sample_rate) / u64::from(TARGET_SAMPLE_RATE) + 1;
+            mono.truncate(source_samples as usize);
+        }
+        normalizer.push(&mut mono, sample_rate);
+        return normalizer.finish();
+    }
+    let mss = MediaSourceStream::new(source, Default::default());
 
     let mut hint = Hint::new();
     hint.with_extension(extension_hint);
@@ -71,8 +373,7 @@ pub fn decode_to_mono_16khz_f32(bytes: &[u8], extension_hint: &str) -> Result<Ve
         })?;
 
     let mut format = probed.format;
-    let track = format
-        .default_track()
+    let track = select_track(format.tracks(), format.default_track())
         .ok_or_else(|| AppError::unsupported_media_type("no audio track found in uploaded file"))?;
 
     if track.codec_params.codec == CODEC_TYPE_NULL {
@@ -81,12 +382,23 @@ pub fn decode_to_mono_16khz_f32(bytes: &[u8], extension_hint: &str) -> Result<Ve
         ));
     }
 
-    let mut decoder = get_codecs()
-        .make(&track.codec_params, &DecoderOptions::default())
-        .map_err(|err| AppError::unsupported_media_type(format!("unsupported codec: {err}")))?;
+    let mut decoder = if track.codec_params.codec == CODEC_TYPE_OPUS {
+        TrackDecoder::opus(track.codec_params.extra_data.as_deref(), downmix)?
+    } else {
+        if let Some(channels) = track.codec_params.channels {
+            downmix.check(channels.count())?;
+        }
+        TrackDecoder::Symphonia(
+            get_codecs()
+                .make(&track.codec_params, &DecoderOptions::default())
+                .map_err(|err| {
+                    AppError::unsupported_media_type(format!("unsupported codec: {err}"))
+                })?,
+        )
+    };
 
-    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(TARGET_SAMPLE_RATE);
     let track_id = track.id;
+    let mut normalizer = Normalizer::new(quality, max_samples);
     let mut mono = Vec::new();
 
     loop {
@@ -109,9 +421,168 @@ pub fn decode_to_mono_16khz_f32(bytes: &[u8], extension_hint: &str) -> Result<Ve
             continue;
         }
 
-        let decoded = match decoder.decode(&packet) {
+        if let Some(rate) = decoder.decode(&packet, downmix, &mut mono)? {
+            normalizer.push(&mut mono, rate);
+            on_packet(&mut normalizer.samples);
+            if normalizer.is_full() {
+                break;
+            }
+        }
+    }
+    normalizer.finish()
+}
+
+/// Sanitizes decoded mono audio and resamples it to 16 kHz packet by packet,
+/// so the source-rate signal is never held in full.
+struct Normalizer {
+    quality: ResampleQuality,
+    /// Resampler for the current source rate; a rate change mid-stream ends
+    /// the current run and starts a new one.
+    resampler: Option<(u32, Resampler)>,
+    samples: Vec<f32>,
+    /// Samples kept at most; the rest are dropped by [`finish`](Self::finish).
+    max_samples: Option<usize>,
+    decoded: usize,
+    replaced: usize,
+    resample_elapsed: Option<Duration>,
+}
+
+impl Normalizer {
+    fn new(quality: ResampleQuality, max_samples: Option<usize>) -> Self {
+        Self {
+            quality,
+            resampler: None,
+            samples: Vec::new(),
+            max_samples,
+            decoded: 0,
+            replaced: 0,
+            resample_elapsed: None,
+        }
+    }
+
+    /// Converts and removes the samples in `mono`, decoded at `sample_rate`.
+    fn push(&mut self, mono: &mut Vec<f32>, sample_rate: u32) {
+        if mono.is_empty() {
+            return;
+        }
+        self.decoded += mono.len();
+        self.replaced += sanitize_samples(mono);
+        if sample_rate == TARGET_SAMPLE_RATE && self.resampler.is_none() {
+            self.samples.append(mono);
+            return;
+        }
+        let started = Instant::now();
+        if self
+            .resampler
+            .as_ref()
+            .is_some_and(|(rate, _)| *rate != sample_rate)
+        {
+            self.finish_run();
+        }
+        let (_, resampler) = self.resampler.get_or_insert_with(|| {
+            (
+                sample_rate,
+                Resampler::new(sample_rate, TARGET_SAMPLE_RATE, self.quality),
+            )
+        });
+        resampler.push(mono, &mut self.samples);
+        mono.clear();
+        *self.resample_elapsed.get_or_insert(Duration::ZERO) += started.elapsed();
+    }
+
+    /// Returns `true` once `max_samples` samples are held.
+    fn is_full(&self) -> bool {
+        self.max_samples
+            .is_some_and(|max_samples| self.samples.len() >= max_samples)
+    }
+
+    fn finish_run(&mut self) {
+        if let Some((_, resampler)) = self.resampler.take() {
+            resampler.finish(&mut self.samples);
+        }
+    }
+
+    fn finish(mut self) -> Result<DecodedAudio, AppError> {
+        if self.decoded == 0 {
+            return Err(AppError::unsupported_media_type(
+                "decoded audio is empty after processing",
+            ));
+        }
+        if self.replaced > 0 {
+            warn!(
+                replaced = self.replaced,
+                total = self.decoded,
+                "replaced non-finite or denormal samples in decoded audio"
+            );
+        }
+        if self.resampler.is_some() {
+            let started = Instant::now();
+            self.finish_run();
+            *self.resample_elapsed.get_or_insert(Duration::ZERO) += started.elapsed();
+        }
+        if let Some(max_samples) = self.max_samples {
+            self.samples.truncate(max_samples);
+        }
+        Ok(DecodedAudio {
+            samples: self.samples,
+            resample_elapsed: self.resample_elapsed,
+        })
+    }
+}
+
+/// Picks the track to decode: the default track when it has a known codec,
+/// otherwise the first track that does.
+///
+/// Video containers often list the video track first; symphonia leaves its
+/// codec unset, so this finds the audio track behind it. Falls back to the
+/// default track so a file with no usable track reports why.
+fn select_track<'a>(tracks: &'a [Track], default: Option<&'a Track>) -> Option<&'a Track> {
+    default
+        .into_iter()
+        .chain(tracks)
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .or(default)
+}
+
+/// Per-track packet decoder: symphonia's, or libopus for Opus tracks.
+enum TrackDecoder {
+    Symphonia(Box<dyn Decoder>),
+    #[cfg(feature = "opus")]
+    Opus(OpusDecoder),
+}
+
+impl TrackDecoder {
+    #[cfg(feature = "opus")]
+    fn opus(header: Option<&[u8]>, downmix: Downmix) -> Result<Self, AppError> {
+        OpusDecoder::new(header, downmix).map(Self::Opus)
+    }
+
+    #[cfg(not(feature = "opus"))]
+    fn opus(_header: Option<&[u8]>, _downmix: Downmix) -> Result<Self, AppError> {
+        Err(AppError::unsupported_media_type(OPUS_UNAVAILABLE))
+    }
+
+    /// Appends the packet's audio to `mono`, returning the decoded sample rate.
+    ///
+    /// Returns `None` for packets skipped as damaged. The Opus decoder applies
+    /// the downmix it was created with.
+    fn decode(
+        &mut self,
+        packet: &Packet,
+        downmix: Downmix,
+        mono: &mut Vec<f32>,
+    ) -> Result<Option<u32>, AppError> {
+        let decoder = match self {
+            Self::Symphonia(decoder) => decoder,
+            #[cfg(feature = "opus")]
+            Self::Opus(decoder) => {
+                decoder.decode(packet.buf(), mono);
+                return Ok(Some(TARGET_SAMPLE_RATE));
+            }
+        };
+        let decoded = match decoder.decode(packet) {
             Ok(decoded) => decoded,
-            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(SymphoniaError::DecodeError(_)) => return Ok(None),
             Err(err) => {
                 return Err(AppError::unsupported_media_type(format!(
                     "failed to decode audio packet: {err}"
@@ -119,69 +590,419 @@ pub fn decode_to_mono_16khz_f32(bytes: &[u8], extension_hint: &str) -> Result<Ve
             }
         };
 
-        sample_rate = decoded.spec().rate;
         let channels = decoded.spec().channels.count();
-
+        let sample_rate = decoded.spec().rate;
         let mut sample_buffer =
             SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
         sample_buffer.copy_interleaved_ref(decoded);
-        let samples = sample_buffer.samples();
+        downmix.apply(sample_buffer.samples(), channels, mono)?;
+        Ok(Some(sample_rate))
+    }
+}
 
-        if channels <= 1 {
-            mono.extend_from_slice(samples);
-            continue;
+/// Zeroes NaN, infinite, and denormal samples and clamps the rest to `[-1.0, 1.0]`.
+///
+/// Corrupted files can decode to non-finite values that would otherwise reach
+/// whisper. Returns the number of samples that were zeroed.
+pub fn sanitize_samples(samples: &mut [f32]) -> usize {
+    let mut replaced = 0;
+    for sample in samples.iter_mut() {
+        if !sample.is_finite() || sample.is_subnormal() {
+            *sample = 0.0;
+            replaced += 1;
+        } else {
+            *sample = sample.clamp(-1.0, 1.0);
         }
+    }
+    replaced
+}
 
-        for frame in samples.chunks(channels) {
-            let sample = frame
-                .iter()
-                .copied()
-                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
-                .unwrap_or(0.0);
-            mono.push(sample);
-        }
+/// Returns whether decoded audio is empty or never exceeds the silence threshold.
+pub fn is_silent(samples: &[f32]) -> bool {
+    samples
+        .iter()
+        .all(|sample| sample.abs() < SILENCE_PEAK_THRESHOLD)
+}
+
+/// Returns the fraction of samples at full scale, a sign of a clipped recording.
+pub fn clipped_fraction(samples: &[f32]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
     }
+    let clipped = samples
+        .iter()
+        .filter(|sample| sample.abs() >= CLIP_LEVEL)
+        .count();
+    clipped as f64 / samples.len() as f64
+}
 
-    if mono.is_empty() {
-        return Err(AppError::unsupported_media_type(
-            "decoded audio is empty after processing",
-        ));
+/// Voice activity detection settings for trimming long silences.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct VadParams {
+    /// Silences at least this long are cut out.
+    pub min_silence_ms: u32,
+    /// Audio kept on each side of speech next to a cut.
+    pub padding_ms: u32,
+}
+
+/// Where the audio kept by [`trim_silence`] came from, for mapping times back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechMap {
+    /// `(trimmed start, original start, length)` in samples, in order.
+    spans: Vec<(usize, usize, usize)>,
+    original_len: usize,
+}
+
+impl SpeechMap {
+    /// Seconds of audio removed.
+    pub fn removed_secs(&self) -> f64 {
+        let kept = self.spans.iter().map(|(_, _, len)| len).sum::<usize>();
+        (self.original_len - kept) as f64 / TARGET_SAMPLE_RATE as f64
     }
 
-    let normalized = mono
-        .into_iter()
-        .map(|s| s.clamp(-1.0, 1.0))
+    /// Maps a time in the trimmed audio to the original recording.
+    ///
+    /// `end` times on a cut map to the end of the earlier span rather than the
+    /// start of the next, so segments do not stretch over removed silence.
+    pub fn original_secs(&self, secs: f64, end: bool) -> f64 {
+        let pos = (secs.max(0.0) * TARGET_SAMPLE_RATE as f64).round() as usize;
+        let span = self
+            .spans
+            .iter()
+            .rev()
+            .find(|(start, _, _)| if end { *start < pos } else { *start <= pos })
+            .or_else(|| self.spans.first());
+        let Some(&(start, original, len)) = span else {
+            return secs;
+        };
+        let original_pos = original + pos.saturating_sub(start).min(len);
+        original_pos as f64 / TARGET_SAMPLE_RATE as f64
+    }
+}
+
+/// Cuts silences of at least `params.min_silence_ms` out of 16 kHz audio.
+///
+/// Speech is told apart from silence by frame energy relative to the
+/// recording's own noise floor. Returns `None`, leaving `samples` untouched,
+/// when no speech is found or there is nothing long enough to cut.
+pub fn trim_silence(samples: &mut Vec<f32>, params: VadParams) -> Option<SpeechMap> {
+    let levels = samples
+        .chunks(VAD_FRAME_SAMPLES)
+        .map(|frame| {
+            let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            10.0 * (power + 1e-12).log10()
+        })
         .collect::<Vec<_>>();
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise_floor = *sorted.get(sorted.len() / 10)?;
+    let threshold = (noise_floor + VAD_MARGIN_DB).clamp(VAD_MIN_THRESHOLD_DB, VAD_MAX_THRESHOLD_DB);
 
-    Ok(if sample_rate == TARGET_SAMPLE_RATE {
-        normalized
+    let frames_for = |ms: u32| (ms as usize * 16 + VAD_FRAME_SAMPLES - 1) / VAD_FRAME_SAMPLES;
+    let min_silence = frames_for(params.min_silence_ms).max(1);
+    // At most half a cut, so padded spans never overlap.
+    let padding = frames_for(params.padding_ms).min(min_silence / 2);
+
+    // Runs of speech frames, widened by the padding, with short gaps bridged.
+    let mut kept: Vec<(usize, usize)> = Vec::new();
+    let mut frame = 0;
+    while frame < levels.len() {
+        if levels[frame] < threshold {
+            frame += 1;
+            continue;
+        }
+        let start = frame;
+        while frame < levels.len() && levels[frame] >= threshold {
+            frame += 1;
+        }
+        match kept.last_mut() {
+            Some(last) if start - last.1 < min_silence => last.1 = frame,
+            _ => kept.push((start, frame)),
+        }
+    }
+    let first = kept.first_mut()?;
+    first.0 = if first.0 < min_silence {
+        0
     } else {
-        resample_linear(&normalized, sample_rate, TARGET_SAMPLE_RATE)
+        first.0 - padding
+    };
+    for idx in 1..kept.len() {
+        kept[idx].0 -= padding;
+        kept[idx - 1].1 += padding;
+    }
+    let last = kept.last_mut()?;
+    last.1 = if levels.len() - last.1 < min_silence {
+        levels.len()
+    } else {
+        last.1 + padding
+    };
+    if kept == [(0, levels.len())] {
+        return None;
+    }
+
+    let original_len = samples.len();
+    let mut trimmed = Vec::with_capacity(original_len);
+    let mut spans = Vec::with_capacity(kept.len());
+    for (start, end) in kept {
+        let start = start * VAD_FRAME_SAMPLES;
+        let end = (end * VAD_FRAME_SAMPLES).min(original_len);
+        spans.push((trimmed.len(), start, end - start));
+        trimmed.extend_from_slice(&samples[start..end]);
+    }
+    *samples = trimmed;
+    Some(SpeechMap {
+        spans,
+        original_len,
     })
 }
 
+/// Resamples a mono signal from `src_rate` to `dst_rate` with `quality`.
+pub fn resample(input: &[f32], src_rate: u32, dst_rate: u32, quality: ResampleQuality) -> Vec<f32> {
+    Resampler::new(src_rate, dst_rate, quality).resample(input)
+}
+
 /// Resamples a mono signal from `src_rate` to `dst_rate` via linear interpolation.
-fn resample_linear(input: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
-    if src_rate == dst_rate || input.len() < 2 {
-        return input.to_vec();
+pub fn resample_linear(input: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
+    resample(input, src_rate, dst_rate, ResampleQuality::Fast)
+}
+
+/// Converts a mono signal between sample rates, in one call or incrementally.
+///
+/// Pushed input is converted as soon as the filter has seen every source
+/// sample an output sample depends on, and source samples no later output
+/// needs are released, so streaming a recording through it holds only a
+/// filter's width of source-rate audio. The output is the same however the
+/// input is split.
+pub struct Resampler {
+    filter: Filter,
+    src_rate: u32,
+    dst_rate: u32,
+    /// Source samples an output still needs, starting at absolute index `offset`.
+    pending: Vec<f32>,
+    offset: usize,
+    /// Source samples pushed so far.
+    pushed: usize,
+    /// Output samples produced so far.
+    produced: usize,
+}
+
+enum Filter {
+    Passthrough,
+    Linear,
+    /// Blackman-windowed sinc filter.
+    ///
+    /// The filter spans `zero_crossings` lobes on each side and cuts off at
+    /// `rolloff` times the lower of the two Nyquist frequencies, so
+    /// downsampling removes what the output cannot represent. Each output
+    /// sample is normalized by its tap sum, which keeps unity gain at the
+    /// edges of the signal.
+    Sinc {
+        kernel: Vec<f64>,
+        cutoff: f64,
+        half_width: f64,
+    },
+}
+
+/// Kernel table entries per zero crossing; taps are interpolated between them.
+const SINC_TABLE_RESOLUTION: usize = 256;
+
+impl Resampler {
+    pub fn new(src_rate: u32, dst_rate: u32, quality: ResampleQuality) -> Self {
+        let filter = if src_rate == dst_rate {
+            Filter::Passthrough
+        } else {
+            match quality {
+                ResampleQuality::Fast => Filter::Linear,
+                ResampleQuality::Balanced => Filter::sinc(src_rate, dst_rate, 8, 0.90),
+                ResampleQuality::High => Filter::sinc(src_rate, dst_rate, 32, 0.97),
+            }
+        };
+        Self {
+            filter,
+            src_rate,
+            dst_rate,
+            pending: Vec::new(),
+            offset: 0,
+            pushed: 0,
+            produced: 0,
+        }
+    }
+
+    /// Resamples a complete signal.
+    pub fn resample(&self, input: &[f32]) -> Vec<f32> {
+        if matches!(self.filter, Filter::Passthrough) || input.len() < 2 {
+            return input.to_vec();
+        }
+        let out_len = self.out_len(input.len(), true);
+        let mut out = Vec::with_capacity(out_len);
+        self.emit(input, 0, out_len, Some(input.len() - 1), &mut out);
+        out
+    }
+
+    /// Adds `input` and appends the output samples it completes to `out`.
+    pub fn push(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.pushed += input.len();
+        if matches!(self.filter, Filter::Passthrough) {
+            out.extend_from_slice(input);
+            return;
+        }
+        self.pending.extend_from_slice(input);
+        // Never emit past the length the whole signal could end up with.
+        let limit = self.out_len(self.pushed, false);
+        self.produced += self.emit(&self.pending, self.offset, limit, None, out);
+        // The last sample is kept for outputs clamped to the end of the signal.
+        let needed = self.first_needed().min(self.pushed.saturating_sub(1));
+        if needed > self.offset {
+            self.pending.drain(..needed - self.offset);
+            self.offset = needed;
+        }
+    }
+
+    /// Ends the signal, appending the remaining output samples to `out`.
+    pub fn finish(mut self, out: &mut Vec<f32>) {
+        if matches!(self.filter, Filter::Passthrough) {
+            return;
+        }
+        if self.pushed < 2 {
+            out.append(&mut self.pending);
+            return;
+        }
+        let out_len = self.out_len(self.pushed, true);
+        self.emit(
+            &self.pending,
+            self.offset,
+            out_len,
+            Some(self.pushed - 1),
+            out,
+        );
+    }
+
+    /// Source samples per output sample.
+    fn ratio(&self) -> f64 {
+        self.src_rate as f64 / self.dst_rate as f64
     }
 
-    let ratio = src_rate as f64 / dst_rate as f64;
-    let out_len = ((input.len() as f64) * (dst_rate as f64) / (src_rate as f64)).round() as usize;
-    let out_len = out_len.max(1);
+    /// Output length for `len` source samples, rounded (for a finished
+    /// signal) or floored (a lower bound while more input may follow).
+    fn out_len(&self, len: usize, round: bool) -> usize {
+        let exact = match self.filter {
+            Filter::Sinc { .. } => len as f64 / self.ratio(),
+            _ => len as f64 * self.dst_rate as f64 / self.src_rate as f64,
+        };
+        if round {
+            (exact.round() as usize).max(1)
+        } else {
+            exact.floor() as usize
+        }
+    }
 
-    let mut out = Vec::with_capacity(out_len);
-    for i in 0..out_len {
-        let src_pos = i as f64 * ratio;
-        let idx = src_pos.floor() as usize;
-        let frac = (src_pos - idx as f64) as f32;
+    /// Absolute index of the first source sample the next output depends on.
+    fn first_needed(&self) -> usize {
+        let position = self.produced as f64 * self.ratio();
+        match &self.filter {
+            Filter::Sinc { half_width, .. } => (position - half_width).ceil().max(0.0) as usize,
+            _ => position.floor() as usize,
+        }
+    }
 
-        let a = input[idx.min(input.len() - 1)];
-        let b = input[(idx + 1).min(input.len() - 1)];
-        out.push(a + (b - a) * frac);
+    /// Appends output samples from `self.produced` up to `limit`, returning
+    /// how many were appended.
+    ///
+    /// `input` holds source samples from absolute index `offset`. `last` is
+    /// the index of the final source sample once the signal has ended; until
+    /// then, output stops at the first sample whose filter reaches past the
+    /// end of `input`.
+    fn emit(
+        &self,
+        input: &[f32],
+        offset: usize,
+        limit: usize,
+        last: Option<usize>,
+        out: &mut Vec<f32>,
+    ) -> usize {
+        let ratio = self.ratio();
+        let available = (offset + input.len()) as i64 - 1;
+        let at = |index: i64| input[index as usize - offset];
+        let mut emitted = 0;
+        for i in self.produced..limit {
+            let center = i as f64 * ratio;
+            match &self.filter {
+                Filter::Passthrough => unreachable!("passthrough is not filtered"),
+                Filter::Linear => {
+                    let idx = center.floor() as i64;
+                    let frac = (center - idx as f64) as f32;
+                    let (a, b) = match last {
+                        Some(last) => {
+                            let last = last as i64;
+                            (idx.min(last), (idx + 1).min(last))
+                        }
+                        None if idx + 1 > available => break,
+                        None => (idx, idx + 1),
+                    };
+                    let (a, b) = (at(a), at(b));
+                    out.push(a + (b - a) * frac);
+                }
+                Filter::Sinc {
+                    kernel,
+                    cutoff,
+                    half_width,
+                } => {
+                    let first = ((center - half_width).ceil() as i64).max(0);
+                    let end = (center + half_width).floor() as i64;
+                    let end = match last {
+                        Some(last) => end.min(last as i64),
+                        None if end > available => break,
+                        None => end,
+                    };
+                    let mut acc = 0.0f64;
+                    let mut weight = 0.0f64;
+                    for j in first..=end {
+                        let position =
+                            (j as f64 - center).abs() * cutoff * SINC_TABLE_RESOLUTION as f64;
+                        let index = position as usize;
+                        let frac = position - index as f64;
+                        let tap = kernel[index] + (kernel[index + 1] - kernel[index]) * frac;
+                        acc += f64::from(at(j)) * tap;
+                        weight += tap;
+                    }
+                    out.push(if weight.abs() > f64::EPSILON {
+                        (acc / weight) as f32
+                    } else {
+                        at((center as i64).min(end))
+                    });
+                }
+            }
+            emitted += 1;
+        }
+        emitted
     }
+}
 
-    out
+impl Filter {
+    fn sinc(src_rate: u32, dst_rate: u32, zero_crossings: usize, rolloff: f64) -> Self {
+        let kernel = (0..=zero_crossings * SINC_TABLE_RESOLUTION + 1)
+            .map(|index| {
+                let x = index as f64 / SINC_TABLE_RESOLUTION as f64;
+                let u = (x / zero_crossings as f64).min(1.0);
+                let window = 0.42
+                    + 0.5 * (std::f64::consts::PI * u).cos()
+                    + 0.08 * (2.0 * std::f64::consts::PI * u).cos();
+                let sinc = if index == 0 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+                };
+                sinc * window
+            })
+            .collect::<Vec<_>>();
+        let ratio = src_rate as f64 / dst_rate as f64;
+        let cutoff = rolloff * (1.0 / ratio).min(1.0);
+        Self::Sinc {
+            kernel,
+            cutoff,
+            half_width: zero_crossings as f64 / cutoff,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -189,8 +1010,114 @@ mod tests {
     use super::*;
 
     #[test]
-    fn rejects_mp4() {
-        assert!(validate_extension("clip.mp4").is_err());
+    fn accepts_mp4_family_and_rejects_unknown_extensions() {
+        for name in ["clip.mp4", "clip.M4V", "clip.mov", "voice.mpga"] {
+            assert!(validate_extension(name).is_ok(), "{name}");
+        }
+        assert!(validate_extension("clip.mkv").is_err());
+        assert!(validate_extension("noext").is_err());
+    }
+
+    #[test]
+    fn sinc_profiles_remove_content_above_8khz() {
+        let tone = |freq: f64| {
+            (0..48_000)
+                .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64 / 48_000.0).sin() as f32)
+                .collect::<Vec<_>>()
+        };
+        let rms = |samples: &[f32]| {
+            let inner = &samples[1_000..samples.len() - 1_000];
+            (inner.iter().map(|s| s * s).sum::<f32>() / inner.len() as f32).sqrt()
+        };
+
+        let speech = tone(1_000.0);
+        let hiss = tone(12_000.0);
+        for quality in [ResampleQuality::Balanced, ResampleQuality::High] {
+            let kept = resample(&speech, 48_000, 16_000, quality);
+            assert_eq!(kept.len(), 16_000);
+            assert!((rms(&kept) - 0.707).abs() < 0.01, "{quality:?}");
+            assert!(
+                rms(&resample(&hiss, 48_000, 16_000, quality)) < 0.01,
+                "{quality:?}"
+            );
+        }
+        // Linear interpolation folds the 12 kHz tone back to 4 kHz.
+        assert!(rms(&resample(&hiss, 48_000, 16_000, ResampleQuality::Fast)) > 0.3);
+    }
+
+    #[test]
+    fn streaming_resampler_matches_one_shot_output() {
+        let signal: Vec<f32> = (0..9_001)
+            .map(|n| ((n as f32 * 0.37).sin() + (n as f32 * 0.011).cos()) * 0.4)
+            .collect();
+        for quality in [
+            ResampleQuality::Fast,
+            ResampleQuality::Balanced,
+            ResampleQuality::High,
+        ] {
+            for src_rate in [8_000, 16_000, 22_050, 44_100, 48_000] {
+                for len in [0, 1, 2, 17, 9_001] {
+                    let input = &signal[..len];
+                    let expected = resample(input, src_rate, 16_000, quality);
+                    for chunk in [1, 7, 1_024] {
+                        let mut resampler = Resampler::new(src_rate, 16_000, quality);
+                        let mut streamed = Vec::new();
+                        for piece in input.chunks(chunk) {
+                            resampler.push(piece, &mut streamed);
+                            assert!(resampler.pending.len() <= chunk + 2_048);
+                        }
+                        resampler.finish(&mut streamed);
+                        assert_eq!(streamed, expected, "{quality:?} {src_rate} {len} {chunk}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn downmix_averages_or_selects_channels() {
+        let stereo = [0.5, -0.5, 1.0, 0.0, -0.25, -0.75];
+        let mut mono = Vec::new();
+        Downmix::Average
+            .apply(&stereo, 2, &mut mono)
+            .expect("average");
+        assert_eq!(mono, [0.0, 0.5, -0.5]);
+
+        mono.clear();
+        Downmix::Channel(1)
+            .apply(&stereo, 2, &mut mono)
+            .expect("select");
+        assert_eq!(mono, [-0.5, 0.0, -0.75]);
+
+        let err = Downmix::Channel(2)
+            .apply(&stereo, 2, &mut mono)
+            .expect_err("out of range");
+        assert!(err.to_string().contains("2 channel(s)"));
+        assert!(Downmix::Channel(0).check(1).is_ok());
+    }
+
+    #[test]
+    fn telephony_audio_is_upsampled_to_16khz() {
+        let decoded = decode_to_mono_16khz_f32(&[0x80; 800], "ul").expect("decode");
+        assert_eq!(decoded.len(), 1_600);
+        assert!(decoded.iter().all(|sample| *sample > 0.98));
+    }
+
+    #[test]
+    fn picks_the_audio_track_behind_a_video_track() {
+        use symphonia::core::codecs::{CodecParameters, CODEC_TYPE_AAC};
+
+        let video = Track::new(1, CodecParameters::new());
+        let mut params = CodecParameters::new();
+        params.for_codec(CODEC_TYPE_AAC).with_sample_rate(44_100);
+        let audio = Track::new(2, params);
+        let tracks = [video.clone(), audio];
+        assert_eq!(select_track(&tracks, tracks.first()).map(|t| t.id), Some(2));
+        assert_eq!(
+            select_track(std::slice::from_ref(&video), Some(&video)).map(|t| t.id),
+            Some(1)
+        );
+        assert!(select_track(&[], None).is_none());
     }
 
     #[test]
@@ -200,4 +1127,58 @@ mod tests {
             Ok("m4a")
         ));
     }
+
+    #[test]
+    fn silence_detection_uses_peak_amplitude() {
+        assert!(is_silent(&[]));
+        assert!(is_silent(&[0.0, 0.0005, -0.0009]));
+        assert!(!is_silent(&[0.0, 0.2, 0.0]));
+    }
+
+    #[test]
+    fn vad_cuts_long_silences_and_maps_times_back() {
+        let params = VadParams {
+            min_silence_ms: 1000,
+            padding_ms: 240,
+        };
+        let tone = |secs: usize| {
+            (0..secs * 16_000)
+                .map(|i| (i as f32 * 0.05).sin() * 0.3)
+                .collect::<Vec<_>>()
+        };
+        let mut samples = tone(2);
+        samples.extend(vec![0.0; 5 * 16_000]);
+        samples.extend(tone(1));
+        samples.extend(vec![0.0; 8_000]);
+        samples.extend(tone(1));
+
+        let map = trim_silence(&mut samples, params).expect("trimmed");
+        // The 5 s gap keeps 240 ms of padding plus the frame straddling each tone edge;
+        // the 0.5 s gap is too short to cut.
+        assert_eq!(samples.len(), 80_000);
+        assert!((map.removed_secs() - 4.5).abs() < 1e-9);
+        assert_eq!(map.original_secs(1.0, false), 1.0);
+        assert!((map.original_secs(2.25, true) - 2.25).abs() < 1e-9);
+        assert!((map.original_secs(2.25, false) - 6.75).abs() < 1e-9);
+        assert!((map.original_secs(3.0, false) - 7.5).abs() < 1e-9);
+
+        let mut speech = tone(3);
+        assert_eq!(trim_silence(&mut speech, params), None);
+        assert_eq!(speech.len(), 3 * 16_000);
+        assert_eq!(trim_silence(&mut vec![0.0; 16_000], params), None);
+    }
+
+    #[test]
+    fn sanitize_zeroes_non_finite_and_denormal_samples() {
+        let mut samples = [
+            0.5,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MIN_POSITIVE / 2.0,
+            1.5,
+        ];
+        assert_eq!(sanitize_samples(&mut samples), 4);
+        assert_eq!(samples, [0.5, 0.0, 0.0, 0.0, 0.0, 1.0]);
+    }
 }
@@ -0,0 +1,86 @@
+//! CPU affinity for inference worker threads.
+//!
+//! whisper.cpp spawns its compute threads from the calling thread, and on Linux
+//! new threads inherit the caller's affinity mask. Pinning the blocking worker
+//! thread for the duration of a request therefore confines the whole decode to
+//! that worker's cores.
+
+/// Returns whether thread pinning is implemented on this platform.
+pub fn is_supported() -> bool {
+    cfg!(target_os = "linux")
+}
+
+/// Restores the thread's previous affinity mask when dropped.
+pub struct AffinityGuard {
+    #[cfg(target_os = "linux")]
+    previous: libc::cpu_set_t,
+}
+
+/// Pins the current thread to `cores`, returning a guard that undoes the pin.
+///
+/// Returns `None` when pinning is unsupported or the kernel rejects the mask
+/// (for example cores outside the container's cpuset).
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cores: &[usize]) -> Option<AffinityGuard> {
+    let set_size = std::mem::size_of::<libc::cpu_set_t>();
+    // SAFETY: `cpu_set_t` is plain data, and both syscalls only read or write
+    // the set passed by pointer with its exact size. Core indices are bounded
+    // by `MAX_CPU_INDEX` at config parse time, so `CPU_SET` stays in range.
+    unsafe {
+        let mut previous: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, set_size, &mut previous) != 0 {
+            return None;
+        }
+        let mut pinned: libc::cpu_set_t = std::mem::zeroed();
+        for &core in cores {
+            libc::CPU_SET(core, &mut pinned);
+        }
+        if libc::sched_setaffinity(0, set_size, &pinned) != 0 {
+            tracing::warn!(
+                error = %std::io::Error::last_os_error(),
+                ?cores,
+                "failed to pin whisper worker thread; running unpinned"
+            );
+            return None;
+        }
+        Some(AffinityGuard { previous })
+    }
+}
+
+/// Pins the current thread to `cores`, returning a guard that undoes the pin.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cores: &[usize]) -> Option<AffinityGuard> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AffinityGuard {
+    fn drop(&mut self) {
+        // SAFETY: restores the mask captured by `pin_current_thread` on this thread.
+        unsafe {
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &self.previous);
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinning_is_restored_when_guard_drops() {
+        let before = std::thread::available_parallelism().map(usize::from).ok();
+        let guard = pin_current_thread(&[0]);
+        if guard.is_some() {
+            assert_eq!(
+                std::thread::available_parallelism().map(usize::from).ok(),
+                Some(1)
+            );
+        }
+        drop(guard);
+        assert_eq!(
+            std::thread::available_parallelism().map(usize::from).ok(),
+            before
+        );
+    }
+}
@@ -3,13 +3,19 @@
 //! The HTTP layer depends on the [`Transcriber`] trait instead of a concrete
 //! implementation, which keeps request handling decoupled from inference code.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::config::{AppConfig, BackendKind};
+use crate::admission::PoolAdmission;
+use crate::config::{AppConfig, BackendKind, ModelWorkers};
 use crate::error::AppError;
+use crate::formats::is_cjk;
 
+pub mod affinity;
 pub mod whisper_rs;
 
 /// Type of inference task requested by the client.
@@ -44,6 +50,18 @@ pub struct TranscribeRequest {
     pub prompt: Option<String>,
     /// Optional sampling temperature in range `[0.0, 1.0]`.
     pub temperature: Option<f32>,
+    /// Optional cap on decoded tokens per segment to cut off repetition loops.
+    pub max_tokens_per_segment: Option<u32>,
+    /// Beam width for beam search decoding; greedy decoding when `None`.
+    pub beam_size: Option<u32>,
+    /// Candidates greedy decoding samples at temperatures above 0, keeping the
+    /// most likely; ignored with `beam_size`.
+    pub best_of: Option<u32>,
+    /// Whether to rank the likely languages into `language_probs` when the
+    /// language is auto-detected; this costs an extra encoder pass.
+    pub language_probs: bool,
+    /// Time after which inference is aborted with [`AppError::Timeout`].
+    pub deadline: Option<Instant>,
 }
 
 /// Timestamped transcript chunk.
@@ -55,6 +73,115 @@ pub struct TranscriptSegment {
     pub end_secs: f64,
     /// Text content for this segment.
     pub text: String,
+    /// Mean log-probability of the segment's text tokens, if the backend reports it.
+    pub avg_logprob: Option<f64>,
+    /// Number of text tokens behind `avg_logprob`; used to weight transcript averages.
+    pub token_count: usize,
+    /// Probability that the segment's window contained no speech, if reported.
+    pub no_speech_prob: Option<f32>,
+    /// Decoded token ids, including special and timestamp tokens.
+    pub tokens: Vec<i32>,
+    /// Text tokens with their log-probabilities, reported for `include[]=logprobs`.
+    pub token_logprobs: Vec<TokenLogprob>,
+    /// Words with their timings; empty unless the backend aligns tokens to audio.
+    pub words: Vec<TranscriptWord>,
+}
+
+#[cfg(test)]
+impl TranscriptSegment {
+    /// A segment with only timing and text, for tests to fill in the rest with
+    /// struct update syntax.
+    pub fn new(start_secs: f64, end_secs: f64, text: &str) -> Self {
+        Self {
+            start_secs,
+            end_secs,
+            text: text.to_string(),
+            avg_logprob: None,
+            token_count: 0,
+            no_speech_prob: None,
+            tokens: Vec::new(),
+            token_logprobs: Vec::new(),
+            words: Vec::new(),
+        }
+    }
+}
+
+/// One word of a segment and when it is spoken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptWord {
+    pub word: String,
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+/// Groups a segment's text tokens, each with the time it was aligned to, into
+/// words spanning `[start_secs, end_secs]`.
+///
+/// A token starting with whitespace begins a new word, and so does each CJK
+/// character, since those scripts do not separate words with spaces. A word
+/// ends where the next one starts and the last at `end_secs`; aligned times
+/// are clamped into the segment and kept in order.
+pub fn group_words(
+    tokens: impl IntoIterator<Item = (String, f64)>,
+    start_secs: f64,
+    end_secs: f64,
+) -> Vec<TranscriptWord> {
+    let mut words: Vec<TranscriptWord> = Vec::new();
+    let mut floor = start_secs;
+    for (text, secs) in tokens {
+        let continues = words.last().is_some_and(|word| {
+            !text.starts_with(char::is_whitespace)
+                && !text.starts_with(is_cjk)
+                && !word.word.ends_with(is_cjk)
+        });
+        match words.last_mut() {
+            Some(word) if continues => word.word.push_str(&text),
+            _ => {
+                floor = secs.clamp(floor, end_secs.max(floor));
+                words.push(TranscriptWord {
+                    word: text,
+                    start_secs: floor,
+                    end_secs,
+                });
+            }
+        }
+    }
+    words.retain_mut(|word| {
+        word.word = word.word.trim().to_string();
+        !word.word.is_empty()
+    });
+    for idx in 1..words.len() {
+        words[idx - 1].end_secs = words[idx].start_secs;
+    }
+    words
+}
+
+/// One decoded text token and its log-probability.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenLogprob {
+    /// Token text as decoded, usually with a leading space.
+    pub token: String,
+    /// Natural-log probability the model assigned to the token.
+    pub logprob: f32,
+}
+
+/// Why a transcript came back without speech.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NoSpeechReason {
+    /// Decoded audio never rose above the silence threshold; inference was skipped.
+    SilentAudio,
+    /// Inference ran but produced no transcribable speech.
+    NoSpeechDetected,
+}
+
+impl NoSpeechReason {
+    /// Returns the wire-format reason used in API responses.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SilentAudio => "silent_audio",
+            Self::NoSpeechDetected => "no_speech_detected",
+        }
+    }
 }
 
 /// Full inference result returned by a backend.
@@ -66,6 +193,169 @@ pub struct TranscriptResult {
     pub language: Option<String>,
     /// Segment-level timing and text details.
     pub segments: Vec<TranscriptSegment>,
+    /// Set when the audio contained no speech and `text` is intentionally empty.
+    pub no_speech: Option<NoSpeechReason>,
+    /// Most likely languages with their auto-detection probabilities, highest first.
+    ///
+    /// Empty when the request pinned a language or the backend cannot report them.
+    pub language_probs: Vec<(String, f32)>,
+    /// Audio length and processing time, filled in by the server after inference.
+    pub usage: Option<Usage>,
+    /// Quality caveats reported as `warnings`, in the order they were found.
+    pub warnings: Vec<TranscriptWarning>,
+}
+
+/// A caveat about transcript quality that does not fail the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptWarning {
+    /// Stable machine-readable identifier such as `audio_clipped`.
+    pub code: &'static str,
+    /// Human-readable explanation suitable for display.
+    pub message: String,
+}
+
+impl TranscriptWarning {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Metered consumption of one transcription, reported as `usage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    /// Length of the decoded input audio in seconds.
+    pub audio_secs: f64,
+    /// Time spent in inference, excluding upload, decoding, and queueing.
+    pub processing_ms: u64,
+}
+
+impl TranscriptResult {
+    /// Builds an empty transcript explaining why no speech was returned.
+    pub fn no_speech(language: Option<String>, reason: NoSpeechReason) -> Self {
+        Self {
+            text: String::new(),
+            language,
+            segments: Vec::new(),
+            no_speech: Some(reason),
+            language_probs: Vec::new(),
+            usage: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Token-weighted mean log-probability across all segments.
+    pub fn avg_logprob(&self) -> Option<f64> {
+        let (sum, tokens) = self
+            .segments
+            .iter()
+            .filter_map(|seg| seg.avg_logprob.map(|lp| (lp, seg.token_count)))
+            .fold((0.0, 0usize), |(sum, tokens), (lp, count)| {
+                (sum + lp * count as f64, tokens + count)
+            });
+        (tokens > 0).then(|| sum / tokens as f64)
+    }
+}
+
+/// Features a backend supports, reported by `GET /v1/models` and checked
+/// against request parameters before any audio is decoded.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Capabilities {
+    /// Per-word timings (`timestamp_granularities[]=word`).
+    pub word_timestamps: bool,
+    /// Segment-by-segment results (`stream=true` and `/v1/audio/stream`).
+    pub streaming: bool,
+    /// Speaker labels on segments.
+    pub diarization: bool,
+    /// Languages `/v1/audio/translations` can produce; empty when unsupported.
+    pub translation_targets: Vec<&'static str>,
+    /// Requests the backend runs at the same time.
+    pub max_parallelism: usize,
+}
+
+impl Default for Capabilities {
+    /// What the trait's default methods provide: streaming of finished
+    /// segments and whisper-style translation into English.
+    fn default() -> Self {
+        Self {
+            word_timestamps: false,
+            streaming: true,
+            diarization: false,
+            translation_targets: vec!["en"],
+            max_parallelism: 1,
+        }
+    }
+}
+
+impl Capabilities {
+    /// JSON object listed with each model by `GET /v1/models`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "word_timestamps": self.word_timestamps,
+            "streaming": self.streaming,
+            "diarization": self.diarization,
+            "translation_targets": self.translation_targets,
+            "max_parallelism": self.max_parallelism,
+        })
+    }
+}
+
+/// Request a backend worker is running, reported by `GET /metrics`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveInference {
+    /// Time since the worker started the request.
+    pub elapsed: Duration,
+    /// Progress of the current inference pass, from 0 to 100.
+    pub progress_percent: u8,
+}
+
+/// Tracks the request one backend worker is running.
+#[derive(Debug, Default)]
+pub struct WorkerGauge {
+    active: Mutex<Option<(Instant, u8)>>,
+}
+
+impl WorkerGauge {
+    /// Marks the worker busy until the returned guard is dropped.
+    pub fn begin(self: &Arc<Self>) -> WorkerBusy {
+        self.set(Some((Instant::now(), 0)));
+        WorkerBusy(Arc::clone(self))
+    }
+
+    /// Records the progress of the running pass; fallback passes restart from 0.
+    pub fn set_progress(&self, percent: i32) {
+        if let Ok(mut active) = self.active.lock() {
+            if let Some((_, progress)) = active.as_mut() {
+                *progress = percent.clamp(0, 100) as u8;
+            }
+        }
+    }
+
+    /// The running request, or `None` when the worker is idle.
+    pub fn snapshot(&self) -> Option<ActiveInference> {
+        let active = *self.active.lock().ok()?;
+        active.map(|(started, progress_percent)| ActiveInference {
+            elapsed: started.elapsed(),
+            progress_percent,
+        })
+    }
+
+    fn set(&self, value: Option<(Instant, u8)>) {
+        if let Ok(mut active) = self.active.lock() {
+            *active = value;
+        }
+    }
+}
+
+/// Keeps a [`WorkerGauge`] busy while held.
+pub struct WorkerBusy(Arc<WorkerGauge>);
+
+impl Drop for WorkerBusy {
+    fn drop(&mut self) {
+        self.0.set(None);
+    }
 }
 
 /// Backend contract implemented by speech-to-text engines.
@@ -73,6 +363,156 @@ pub struct TranscriptResult {
 pub trait Transcriber: Send + Sync {
     /// Runs inference and returns a transcript result.
     async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError>;
+
+    /// Runs inference, sending each segment to `segments` as soon as it is decoded.
+    ///
+    /// The returned result is authoritative: fallback passes may revise text that
+    /// was already streamed. The default implementation sends the finished
+    /// segments after a regular [`Transcriber::transcribe`] call.
+    async fn transcribe_stream(
+        &self,
+        req: TranscribeRequest,
+        segments: UnboundedSender<TranscriptSegment>,
+    ) -> Result<TranscriptResult, AppError> {
+        let result = self.transcribe(req).await?;
+        for segment in &result.segments {
+            let _ = segments.send(segment.clone());
+        }
+        Ok(result)
+    }
+
+    /// Detects the spoken language without decoding a transcript.
+    ///
+    /// Returns the most likely languages with their probabilities, highest first.
+    async fn detect_language(
+        &self,
+        _audio_16khz_mono_f32: Vec<f32>,
+    ) -> Result<Vec<(String, f32)>, AppError> {
+        Err(AppError::invalid_request(
+            "this model does not support language detection",
+            Some("model"),
+            Some("unsupported_model"),
+        ))
+    }
+
+    /// Whether requests wait for a CPU-only worker (`WHISPER_CPU_WORKERS`)
+    /// rather than one of the default inference workers.
+    fn uses_cpu_pool(&self) -> bool {
+        false
+    }
+
+    /// Workers and queue of a model with a pool of its own
+    /// (`WHISPER_MODEL_WORKERS`); `None` when it shares the default workers.
+    fn dedicated_pool(&self) -> Option<&PoolAdmission> {
+        None
+    }
+
+    /// Features this backend supports.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// What each worker is running, indexed by worker; empty when the backend
+    /// does not report it.
+    fn worker_activity(&self) -> Vec<Option<ActiveInference>> {
+        Vec::new()
+    }
+}
+
+/// CPU-only copy of the default model serving `compute=cpu` requests.
+pub struct CpuPool(pub Arc<dyn Transcriber>);
+
+#[async_trait]
+impl Transcriber for CpuPool {
+    async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+        self.0.transcribe(req).await
+    }
+
+    async fn transcribe_stream(
+        &self,
+        req: TranscribeRequest,
+        segments: UnboundedSender<TranscriptSegment>,
+    ) -> Result<TranscriptResult, AppError> {
+        self.0.transcribe_stream(req, segments).await
+    }
+
+    async fn detect_language(
+        &self,
+        audio_16khz_mono_f32: Vec<f32>,
+    ) -> Result<Vec<(String, f32)>, AppError> {
+        self.0.detect_language(audio_16khz_mono_f32).await
+    }
+
+    fn uses_cpu_pool(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.0.capabilities()
+    }
+
+    fn worker_activity(&self) -> Vec<Option<ActiveInference>> {
+        self.0.worker_activity()
+    }
+}
+
+/// A model served by workers of its own, admitted through its own queue
+/// instead of the default workers.
+pub struct DedicatedPool {
+    backend: Arc<dyn Transcriber>,
+    admission: PoolAdmission,
+}
+
+impl DedicatedPool {
+    /// Wraps `backend`, loaded with `limits.workers` workers; the queue limit
+    /// falls back to `default_max_depth` (`MAX_QUEUE_DEPTH`).
+    pub fn new(
+        backend: Arc<dyn Transcriber>,
+        limits: ModelWorkers,
+        default_max_depth: Option<usize>,
+    ) -> Self {
+        Self {
+            backend,
+            admission: PoolAdmission::new(
+                limits.workers,
+                limits.max_queue_depth.or(default_max_depth),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Transcriber for DedicatedPool {
+    async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
+        self.backend.transcribe(req).await
+    }
+
+    async fn transcribe_stream(
+        &self,
+        req: TranscribeRequest,
+        segments: UnboundedSender<TranscriptSegment>,
+    ) -> Result<TranscriptResult, AppError> {
+        self.backend.transcribe_stream(req, segments).await
+    }
+
+    async fn detect_language(
+        &self,
+        audio_16khz_mono_f32: Vec<f32>,
+    ) -> Result<Vec<(String, f32)>, AppError> {
+        self.backend.detect_language(audio_16khz_mono_f32).await
+    }
+
+    fn dedicated_pool(&self) -> Option<&PoolAdmission> {
+        Some(&self.admission)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.backend.capabilities()
+    }
+
+    fn worker_activity(&self) -> Vec<Option<ActiveInference>> {
+        self.backend.worker_activity()
+    }
 }
 
 /// Builds the configured backend implementation.
@@ -81,3 +521,93 @@ pub fn build_backend(cfg: &AppConfig) -> Result<Arc<dyn Transcriber>, AppError>
         BackendKind::WhisperRs => Ok(Arc::new(whisper_rs::WhisperRsBackend::new(cfg.clone())?)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(avg_logprob: Option<f64>, token_count: usize) -> TranscriptSegment {
+        TranscriptSegment {
+            avg_logprob,
+            token_count,
+            ..TranscriptSegment::new(0.0, 1.0, "x")
+        }
+    }
+
+    #[test]
+    fn tokens_group_into_timed_words() {
+        let tokens = [
+            (" Hel", 1.0),
+            ("lo", 1.2),
+            (" world", 1.5),
+            ("!", 2.4),
+            (" ", 2.5),
+        ];
+        let words = group_words(
+            tokens.iter().map(|(text, secs)| (text.to_string(), *secs)),
+            1.1,
+            3.0,
+        );
+        let timed = words
+            .iter()
+            .map(|word| (word.word.as_str(), word.start_secs, word.end_secs))
+            .collect::<Vec<_>>();
+        assert_eq!(timed, [("Hello", 1.1, 1.5), ("world!", 1.5, 3.0)]);
+
+        let tokens = [("你", 0.0), ("好", 0.4)];
+        let words = group_words(
+            tokens.iter().map(|(text, secs)| (text.to_string(), *secs)),
+            0.0,
+            1.0,
+        );
+        assert_eq!(
+            words
+                .iter()
+                .map(|word| word.word.as_str())
+                .collect::<Vec<_>>(),
+            ["你", "好"]
+        );
+    }
+
+    #[test]
+    fn worker_gauge_reports_progress_while_busy() {
+        let gauge = Arc::new(WorkerGauge::default());
+        assert_eq!(gauge.snapshot(), None);
+        let busy = gauge.begin();
+        gauge.set_progress(140);
+        let active = gauge.snapshot().expect("busy");
+        assert_eq!(active.progress_percent, 100);
+        drop(busy);
+        assert_eq!(gauge.snapshot(), None);
+    }
+
+    #[test]
+    fn avg_logprob_is_weighted_by_token_count() {
+        let result = TranscriptResult {
+            text: String::new(),
+            language: None,
+            segments: vec![
+                segment(Some(-1.0), 3),
+                segment(Some(-0.2), 1),
+                segment(None, 0),
+            ],
+            no_speech: None,
+            language_probs: Vec::new(),
+            usage: None,
+            warnings: Vec::new(),
+        };
+        let avg = result.avg_logprob().expect("avg");
+        assert!((avg - -0.8).abs() < 1e-9);
+
+        let empty = TranscriptResult {
+            text: String::new(),
+            language: None,
+            segments: vec![segment(None, 0)],
+            no_speech: None,
+            language_probs: Vec::new(),
+            usage: None,
+            warnings: Vec::new(),
+        };
+        assert_eq!(empty.avg_logprob(), None);
+    }
+}
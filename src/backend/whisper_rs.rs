@@ -1,99 +1,142 @@
 //! `whisper-rs` backend implementation.
 //!
 //! This backend keeps a pool of Whisper contexts in memory and runs inference
-//! on blocking worker threads.
+//! on blocking worker threads. With `WORKER_MAX_REQUESTS` or
+//! `WORKER_MAX_AGE_SECS` set, a worker's context is rebuilt from the model file
+//! once it has served that many inferences or reached that age, releasing any
+//! native memory the old context accumulated.
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::task;
 use tracing::{info, warn};
 use whisper_rs::{
-    get_lang_str, FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters,
+    get_lang_str, DtwMode, DtwModelPreset, DtwParameters, FullParams, SamplingStrategy,
+    SegmentCallbackData, WhisperContext, WhisperContextParameters, WhisperError, WhisperSegment,
+    WhisperState, WhisperTokenId,
 };
 
-use crate::backend::{TranscribeRequest, Transcriber, TranscriptResult, TranscriptSegment};
-use crate::config::{AccelerationKind, AppConfig};
+use crate::backend::affinity::{self, pin_current_thread};
+use crate::backend::{
+    group_words, ActiveInference, Capabilities, NoSpeechReason, TokenLogprob, TranscribeRequest,
+    Transcriber, TranscriptResult, TranscriptSegment, TranscriptWarning, TranscriptWord,
+    WorkerGauge,
+};
+use crate::config::{AccelerationKind, AppConfig, DtwHeads, WhisperModelSize};
 use crate::error::AppError;
 use crate::formats::normalize_text;
 
+/// Number of candidate languages reported with an auto-detected transcript or
+/// by `POST /v1/audio/language`.
+const LANGUAGE_PROBS_TOP_N: usize = 5;
+/// Samples whisper's language detector reads: one 30-second window at 16 kHz.
+const LANGUAGE_DETECT_SAMPLES: usize = 30 * 16_000;
+
 /// Local inference backend powered by `whisper-rs`.
 pub struct WhisperRsBackend {
     model_path: String,
-    contexts: Vec<Arc<Mutex<WhisperContext>>>,
+    contexts: Vec<Arc<Mutex<Worker>>>,
+    /// Acceleration the contexts were built with, reused when one is rebuilt.
+    acceleration: AccelerationKind,
+    /// Model size whose alignment heads time words, when DTW is enabled.
+    dtw: Option<WhisperModelSize>,
+    recycle: RecyclePolicy,
+    /// What each context's worker is running, for `GET /metrics`.
+    gauges: Vec<Arc<WorkerGauge>>,
     next_context_idx: AtomicUsize,
+    worker_cores: Option<Vec<Vec<usize>>>,
+    max_initial_ts: f32,
+}
+
+/// One worker's whisper context and the usage that decides when it is rebuilt.
+struct Worker {
+    /// Zero-based worker number, for logs.
+    idx: usize,
+    context: WhisperContext,
+    served: u64,
+    built_at: Instant,
+}
+
+impl Worker {
+    fn new(idx: usize, context: WhisperContext) -> Self {
+        Self {
+            idx,
+            context,
+            served: 0,
+            built_at: Instant::now(),
+        }
+    }
+}
+
+/// When worker contexts are retired and rebuilt.
+#[derive(Debug, Clone, Copy, Default)]
+struct RecyclePolicy {
+    max_requests: Option<u64>,
+    max_age: Option<Duration>,
+}
+
+impl RecyclePolicy {
+    fn is_due(&self, worker: &Worker) -> bool {
+        self.max_requests.is_some_and(|max| worker.served >= max)
+            || self
+                .max_age
+                .is_some_and(|max| worker.built_at.elapsed() >= max)
+    }
 }
 
 impl WhisperRsBackend {
     /// Loads the configured Whisper model and prepares reusable contexts.
     pub fn new(cfg: AppConfig) -> Result<Self, AppError> {
         let model_path = cfg.whisper_model.clone();
+        let dtw = cfg.whisper_dtw.resolve(&model_path);
+        if dtw.is_none() && cfg.whisper_dtw != DtwHeads::Off {
+            warn!(
+                model_path = %model_path,
+                "WHISPER_DTW=auto cannot tell the model size from its file name; word timestamps are disabled for it"
+            );
+        }
         let (contexts, effective_acceleration) = match cfg.acceleration_kind {
             AccelerationKind::None => (
-                build_contexts(&model_path, cfg.whisper_parallelism, AccelerationKind::None)?,
-                AccelerationKind::None,
-            ),
-            AccelerationKind::Metal => {
-                match build_contexts(
+                build_contexts(
                     &model_path,
                     cfg.whisper_parallelism,
-                    AccelerationKind::Metal,
-                ) {
-                    Ok(contexts) => (contexts, AccelerationKind::Metal),
-                    Err(err) if !cfg.acceleration_explicit => {
-                        warn!(
-                            error = %err,
-                            requested_acceleration = "metal",
-                            fallback_acceleration = "none",
-                            "metal initialization failed; falling back to cpu"
-                        );
-                        (
-                            build_contexts(&model_path, cfg.whisper_parallelism, AccelerationKind::None).map_err(
-                                |cpu_err| {
-                                    AppError::backend(format!(
-                                        "failed to initialize metal acceleration ({err}); cpu fallback also failed: {cpu_err}"
-                                    ))
-                                },
-                            )?,
-                            AccelerationKind::None,
-                        )
-                    }
-                    Err(err) => {
-                        return Err(AppError::backend(format!(
-                            "failed to initialize whisper with metal acceleration: {err}"
-                        )));
-                    }
+                    AccelerationKind::None,
+                    dtw,
+                )?,
+                AccelerationKind::None,
+            ),
+            gpu => match build_contexts(&model_path, cfg.whisper_parallelism, gpu, dtw) {
+                Ok(contexts) => (contexts, gpu),
+                Err(err) if !cfg.acceleration_explicit => {
+                    let name = gpu.as_str();
+                    warn!(
+                        error = %err,
+                        requested_acceleration = name,
+                        fallback_acceleration = "none",
+                        "{name} initialization failed; falling back to cpu"
+                    );
+                    (
+                        build_contexts(&model_path, cfg.whisper_parallelism, AccelerationKind::None, dtw).map_err(
+                            |cpu_err| {
+                                AppError::backend(format!(
+                                    "failed to initialize {name} acceleration ({err}); cpu fallback also failed: {cpu_err}"
+                                ))
+                            },
+                        )?,
+                        AccelerationKind::None,
+                    )
                 }
-            }
-            AccelerationKind::Cuda => {
-                match build_contexts(&model_path, cfg.whisper_parallelism, AccelerationKind::Cuda) {
-                    Ok(contexts) => (contexts, AccelerationKind::Cuda),
-                    Err(err) if !cfg.acceleration_explicit => {
-                        warn!(
-                            error = %err,
-                            requested_acceleration = "cuda",
-                            fallback_acceleration = "none",
-                            "cuda initialization failed; falling back to cpu"
-                        );
-                        (
-                            build_contexts(&model_path, cfg.whisper_parallelism, AccelerationKind::None).map_err(
-                                |cpu_err| {
-                                    AppError::backend(format!(
-                                        "failed to initialize cuda acceleration ({err}); cpu fallback also failed: {cpu_err}"
-                                    ))
-                                },
-                            )?,
-                            AccelerationKind::None,
-                        )
-                    }
-                    Err(err) => {
-                        return Err(AppError::backend(format!(
-                            "failed to initialize whisper with cuda acceleration: {err}"
-                        )));
-                    }
+                Err(err) => {
+                    return Err(AppError::backend(format!(
+                        "failed to initialize whisper with {} acceleration: {err}",
+                        gpu.as_str()
+                    )));
                 }
-            }
+            },
         };
 
         info!(
@@ -103,10 +146,31 @@ impl WhisperRsBackend {
             "initialized whisper acceleration"
         );
 
+        let worker_cores = match cfg.whisper_cpu_affinity {
+            Some(sets) if affinity::is_supported() => {
+                info!(worker_cores = ?sets, "pinning whisper workers to cpu cores");
+                Some(sets)
+            }
+            Some(_) => {
+                warn!("WHISPER_CPU_AFFINITY is only supported on Linux; ignoring");
+                None
+            }
+            None => None,
+        };
+
         Ok(Self {
             model_path,
+            gauges: contexts.iter().map(|_| Arc::default()).collect(),
             contexts,
+            acceleration: effective_acceleration,
+            dtw,
+            recycle: RecyclePolicy {
+                max_requests: cfg.worker_max_requests,
+                max_age: cfg.worker_max_age,
+            },
             next_context_idx: AtomicUsize::new(0),
+            worker_cores,
+            max_initial_ts: cfg.whisper_max_initial_ts,
         })
     }
 }
@@ -115,61 +179,252 @@ fn build_contexts(
     model_path: &str,
     whisper_parallelism: usize,
     acceleration: AccelerationKind,
-) -> Result<Vec<Arc<Mutex<WhisperContext>>>, AppError> {
+    dtw: Option<WhisperModelSize>,
+) -> Result<Vec<Arc<Mutex<Worker>>>, AppError> {
     let mut contexts = Vec::with_capacity(whisper_parallelism);
-    let use_gpu = acceleration != AccelerationKind::None;
-    let acceleration_name = acceleration.as_str();
-
     for worker_idx in 0..whisper_parallelism {
-        let mut params = WhisperContextParameters::default();
-        params.use_gpu(use_gpu);
-
-        let context = WhisperContext::new_with_params(model_path, params).map_err(|err| {
-            AppError::backend(format!(
-                "failed to load model at {model_path:?} for worker {} using acceleration={acceleration_name}: {err}",
-                worker_idx + 1,
-            ))
-        })?;
+        let context = build_context(model_path, worker_idx, acceleration, dtw)?;
+        contexts.push(Arc::new(Mutex::new(Worker::new(worker_idx, context))));
+    }
+    Ok(contexts)
+}
 
-        contexts.push(Arc::new(Mutex::new(context)));
+fn build_context(
+    model_path: &str,
+    worker_idx: usize,
+    acceleration: AccelerationKind,
+    dtw: Option<WhisperModelSize>,
+) -> Result<WhisperContext, AppError> {
+    let mut params = WhisperContextParameters::default();
+    params.use_gpu(acceleration != AccelerationKind::None);
+    if let Some(size) = dtw {
+        params.dtw_parameters(DtwParameters {
+            mode: DtwMode::ModelPreset {
+                model_preset: dtw_preset(size),
+            },
+            ..DtwParameters::default()
+        });
     }
 
-    Ok(contexts)
+    WhisperContext::new_with_params(model_path, params).map_err(|err| {
+        AppError::backend(format!(
+            "failed to load model at {model_path:?} for worker {} using acceleration={}: {err}",
+            worker_idx + 1,
+            acceleration.as_str(),
+        ))
+    })
+}
+
+/// Runs `infer` on a worker's context, then rebuilds the context in the
+/// background if the worker is due for recycling.
+///
+/// The rebuild runs after the result is returned; the worker's next request
+/// waits for it to finish.
+fn with_worker<T>(
+    worker: &Arc<Mutex<Worker>>,
+    recycler: &Recycler,
+    infer: impl FnOnce(&WhisperContext) -> T,
+) -> Result<T, AppError> {
+    let mut guard = worker
+        .lock()
+        .map_err(|_| AppError::backend("failed to lock whisper model context"))?;
+    let result = infer(&guard.context);
+    guard.served += 1;
+    let due = recycler.policy.is_due(&guard);
+    drop(guard);
+    if due {
+        let worker = Arc::clone(worker);
+        let recycler = recycler.clone();
+        task::spawn_blocking(move || recycler.rebuild(&worker));
+    }
+    Ok(result)
+}
+
+/// What a worker needs to rebuild its context.
+#[derive(Clone)]
+struct Recycler {
+    model_path: String,
+    acceleration: AccelerationKind,
+    dtw: Option<WhisperModelSize>,
+    policy: RecyclePolicy,
+}
+
+impl Recycler {
+    /// Replaces the worker's context with a fresh one from the model file.
+    ///
+    /// The new context is loaded before the old one is dropped, so a failed
+    /// load leaves the worker serving with the context it had.
+    fn rebuild(&self, worker: &Mutex<Worker>) {
+        let Ok(mut guard) = worker.lock() else {
+            return;
+        };
+        // Another rebuild may have run since this one was scheduled.
+        if !self.policy.is_due(&guard) {
+            return;
+        }
+        let served = guard.served;
+        let age_secs = guard.built_at.elapsed().as_secs();
+        let started = Instant::now();
+        match build_context(&self.model_path, guard.idx, self.acceleration, self.dtw) {
+            Ok(context) => {
+                let idx = guard.idx;
+                *guard = Worker::new(idx, context);
+                info!(
+                    worker = idx + 1,
+                    served,
+                    age_secs,
+                    rebuild_ms = started.elapsed().as_millis() as u64,
+                    "recycled whisper worker context"
+                );
+            }
+            Err(err) => {
+                guard.served = 0;
+                guard.built_at = Instant::now();
+                warn!(
+                    worker = guard.idx + 1,
+                    error = %err,
+                    "failed to recycle whisper worker context; keeping the current one"
+                );
+            }
+        }
+    }
 }
 
 #[async_trait]
 impl Transcriber for WhisperRsBackend {
     async fn transcribe(&self, req: TranscribeRequest) -> Result<TranscriptResult, AppError> {
-        let model_path = self.model_path.clone();
+        self.run(req, None).await
+    }
+
+    async fn transcribe_stream(
+        &self,
+        req: TranscribeRequest,
+        segments: UnboundedSender<TranscriptSegment>,
+    ) -> Result<TranscriptResult, AppError> {
+        self.run(req, Some(segments)).await
+    }
+
+    async fn detect_language(
+        &self,
+        audio_16khz_mono_f32: Vec<f32>,
+    ) -> Result<Vec<(String, f32)>, AppError> {
+        let (worker, gauge, cores) = self.next_context();
+        let recycler = self.recycler();
+        task::spawn_blocking(move || {
+            let _pinned = cores.as_deref().and_then(pin_current_thread);
+            with_worker(&worker, &recycler, |context| {
+                let _busy = gauge.begin();
+                let mut state = context.create_state().map_err(|err| {
+                    AppError::backend(format!("failed to create whisper state: {err}"))
+                })?;
+                rank_languages(
+                    &mut state,
+                    &audio_16khz_mono_f32,
+                    cores.as_ref().map(Vec::len),
+                )
+                .map_err(|err| {
+                    AppError::backend(format!("whisper language detection failed: {err}"))
+                })
+            })?
+        })
+        .await
+        .map_err(|err| AppError::backend(format!("whisper-rs worker task failed: {err}")))?
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Segments stream as whisper decodes them; words are timed only with
+        // DTW alignment heads, and there are no speaker labels yet.
+        Capabilities {
+            word_timestamps: self.dtw.is_some(),
+            max_parallelism: self.contexts.len(),
+            ..Capabilities::default()
+        }
+    }
+
+    fn worker_activity(&self) -> Vec<Option<ActiveInference>> {
+        self.gauges.iter().map(|gauge| gauge.snapshot()).collect()
+    }
+}
+
+impl WhisperRsBackend {
+    /// Picks the next context round-robin, with its worker's gauge and the
+    /// cores the worker is pinned to.
+    fn next_context(&self) -> (Arc<Mutex<Worker>>, Arc<WorkerGauge>, Option<Vec<usize>>) {
         let context_idx =
             self.next_context_idx.fetch_add(1, Ordering::Relaxed) % self.contexts.len();
-        let context = Arc::clone(&self.contexts[context_idx]);
-        task::spawn_blocking(move || run_whisper_rs(req, &model_path, context))
-            .await
-            .map_err(|err| AppError::backend(format!("whisper-rs worker task failed: {err}")))?
+        let cores = self
+            .worker_cores
+            .as_ref()
+            .map(|sets| sets[context_idx].clone());
+        (
+            Arc::clone(&self.contexts[context_idx]),
+            Arc::clone(&self.gauges[context_idx]),
+            cores,
+        )
+    }
+
+    fn recycler(&self) -> Recycler {
+        Recycler {
+            model_path: self.model_path.clone(),
+            acceleration: self.acceleration,
+            dtw: self.dtw,
+            policy: self.recycle,
+        }
+    }
+
+    /// Runs inference on the next context, optionally streaming segments as they decode.
+    async fn run(
+        &self,
+        req: TranscribeRequest,
+        segment_tx: Option<UnboundedSender<TranscriptSegment>>,
+    ) -> Result<TranscriptResult, AppError> {
+        let model_path = self.model_path.clone();
+        let max_initial_ts = self.max_initial_ts;
+        let (worker, gauge, cores) = self.next_context();
+        let recycler = self.recycler();
+        task::spawn_blocking(move || {
+            let _pinned = cores.as_deref().and_then(pin_current_thread);
+            let n_threads = cores.as_ref().map(Vec::len);
+            with_worker(&worker, &recycler, |context| {
+                run_whisper_rs(
+                    req,
+                    &model_path,
+                    context,
+                    &gauge,
+                    n_threads,
+                    max_initial_ts,
+                    segment_tx,
+                )
+            })?
+        })
+        .await
+        .map_err(|err| AppError::backend(format!("whisper-rs worker task failed: {err}")))?
     }
 }
 
 fn run_whisper_rs(
     req: TranscribeRequest,
     model_path: &str,
-    context: Arc<Mutex<WhisperContext>>,
+    context: &WhisperContext,
+    gauge: &Arc<WorkerGauge>,
+    n_threads: Option<usize>,
+    max_initial_ts: f32,
+    segment_tx: Option<UnboundedSender<TranscriptSegment>>,
 ) -> Result<TranscriptResult, AppError> {
-    let context_guard = context
-        .lock()
-        .map_err(|_| AppError::backend("failed to lock whisper model context"))?;
+    let _busy = gauge.begin();
 
-    let mut state = context_guard
+    let token_eot = context.token_eot();
+    let mut state = context
         .create_state()
         .map_err(|err| AppError::backend(format!("failed to create whisper state: {err}")))?;
 
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    let mut params = FullParams::new(sampling_strategy(&req));
     params.set_no_timestamps(false);
     params.set_print_special(false);
     params.set_print_progress(false);
     params.set_print_realtime(false);
     params.set_print_timestamps(false);
-    params.set_max_initial_ts(5.0);
+    params.set_max_initial_ts(max_initial_ts);
     if let Some(language) = req.language.as_deref() {
         let trimmed = language.trim();
         if !trimmed.is_empty() {
@@ -187,26 +442,34 @@ fn run_whisper_rs(
     if let Some(temp) = req.temperature {
         params.set_temperature(temp);
     }
+    apply_decode_guards(&mut params, &req);
+    apply_thread_count(&mut params, n_threads);
+    apply_deadline(&mut params, req.deadline);
+    attach_progress(&mut params, gauge);
+    attach_segment_stream(&mut params, segment_tx.as_ref());
     params.set_translate(matches!(req.task, crate::backend::TaskKind::Translate));
 
     state
         .full(params, &req.audio_16khz_mono_f32)
         .map_err(|err| {
-            AppError::backend(format!(
-                "whisper inference failed using {model_path:?}: {err}"
-            ))
+            pass_error(
+                req.deadline,
+                format!("whisper inference failed using {model_path:?}: {err}"),
+            )
         })?;
+    check_deadline(req.deadline)?;
 
-    let (mut count, mut segments) = extract_segments(&state)?;
+    let (mut count, mut segments) = extract_segments(&state, token_eot)?;
+    let mut warnings = Vec::new();
 
     if count == 0 && req.language.is_none() {
-        let mut fallback = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        let mut fallback = FullParams::new(sampling_strategy(&req));
         fallback.set_no_timestamps(false);
         fallback.set_print_special(false);
         fallback.set_print_progress(false);
         fallback.set_print_realtime(false);
         fallback.set_print_timestamps(false);
-        fallback.set_max_initial_ts(5.0);
+        fallback.set_max_initial_ts(max_initial_ts);
         fallback.set_language(Some("en"));
         if let Some(prompt) = req.prompt.as_deref() {
             let trimmed = prompt.trim();
@@ -217,35 +480,47 @@ fn run_whisper_rs(
         if let Some(temp) = req.temperature {
             fallback.set_temperature(temp);
         }
+        apply_decode_guards(&mut fallback, &req);
+        apply_thread_count(&mut fallback, n_threads);
+        apply_deadline(&mut fallback, req.deadline);
+        attach_progress(&mut fallback, gauge);
+        // The primary pass produced no segments, so nothing was streamed yet.
+        attach_segment_stream(&mut fallback, segment_tx.as_ref());
         fallback.set_translate(matches!(req.task, crate::backend::TaskKind::Translate));
 
         state
             .full(fallback, &req.audio_16khz_mono_f32)
             .map_err(|err| {
-                AppError::backend(format!(
-                    "whisper fallback inference failed using {model_path:?}: {err}"
-                ))
+                pass_error(
+                    req.deadline,
+                    format!("whisper fallback inference failed using {model_path:?}: {err}"),
+                )
             })?;
-        let (fallback_count, fallback_segments) = extract_segments(&state)?;
+        check_deadline(req.deadline)?;
+        let (fallback_count, fallback_segments) = extract_segments(&state, token_eot)?;
         if fallback_count > 0 {
             warn!(
                 audio_samples = req.audio_16khz_mono_f32.len(),
                 segment_count = fallback_count,
                 "whisper fallback used fixed language after empty auto-detect output"
             );
+            warnings.push(TranscriptWarning::new(
+                "fallback_decode",
+                "language detection produced no text; the audio was decoded again as English",
+            ));
             count = fallback_count;
             segments = fallback_segments;
         }
     }
 
     if looks_like_non_speech_only(&segments) {
-        let mut aggressive = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        let mut aggressive = FullParams::new(sampling_strategy(&req));
         aggressive.set_no_timestamps(false);
         aggressive.set_print_special(false);
         aggressive.set_print_progress(false);
         aggressive.set_print_realtime(false);
         aggressive.set_print_timestamps(false);
-        aggressive.set_max_initial_ts(5.0);
+        aggressive.set_max_initial_ts(max_initial_ts);
         aggressive.set_no_speech_thold(1.0);
         aggressive.set_suppress_blank(false);
 
@@ -266,17 +541,23 @@ fn run_whisper_rs(
         if let Some(temp) = req.temperature {
             aggressive.set_temperature(temp);
         }
+        apply_decode_guards(&mut aggressive, &req);
+        apply_thread_count(&mut aggressive, n_threads);
+        apply_deadline(&mut aggressive, req.deadline);
+        attach_progress(&mut aggressive, gauge);
         aggressive.set_translate(matches!(req.task, crate::backend::TaskKind::Translate));
 
         state
             .full(aggressive, &req.audio_16khz_mono_f32)
             .map_err(|err| {
-                AppError::backend(format!(
-                    "whisper aggressive fallback failed using {model_path:?}: {err}"
-                ))
+                pass_error(
+                    req.deadline,
+                    format!("whisper aggressive fallback failed using {model_path:?}: {err}"),
+                )
             })?;
+        check_deadline(req.deadline)?;
 
-        let (aggressive_count, aggressive_segments) = extract_segments(&state)?;
+        let (aggressive_count, aggressive_segments) = extract_segments(&state, token_eot)?;
         if transcript_score(&aggressive_segments) > transcript_score(&segments) {
             warn!(
                 audio_samples = req.audio_16khz_mono_f32.len(),
@@ -284,6 +565,10 @@ fn run_whisper_rs(
                 new_segment_count = aggressive_count,
                 "whisper aggressive fallback replaced non-speech-only transcript"
             );
+            warnings.push(TranscriptWarning::new(
+                "fallback_decode",
+                "the first pass found only non-speech markers; the audio was decoded again without speech suppression",
+            ));
             count = aggressive_count;
             segments = aggressive_segments;
         }
@@ -305,21 +590,166 @@ fn run_whisper_rs(
         );
     }
 
-    let detected_language = if let Some(lang) = req.language {
-        Some(lang)
-    } else {
-        get_lang_str(state.full_lang_id_from_state()).map(ToOwned::to_owned)
+    let (detected_language, language_probs) = match req.language {
+        Some(lang) => (Some(lang), Vec::new()),
+        None => (
+            get_lang_str(state.full_lang_id_from_state()).map(ToOwned::to_owned),
+            if req.language_probs {
+                top_language_probs(&mut state, &req.audio_16khz_mono_f32, n_threads)
+            } else {
+                Vec::new()
+            },
+        ),
     };
 
+    let no_speech = text.is_empty().then_some(NoSpeechReason::NoSpeechDetected);
     Ok(TranscriptResult {
         text,
         language: detected_language,
         segments,
+        no_speech,
+        language_probs,
+        usage: None,
+        warnings,
+    })
+}
+
+/// Runs whisper's language detector on the first 30s of audio and returns the
+/// most likely languages, or nothing if detection fails.
+fn top_language_probs(
+    state: &mut WhisperState,
+    audio: &[f32],
+    n_threads: Option<usize>,
+) -> Vec<(String, f32)> {
+    rank_languages(state, audio, n_threads).unwrap_or_else(|err| {
+        warn!(error = %err, "whisper language probability detection failed");
+        Vec::new()
     })
 }
 
+/// Runs whisper's language detector on the first 30s of audio and returns the
+/// most likely languages, highest first.
+fn rank_languages(
+    state: &mut WhisperState,
+    audio: &[f32],
+    n_threads: Option<usize>,
+) -> Result<Vec<(String, f32)>, WhisperError> {
+    let threads = n_threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get().min(4))
+            .unwrap_or(1)
+    });
+    // The detector reads only the first window; skip the mel of the rest.
+    let audio = &audio[..audio.len().min(LANGUAGE_DETECT_SAMPLES)];
+    state.pcm_to_mel(audio, threads)?;
+    let (_, probs) = state.lang_detect(0, threads)?;
+    let mut ranked = probs
+        .into_iter()
+        .enumerate()
+        .filter_map(|(id, prob)| get_lang_str(id as i32).map(|lang| (lang.to_string(), prob)))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(LANGUAGE_PROBS_TOP_N);
+    Ok(ranked)
+}
+
+/// whisper.cpp's compile-time CPU and accelerator feature summary.
+pub fn system_info() -> &'static str {
+    whisper_rs::print_system_info()
+}
+
+/// Forwards segments to a streaming client as whisper finalizes them.
+///
+/// Not attached to the aggressive non-speech pass: its output only replaces the
+/// primary transcript when it scores higher, which the final result reflects.
+fn attach_segment_stream(
+    params: &mut FullParams,
+    segment_tx: Option<&UnboundedSender<TranscriptSegment>>,
+) {
+    let Some(segment_tx) = segment_tx.cloned() else {
+        return;
+    };
+    params.set_segment_callback_safe_lossy(move |data: SegmentCallbackData| {
+        let text = data.text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let _ = segment_tx.send(TranscriptSegment {
+            start_secs: (data.start_timestamp as f64) * 0.01,
+            end_secs: (data.end_timestamp as f64) * 0.01,
+            text: text.to_string(),
+            avg_logprob: None,
+            token_count: 0,
+            no_speech_prob: None,
+            tokens: Vec::new(),
+            token_logprobs: Vec::new(),
+            words: Vec::new(),
+        });
+    });
+}
+
+/// Reports whisper.cpp's progress through the pass to the worker's gauge.
+fn attach_progress(params: &mut FullParams, gauge: &Arc<WorkerGauge>) {
+    let gauge = Arc::clone(gauge);
+    params.set_progress_callback_safe(move |percent: i32| gauge.set_progress(percent));
+}
+
+/// Aborts whisper.cpp's decoding loop once `deadline` passes.
+fn apply_deadline(params: &mut FullParams, deadline: Option<Instant>) {
+    if let Some(deadline) = deadline {
+        params.set_abort_callback_safe(move || Instant::now() >= deadline);
+    }
+}
+
+/// Returns a timeout error once `deadline` has passed.
+fn check_deadline(deadline: Option<Instant>) -> Result<(), AppError> {
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(AppError::timeout(
+            "inference aborted after exceeding REQUEST_TIMEOUT_SECS",
+        ));
+    }
+    Ok(())
+}
+
+/// Reports a failed inference pass, as a timeout when the deadline aborted it.
+fn pass_error(deadline: Option<Instant>, message: String) -> AppError {
+    check_deadline(deadline)
+        .err()
+        .unwrap_or_else(|| AppError::backend(message))
+}
+
+/// Matches whisper.cpp's thread count to a pinned worker's core set.
+fn apply_thread_count(params: &mut FullParams, n_threads: Option<usize>) {
+    if let Some(n_threads) = n_threads {
+        params.set_n_threads(n_threads as i32);
+    }
+}
+
+/// Picks beam search when the request sets a beam width, greedy decoding
+/// with `best_of` candidates otherwise.
+fn sampling_strategy(req: &TranscribeRequest) -> SamplingStrategy {
+    match req.beam_size {
+        Some(beam_size) => SamplingStrategy::BeamSearch {
+            beam_size: beam_size as i32,
+            // whisper.cpp does not implement patience; -1 keeps its default.
+            patience: -1.0,
+        },
+        None => SamplingStrategy::Greedy {
+            best_of: req.best_of.unwrap_or(1) as i32,
+        },
+    }
+}
+
+/// Applies request options that guard against runaway decoding.
+fn apply_decode_guards(params: &mut FullParams, req: &TranscribeRequest) {
+    if let Some(max_tokens) = req.max_tokens_per_segment {
+        params.set_max_tokens(max_tokens as i32);
+    }
+}
+
 fn extract_segments(
     state: &whisper_rs::WhisperState,
+    token_eot: WhisperTokenId,
 ) -> Result<(i32, Vec<TranscriptSegment>), AppError> {
     let count = state.full_n_segments();
     let mut segments = Vec::with_capacity(count as usize);
@@ -336,16 +766,96 @@ fn extract_segments(
             continue;
         }
 
+        let token_logprobs = text_token_logprobs(&seg, token_eot);
+        let token_count = token_logprobs.len();
+        let logprob_sum = token_logprobs
+            .iter()
+            .map(|token| f64::from(token.logprob))
+            .sum::<f64>();
+        let start_secs = (seg.start_timestamp() as f64) * 0.01;
+        let end_secs = (seg.end_timestamp() as f64) * 0.01;
         segments.push(TranscriptSegment {
-            start_secs: (seg.start_timestamp() as f64) * 0.01,
-            end_secs: (seg.end_timestamp() as f64) * 0.01,
+            start_secs,
+            end_secs,
+            words: aligned_words(&seg, token_eot, start_secs, end_secs),
             text,
+            avg_logprob: (token_count > 0).then(|| logprob_sum / token_count as f64),
+            token_count,
+            no_speech_prob: Some(seg.no_speech_probability()),
+            tokens: (0..seg.n_tokens())
+                .filter_map(|idx| seg.get_token(idx))
+                .map(|token| token.token_id())
+                .collect(),
+            token_logprobs,
         });
     }
 
     Ok((count, segments))
 }
 
+/// Collects a segment's text tokens with their log-probabilities, skipping
+/// special and timestamp tokens (ids at or above end-of-text).
+fn text_token_logprobs(seg: &WhisperSegment<'_>, token_eot: WhisperTokenId) -> Vec<TokenLogprob> {
+    (0..seg.n_tokens())
+        .filter_map(|idx| seg.get_token(idx))
+        .filter_map(|token| {
+            let data = token.token_data();
+            (data.id < token_eot).then(|| TokenLogprob {
+                token: token
+                    .to_str_lossy()
+                    .map(|text| text.into_owned())
+                    .unwrap_or_default(),
+                logprob: data.plog,
+            })
+        })
+        .collect()
+}
+
+/// Times a segment's words by the DTW alignment of their tokens; empty when
+/// the context was built without alignment heads.
+fn aligned_words(
+    seg: &WhisperSegment<'_>,
+    token_eot: WhisperTokenId,
+    start_secs: f64,
+    end_secs: f64,
+) -> Vec<TranscriptWord> {
+    let mut tokens = Vec::new();
+    for token in (0..seg.n_tokens()).filter_map(|idx| seg.get_token(idx)) {
+        let data = token.token_data();
+        if data.id >= token_eot {
+            continue;
+        }
+        // whisper.cpp leaves `t_dtw` at -1 when it did not align the token.
+        if data.t_dtw < 0 {
+            return Vec::new();
+        }
+        let text = token
+            .to_str_lossy()
+            .map(|text| text.into_owned())
+            .unwrap_or_default();
+        tokens.push((text, data.t_dtw as f64 * 0.01));
+    }
+    group_words(tokens, start_secs, end_secs)
+}
+
+/// whisper.cpp's alignment heads for a model size.
+fn dtw_preset(size: WhisperModelSize) -> DtwModelPreset {
+    match size {
+        WhisperModelSize::Tiny => DtwModelPreset::Tiny,
+        WhisperModelSize::TinyEn => DtwModelPreset::TinyEn,
+        WhisperModelSize::Base => DtwModelPreset::Base,
+        WhisperModelSize::BaseEn => DtwModelPreset::BaseEn,
+        WhisperModelSize::Small => DtwModelPreset::Small,
+        WhisperModelSize::SmallEn => DtwModelPreset::SmallEn,
+        WhisperModelSize::Medium => DtwModelPreset::Medium,
+        WhisperModelSize::MediumEn => DtwModelPreset::MediumEn,
+        WhisperModelSize::LargeV1 => DtwModelPreset::LargeV1,
+        WhisperModelSize::LargeV2 => DtwModelPreset::LargeV2,
+        WhisperModelSize::LargeV3 => DtwModelPreset::LargeV3,
+        WhisperModelSize::Turbo => DtwModelPreset::LargeV3Turbo,
+    }
+}
+
 fn looks_like_non_speech_only(segments: &[TranscriptSegment]) -> bool {
     !segments.is_empty()
         && segments
@@ -0,0 +1,86 @@
+//! Build metadata reported by `GET /version`.
+//!
+//! Values other than the crate version are injected by `build.rs`, so a
+//! running binary can be traced back to the commit and dependencies it was
+//! built from.
+
+use serde_json::{json, Value};
+
+use crate::archive::utc_date;
+
+/// Crate version from `Cargo.toml`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short git commit hash, or `unknown` when built outside a checkout.
+pub const GIT_COMMIT: &str = env!("WOS_GIT_COMMIT");
+/// Build timestamp as Unix seconds (honours `SOURCE_DATE_EPOCH`).
+const BUILD_UNIX_TIME: &str = env!("WOS_BUILD_UNIX_TIME");
+/// Locked `whisper-rs-sys` version, which pins the bundled whisper.cpp sources.
+const WHISPER_RS_SYS_VERSION: &str = env!("WOS_WHISPER_RS_SYS_VERSION");
+
+/// Cargo features compiled into this binary.
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "cuda") {
+        features.push("cuda");
+    }
+    if cfg!(feature = "metal") {
+        features.push("metal");
+    }
+    if cfg!(feature = "rocm") {
+        features.push("rocm");
+    }
+    if cfg!(feature = "opus") {
+        features.push("opus");
+    }
+    if cfg!(feature = "tls") {
+        features.push("tls");
+    }
+    if cfg!(feature = "tts") {
+        features.push("tts");
+    }
+    features
+}
+
+/// Build time as an RFC 3339 UTC timestamp.
+pub fn build_date() -> String {
+    let secs: u64 = BUILD_UNIX_TIME.parse().unwrap_or(0);
+    let (year, month, day) = utc_date(secs);
+    let time_of_day = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// JSON payload for `GET /version`; `whisper_system_info` comes from the backend.
+pub fn version_json(whisper_system_info: &str) -> Value {
+    json!({
+        "object": "version",
+        "version": VERSION,
+        "git_commit": GIT_COMMIT,
+        "build_date": build_date(),
+        "whisper_cpp": {
+            "whisper_rs_sys": WHISPER_RS_SYS_VERSION,
+            "system_info": whisper_system_info.trim(),
+        },
+        "features": enabled_features(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_json_reports_build_metadata() {
+        let body = version_json("AVX = 1 | ");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!body["git_commit"].as_str().expect("commit").is_empty());
+        assert_eq!(body["whisper_cpp"]["system_info"], "AVX = 1 |");
+        let date = body["build_date"].as_str().expect("date");
+        assert_eq!(date.len(), "1970-01-01T00:00:00Z".len());
+        assert!(date.ends_with('Z'));
+    }
+}
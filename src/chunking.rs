@@ -0,0 +1,304 @@
+//! Splitting long recordings into overlapping windows and stitching the results.
+//!
+//! With `CHUNK_SECS` set, audio longer than one window is cut into windows of
+//! that length, each overlapping the next by `CHUNK_OVERLAP_SECS`, and every
+//! window is transcribed as its own request on the worker pool. Segments are
+//! then shifted back onto the recording's timeline and joined at the middle of
+//! each overlap: a segment belongs to the window its midpoint falls in, and a
+//! segment repeated on both sides of the cut is kept once. A [`Stitcher`]
+//! does this as windows finish, so a window's segments are final as soon as
+//! every window before it is done.
+
+use std::ops::Range;
+
+use crate::backend::{NoSpeechReason, TranscriptResult, TranscriptSegment, TranscriptWarning};
+use crate::formats::TextNormalization;
+
+/// Sample rate of decoded audio.
+const SAMPLE_RATE: f64 = 16_000.0;
+
+/// Window size and scheduling for long-audio chunking.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ChunkParams {
+    /// Length of each window; shorter recordings are transcribed whole.
+    pub window_secs: u32,
+    /// Audio shared by neighbouring windows.
+    pub overlap_secs: u32,
+    /// Whether windows are transcribed at the same time on several workers.
+    pub parallel: bool,
+}
+
+impl ChunkParams {
+    /// Sample ranges of the windows covering `len` samples; `None` when the
+    /// audio fits in one window.
+    pub fn plan(&self, len: usize) -> Option<Vec<Range<usize>>> {
+        let window = self.window_secs as usize * SAMPLE_RATE as usize;
+        let overlap = (self.overlap_secs.min(self.window_secs / 2) as usize) * SAMPLE_RATE as usize;
+        if len <= window {
+            return None;
+        }
+        let step = window - overlap;
+        let mut windows = Vec::new();
+        let mut start = 0;
+        while start + window < len {
+            windows.push(start..start + window);
+            start += step;
+        }
+        windows.push(start..len);
+        Some(windows)
+    }
+}
+
+/// Joins per-window transcripts into one as windows finish, in any order.
+///
+/// Segment times must already be relative to their window and within it.
+/// The language and language probabilities come from the first window; the
+/// result is only marked as having no speech when every window was. Warnings
+/// are kept once per code, from the first window that raised them.
+pub struct Stitcher {
+    bounds: Vec<Range<usize>>,
+    /// Results of windows that finished before an earlier one.
+    waiting: Vec<Option<TranscriptResult>>,
+    /// Index of the next window to join.
+    next: usize,
+    segments: Vec<TranscriptSegment>,
+    language: Option<String>,
+    language_probs: Vec<(String, f32)>,
+    no_speech: Option<NoSpeechReason>,
+    warnings: Vec<TranscriptWarning>,
+    all_silent: bool,
+}
+
+impl Stitcher {
+    /// Starts stitching the windows covering the sample ranges `bounds`.
+    pub fn new(bounds: Vec<Range<usize>>) -> Self {
+        Self {
+            waiting: bounds.iter().map(|_| None).collect(),
+            bounds,
+            next: 0,
+            segments: Vec::new(),
+            language: None,
+            language_probs: Vec::new(),
+            no_speech: None,
+            warnings: Vec::new(),
+            all_silent: true,
+        }
+    }
+
+    /// Adds the result of window `idx` and returns the segments that became
+    /// final, in order: none until every earlier window is in.
+    pub fn push(&mut self, idx: usize, result: TranscriptResult) -> &[TranscriptSegment] {
+        let joined = self.segments.len();
+        if let Some(slot) = self.waiting.get_mut(idx) {
+            *slot = Some(result);
+        }
+        while let Some(result) = self.waiting.get_mut(self.next).and_then(Option::take) {
+            self.join(result);
+            self.next += 1;
+        }
+        &self.segments[joined..]
+    }
+
+    /// Language of the first window, once it is in.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    fn join(&mut self, result: TranscriptResult) {
+        let idx = self.next;
+        let range = &self.bounds[idx];
+        if idx == 0 {
+            self.language = result.language;
+            self.language_probs = result.language_probs;
+            self.no_speech = result.no_speech;
+        }
+        self.all_silent &= result.no_speech.is_some();
+        for warning in result.warnings {
+            if !self.warnings.iter().any(|kept| kept.code == warning.code) {
+                self.warnings.push(warning);
+            }
+        }
+        let offset = range.start as f64 / SAMPLE_RATE;
+        // Cut points: the middle of the overlap with each neighbour.
+        let from = match idx {
+            0 => f64::NEG_INFINITY,
+            _ => overlap_middle(&self.bounds[idx - 1], range),
+        };
+        let until = self
+            .bounds
+            .get(idx + 1)
+            .map_or(f64::INFINITY, |next| overlap_middle(range, next));
+        // Only the first segment kept past a cut can repeat the previous window.
+        let mut first = true;
+        for mut seg in result.segments {
+            seg.start_secs += offset;
+            seg.end_secs += offset;
+            for word in &mut seg.words {
+                word.start_secs += offset;
+                word.end_secs += offset;
+            }
+            let middle = (seg.start_secs + seg.end_secs) / 2.0;
+            if middle < from || middle >= until {
+                continue;
+            }
+            if std::mem::take(&mut first)
+                && self
+                    .segments
+                    .last()
+                    .is_some_and(|last| same_text(last, &seg))
+            {
+                continue;
+            }
+            self.segments.push(seg);
+        }
+    }
+
+    /// Returns the stitched transcript of the windows joined so far.
+    pub fn finish(self) -> TranscriptResult {
+        TranscriptResult {
+            text: TextNormalization::default().join_segments(&self.segments),
+            language: self.language,
+            segments: self.segments,
+            no_speech: if self.all_silent {
+                self.no_speech
+            } else {
+                None
+            },
+            language_probs: self.language_probs,
+            usage: None,
+            warnings: self.warnings,
+        }
+    }
+}
+
+/// Time in seconds halfway through the overlap of two consecutive windows.
+fn overlap_middle(earlier: &Range<usize>, later: &Range<usize>) -> f64 {
+    (later.start + earlier.end) as f64 / 2.0 / SAMPLE_RATE
+}
+
+/// Whether two segments say the same thing, ignoring case, spacing, and punctuation.
+fn same_text(a: &TranscriptSegment, b: &TranscriptSegment) -> bool {
+    let key = |seg: &TranscriptSegment| {
+        seg.text
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let key_a = key(a);
+    !key_a.is_empty() && key_a == key(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stitch(windows: Vec<(Range<usize>, TranscriptResult)>) -> TranscriptResult {
+        let mut stitcher = Stitcher::new(windows.iter().map(|(range, _)| range.clone()).collect());
+        for (idx, (_, result)) in windows.into_iter().enumerate() {
+            stitcher.push(idx, result);
+        }
+        stitcher.finish()
+    }
+
+    fn result(segments: &[(f64, f64, &str)]) -> TranscriptResult {
+        let segments = segments
+            .iter()
+            .map(|&(start_secs, end_secs, text)| TranscriptSegment::new(start_secs, end_secs, text))
+            .collect();
+        TranscriptResult {
+            text: String::new(),
+            language: Some("en".to_string()),
+            segments,
+            no_speech: None,
+            language_probs: Vec::new(),
+            usage: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plans_overlapping_windows_only_for_long_audio() {
+        let params = ChunkParams {
+            window_secs: 60,
+            overlap_secs: 5,
+            parallel: false,
+        };
+        assert_eq!(params.plan(60 * 16_000), None);
+        let windows = params.plan(150 * 16_000).expect("windows");
+        let secs = windows
+            .iter()
+            .map(|range| (range.start / 16_000, range.end / 16_000))
+            .collect::<Vec<_>>();
+        assert_eq!(secs, [(0, 60), (55, 115), (110, 150)]);
+    }
+
+    #[test]
+    fn stitching_cuts_at_the_overlap_middle_and_drops_repeats() {
+        let second = 16_000;
+        let stitched = stitch(vec![
+            (
+                0..60 * second,
+                result(&[
+                    (0.0, 50.0, " One."),
+                    (56.0, 58.75, " Two."),
+                    (59.0, 60.0, " Thr"),
+                ]),
+            ),
+            (
+                55 * second..100 * second,
+                result(&[
+                    (2.75, 3.5, " two"),
+                    (3.5, 5.0, " Three."),
+                    (5.0, 9.0, " Four."),
+                ]),
+            ),
+        ]);
+        let segments = stitched
+            .segments
+            .iter()
+            .map(|seg| (seg.start_secs, seg.end_secs, seg.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                (0.0, 50.0, " One."),
+                (56.0, 58.75, " Two."),
+                (58.5, 60.0, " Three."),
+                (60.0, 64.0, " Four."),
+            ]
+        );
+        assert_eq!(stitched.text, "One. Two. Three. Four.");
+        assert_eq!(stitched.language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn stitchers_release_segments_once_earlier_windows_are_in() {
+        let second = 16_000;
+        let mut stitcher = Stitcher::new(vec![
+            0..60 * second,
+            55 * second..115 * second,
+            110 * second..150 * second,
+        ]);
+        let texts = |segments: &[TranscriptSegment]| {
+            segments
+                .iter()
+                .map(|seg| seg.text.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(stitcher
+            .push(1, result(&[(10.0, 20.0, " Middle.")]))
+            .is_empty());
+        assert_eq!(
+            texts(stitcher.push(0, result(&[(0.0, 10.0, " Start.")]))),
+            [" Start.", " Middle."]
+        );
+        assert_eq!(
+            texts(stitcher.push(2, result(&[(10.0, 20.0, " End.")]))),
+            [" End."]
+        );
+        let stitched = stitcher.finish();
+        assert_eq!(stitched.text, "Start. Middle. End.");
+        assert_eq!(stitched.segments[2].start_secs, 120.0);
+    }
+}
@@ -3,10 +3,31 @@
 //! Values are intentionally validated early so startup fails fast with
 //! actionable errors.
 
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::{ResampleQuality, VadParams};
+use crate::chunking::ChunkParams;
+use crate::denoise;
+use crate::encryption::StorageCipher;
 use crate::error::AppError;
-use clap::{Parser, ValueEnum};
+use crate::formats::{ResponseFormat, TextNormalization};
+use crate::model_registry::parse_on_demand_model_id;
+use crate::presets::{load_presets, Preset};
+use crate::storage::{Storage, StorageLocation};
+use axum::http::{HeaderName, HeaderValue, Method};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 
 pub const MAX_WHISPER_PARALLELISM: usize = 8;
+/// Upper bound for `max_tokens_per_segment`; whisper decodes at most half its
+/// 448-token text context per window, so larger caps would have no effect.
+pub const MAX_TOKENS_PER_SEGMENT_LIMIT: u32 = 224;
+/// Upper bound for `beam_size` and `best_of`; whisper.cpp runs at most 8 decoders at once.
+pub const MAX_DECODERS: u32 = 8;
+/// Highest CPU index accepted in `WHISPER_CPU_AFFINITY` (size of a Linux `cpu_set_t`).
+pub const MAX_CPU_INDEX: usize = 1023;
 
 /// Supported acceleration modes for whisper-rs context initialization.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
@@ -15,6 +36,8 @@ pub enum AccelerationKind {
     Metal,
     /// Prefer CUDA acceleration (Linux/Windows with NVIDIA GPU).
     Cuda,
+    /// Prefer ROCm/HIP acceleration (Linux with AMD GPU).
+    Rocm,
     /// Disable GPU acceleration and run on CPU.
     None,
 }
@@ -24,13 +47,32 @@ impl AccelerationKind {
         match self {
             Self::Metal => "metal",
             Self::Cuda => "cuda",
+            Self::Rocm => "rocm",
             Self::None => "none",
         }
     }
 }
 
-/// Supported whisper.cpp model sizes.
+/// Worker pool requested with the `compute` form field or an API key's `compute=` option.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Compute {
+    /// CPU-only workers from `WHISPER_CPU_WORKERS`.
+    Cpu,
+    /// The default, accelerated workers.
+    Gpu,
+}
+
+impl Compute {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Gpu => "gpu",
+        }
+    }
+}
+
+/// Supported whisper.cpp model sizes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, ValueEnum)]
 pub enum WhisperModelSize {
     Tiny,
     #[value(name = "tiny.en")]
@@ -60,6 +102,55 @@ impl Default for WhisperModelSize {
     }
 }
 
+/// Which alignment heads whisper.cpp's DTW uses for word timestamps (`WHISPER_DTW`).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DtwHeads {
+    /// No DTW alignment; word timestamps are unavailable.
+    Off,
+    /// Heads of the model size named by each model's file name.
+    Auto,
+    /// Like `Auto`, with this size's heads for file names that name no size,
+    /// such as fine-tunes.
+    Size(WhisperModelSize),
+}
+
+impl DtwHeads {
+    /// The model size whose heads align `model_path`, or `None` for no DTW.
+    pub fn resolve(self, model_path: &str) -> Option<WhisperModelSize> {
+        match self {
+            Self::Off => None,
+            Self::Auto => model_size_from_filename(model_path),
+            Self::Size(size) => Some(model_size_from_filename(model_path).unwrap_or(size)),
+        }
+    }
+}
+
+/// Parses `WHISPER_DTW`: `off`, `auto`, or a model size such as `small.en`.
+pub fn parse_dtw_heads(s: &str) -> Result<DtwHeads, String> {
+    match s.trim() {
+        "off" | "false" => Ok(DtwHeads::Off),
+        "auto" => Ok(DtwHeads::Auto),
+        other => WhisperModelSize::from_str(other, true)
+            .map(DtwHeads::Size)
+            .map_err(|_| format!("expected off, auto, or a model size, not {other:?}")),
+    }
+}
+
+/// The size a model file is named for, such as `ggml-small.en.bin` or the
+/// quantized `ggml-large-v3-turbo-q5_0.bin`.
+pub fn model_size_from_filename(model_path: &str) -> Option<WhisperModelSize> {
+    let name = Path::new(model_path).file_name()?.to_str()?;
+    let stem = name.strip_suffix(".bin").unwrap_or(name);
+    let stem = match stem.rsplit_once("-q") {
+        Some((base, quant)) if quant.starts_with(|c: char| c.is_ascii_digit()) => base,
+        _ => stem,
+    };
+    WhisperModelSize::value_variants()
+        .iter()
+        .copied()
+        .find(|size| whisper_model_filename(*size).strip_suffix(".bin") == Some(stem))
+}
+
 /// Supported inference backend implementations.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
 pub enum BackendKind {
@@ -74,6 +165,12 @@ impl Default for BackendKind {
     }
 }
 
+/// Prefix for namespaced environment variables (`WOS_PORT`, `WOS_API_KEY`, ...).
+///
+/// A prefixed variable takes precedence over its bare counterpart, so shared
+/// container environments can leave generic names like `PORT` untouched.
+pub const ENV_PREFIX: &str = "WOS_";
+
 /// Command-line arguments for whisper-openai-server.
 #[derive(Parser, Debug, Clone)]
 #[command(
@@ -90,10 +187,26 @@ pub struct CliArgs {
     #[arg(long, env = "PORT", default_value = "8000")]
     pub port: u16,
 
+    /// UDP port receiving RTP audio for streaming sessions opened with `encoding=rtp` (optional)
+    #[arg(long, env = "RTP_PORT")]
+    pub rtp_port: Option<u16>,
+
     /// API key for authentication (optional)
     #[arg(long, env = "API_KEY")]
     pub api_key: Option<String>,
 
+    /// Comma-separated API keys, each `<key> [label=<name>] [requests_per_minute=<n>] [concurrent_requests=<n>]` (optional)
+    #[arg(long, env = "API_KEYS")]
+    pub api_keys: Option<String>,
+
+    /// File listing API keys, one `API_KEYS` entry per line (optional)
+    #[arg(long, env = "API_KEYS_FILE")]
+    pub api_keys_file: Option<String>,
+
+    /// Bearer token enabling the /admin endpoints (optional)
+    #[arg(long, env = "ADMIN_API_KEY")]
+    pub admin_api_key: Option<String>,
+
     /// Local model path
     #[arg(long, env = "WHISPER_MODEL")]
     pub model: Option<String>,
@@ -122,10 +235,26 @@ pub struct CliArgs {
     #[arg(long, env = "HF_TOKEN")]
     pub hf_token: Option<String>,
 
+    /// Comma-separated `repo:filename` (or `repo:dir/` for multi-file models) auxiliary models (VAD, diarization, ...) to cache at startup
+    #[arg(long, env = "WHISPER_AUX_MODELS", default_value = "")]
+    pub aux_models: String,
+
     /// Extra accepted model id for API requests
     #[arg(long, env = "WHISPER_MODEL_ALIAS", default_value = "whisper-1")]
     pub model_alias: String,
 
+    /// Comma-separated `alias=model` pairs mapping other model ids to local models
+    #[arg(
+        long,
+        env = "MODEL_ALIASES",
+        default_value = "gpt-4o-transcribe=whisper-1,gpt-4o-mini-transcribe=whisper-1"
+    )]
+    pub model_aliases: String,
+
+    /// Comma-separated `id=model` pairs loading additional models, each a size or a file path
+    #[arg(long, env = "WHISPER_EXTRA_MODELS", default_value = "")]
+    pub extra_models: String,
+
     /// Inference backend
     #[arg(
         long,
@@ -147,6 +276,349 @@ pub struct CliArgs {
     /// Number of inference workers (1-8)
     #[arg(long, env = "WHISPER_PARALLELISM", default_value = "1", value_parser = parse_parallelism)]
     pub parallelism: usize,
+
+    /// CPU-only workers serving `compute=cpu` requests for the default model (1-8)
+    #[arg(long, env = "WHISPER_CPU_WORKERS", value_parser = parse_parallelism)]
+    pub cpu_workers: Option<usize>,
+
+    /// Comma-separated `id=workers[:queue_depth]` pairs giving models their own workers and queue
+    #[arg(long, env = "WHISPER_MODEL_WORKERS", default_value = "")]
+    pub model_workers: String,
+
+    /// Merge short or mid-sentence segments into sentences by default
+    #[arg(long, env = "MERGE_SEGMENTS", default_value = "false")]
+    pub merge_segments: bool,
+
+    /// Guess missing punctuation and casing in English transcripts from pauses by default (a heuristic, not a model)
+    #[arg(long, env = "HEURISTIC_PUNCTUATION", default_value = "false")]
+    pub heuristic_punctuation: bool,
+
+    /// Keep line breaks in transcript text and put each segment on its own line
+    #[arg(long, env = "TEXT_KEEP_NEWLINES", default_value = "false")]
+    pub text_keep_newlines: bool,
+
+    /// Keep runs of spaces in transcript text instead of collapsing them
+    #[arg(long, env = "TEXT_KEEP_SPACES", default_value = "false")]
+    pub text_keep_spaces: bool,
+
+    /// Compose transcript text to Unicode NFC
+    #[arg(long, env = "TEXT_UNICODE_NFC", default_value = "false")]
+    pub text_unicode_nfc: bool,
+
+    /// Strip leading dashes, ellipses, and separators from transcript lines
+    #[arg(long, env = "TEXT_STRIP_LEADING_PUNCTUATION", default_value = "false")]
+    pub text_strip_leading_punctuation: bool,
+
+    /// Split audio longer than this many seconds into windows transcribed separately (optional)
+    #[arg(long, env = "CHUNK_SECS", value_parser = clap::value_parser!(u32).range(60..))]
+    pub chunk_secs: Option<u32>,
+
+    /// Seconds of audio shared by neighbouring chunks
+    #[arg(long, env = "CHUNK_OVERLAP_SECS", default_value = "5", value_parser = clap::value_parser!(u32).range(0..=30))]
+    pub chunk_overlap_secs: u32,
+
+    /// Transcribe the chunks of one request on several workers at once
+    #[arg(long, env = "CHUNK_PARALLEL", default_value = "false")]
+    pub chunk_parallel: bool,
+
+    /// Cut long silences out of the audio before inference by default
+    #[arg(long, env = "VAD_FILTER", default_value = "false")]
+    pub vad_filter: bool,
+
+    /// Suppress background noise with RNNoise by default (needs the `denoise` feature)
+    #[arg(long, env = "DENOISE", default_value = "false")]
+    pub denoise: bool,
+
+    /// Default resampler for audio not recorded at 16 kHz: fast, balanced, or high
+    #[arg(long, env = "RESAMPLE_QUALITY", value_enum, default_value = "fast")]
+    pub resample_quality: ResampleQuality,
+
+    /// Shortest silence, in milliseconds, that the VAD filter cuts out
+    #[arg(long, env = "VAD_MIN_SILENCE_MS", default_value = "1000", value_parser = clap::value_parser!(u32).range(100..=60_000))]
+    pub vad_min_silence_ms: u32,
+
+    /// Milliseconds of audio the VAD filter keeps on each side of speech
+    #[arg(long, env = "VAD_PADDING_MS", default_value = "200", value_parser = clap::value_parser!(u32).range(0..=10_000))]
+    pub vad_padding_ms: u32,
+
+    /// JSON file of named presets added to, or replacing, the built-in ones (optional)
+    #[arg(long, env = "PRESETS_FILE")]
+    pub presets_file: Option<String>,
+
+    /// Path to an ffmpeg executable used to decode uploads the built-in decoders reject (optional)
+    #[arg(long, env = "FFMPEG_BIN")]
+    pub ffmpeg_bin: Option<String>,
+
+    /// Decode and transcribe a built-in sample before accepting requests
+    #[arg(long, env = "STARTUP_SELF_TEST", default_value = "true")]
+    pub startup_self_test: bool,
+
+    /// Download and load other model sizes when requested as whisper-<size>
+    #[arg(long, env = "WHISPER_ON_DEMAND_MODELS", default_value = "false")]
+    pub on_demand_models: bool,
+
+    /// Core sets to pin each inference worker to, one per worker (for example 0-7,8-15)
+    #[arg(long, env = "WHISPER_CPU_AFFINITY", value_parser = parse_cpu_affinity)]
+    pub cpu_affinity: Option<CpuAffinity>,
+
+    /// Rebuild a worker's whisper context after this many inferences (optional)
+    #[arg(long, env = "WORKER_MAX_REQUESTS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub worker_max_requests: Option<u64>,
+
+    /// Rebuild a worker's whisper context once it is this many seconds old (optional)
+    #[arg(long, env = "WORKER_MAX_AGE_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub worker_max_age_secs: Option<u64>,
+
+    /// Default cap on decoded tokens per segment (1-224) to stop repetition loops
+    #[arg(long, env = "WHISPER_MAX_TOKENS_PER_SEGMENT", value_parser = parse_max_tokens_per_segment)]
+    pub max_tokens_per_segment: Option<u32>,
+
+    /// Default beam width (1-8); enables beam search instead of greedy decoding (optional)
+    #[arg(long, env = "WHISPER_BEAM_SIZE", value_parser = parse_decoders)]
+    pub beam_size: Option<u32>,
+
+    /// Default candidates sampled per segment when decoding at temperature above 0 (1-8) (optional)
+    #[arg(long, env = "WHISPER_BEST_OF", value_parser = parse_decoders)]
+    pub best_of: Option<u32>,
+
+    /// Alignment heads for DTW word timestamps: off, auto (from the model file name), or a model size
+    #[arg(long, env = "WHISPER_DTW", default_value = "off", value_parser = parse_dtw_heads)]
+    pub dtw: DtwHeads,
+
+    /// Latest time, in seconds, the first segment may start (0-30)
+    #[arg(long, env = "WHISPER_MAX_INITIAL_TS", default_value = "5.0", value_parser = parse_max_initial_ts)]
+    pub max_initial_ts: f32,
+
+    /// Maximum seconds a request may wait for a free inference worker before a 503
+    #[arg(long, env = "MAX_QUEUE_WAIT_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_queue_wait_secs: Option<u64>,
+
+    /// Requests allowed to wait for a busy inference worker before new ones get a 429 (optional)
+    #[arg(long, env = "MAX_QUEUE_DEPTH")]
+    pub max_queue_depth: Option<usize>,
+
+    /// Waiting requests that, sustained for READY_OVERLOAD_SECS, make /health/ready return 503 (optional)
+    #[arg(long, env = "READY_OVERLOAD_QUEUE_DEPTH", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub ready_overload_queue_depth: Option<usize>,
+
+    /// Seconds the queue must stay at READY_OVERLOAD_QUEUE_DEPTH before /health/ready reports overload
+    #[arg(long, env = "READY_OVERLOAD_SECS", default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    pub ready_overload_secs: u64,
+
+    /// Maximum seconds to decode and transcribe a synchronous request before a 504
+    #[arg(long, env = "REQUEST_TIMEOUT_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub request_timeout_secs: Option<u64>,
+
+    /// Maximum number of parts (file and fields) accepted in a multipart body
+    #[arg(long, env = "MULTIPART_MAX_PARTS", default_value = "64", value_parser = clap::value_parser!(u64).range(1..))]
+    pub multipart_max_parts: u64,
+
+    /// Maximum number of non-file form fields accepted in a multipart body
+    #[arg(long, env = "MULTIPART_MAX_FIELDS", default_value = "32", value_parser = clap::value_parser!(u64).range(1..))]
+    pub multipart_max_fields: u64,
+
+    /// Maximum size in bytes of a single non-file multipart form field
+    #[arg(long, env = "MULTIPART_MAX_FIELD_BYTES", default_value = "65536", value_parser = clap::value_parser!(u64).range(1..))]
+    pub multipart_max_field_bytes: u64,
+
+    /// Maximum concurrent client connections; extra connections get a 503 and are closed
+    #[arg(long, env = "MAX_CONNECTIONS", default_value = "1024", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_connections: u64,
+
+    /// Maximum audio uploads read at the same time; extra uploads get a 503
+    #[arg(long, env = "MAX_CONCURRENT_UPLOADS", default_value = "16", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_concurrent_uploads: u64,
+
+    /// Memory budget in MiB for in-flight request audio; requests queue when it is exhausted (optional)
+    #[arg(long, env = "MEMORY_BUDGET_MB", value_parser = clap::value_parser!(u32).range(1..))]
+    pub memory_budget_mb: Option<u32>,
+
+    /// Maximum total size in bytes of uploaded audio per request; larger uploads get a 413
+    #[arg(long, env = "MAX_UPLOAD_BYTES", default_value = "26214400", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_upload_bytes: u64,
+
+    /// Maximum decoded audio duration in seconds per file; longer audio gets a 400 (optional)
+    #[arg(long, env = "MAX_AUDIO_SECONDS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_audio_seconds: Option<u32>,
+
+    /// Uploads larger than this many bytes are spooled to disk instead of held in memory (optional)
+    #[arg(long, env = "UPLOAD_SPILL_THRESHOLD_BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub upload_spill_threshold_bytes: Option<u64>,
+
+    /// Directory for spooled uploads (defaults to the system temp directory)
+    #[arg(long, env = "UPLOAD_SPILL_DIR")]
+    pub upload_spill_dir: Option<String>,
+
+    /// Requests per minute allowed per API key or client IP on /v1 endpoints (optional)
+    #[arg(long, env = "RATE_LIMIT_REQUESTS_PER_MINUTE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit_requests_per_minute: Option<u32>,
+
+    /// Concurrent /v1 requests allowed per API key or client IP (optional)
+    #[arg(long, env = "RATE_LIMIT_CONCURRENT_REQUESTS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub rate_limit_concurrent_requests: Option<u64>,
+
+    /// Comma-separated origins allowed to call the API from a browser, or `*` (optional; CORS is off when unset)
+    #[arg(long, env = "CORS_ALLOWED_ORIGINS", value_delimiter = ',', value_parser = parse_cors_origin)]
+    pub cors_allowed_origins: Vec<HeaderValue>,
+
+    /// Comma-separated request headers browsers may send, or `*`
+    #[arg(long, env = "CORS_ALLOWED_HEADERS", value_delimiter = ',', default_value = "authorization,content-type", value_parser = parse_header_name)]
+    pub cors_allowed_headers: Vec<String>,
+
+    /// Comma-separated HTTP methods browsers may use
+    #[arg(long, env = "CORS_ALLOWED_METHODS", value_delimiter = ',', default_value = "GET,POST", value_parser = parse_method)]
+    pub cors_allowed_methods: Vec<Method>,
+
+    /// Serve the live caption page at /demo
+    #[arg(long, env = "DEMO_PAGE", default_value = "true")]
+    pub demo_page: bool,
+
+    /// Serve per-worker inference gauges for Prometheus at /metrics
+    #[arg(long, env = "PROMETHEUS_METRICS", default_value = "false")]
+    pub prometheus_metrics: bool,
+
+    /// Seconds a client may take to send request headers
+    #[arg(long, env = "HTTP_HEADER_TIMEOUT_SECS", default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http_header_timeout_secs: u64,
+
+    /// Seconds a request body may stall between chunks before the request fails
+    #[arg(long, env = "HTTP_READ_TIMEOUT_SECS", default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http_read_timeout_secs: u64,
+
+    /// Seconds a response body may stall between chunks before the connection is dropped
+    #[arg(long, env = "HTTP_WRITE_TIMEOUT_SECS", default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http_write_timeout_secs: u64,
+
+    /// Seconds without socket activity before a connection is closed
+    #[arg(long, env = "HTTP_IDLE_TIMEOUT_SECS", default_value = "120", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http_idle_timeout_secs: u64,
+
+    /// Keep HTTP/1.1 connections open between requests
+    #[arg(long, env = "HTTP_KEEPALIVE", default_value = "true")]
+    pub http_keepalive: bool,
+
+    /// Accept HTTP/2: h2c with prior knowledge on plain connections, and h2 negotiated over TLS
+    #[arg(long, env = "HTTP2", default_value = "true")]
+    pub http2: bool,
+
+    /// Maximum concurrent requests (streams) on one HTTP/2 connection
+    #[arg(long, env = "HTTP2_MAX_CONCURRENT_STREAMS", default_value = "256", value_parser = clap::value_parser!(u32).range(1..))]
+    pub http2_max_concurrent_streams: u32,
+
+    /// Seconds between HTTP/2 keep-alive pings on a connection (optional)
+    #[arg(long, env = "HTTP2_KEEPALIVE_INTERVAL_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http2_keepalive_interval_secs: Option<u64>,
+
+    /// Seconds to wait for a keep-alive ping acknowledgement before closing the connection
+    #[arg(long, env = "HTTP2_KEEPALIVE_TIMEOUT_SECS", default_value = "20", value_parser = clap::value_parser!(u64).range(1..))]
+    pub http2_keepalive_timeout_secs: u64,
+
+    /// PEM certificate chain to serve HTTPS with; needs the `tls` feature (optional)
+    #[arg(long, env = "TLS_CERT_FILE")]
+    pub tls_cert_file: Option<String>,
+
+    /// PEM private key for TLS_CERT_FILE (optional)
+    #[arg(long, env = "TLS_KEY_FILE")]
+    pub tls_key_file: Option<String>,
+
+    /// Seconds finished async jobs remain available at /v1/audio/jobs/{id}
+    #[arg(long, env = "ASYNC_JOB_RETENTION_SECS", default_value = "3600", value_parser = clap::value_parser!(u64).range(1..))]
+    pub async_job_retention_secs: u64,
+
+    /// Bytes of finished async job results kept in memory before the oldest are evicted (optional)
+    #[arg(long, env = "ASYNC_JOB_MAX_TOTAL_BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub async_job_max_total_bytes: Option<u64>,
+
+    /// Bytes of finished async job results kept per API key before its oldest are evicted (optional)
+    #[arg(long, env = "ASYNC_JOB_MAX_BYTES_PER_KEY", value_parser = clap::value_parser!(u64).range(1..))]
+    pub async_job_max_bytes_per_key: Option<u64>,
+
+    /// Directory or sqlite:<file> persisting queued async jobs so they resume after a restart (optional)
+    #[arg(long, env = "ASYNC_JOB_DIR")]
+    pub async_job_dir: Option<String>,
+
+    /// Directory or sqlite:<file> where every completed transcript is archived by date (optional)
+    #[arg(long, env = "TRANSCRIPT_ARCHIVE_DIR")]
+    pub transcript_archive_dir: Option<String>,
+
+    /// Directory or sqlite:<file> storing debug traces of requests sent with debug_trace=true (optional)
+    #[arg(long, env = "DEBUG_TRACE_DIR")]
+    pub debug_trace_dir: Option<String>,
+
+    /// Delete debug traces older than this many seconds (optional; kept until purged by default)
+    #[arg(long, env = "DEBUG_TRACE_RETENTION_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub debug_trace_retention_secs: Option<u64>,
+
+    /// Seconds between sweeps removing temporary files abandoned by crashes
+    #[arg(long, env = "JANITOR_INTERVAL_SECS", default_value = "3600", value_parser = clap::value_parser!(u64).range(1..))]
+    pub janitor_interval_secs: u64,
+
+    /// JSON-lines file recording every POST /admin/purge (optional)
+    #[arg(long, env = "PURGE_AUDIT_LOG")]
+    pub purge_audit_log: Option<String>,
+
+    /// AES-256 key (hex or base64) sealing spilled uploads, the job spool, and the archive (optional)
+    #[arg(long, env = "STORAGE_ENCRYPTION_KEY")]
+    pub storage_encryption_key: Option<String>,
+
+    /// File holding the storage encryption key, such as one written by a KMS agent (optional)
+    #[arg(long, env = "STORAGE_ENCRYPTION_KEY_FILE")]
+    pub storage_encryption_key_file: Option<String>,
+
+    /// StatsD/DogStatsD address (host:port) for pushed metrics (optional)
+    #[arg(long, env = "STATSD_ADDR")]
+    pub statsd_addr: Option<String>,
+
+    /// Metric name prefix for StatsD export
+    #[arg(long, env = "STATSD_PREFIX", default_value = "whisper_openai_server")]
+    pub statsd_prefix: String,
+
+    /// Append DogStatsD-style tags to StatsD metrics
+    #[arg(long, env = "STATSD_TAGS", default_value = "true")]
+    pub statsd_tags: bool,
+
+    /// Path to the piper executable used for speech synthesis
+    #[cfg(feature = "tts")]
+    #[arg(long, env = "PIPER_BIN", default_value = "piper")]
+    pub piper_bin: String,
+
+    /// Piper voice name (for example en_US-lessac-medium) or path to a .onnx voice
+    #[cfg(feature = "tts")]
+    #[arg(long, env = "TTS_VOICE", default_value = "en_US-lessac-medium")]
+    pub tts_voice: String,
+
+    /// Local cache directory for downloaded piper voices
+    #[cfg(feature = "tts")]
+    #[arg(long, env = "TTS_VOICES_DIR")]
+    pub tts_voices_dir: Option<String>,
+
+    /// Hugging Face repository for piper voice download
+    #[cfg(feature = "tts")]
+    #[arg(long, env = "TTS_HF_REPO", default_value = "rhasspy/piper-voices")]
+    pub tts_hf_repo: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Maintenance commands run instead of the server.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Write the configured Whisper model and its metadata into one bundle for offline hosts
+    ExportModelBundle {
+        /// Path of the bundle to write
+        #[arg(long, short)]
+        output: PathBuf,
+
+        /// Hugging Face revision the model was downloaded from, recorded in the bundle
+        #[arg(long, default_value = "main")]
+        revision: String,
+    },
+    /// Verify a bundle written by export-model-bundle and install its model into the cache directory
+    ImportModelBundle {
+        /// Path of the bundle to read
+        bundle: PathBuf,
+    },
 }
 
 fn parse_parallelism(s: &str) -> Result<usize, String> {
@@ -161,6 +633,458 @@ fn parse_parallelism(s: &str) -> Result<usize, String> {
     Ok(value)
 }
 
+/// Parses `WHISPER_AUX_MODELS` (`repo:filename,...`); an empty value caches nothing.
+///
+/// A filename ending in `/` names a directory of the repository.
+fn parse_aux_models(raw: &str) -> Result<Vec<(String, String)>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .map(|(repo, filename)| (repo.trim(), filename.trim()))
+                .filter(|(repo, filename)| {
+                    repo.contains('/') && !filename.is_empty() && !filename.contains("..")
+                })
+                .map(|(repo, filename)| (repo.to_string(), filename.to_string()))
+                .ok_or_else(|| {
+                    format!(
+                        "invalid WHISPER_AUX_MODELS entry {entry:?}; expected owner/repo:filename"
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Parses `MODEL_ALIASES` (`alias=model,...`); an empty value defines no aliases.
+fn parse_model_aliases(raw: &str) -> Result<Vec<(String, String)>, String> {
+    let mut aliases: Vec<(String, String)> = Vec::new();
+    for entry in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (alias, target) = entry
+            .split_once('=')
+            .map(|(alias, target)| (alias.trim(), target.trim()))
+            .filter(|(alias, target)| !alias.is_empty() && !target.is_empty())
+            .ok_or_else(|| {
+                format!("invalid MODEL_ALIASES entry {entry:?}; expected alias=model")
+            })?;
+        if alias == target || aliases.iter().any(|(existing, _)| existing == alias) {
+            return Err(format!(
+                "MODEL_ALIASES defines {alias:?} more than once or as itself"
+            ));
+        }
+        aliases.push((alias.to_string(), target.to_string()));
+    }
+    if let Some((alias, target)) = aliases
+        .iter()
+        .find(|(_, target)| aliases.iter().any(|(alias, _)| alias == target))
+    {
+        return Err(format!(
+            "MODEL_ALIASES maps {alias:?} to {target:?}, which is itself an alias"
+        ));
+    }
+    Ok(aliases)
+}
+
+/// Parses `WHISPER_EXTRA_MODELS` (`id=size-or-path,...`); an empty value loads nothing.
+fn parse_extra_models(raw: &str) -> Result<Vec<(String, String)>, String> {
+    let mut models: Vec<(String, String)> = Vec::new();
+    for entry in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (id, source) = entry
+            .split_once('=')
+            .map(|(id, source)| (id.trim(), source.trim()))
+            .filter(|(id, source)| !id.is_empty() && !source.is_empty())
+            .ok_or_else(|| {
+                format!("invalid WHISPER_EXTRA_MODELS entry {entry:?}; expected id=model")
+            })?;
+        if id == "whisper-1" || models.iter().any(|(existing, _)| existing == id) {
+            return Err(format!(
+                "WHISPER_EXTRA_MODELS defines {id:?} more than once or as whisper-1"
+            ));
+        }
+        models.push((id.to_string(), source.to_string()));
+    }
+    Ok(models)
+}
+
+/// Parses `WHISPER_MODEL_WORKERS` (`id=workers[:queue_depth],...`); an empty
+/// value gives no model a pool of its own.
+fn parse_model_workers(raw: &str) -> Result<Vec<(String, ModelWorkers)>, String> {
+    let mut pools: Vec<(String, ModelWorkers)> = Vec::new();
+    for entry in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let invalid =
+            |detail: String| format!("invalid WHISPER_MODEL_WORKERS entry {entry:?}: {detail}");
+        let (id, limits) = entry
+            .split_once('=')
+            .map(|(id, limits)| (id.trim(), limits.trim()))
+            .filter(|(id, limits)| !id.is_empty() && !limits.is_empty())
+            .ok_or_else(|| invalid("expected id=workers[:queue_depth]".to_string()))?;
+        let (workers, depth) = match limits.split_once(':') {
+            Some((workers, depth)) => (workers, Some(depth)),
+            None => (limits, None),
+        };
+        let workers =
+            parse_parallelism(workers.trim()).map_err(|err| invalid(format!("workers: {err}")))?;
+        let max_queue_depth = depth
+            .map(|depth| depth.trim().parse::<usize>())
+            .transpose()
+            .map_err(|_| invalid("queue_depth must be a non-negative integer".to_string()))?;
+        if pools.iter().any(|(existing, _)| existing == id) {
+            return Err(format!(
+                "WHISPER_MODEL_WORKERS defines {id:?} more than once"
+            ));
+        }
+        pools.push((
+            id.to_string(),
+            ModelWorkers {
+                workers,
+                max_queue_depth,
+            },
+        ));
+    }
+    Ok(pools)
+}
+
+/// Parses a CORS origin (`https://app.example.com` or `*`).
+fn parse_cors_origin(s: &str) -> Result<HeaderValue, String> {
+    let origin = s.trim().trim_end_matches('/');
+    HeaderValue::from_str(origin).map_err(|_| format!("invalid origin {s:?}"))
+}
+
+/// Parses a CORS request header name, or `*`.
+fn parse_header_name(s: &str) -> Result<String, String> {
+    let name = s.trim().to_ascii_lowercase();
+    if name == "*" || HeaderName::from_bytes(name.as_bytes()).is_ok() {
+        Ok(name)
+    } else {
+        Err(format!("invalid header name {s:?}"))
+    }
+}
+
+/// Parses an HTTP method name such as `GET`.
+fn parse_method(s: &str) -> Result<Method, String> {
+    Method::from_bytes(s.trim().to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("invalid HTTP method {s:?}"))
+}
+
+/// Per-worker CPU core sets parsed from `WHISPER_CPU_AFFINITY`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuAffinity(pub Vec<Vec<usize>>);
+
+/// Parses comma-separated per-worker core sets, each a single core (`3`) or range (`0-7`).
+pub fn parse_cpu_affinity(s: &str) -> Result<CpuAffinity, String> {
+    let mut workers = Vec::new();
+    for item in s.split(',') {
+        let item = item.trim();
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (item, item),
+        };
+        let parse_core = |raw: &str| -> Result<usize, String> {
+            let core: usize = raw
+                .parse()
+                .map_err(|_| format!("invalid core set {item:?}: expected N or N-M"))?;
+            if core > MAX_CPU_INDEX {
+                return Err(format!(
+                    "invalid core set {item:?}: core index must be at most {MAX_CPU_INDEX}"
+                ));
+            }
+            Ok(core)
+        };
+        let (first, last) = (parse_core(first)?, parse_core(last)?);
+        if first > last {
+            return Err(format!(
+                "invalid core set {item:?}: range start exceeds end"
+            ));
+        }
+        workers.push((first..=last).collect());
+    }
+    Ok(CpuAffinity(workers))
+}
+
+/// An accepted API key with its label and optional per-key rate limits.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    /// Bearer token clients present.
+    pub key: String,
+    /// Name used in logs and rate limiting; unique across keys.
+    pub label: String,
+    /// Overrides `RATE_LIMIT_REQUESTS_PER_MINUTE` for this key.
+    pub requests_per_minute: Option<u32>,
+    /// Overrides `RATE_LIMIT_CONCURRENT_REQUESTS` for this key.
+    pub concurrent_requests: Option<usize>,
+    /// Worker pool for every request made with this key, overriding the `compute` field.
+    pub compute: Option<Compute>,
+    /// Request fields filled in when a client using this key omits them.
+    pub defaults: KeyDefaults,
+}
+
+/// Per-key request defaults, so clients that cannot send a field still get
+/// the behavior they need.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyDefaults {
+    /// Spoken language used when neither `language` nor `source_language` is sent.
+    pub language: Option<String>,
+    /// Preset applied when the `preset` field is omitted; must name a known preset.
+    pub preset: Option<String>,
+    /// Response format used when the `response_format` field is omitted.
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("key", &"<redacted>")
+            .field("label", &self.label)
+            .field("requests_per_minute", &self.requests_per_minute)
+            .field("concurrent_requests", &self.concurrent_requests)
+            .field("compute", &self.compute)
+            .field("defaults", &self.defaults)
+            .finish()
+    }
+}
+
+/// Parses one `<key> [label=<name>] [requests_per_minute=<n>] [concurrent_requests=<n>]
+/// [compute=cpu|gpu] [language=<code>] [preset=<name>] [response_format=<format>]` entry.
+///
+/// Unlabeled keys get an empty label, filled in by [`load_api_keys`].
+pub fn parse_api_key_entry(entry: &str) -> Result<ApiKey, String> {
+    let mut fields = entry.split_whitespace();
+    let key = fields
+        .next()
+        .filter(|key| !key.contains('='))
+        .ok_or_else(|| format!("invalid API key entry {entry:?}: expected the key first"))?;
+    let mut api_key = ApiKey {
+        key: key.to_string(),
+        label: String::new(),
+        requests_per_minute: None,
+        concurrent_requests: None,
+        compute: None,
+        defaults: KeyDefaults::default(),
+    };
+    for field in fields {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("invalid API key option {field:?}: expected name=value"))?;
+        let positive = || -> Result<u32, String> {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("invalid {name}={value:?}: expected a positive integer"))
+        };
+        match name {
+            "label" if !value.is_empty() => api_key.label = value.to_string(),
+            "requests_per_minute" => api_key.requests_per_minute = Some(positive()?),
+            "concurrent_requests" => api_key.concurrent_requests = Some(positive()? as usize),
+            "compute" => {
+                api_key.compute = Some(
+                    Compute::from_str(value, true)
+                        .map_err(|_| format!("invalid compute={value:?}: expected cpu or gpu"))?,
+                );
+            }
+            "language" if !value.is_empty() => api_key.defaults.language = Some(value.to_string()),
+            "preset" if !value.is_empty() => api_key.defaults.preset = Some(value.to_string()),
+            "response_format" => {
+                api_key.defaults.response_format =
+                    Some(ResponseFormat::parse(value).map_err(|err| err.to_string())?);
+            }
+            _ => return Err(format!("invalid API key option {field:?}")),
+        }
+    }
+    Ok(api_key)
+}
+
+/// Loads the key from `STORAGE_ENCRYPTION_KEY` or `STORAGE_ENCRYPTION_KEY_FILE`;
+/// setting both is an error.
+fn load_storage_cipher(
+    key: Option<&str>,
+    file: Option<&str>,
+) -> Result<Option<StorageCipher>, String> {
+    let key = key.map(str::trim).filter(|key| !key.is_empty());
+    let file = file.map(str::trim).filter(|file| !file.is_empty());
+    match (key, file) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err(
+            "set only one of STORAGE_ENCRYPTION_KEY and STORAGE_ENCRYPTION_KEY_FILE".to_string(),
+        ),
+        (Some(key), None) => StorageCipher::from_key_material(key).map(Some),
+        (None, Some(path)) => {
+            let material = std::fs::read_to_string(path).map_err(|err| {
+                format!("failed to read STORAGE_ENCRYPTION_KEY_FILE {path:?}: {err}")
+            })?;
+            StorageCipher::from_key_material(&material).map(Some)
+        }
+    }
+}
+
+/// Opens the storage location named by the `setting` (a directory or
+/// `sqlite:<file>`), or `None` when it is unset or empty.
+fn open_storage(
+    setting: &str,
+    location: Option<&str>,
+    namespace: &str,
+) -> Result<Option<Arc<dyn Storage>>, String> {
+    let Some(location) = location
+        .map(str::trim)
+        .filter(|location| !location.is_empty())
+    else {
+        return Ok(None);
+    };
+    StorageLocation::parse(location)
+        .map_err(|err| format!("invalid {setting}: {err}"))?
+        .open(namespace)
+        .map(Some)
+        .map_err(|err| format!("failed to open {setting} {location:?}: {err}"))
+}
+
+/// PEM files the server terminates TLS with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub cert: String,
+    pub key: String,
+}
+
+/// Pairs `TLS_CERT_FILE` with `TLS_KEY_FILE`; setting only one is an error.
+fn tls_files(cert: Option<&str>, key: Option<&str>) -> Result<Option<TlsFiles>, String> {
+    let cert = cert.map(str::trim).filter(|path| !path.is_empty());
+    let key = key.map(str::trim).filter(|path| !path.is_empty());
+    match (cert, key) {
+        (None, None) => Ok(None),
+        (Some(cert), Some(key)) => Ok(Some(TlsFiles {
+            cert: cert.to_string(),
+            key: key.to_string(),
+        })),
+        _ => Err("TLS_CERT_FILE and TLS_KEY_FILE must be set together".to_string()),
+    }
+}
+
+/// Combines `API_KEY`, `API_KEYS`, and `API_KEYS_FILE` into one key list.
+///
+/// Unlabeled keys are named `default` (for `API_KEY`) or `key-<n>`; keys and
+/// labels must be unique.
+fn load_api_keys(
+    single: Option<&str>,
+    list: Option<&str>,
+    file: Option<&str>,
+) -> Result<Vec<ApiKey>, String> {
+    let mut keys = Vec::new();
+    if let Some(key) = single.map(str::trim).filter(|key| !key.is_empty()) {
+        keys.push(ApiKey {
+            key: key.to_string(),
+            label: "default".to_string(),
+            requests_per_minute: None,
+            concurrent_requests: None,
+            compute: None,
+            defaults: KeyDefaults::default(),
+        });
+    }
+    for entry in list.unwrap_or_default().split(',') {
+        if !entry.trim().is_empty() {
+            keys.push(parse_api_key_entry(entry)?);
+        }
+    }
+    if let Some(path) = file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read API_KEYS_FILE {path:?}: {err}"))?;
+        for line in contents.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                keys.push(parse_api_key_entry(line)?);
+            }
+        }
+    }
+
+    for (idx, key) in keys.iter_mut().enumerate() {
+        if key.label.is_empty() {
+            key.label = format!("key-{}", idx + 1);
+        }
+    }
+    for (idx, key) in keys.iter().enumerate() {
+        if keys[..idx].iter().any(|other| other.key == key.key) {
+            return Err(format!(
+                "API key labeled {:?} is listed more than once",
+                key.label
+            ));
+        }
+        if keys[..idx].iter().any(|other| other.label == key.label) {
+            return Err(format!(
+                "API key label {:?} is used more than once",
+                key.label
+            ));
+        }
+    }
+    Ok(keys)
+}
+
+/// Rebinds each argument's environment variable to its `WOS_`-prefixed name when that
+/// variable is set, leaving the bare name in effect otherwise.
+fn apply_env_prefix(command: clap::Command, is_set: impl Fn(&str) -> bool) -> clap::Command {
+    let prefixed: Vec<(clap::Id, String)> = command
+        .get_arguments()
+        .filter_map(|arg| {
+            let env = arg.get_env()?.to_string_lossy();
+            let name = format!("{ENV_PREFIX}{env}");
+            is_set(&name).then(|| (arg.get_id().clone(), name))
+        })
+        .collect();
+    prefixed.into_iter().fold(command, |command, (id, name)| {
+        command.mut_arg(id, |arg| arg.env(name))
+    })
+}
+
+/// Parses a per-segment token cap in range `[1, MAX_TOKENS_PER_SEGMENT_LIMIT]`.
+pub fn parse_max_tokens_per_segment(s: &str) -> Result<u32, String> {
+    let expected = format!("expected integer in range [1, {MAX_TOKENS_PER_SEGMENT_LIMIT}]");
+    let value: u32 = s.trim().parse().map_err(|_| expected.clone())?;
+    if !(1..=MAX_TOKENS_PER_SEGMENT_LIMIT).contains(&value) {
+        return Err(expected);
+    }
+    Ok(value)
+}
+
+/// Parses a beam width or candidate count in range `[1, MAX_DECODERS]`.
+pub fn parse_decoders(s: &str) -> Result<u32, String> {
+    let expected = format!("expected integer in range [1, {MAX_DECODERS}]");
+    let value: u32 = s.trim().parse().map_err(|_| expected.clone())?;
+    if !(1..=MAX_DECODERS).contains(&value) {
+        return Err(expected);
+    }
+    Ok(value)
+}
+
+/// Parses the first-timestamp limit in range `[0, 30]` seconds (one whisper window).
+pub fn parse_max_initial_ts(s: &str) -> Result<f32, String> {
+    let expected = "expected seconds in range [0, 30]".to_string();
+    let value: f32 = s.trim().parse().map_err(|_| expected.clone())?;
+    if !(0.0..=30.0).contains(&value) {
+        return Err(expected);
+    }
+    Ok(value)
+}
+
+/// Workers and queue limit of a model served by a pool of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelWorkers {
+    /// Inference workers loaded for the model.
+    pub workers: usize,
+    /// Waiting requests beyond which new requests for the model are rejected;
+    /// falls back to `MAX_QUEUE_DEPTH` when unset.
+    pub max_queue_depth: Option<usize>,
+}
+
 /// Runtime configuration for the HTTP server and inference backend.
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -168,8 +1092,13 @@ pub struct AppConfig {
     pub host: String,
     /// TCP port to bind.
     pub port: u16,
-    /// Optional bearer token required by all endpoints.
-    pub api_key: Option<String>,
+    /// UDP port bound on `host` for the RTP audio of streaming sessions;
+    /// `None` disables `encoding=rtp`.
+    pub rtp_port: Option<u16>,
+    /// Accepted bearer tokens; empty disables authentication.
+    pub api_keys: Vec<ApiKey>,
+    /// Bearer token for `/admin` endpoints; `None` disables them.
+    pub admin_api_key: Option<String>,
     /// Path to a Whisper model file on disk.
     pub whisper_model: String,
     /// Whether `whisper_model` came from explicit `WHISPER_MODEL`.
@@ -184,8 +1113,14 @@ pub struct AppConfig {
     pub whisper_cache_dir: String,
     /// Optional Hugging Face token for authenticated model downloads.
     pub hf_token: Option<String>,
+    /// Auxiliary model files to cache at startup, as `(hf_repo, hf_filename)` pairs.
+    pub aux_models: Vec<(String, String)>,
     /// Additional accepted model identifier exposed by the API.
     pub api_model_alias: String,
+    /// Model ids mapped onto other accepted ids, as `(alias, target)` pairs.
+    pub model_aliases: Vec<(String, String)>,
+    /// Additional models served alongside the default, as `(id, size or path)` pairs.
+    pub extra_models: Vec<(String, String)>,
     /// Selected backend implementation.
     pub backend_kind: BackendKind,
     /// Requested acceleration mode used when initializing whisper contexts.
@@ -194,14 +1129,169 @@ pub struct AppConfig {
     pub acceleration_explicit: bool,
     /// Number of parallel whisper-rs inference workers.
     pub whisper_parallelism: usize,
+    /// Default for the `merge_segments` request field.
+    pub merge_segments: bool,
+    /// Default for the `heuristic_punctuation` request field.
+    pub heuristic_punctuation: bool,
+    /// How transcript and segment text is normalized in responses.
+    pub text_normalization: TextNormalization,
+    /// Window settings for splitting long audio, when `CHUNK_SECS` is set.
+    pub chunking: Option<ChunkParams>,
+    /// Default for the `vad_filter` request field.
+    pub vad_filter: bool,
+    /// Silence length and padding used by the VAD filter.
+    pub vad_params: VadParams,
+    /// Default for the `resample_quality` request field.
+    pub resample_quality: ResampleQuality,
+    /// Default for the `denoise` request field.
+    pub denoise: bool,
+    /// Presets selectable with the `preset` request field, by name.
+    pub presets: BTreeMap<String, Preset>,
+    /// ffmpeg executable for decoding other containers, when `FFMPEG_BIN` is set.
+    pub ffmpeg_bin: Option<String>,
+    /// Whether the startup self-test runs before the server listens.
+    pub startup_self_test: bool,
+    /// Whether `whisper-<size>` model ids are downloaded and loaded on first use.
+    pub whisper_on_demand_models: bool,
+    /// Size of the CPU-only pool serving `compute=cpu` requests, when configured.
+    pub whisper_cpu_workers: Option<usize>,
+    /// Models with their own workers and queue, as `(id, limits)` pairs.
+    pub model_workers: Vec<(String, ModelWorkers)>,
+    /// Optional core set per inference worker; length equals `whisper_parallelism`.
+    pub whisper_cpu_affinity: Option<Vec<Vec<usize>>>,
+    /// Inferences after which a worker's whisper context is rebuilt.
+    pub worker_max_requests: Option<u64>,
+    /// Age after which a worker's whisper context is rebuilt.
+    pub worker_max_age: Option<Duration>,
     /// Requested model size used to resolve default model filename.
     pub whisper_model_size: WhisperModelSize,
+    /// Default per-segment token cap applied when requests omit one.
+    pub whisper_max_tokens_per_segment: Option<u32>,
+    /// Default beam width applied when requests omit one; greedy decoding when unset.
+    pub whisper_beam_size: Option<u32>,
+    /// Default greedy candidate count applied when requests omit one.
+    pub whisper_best_of: Option<u32>,
+    /// DTW alignment heads used for word timestamps.
+    pub whisper_dtw: DtwHeads,
+    /// Latest start time whisper may assign to the first segment.
+    pub whisper_max_initial_ts: f32,
+    /// Maximum time a request may wait for a free inference worker.
+    pub max_queue_wait: Option<Duration>,
+    /// Waiting requests beyond which new audio requests are rejected with `429`.
+    pub max_queue_depth: Option<usize>,
+    /// Queue length that, held for `ready_overload_after`, makes readiness fail.
+    pub ready_overload_depth: Option<usize>,
+    /// How long the queue must stay at `ready_overload_depth` to count as overloaded.
+    pub ready_overload_after: Duration,
+    /// Maximum time to process a synchronous request, including queueing.
+    pub request_timeout: Option<Duration>,
+    /// Maximum number of parts accepted in a multipart body.
+    pub multipart_max_parts: usize,
+    /// Maximum number of non-file form fields accepted in a multipart body.
+    pub multipart_max_fields: usize,
+    /// Maximum size in bytes of a single non-file multipart form field.
+    pub multipart_max_field_bytes: usize,
+    /// Maximum concurrent client connections.
+    pub max_connections: usize,
+    /// Maximum audio uploads whose bodies are read concurrently.
+    pub max_concurrent_uploads: usize,
+    /// Memory budget in MiB for in-flight request audio.
+    pub memory_budget_mb: Option<u32>,
+    /// Maximum total bytes of uploaded audio per request.
+    pub max_upload_bytes: usize,
+    /// Maximum decoded audio duration per file.
+    pub max_audio_duration: Option<Duration>,
+    /// Upload size above which request bodies are spooled to disk.
+    pub upload_spill_threshold_bytes: Option<usize>,
+    /// Directory for spooled uploads; the system temp directory when unset.
+    pub upload_spill_dir: Option<String>,
+    /// Requests per minute allowed per client.
+    pub rate_limit_requests_per_minute: Option<u32>,
+    /// Concurrent requests allowed per client.
+    pub rate_limit_concurrent_requests: Option<usize>,
+    /// Browser origins allowed by CORS; empty disables CORS.
+    pub cors_allowed_origins: Vec<HeaderValue>,
+    /// Request headers allowed by CORS (`*` for any).
+    pub cors_allowed_headers: Vec<String>,
+    /// Methods allowed by CORS.
+    pub cors_allowed_methods: Vec<Method>,
+    /// Whether the live caption page is served at `/demo`.
+    pub demo_page: bool,
+    /// Whether per-worker gauges are served at `/metrics`.
+    pub prometheus_metrics: bool,
+    /// Maximum time a client may take to send request headers.
+    pub http_header_timeout: Duration,
+    /// Maximum stall between request body chunks.
+    pub http_read_timeout: Duration,
+    /// Maximum stall between response body chunks.
+    pub http_write_timeout: Duration,
+    /// Maximum time a connection may go without socket activity.
+    pub http_idle_timeout: Duration,
+    /// Whether HTTP/1.1 connections are kept open between requests.
+    pub http_keepalive: bool,
+    /// Whether HTTP/2 connections are accepted.
+    pub http2: bool,
+    /// Maximum concurrent streams per HTTP/2 connection.
+    pub http2_max_concurrent_streams: u32,
+    /// Interval between HTTP/2 keep-alive pings; `None` disables them.
+    pub http2_keepalive_interval: Option<Duration>,
+    /// How long an HTTP/2 keep-alive ping may go unacknowledged.
+    pub http2_keepalive_timeout: Duration,
+    /// Certificate chain and private key files, when the server terminates TLS.
+    pub tls: Option<TlsFiles>,
+    /// How long finished async jobs remain retrievable.
+    pub async_job_retention: Duration,
+    /// Cap on the bytes of finished job results held in memory.
+    pub async_job_max_total_bytes: Option<usize>,
+    /// Cap on the bytes of finished job results held per API key.
+    pub async_job_max_bytes_per_key: Option<usize>,
+    /// Storage persisting queued async jobs across restarts (`ASYNC_JOB_DIR`).
+    pub async_job_storage: Option<Arc<dyn Storage>>,
+    /// Storage receiving a copy of every completed transcript (`TRANSCRIPT_ARCHIVE_DIR`).
+    pub transcript_archive_storage: Option<Arc<dyn Storage>>,
+    /// Storage for per-request debug traces (`DEBUG_TRACE_DIR`).
+    pub debug_trace_storage: Option<Arc<dyn Storage>>,
+    /// Age past which debug traces are deleted; `None` keeps them.
+    pub debug_trace_retention: Option<Duration>,
+    /// Time between janitor sweeps.
+    pub janitor_interval: Duration,
+    /// File receiving an audit entry for every purge.
+    pub purge_audit_log: Option<String>,
+    /// Key sealing files written to disk, when storage encryption is configured.
+    pub storage_cipher: Option<StorageCipher>,
+    /// Optional StatsD/DogStatsD address that receives pushed metrics.
+    pub statsd_addr: Option<String>,
+    /// Prefix prepended to exported StatsD metric names.
+    pub statsd_prefix: String,
+    /// Whether DogStatsD `|#tag:value` tags are appended to StatsD lines.
+    pub statsd_tags: bool,
+    /// Path to the piper executable used by `/v1/audio/speech`.
+    #[cfg(feature = "tts")]
+    pub tts_piper_bin: String,
+    /// Configured piper voice name or explicit `.onnx` path.
+    #[cfg(feature = "tts")]
+    pub tts_voice: String,
+    /// Resolved path to the piper voice `.onnx` file on disk.
+    #[cfg(feature = "tts")]
+    pub tts_voice_model: String,
+    /// Local cache directory for downloaded piper voices.
+    #[cfg(feature = "tts")]
+    pub tts_voices_dir: String,
+    /// Hugging Face repository used for piper voice download.
+    #[cfg(feature = "tts")]
+    pub tts_hf_repo: String,
+    /// Maintenance command to run instead of serving.
+    pub command: Option<Command>,
 }
 
 impl AppConfig {
     /// Builds configuration from CLI arguments (which also read environment variables).
+    ///
+    /// Precedence is: command-line flag, `WOS_`-prefixed variable, bare variable, default.
     pub fn from_args() -> Result<Self, AppError> {
-        let args = CliArgs::parse();
+        let command = apply_env_prefix(CliArgs::command(), |name| std::env::var_os(name).is_some());
+        let matches = command.get_matches();
+        let args = CliArgs::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
         Self::from_cli_args(args)
     }
 
@@ -218,11 +1308,78 @@ impl AppConfig {
         let model = args
             .model
             .unwrap_or_else(|| format!("{}/ {}", cache_dir, hf_filename));
+        if let Some(CpuAffinity(sets)) = &args.cpu_affinity {
+            if sets.len() != args.parallelism {
+                return Err(AppError::internal(format!(
+                    "WHISPER_CPU_AFFINITY lists {} core sets but WHISPER_PARALLELISM is {}; provide one set per worker",
+                    sets.len(),
+                    args.parallelism
+                )));
+            }
+        }
+
+        let api_keys = load_api_keys(
+            args.api_key.as_deref(),
+            args.api_keys.as_deref(),
+            args.api_keys_file.as_deref(),
+        )
+        .map_err(AppError::internal)?;
+        let storage_cipher = load_storage_cipher(
+            args.storage_encryption_key.as_deref(),
+            args.storage_encryption_key_file.as_deref(),
+        )
+        .map_err(AppError::internal)?;
+        if args.denoise && !denoise::AVAILABLE {
+            return Err(AppError::internal(
+                "DENOISE=true requires a server built with the `denoise` feature",
+            ));
+        }
+        let presets = load_presets(args.presets_file.as_deref()).map_err(AppError::internal)?;
+        for key in &api_keys {
+            if let Some(preset) = key
+                .defaults
+                .preset
+                .as_ref()
+                .filter(|preset| !presets.contains_key(*preset))
+            {
+                return Err(AppError::internal(format!(
+                    "API key labeled {:?} defaults to unknown preset {preset:?}",
+                    key.label
+                )));
+            }
+        }
+        let extra_models = parse_extra_models(&args.extra_models).map_err(AppError::internal)?;
+        let model_workers = parse_model_workers(&args.model_workers).map_err(AppError::internal)?;
+        for (id, _) in &model_workers {
+            let extra = extra_models.iter().any(|(extra, _)| extra == id);
+            let on_demand = args.on_demand_models && parse_on_demand_model_id(id).is_some();
+            if !extra && !on_demand {
+                return Err(AppError::internal(format!(
+                    "WHISPER_MODEL_WORKERS names {id:?}, which is not a WHISPER_EXTRA_MODELS id or an on-demand whisper-<size> model; the default model uses WHISPER_PARALLELISM"
+                )));
+            }
+        }
+        let async_job_storage =
+            open_storage("ASYNC_JOB_DIR", args.async_job_dir.as_deref(), "jobs")
+                .map_err(AppError::internal)?;
+        let transcript_archive_storage = open_storage(
+            "TRANSCRIPT_ARCHIVE_DIR",
+            args.transcript_archive_dir.as_deref(),
+            "archive",
+        )
+        .map_err(AppError::internal)?;
+        let tls = tls_files(args.tls_cert_file.as_deref(), args.tls_key_file.as_deref())
+            .map_err(AppError::internal)?;
+        let debug_trace_storage =
+            open_storage("DEBUG_TRACE_DIR", args.debug_trace_dir.as_deref(), "traces")
+                .map_err(AppError::internal)?;
 
         Ok(Self {
             host: args.host,
             port: args.port,
-            api_key: args.api_key,
+            rtp_port: args.rtp_port,
+            api_keys,
+            admin_api_key: args.admin_api_key.filter(|key| !key.trim().is_empty()),
             whisper_model: model,
             whisper_model_explicit: model_explicit,
             whisper_auto_download: args.auto_download,
@@ -230,26 +1387,157 @@ impl AppConfig {
             whisper_hf_filename: hf_filename,
             whisper_cache_dir: cache_dir,
             hf_token: args.hf_token,
+            aux_models: parse_aux_models(&args.aux_models).map_err(AppError::internal)?,
+            model_aliases: parse_model_aliases(&args.model_aliases).map_err(AppError::internal)?,
+            extra_models,
             api_model_alias: args.model_alias,
             backend_kind: args.backend,
             acceleration_kind: args.acceleration,
             acceleration_explicit: true,
             whisper_parallelism: args.parallelism,
+            whisper_on_demand_models: args.on_demand_models,
+            whisper_cpu_workers: args.cpu_workers,
+            model_workers,
+            whisper_cpu_affinity: args.cpu_affinity.map(|CpuAffinity(sets)| sets),
+            worker_max_requests: args.worker_max_requests,
+            worker_max_age: args.worker_max_age_secs.map(Duration::from_secs),
             whisper_model_size: model_size,
+            whisper_max_tokens_per_segment: args.max_tokens_per_segment,
+            whisper_beam_size: args.beam_size,
+            whisper_best_of: args.best_of,
+            whisper_dtw: args.dtw,
+            whisper_max_initial_ts: args.max_initial_ts,
+            merge_segments: args.merge_segments,
+            heuristic_punctuation: args.heuristic_punctuation,
+            text_normalization: TextNormalization {
+                keep_newlines: args.text_keep_newlines,
+                keep_spaces: args.text_keep_spaces,
+                nfc: args.text_unicode_nfc,
+                strip_leading_punctuation: args.text_strip_leading_punctuation,
+            },
+            chunking: args.chunk_secs.map(|window_secs| ChunkParams {
+                window_secs,
+                overlap_secs: args.chunk_overlap_secs,
+                parallel: args.chunk_parallel,
+            }),
+            vad_filter: args.vad_filter,
+            resample_quality: args.resample_quality,
+            denoise: args.denoise,
+            presets,
+            vad_params: VadParams {
+                min_silence_ms: args.vad_min_silence_ms,
+                padding_ms: args.vad_padding_ms,
+            },
+            ffmpeg_bin: args.ffmpeg_bin.filter(|bin| !bin.trim().is_empty()),
+            startup_self_test: args.startup_self_test,
+            max_queue_wait: args.max_queue_wait_secs.map(Duration::from_secs),
+            max_queue_depth: args.max_queue_depth,
+            ready_overload_depth: args.ready_overload_queue_depth,
+            ready_overload_after: Duration::from_secs(args.ready_overload_secs),
+            request_timeout: args.request_timeout_secs.map(Duration::from_secs),
+            multipart_max_parts: args.multipart_max_parts as usize,
+            multipart_max_fields: args.multipart_max_fields as usize,
+            multipart_max_field_bytes: args.multipart_max_field_bytes as usize,
+            max_connections: args.max_connections as usize,
+            max_concurrent_uploads: args.max_concurrent_uploads as usize,
+            memory_budget_mb: args.memory_budget_mb,
+            max_upload_bytes: usize::try_from(args.max_upload_bytes).unwrap_or(usize::MAX),
+            max_audio_duration: args
+                .max_audio_seconds
+                .map(|secs| Duration::from_secs(secs.into())),
+            upload_spill_threshold_bytes: args
+                .upload_spill_threshold_bytes
+                .map(|bytes| bytes as usize),
+            upload_spill_dir: args.upload_spill_dir.filter(|dir| !dir.is_empty()),
+            rate_limit_requests_per_minute: args.rate_limit_requests_per_minute,
+            rate_limit_concurrent_requests: args
+                .rate_limit_concurrent_requests
+                .map(|limit| limit as usize),
+            cors_allowed_origins: args.cors_allowed_origins,
+            cors_allowed_headers: args.cors_allowed_headers,
+            cors_allowed_methods: args.cors_allowed_methods,
+            demo_page: args.demo_page,
+            prometheus_metrics: args.prometheus_metrics,
+            http_header_timeout: Duration::from_secs(args.http_header_timeout_secs),
+            http_read_timeout: Duration::from_secs(args.http_read_timeout_secs),
+            http_write_timeout: Duration::from_secs(args.http_write_timeout_secs),
+            http_idle_timeout: Duration::from_secs(args.http_idle_timeout_secs),
+            http_keepalive: args.http_keepalive,
+            http2: args.http2,
+            http2_max_concurrent_streams: args.http2_max_concurrent_streams,
+            http2_keepalive_interval: args.http2_keepalive_interval_secs.map(Duration::from_secs),
+            http2_keepalive_timeout: Duration::from_secs(args.http2_keepalive_timeout_secs),
+            tls,
+            async_job_retention: Duration::from_secs(args.async_job_retention_secs),
+            async_job_max_total_bytes: args.async_job_max_total_bytes.map(|bytes| bytes as usize),
+            async_job_max_bytes_per_key: args
+                .async_job_max_bytes_per_key
+                .map(|bytes| bytes as usize),
+            async_job_storage,
+            transcript_archive_storage,
+            debug_trace_storage,
+            debug_trace_retention: args.debug_trace_retention_secs.map(Duration::from_secs),
+            janitor_interval: Duration::from_secs(args.janitor_interval_secs),
+            purge_audit_log: args.purge_audit_log.filter(|path| !path.is_empty()),
+            storage_cipher,
+            statsd_addr: args.statsd_addr,
+            statsd_prefix: args.statsd_prefix.trim_end_matches('.').to_string(),
+            statsd_tags: args.statsd_tags,
+            #[cfg(feature = "tts")]
+            tts_piper_bin: args.piper_bin,
+            #[cfg(feature = "tts")]
+            tts_voice_model: args.tts_voice.clone(),
+            #[cfg(feature = "tts")]
+            tts_voice: args.tts_voice,
+            #[cfg(feature = "tts")]
+            tts_voices_dir: args.tts_voices_dir.unwrap_or_else(default_piper_voices_dir),
+            #[cfg(feature = "tts")]
+            tts_hf_repo: args.tts_hf_repo,
+            command: args.command,
         })
     }
 
     /// Returns all accepted model identifiers for request validation.
     ///
     /// This always includes `whisper-1` for OpenAI compatibility and may include
-    /// `api_model_alias` when it is different.
+    /// `api_model_alias` when it is different, followed by the `extra_models` ids.
     pub fn accepted_model_ids(&self) -> Vec<String> {
         let mut ids = vec!["whisper-1".to_string()];
         if self.api_model_alias != "whisper-1" {
             ids.push(self.api_model_alias.clone());
         }
+        for (id, _) in &self.extra_models {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
         ids
     }
+
+    /// Returns the dedicated pool limits of model `id`, if it has its own workers.
+    pub fn model_workers(&self, id: &str) -> Option<ModelWorkers> {
+        self.model_workers
+            .iter()
+            .find(|(pool, _)| pool == id)
+            .map(|(_, limits)| *limits)
+    }
+
+    /// Points the model settings at the cached download of `size`.
+    pub fn use_model_size(&mut self, size: WhisperModelSize) {
+        let filename = whisper_model_filename(size).to_string();
+        self.whisper_model = format!("{}/{}", self.whisper_cache_dir, filename);
+        self.whisper_model_explicit = false;
+        self.whisper_hf_filename = filename;
+        self.whisper_model_size = size;
+    }
+
+    /// Maps a requested model id through `model_aliases`.
+    pub fn resolve_model_alias<'a>(&'a self, requested_model: &'a str) -> &'a str {
+        self.model_aliases
+            .iter()
+            .find(|(alias, _)| alias == requested_model)
+            .map_or(requested_model, |(_, target)| target.as_str())
+    }
 }
 
 fn default_whisper_cache_dir() -> String {
@@ -259,7 +1547,16 @@ fn default_whisper_cache_dir() -> String {
     )
 }
 
-fn whisper_model_filename(size: WhisperModelSize) -> &'static str {
+#[cfg(feature = "tts")]
+fn default_piper_voices_dir() -> String {
+    format!(
+        "{}/.cache/piper/voices",
+        std::env::var("HOME").unwrap_or_else(|_| "/Users/user".to_string())
+    )
+}
+
+/// Returns the `ggerganov/whisper.cpp` file name for a model size.
+pub fn whisper_model_filename(size: WhisperModelSize) -> &'static str {
     match size {
         WhisperModelSize::Tiny => "ggml-tiny.bin",
         WhisperModelSize::TinyEn => "ggml-tiny.en.bin",
@@ -278,8 +1575,16 @@ fn whisper_model_filename(size: WhisperModelSize) -> &'static str {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_parallelism, whisper_model_filename, CliArgs, WhisperModelSize};
+    use super::{
+        apply_env_prefix, load_api_keys, parse_api_key_entry, parse_aux_models, parse_cpu_affinity,
+        parse_decoders, parse_dtw_heads, parse_extra_models, parse_max_initial_ts,
+        parse_max_tokens_per_segment, parse_model_aliases, parse_model_workers, parse_parallelism,
+        whisper_model_filename, AppConfig, CliArgs, Command, CpuAffinity, DtwHeads, KeyDefaults,
+        ModelWorkers, ResponseFormat, TlsFiles, WhisperModelSize,
+    };
     use clap::Parser;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::path::PathBuf;
 
     #[test]
     fn parse_parallelism_accepts_in_range_values() {
@@ -298,18 +1603,298 @@ mod tests {
         assert!(parse_parallelism("9").is_err());
     }
 
+    #[test]
+    fn api_key_entries_parse_labels_and_limits() {
+        let key =
+            parse_api_key_entry("sk-a label=alice requests_per_minute=30 concurrent_requests=2")
+                .expect("entry");
+        assert_eq!(key.key, "sk-a");
+        assert_eq!(key.label, "alice");
+        assert_eq!(key.requests_per_minute, Some(30));
+        assert_eq!(key.concurrent_requests, Some(2));
+        assert_eq!(key.compute, None);
+        let key = parse_api_key_entry("sk-b compute=cpu").expect("entry");
+        assert_eq!(key.compute, Some(super::Compute::Cpu));
+        assert!(parse_api_key_entry("sk-b compute=tpu").is_err());
+        assert!(!format!("{key:?}").contains("sk-a"));
+
+        assert!(parse_api_key_entry("label=alice").is_err());
+        assert!(parse_api_key_entry("sk-a requests_per_minute=0").is_err());
+        assert!(parse_api_key_entry("sk-a colour=blue").is_err());
+    }
+
+    #[test]
+    fn api_key_entries_parse_request_defaults() {
+        let key = parse_api_key_entry("sk-pbx language=de preset=voicemail response_format=text")
+            .expect("entry");
+        assert_eq!(
+            key.defaults,
+            KeyDefaults {
+                language: Some("de".to_string()),
+                preset: Some("voicemail".to_string()),
+                response_format: Some(ResponseFormat::Text),
+            }
+        );
+        assert!(parse_api_key_entry("sk-pbx response_format=docx").is_err());
+
+        let cfg = |keys: &str| {
+            AppConfig::from_cli_args(CliArgs::parse_from([
+                "whisper-openai-server",
+                &format!("--api-keys={keys}"),
+            ]))
+        };
+        assert!(cfg("sk-pbx preset=voicemail").is_ok());
+        let err = cfg("sk-pbx label=pbx preset=podcast").expect_err("unknown preset");
+        assert!(
+            err.to_string().contains("unknown preset \"podcast\""),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn api_keys_combine_sources_and_reject_duplicates() {
+        let keys =
+            load_api_keys(Some("sk-main"), Some("sk-a label=alice, sk-b"), None).expect("keys");
+        let labels = keys
+            .iter()
+            .map(|key| key.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["default", "alice", "key-3"]);
+
+        assert!(load_api_keys(Some("sk-a"), Some("sk-a label=again"), None).is_err());
+        assert!(load_api_keys(None, Some("sk-a label=x, sk-b label=x"), None).is_err());
+        assert!(load_api_keys(None, None, None).expect("keys").is_empty());
+    }
+
+    #[test]
+    fn parse_aux_models_requires_repo_and_filename() {
+        assert_eq!(
+            parse_aux_models(" ggml-org/whisper-vad:ggml-silero-v5.1.2.bin ,").expect("aux"),
+            vec![(
+                "ggml-org/whisper-vad".to_string(),
+                "ggml-silero-v5.1.2.bin".to_string()
+            )]
+        );
+        assert_eq!(
+            parse_aux_models("Systran/faster-whisper-small:/").expect("directory"),
+            vec![("Systran/faster-whisper-small".to_string(), "/".to_string())]
+        );
+        assert!(parse_aux_models("").expect("empty").is_empty());
+        assert!(parse_aux_models("whisper-vad:model.bin").is_err());
+        assert!(parse_aux_models("org/repo:").is_err());
+        assert!(parse_aux_models("org/repo:../escape.bin").is_err());
+    }
+
+    #[test]
+    fn parse_extra_models_rejects_duplicates_and_whisper_1() {
+        assert_eq!(
+            parse_extra_models(" draft = tiny.en , final=/models/ggml-large-v3.bin")
+                .expect("extra"),
+            vec![
+                ("draft".to_string(), "tiny.en".to_string()),
+                ("final".to_string(), "/models/ggml-large-v3.bin".to_string()),
+            ]
+        );
+        assert!(parse_extra_models("").expect("empty").is_empty());
+        assert!(parse_extra_models("draft").is_err());
+        assert!(parse_extra_models("draft=tiny,draft=base").is_err());
+        assert!(parse_extra_models("whisper-1=tiny").is_err());
+    }
+
+    #[test]
+    fn model_workers_must_name_extra_or_on_demand_models() {
+        assert_eq!(
+            parse_model_workers(" draft = 4 , final=1:2").expect("pools"),
+            vec![
+                (
+                    "draft".to_string(),
+                    ModelWorkers {
+                        workers: 4,
+                        max_queue_depth: None
+                    }
+                ),
+                (
+                    "final".to_string(),
+                    ModelWorkers {
+                        workers: 1,
+                        max_queue_depth: Some(2)
+                    }
+                ),
+            ]
+        );
+        for invalid in [
+            "draft",
+            "draft=0",
+            "draft=9",
+            "draft=1:x",
+            "draft=1,draft=2",
+        ] {
+            assert!(parse_model_workers(invalid).is_err(), "{invalid}");
+        }
+
+        let cfg = |args: &[&str]| {
+            AppConfig::from_cli_args(CliArgs::parse_from(
+                ["whisper-openai-server"].iter().chain(args),
+            ))
+        };
+        let cfg_ok = cfg(&[
+            "--extra-models=final=large-v3",
+            "--on-demand-models",
+            "--model-workers=final=1,whisper-tiny=4",
+        ])
+        .expect("config");
+        assert_eq!(
+            cfg_ok.model_workers("final").map(|pool| pool.workers),
+            Some(1)
+        );
+        assert!(cfg(&["--model-workers=final=1"]).is_err());
+        assert!(cfg(&["--model-workers=whisper-tiny=4"]).is_err());
+        assert!(cfg(&["--model-workers=whisper-1=2"]).is_err());
+    }
+
+    #[test]
+    fn parse_model_aliases_rejects_duplicates_and_chains() {
+        let aliases =
+            parse_model_aliases(" gpt-4o-transcribe = whisper-large-v3 ,, ").expect("aliases");
+        assert_eq!(
+            aliases,
+            [(
+                "gpt-4o-transcribe".to_string(),
+                "whisper-large-v3".to_string()
+            )]
+        );
+        assert!(parse_model_aliases("").expect("empty").is_empty());
+        assert!(parse_model_aliases("gpt-4o-transcribe").is_err());
+        assert!(parse_model_aliases("a=whisper-1,a=whisper-1").is_err());
+        assert!(parse_model_aliases("a=b,b=whisper-1").is_err());
+    }
+
+    #[test]
+    fn parse_max_tokens_per_segment_enforces_bounds() {
+        assert_eq!(parse_max_tokens_per_segment("64").unwrap(), 64);
+        assert!(parse_max_tokens_per_segment("0").is_err());
+        assert!(parse_max_tokens_per_segment("225").is_err());
+        assert!(parse_max_tokens_per_segment("many").is_err());
+    }
+
+    #[test]
+    fn parse_decoders_enforces_bounds() {
+        assert_eq!(parse_decoders("5").unwrap(), 5);
+        assert!(parse_decoders("0").is_err());
+        assert!(parse_decoders("9").is_err());
+        assert!(parse_decoders("wide").is_err());
+        assert_eq!(parse_max_initial_ts("1.5").unwrap(), 1.5);
+        assert!(parse_max_initial_ts("-1").is_err());
+        assert!(parse_max_initial_ts("31").is_err());
+    }
+
     #[test]
     fn cli_parsing_supports_model_size() {
         let args = CliArgs::parse_from(["whisper-openai-server", "--model-size=medium"]);
         assert_eq!(args.model_size, WhisperModelSize::Medium);
     }
 
+    #[test]
+    fn worker_recycling_limits_must_be_positive() {
+        let cfg = AppConfig::from_cli_args(CliArgs::parse_from([
+            "whisper-openai-server",
+            "--worker-max-requests=500",
+            "--worker-max-age-secs=3600",
+        ]))
+        .expect("config");
+        assert_eq!(cfg.worker_max_requests, Some(500));
+        assert_eq!(
+            cfg.worker_max_age,
+            Some(std::time::Duration::from_secs(3600))
+        );
+        for invalid in ["--worker-max-requests=0", "--worker-max-age-secs=0"] {
+            assert!(
+                CliArgs::try_parse_from(["whisper-openai-server", invalid]).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn http2_and_tls_settings_are_validated() {
+        let cfg = AppConfig::from_cli_args(CliArgs::parse_from([
+            "whisper-openai-server",
+            "--http2-max-concurrent-streams=512",
+            "--http2-keepalive-interval-secs=30",
+            "--tls-cert-file=/etc/whisper/cert.pem",
+            "--tls-key-file=/etc/whisper/key.pem",
+        ]))
+        .expect("config");
+        assert!(cfg.http2);
+        assert_eq!(cfg.http2_max_concurrent_streams, 512);
+        assert_eq!(
+            cfg.http2_keepalive_interval,
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            cfg.tls,
+            Some(TlsFiles {
+                cert: "/etc/whisper/cert.pem".to_string(),
+                key: "/etc/whisper/key.pem".to_string(),
+            })
+        );
+        assert!(AppConfig::from_cli_args(CliArgs::parse_from([
+            "whisper-openai-server",
+            "--tls-cert-file=/etc/whisper/cert.pem",
+        ]))
+        .is_err());
+        for invalid in [
+            "--http2-max-concurrent-streams=0",
+            "--http2-keepalive-interval-secs=0",
+        ] {
+            assert!(
+                CliArgs::try_parse_from(["whisper-openai-server", invalid]).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
     #[test]
     fn cli_parsing_supports_acceleration() {
         let args = CliArgs::parse_from(["whisper-openai-server", "--acceleration=none"]);
         assert_eq!(args.acceleration, super::AccelerationKind::None);
     }
 
+    #[test]
+    fn cli_parsing_supports_rocm_acceleration() {
+        let args = CliArgs::parse_from(["whisper-openai-server", "--acceleration=rocm"]);
+        assert_eq!(args.acceleration, super::AccelerationKind::Rocm);
+    }
+
+    #[test]
+    fn dtw_heads_follow_the_model_file_name() {
+        assert_eq!(parse_dtw_heads("off"), Ok(DtwHeads::Off));
+        assert_eq!(
+            parse_dtw_heads("turbo"),
+            Ok(DtwHeads::Size(WhisperModelSize::Turbo))
+        );
+        assert!(parse_dtw_heads("huge").is_err());
+
+        assert_eq!(
+            DtwHeads::Auto.resolve("/models/ggml-small.en.bin"),
+            Some(WhisperModelSize::SmallEn)
+        );
+        assert_eq!(
+            DtwHeads::Auto.resolve("ggml-large-v3-turbo-q5_0.bin"),
+            Some(WhisperModelSize::Turbo)
+        );
+        assert_eq!(DtwHeads::Auto.resolve("/models/my-finetune.bin"), None);
+        assert_eq!(
+            DtwHeads::Size(WhisperModelSize::Medium).resolve("/models/my-finetune.bin"),
+            Some(WhisperModelSize::Medium)
+        );
+        assert_eq!(
+            DtwHeads::Size(WhisperModelSize::Medium).resolve("ggml-base.bin"),
+            Some(WhisperModelSize::Base)
+        );
+        assert_eq!(DtwHeads::Off.resolve("ggml-base.bin"), None);
+    }
+
     #[test]
     fn whisper_model_filename_uses_expected_small_name() {
         assert_eq!(
@@ -325,4 +1910,91 @@ mod tests {
             "ggml-small.en.bin"
         );
     }
+
+    #[test]
+    fn prefixed_env_names_replace_bare_names_only_when_set() {
+        let command = apply_env_prefix(CliArgs::command(), |name| name == "WOS_PORT");
+        let env_of = |id: &str| {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_env())
+                .map(|env| env.to_string_lossy().into_owned())
+        };
+        assert_eq!(env_of("port").as_deref(), Some("WOS_PORT"));
+        assert_eq!(env_of("host").as_deref(), Some("HOST"));
+    }
+
+    #[test]
+    fn cli_flags_override_prefixed_env() {
+        std::env::set_var("WOS_STATSD_PREFIX", "from_env");
+        let command = apply_env_prefix(CliArgs::command(), |name| name == "WOS_STATSD_PREFIX");
+
+        let matches = command
+            .clone()
+            .try_get_matches_from(["whisper-openai-server"])
+            .expect("matches");
+        let args = CliArgs::from_arg_matches(&matches).expect("args");
+        assert_eq!(args.statsd_prefix, "from_env");
+
+        let matches = command
+            .try_get_matches_from(["whisper-openai-server", "--statsd-prefix=from_cli"])
+            .expect("matches");
+        let args = CliArgs::from_arg_matches(&matches).expect("args");
+        assert_eq!(args.statsd_prefix, "from_cli");
+        std::env::remove_var("WOS_STATSD_PREFIX");
+    }
+
+    #[test]
+    fn parse_cpu_affinity_accepts_ranges_and_single_cores() {
+        assert_eq!(
+            parse_cpu_affinity("0-3, 8-9,12").unwrap(),
+            CpuAffinity(vec![vec![0, 1, 2, 3], vec![8, 9], vec![12]])
+        );
+        assert!(parse_cpu_affinity("3-1").is_err());
+        assert!(parse_cpu_affinity("0-7,").is_err());
+        assert!(parse_cpu_affinity("0-4096").is_err());
+    }
+
+    #[test]
+    fn cpu_affinity_must_match_parallelism() {
+        let args = CliArgs::parse_from([
+            "whisper-openai-server",
+            "--parallelism=2",
+            "--cpu-affinity=0-3",
+        ]);
+        assert!(AppConfig::from_cli_args(args).is_err());
+
+        let args = CliArgs::parse_from([
+            "whisper-openai-server",
+            "--parallelism=2",
+            "--cpu-affinity=0-3,4-7",
+        ]);
+        let cfg = AppConfig::from_cli_args(args).expect("config");
+        assert_eq!(cfg.whisper_cpu_affinity.map(|sets| sets.len()), Some(2));
+    }
+
+    #[test]
+    fn bundle_subcommands_follow_server_flags() {
+        let args = CliArgs::parse_from([
+            "whisper-openai-server",
+            "--model-size=tiny",
+            "export-model-bundle",
+            "--output=tiny.tar",
+        ]);
+        assert_eq!(args.model_size, WhisperModelSize::Tiny);
+        assert_eq!(
+            args.command,
+            Some(Command::ExportModelBundle {
+                output: PathBuf::from("tiny.tar"),
+                revision: "main".to_string(),
+            })
+        );
+
+        let args = CliArgs::parse_from(["whisper-openai-server"]);
+        assert_eq!(
+            AppConfig::from_cli_args(args).expect("config").command,
+            None
+        );
+    }
 }
@@ -0,0 +1,299 @@
+//! Per-request debug traces, enabled by setting `DEBUG_TRACE_DIR`.
+//!
+//! A synchronous transcription or translation sent with `debug_trace=true`
+//! records how it was processed: the decode options it ran with, the time
+//! spent in each stage, the decisions the pipeline made along the way (VAD
+//! trimming, chunking, fallback decodes), and statistics over the decoded
+//! segments. Once the request finishes, successfully or not, the trace is
+//! written to storage as `<trace id>.json` and served by
+//! `GET /admin/traces/{trace_id}`. Trace ids are generated by the server and
+//! returned in the `x-debug-trace-id` header; the client's `x-request-id` is
+//! only recorded in the trace, so a reused request id cannot replace another
+//! request's trace, and an existing trace is never overwritten.
+//!
+//! Traces hold the request's prompt but no transcript text; they are sealed
+//! when storage encryption is configured, removed by `POST /admin/purge` like
+//! archived transcripts, and expired by the janitor after
+//! `DEBUG_TRACE_RETENTION_SECS`.
+//!
+//! Pipeline code reports into the trace of the task it runs on through
+//! [`stage`], [`event`], and [`result`], which do nothing for untraced requests.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::backend::TranscriptResult;
+use crate::encryption::{is_sealed, StorageCipher};
+use crate::purge::PurgeFilter;
+use crate::storage::Storage;
+
+tokio::task_local! {
+    static ACTIVE: RefCell<Trace>;
+}
+
+/// What a traced request reported while it ran.
+#[derive(Debug, Default)]
+pub struct Trace {
+    stages: Vec<Value>,
+    events: Vec<Value>,
+    result: Option<Value>,
+}
+
+impl Trace {
+    /// Builds the stored trace document around the recorded stages and events.
+    ///
+    /// `outcome` is the request's error message, if it failed.
+    pub fn into_json(
+        self,
+        request: Value,
+        parameters: Value,
+        total: Duration,
+        outcome: Result<(), String>,
+    ) -> Value {
+        let mut trace = json!({
+            "object": "debug_trace",
+            "request": request,
+            "parameters": parameters,
+            "stages": self.stages,
+            "events": self.events,
+            "total_ms": duration_ms(total),
+        });
+        if let Some(result) = self.result {
+            trace["result"] = result;
+        }
+        if let Err(message) = outcome {
+            trace["error"] = json!(message);
+        }
+        trace
+    }
+}
+
+/// Runs `work` with a fresh trace collecting what it reports.
+pub async fn capture<T>(work: impl Future<Output = T>) -> (T, Trace) {
+    ACTIVE
+        .scope(RefCell::new(Trace::default()), async {
+            let output = work.await;
+            (output, ACTIVE.with(|trace| trace.take()))
+        })
+        .await
+}
+
+/// Records that a pipeline stage took `elapsed`; stages may repeat, such as
+/// inference once per chunking window.
+pub fn stage(name: &'static str, elapsed: Duration) {
+    record(|trace| {
+        trace
+            .stages
+            .push(json!({"stage": name, "ms": duration_ms(elapsed)}))
+    });
+}
+
+/// Records a decision the pipeline made, with the details behind it.
+pub fn event(name: &'static str, details: Value) {
+    record(|trace| {
+        let mut event = json!({"event": name});
+        if let (Some(event), Value::Object(details)) = (event.as_object_mut(), details) {
+            event.extend(details);
+        }
+        trace.events.push(event);
+    });
+}
+
+/// Records statistics over the final transcript.
+pub fn result(result: &TranscriptResult) {
+    record(|trace| trace.result = Some(summarize(result)));
+}
+
+fn record(update: impl FnOnce(&mut Trace)) {
+    let _ = ACTIVE.try_with(|trace| update(&mut trace.borrow_mut()));
+}
+
+/// Segment statistics, detected language, and warnings of a transcript.
+fn summarize(result: &TranscriptResult) -> Value {
+    let segments = &result.segments;
+    // Token-weighted, like the transcript-level average in responses.
+    let (weighted_sum, weight) = segments
+        .iter()
+        .filter_map(|segment| Some((segment.avg_logprob?, segment.token_count as f64)))
+        .fold((0.0, 0.0), |(sum, weight), (logprob, tokens)| {
+            (sum + logprob * tokens, weight + tokens)
+        });
+    let mean_logprob = (weight > 0.0).then(|| weighted_sum / weight);
+    let min_logprob = segments
+        .iter()
+        .filter_map(|segment| segment.avg_logprob)
+        .reduce(f64::min);
+    let max_no_speech_prob = segments
+        .iter()
+        .filter_map(|segment| segment.no_speech_prob)
+        .reduce(f32::max);
+    let longest_secs = segments
+        .iter()
+        .map(|segment| segment.end_secs - segment.start_secs)
+        .reduce(f64::max);
+    json!({
+        "language": result.language,
+        "language_probabilities": result
+            .language_probs
+            .iter()
+            .map(|(language, probability)| json!({"language": language, "probability": probability}))
+            .collect::<Vec<_>>(),
+        "no_speech_reason": result.no_speech.map(|reason| reason.as_str()),
+        "text_chars": result.text.chars().count(),
+        "segments": {
+            "count": segments.len(),
+            "tokens": segments.iter().map(|segment| segment.token_count).sum::<usize>(),
+            "mean_avg_logprob": mean_logprob,
+            "min_avg_logprob": min_logprob,
+            "max_no_speech_prob": max_no_speech_prob,
+            "longest_secs": longest_secs,
+        },
+        "warnings": result
+            .warnings
+            .iter()
+            .map(|warning| json!({"code": warning.code, "message": warning.message}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn duration_ms(elapsed: Duration) -> f64 {
+    (elapsed.as_secs_f64() * 1_000_000.0).round() / 1_000.0
+}
+
+/// Stored traces, keyed by trace id.
+pub struct DebugTraces {
+    storage: Arc<dyn Storage>,
+    cipher: Option<StorageCipher>,
+}
+
+impl DebugTraces {
+    /// Creates a trace store in `storage`.
+    pub fn new(storage: Arc<dyn Storage>, cipher: Option<StorageCipher>) -> Self {
+        Self { storage, cipher }
+    }
+
+    /// Writes the trace `trace_id`; fails if a trace with that id exists.
+    pub async fn save(&self, trace_id: &str, trace: &Value) -> io::Result<()> {
+        let body = serde_json::to_vec_pretty(trace)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let body = match &self.cipher {
+            Some(cipher) => cipher.seal(&body)?,
+            None => body,
+        };
+        let key = trace_key(trace_id);
+        let storage = Arc::clone(&self.storage);
+        tokio::task::spawn_blocking(move || storage.put_new(&key, &body))
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    }
+
+    /// Reads the trace `trace_id`, if one was written.
+    pub fn get(&self, trace_id: &str) -> io::Result<Option<Value>> {
+        self.read(&trace_key(trace_id))
+    }
+
+    /// Deletes the traces matching `filter` and returns how many matched.
+    ///
+    /// With `dry_run` set, matches are only counted.
+    pub fn purge(&self, filter: &PurgeFilter, dry_run: bool) -> io::Result<usize> {
+        let mut purged = 0;
+        for object in self.storage.list("")? {
+            let Some(trace) = self.read(&object.key)? else {
+                continue;
+            };
+            let api_key = trace["request"]["api_key"].as_str();
+            let created_at = trace["request"]["created_at"]
+                .as_u64()
+                .unwrap_or(object.modified);
+            if !filter.matches(api_key, created_at) {
+                continue;
+            }
+            purged += 1;
+            if !dry_run {
+                self.storage.delete(&object.key)?;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn read(&self, key: &str) -> io::Result<Option<Value>> {
+        let Some(mut contents) = self.storage.get(key)? else {
+            return Ok(None);
+        };
+        if let (Some(cipher), true) = (&self.cipher, is_sealed(&contents)) {
+            contents = cipher.open(&contents)?;
+        }
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Storage key of a trace.
+///
+/// Ids looked up by admins may contain `/` or `.`, so anything but ASCII
+/// letters, digits, `-`, and `_` is replaced to keep the key a single name.
+fn trace_key(trace_id: &str) -> String {
+    let name = trace_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{name}.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    #[tokio::test]
+    async fn traces_collect_reports_and_round_trip() {
+        stage("decode", Duration::from_millis(5));
+        let ((), trace) = capture(async {
+            stage("decode", Duration::from_micros(1_500));
+            event("vad", json!({"removed_secs": 2.5}));
+        })
+        .await;
+        let trace = trace.into_json(
+            json!({"id": "req_1", "api_key": "alpha", "created_at": 100}),
+            json!({"temperature": 0.0}),
+            Duration::from_millis(20),
+            Err("inference failed".to_string()),
+        );
+        assert_eq!(trace["stages"], json!([{"stage": "decode", "ms": 1.5}]));
+        assert_eq!(
+            trace["events"],
+            json!([{"event": "vad", "removed_secs": 2.5}])
+        );
+        assert_eq!(trace["error"], "inference failed");
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let traces = DebugTraces::new(Arc::new(LocalStorage::new(dir.path())), None);
+        traces.save("../req 1", &trace).await.expect("save");
+        assert!(dir.path().join("___req_1.json").exists());
+        assert_eq!(traces.get("../req 1").expect("get"), Some(trace.clone()));
+        let replaced = traces.save("../req 1", &json!({})).await.expect_err("save");
+        assert_eq!(replaced.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(traces.get("../req 1").expect("get"), Some(trace));
+        assert_eq!(traces.get("req_2").expect("get"), None);
+
+        let filter = |api_key: &str| PurgeFilter {
+            api_key: Some(api_key.to_string()),
+            ..PurgeFilter::default()
+        };
+        assert_eq!(traces.purge(&filter("beta"), false).expect("purge"), 0);
+        assert_eq!(traces.purge(&filter("alpha"), false).expect("purge"), 1);
+        assert_eq!(traces.get("../req 1").expect("get"), None);
+    }
+}
@@ -0,0 +1,31 @@
+//! Built-in live caption page (`GET /demo`).
+//!
+//! A single self-contained HTML page that records from the browser microphone
+//! and shows captions as they arrive, so a deployment can be checked end to
+//! end without writing a client. Audio is captured as PCM and sent to
+//! `/v1/audio/stream`; because browsers cannot put an API key on a WebSocket
+//! handshake, the page instead uploads five-second WAV segments to
+//! `/v1/audio/transcriptions` with `stream=true` when a key is entered.
+//! Disabled with `DEMO_PAGE=false`.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::response::Html;
+
+use crate::api::AppState;
+use crate::error::AppError;
+
+/// The page, with its script and styles inline.
+const DEMO_HTML: &str = include_str!("demo.html");
+
+/// Serves the live caption page (`GET /demo`).
+///
+/// Served without authentication like `/openapi.json`; the page holds no
+/// data, and the API key entered in it is sent only with its own requests.
+pub async fn demo_page(State(state): State<Arc<AppState>>) -> Result<Html<&'static str>, AppError> {
+    if !state.cfg.demo_page {
+        return Err(AppError::not_found("the demo page is disabled", None));
+    }
+    Ok(Html(DEMO_HTML))
+}
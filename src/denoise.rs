@@ -0,0 +1,101 @@
+//! Optional RNNoise noise suppression, compiled with the `denoise` feature.
+//!
+//! RNNoise (through the pure-Rust `nnnoiseless` port) runs on 10 ms frames of
+//! 48 kHz audio, so decoded 16 kHz samples are upsampled, denoised, and
+//! brought back to 16 kHz with the same length, keeping segment times aligned
+//! with the upload.
+
+#[cfg(feature = "denoise")]
+use nnnoiseless::DenoiseState;
+
+#[cfg(feature = "denoise")]
+use crate::audio::{resample, ResampleQuality};
+use crate::error::AppError;
+
+/// Whether noise suppression was compiled in.
+pub const AVAILABLE: bool = cfg!(feature = "denoise");
+
+/// Rate RNNoise operates at.
+#[cfg(feature = "denoise")]
+const RNNOISE_SAMPLE_RATE: u32 = 48_000;
+/// Rate of decoded request audio.
+#[cfg(feature = "denoise")]
+const SAMPLE_RATE: u32 = 16_000;
+/// RNNoise expects samples on the 16-bit integer scale rather than `[-1.0, 1.0]`.
+#[cfg(feature = "denoise")]
+const PCM16_SCALE: f32 = 32_768.0;
+
+/// Error for `denoise=true` on a server built without the `denoise` feature.
+pub fn unavailable() -> AppError {
+    AppError::invalid_request(
+        "denoise requires a server built with the `denoise` feature",
+        Some("denoise"),
+        Some("denoise_unavailable"),
+    )
+}
+
+/// Suppresses background noise in 16 kHz mono samples, returning as many samples.
+#[cfg(feature = "denoise")]
+pub fn denoise(samples: &[f32]) -> Vec<f32> {
+    let frame_size = DenoiseState::FRAME_SIZE;
+    let upsampled = resample(
+        samples,
+        SAMPLE_RATE,
+        RNNOISE_SAMPLE_RATE,
+        ResampleQuality::Balanced,
+    );
+    let mut state = DenoiseState::new();
+    let mut input = vec![0.0; frame_size];
+    let mut output = vec![0.0; frame_size];
+    let mut cleaned = Vec::with_capacity(upsampled.len());
+    for chunk in upsampled.chunks(frame_size) {
+        // The last frame is zero-padded and trimmed back afterwards.
+        input.fill(0.0);
+        for (dst, sample) in input.iter_mut().zip(chunk) {
+            *dst = sample * PCM16_SCALE;
+        }
+        state.process_frame(&mut output, &input);
+        cleaned.extend(
+            output[..chunk.len()]
+                .iter()
+                .map(|sample| (sample / PCM16_SCALE).clamp(-1.0, 1.0)),
+        );
+    }
+    let mut denoised = resample(
+        &cleaned,
+        RNNOISE_SAMPLE_RATE,
+        SAMPLE_RATE,
+        ResampleQuality::Balanced,
+    );
+    denoised.resize(samples.len(), 0.0);
+    denoised
+}
+
+/// Without the `denoise` feature, requests asking for it are rejected before
+/// decoding, so this is never reached.
+#[cfg(not(feature = "denoise"))]
+pub fn denoise(samples: &[f32]) -> Vec<f32> {
+    samples.to_vec()
+}
+
+#[cfg(all(test, feature = "denoise"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denoising_keeps_length_and_silence() {
+        for len in [0, 1, 159, 16_000] {
+            let denoised = denoise(&vec![0.0; len]);
+            assert_eq!(denoised.len(), len);
+            assert!(denoised.iter().all(|sample| sample.abs() < 1e-4), "{len}");
+        }
+        let tone: Vec<f32> = (0..16_000)
+            .map(|idx| (idx as f32 * 440.0 * std::f32::consts::TAU / 16_000.0).sin() * 0.5)
+            .collect();
+        let denoised = denoise(&tone);
+        assert_eq!(denoised.len(), tone.len());
+        assert!(denoised
+            .iter()
+            .all(|sample| sample.is_finite() && sample.abs() <= 1.0));
+    }
+}
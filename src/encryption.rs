@@ -0,0 +1,462 @@
+//! Optional AES-256-GCM encryption of audio and transcripts stored on disk.
+//!
+//! With `STORAGE_ENCRYPTION_KEY` or `STORAGE_ENCRYPTION_KEY_FILE` set, spilled
+//! uploads, the async job spool, and the transcript archive are written
+//! sealed. Files are sealed in 64 KiB chunks so uploads can be encrypted as
+//! they stream in:
+//!
+//! ```text
+//! "WOSE" | version (1) | nonce prefix (7) | { length (u32 BE) | ciphertext + tag }*
+//! ```
+//!
+//! Each chunk's nonce is the random per-file prefix, the chunk counter, and a
+//! final-chunk flag, so reordered, dropped, or truncated chunks fail to open.
+//! Every chunk but the last holds exactly 64 KiB, so a [`SealedReader`] can
+//! seek to any plaintext offset and decrypt one chunk at a time.
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, OsRng};
+use aes_gcm::{Aes256Gcm, KeyInit};
+
+const MAGIC: &[u8; 4] = b"WOSE";
+const VERSION: u8 = 1;
+const PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + 1 + PREFIX_LEN;
+/// Plaintext bytes per sealed chunk.
+const CHUNK_LEN: usize = 64 * 1024;
+/// AES-GCM authentication tag appended to every chunk.
+const TAG_LEN: usize = 16;
+/// Bytes on disk of every chunk but the last: length, ciphertext, and tag.
+const SEALED_CHUNK_LEN: u64 = (4 + CHUNK_LEN + TAG_LEN) as u64;
+
+/// Returns `true` when `data` starts like a sealed file.
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// AES-256-GCM key used to seal files at rest.
+#[derive(Clone)]
+pub struct StorageCipher(Arc<Aes256Gcm>);
+
+impl fmt::Debug for StorageCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StorageCipher(<redacted>)")
+    }
+}
+
+impl StorageCipher {
+    /// Builds a cipher from a 32-byte key written as 64 hex characters or in base64.
+    ///
+    /// Raw bytes are refused so a 32-character passphrase is never taken as
+    /// the key itself.
+    pub fn from_key_material(material: &str) -> Result<Self, String> {
+        const EXPECTED: &str = "expected 32 bytes as 64 hex characters or base64";
+        let material = material.trim();
+        let key = match material.len() {
+            64 => decode_hex(material),
+            _ => decode_base64(material),
+        }
+        .filter(|key| key.len() == 32)
+        .ok_or_else(|| format!("invalid storage encryption key; {EXPECTED}"))?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| format!("invalid storage encryption key; {EXPECTED}"))?;
+        Ok(Self(Arc::new(cipher)))
+    }
+
+    /// Starts sealing a file; write the returned header first.
+    pub fn sealer(&self) -> (Sealer, Vec<u8>) {
+        let mut prefix = [0u8; PREFIX_LEN];
+        OsRng.fill_bytes(&mut prefix);
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.extend_from_slice(&prefix);
+        let sealer = Sealer {
+            cipher: self.clone(),
+            prefix,
+            counter: 0,
+            pending: Vec::new(),
+        };
+        (sealer, header)
+    }
+
+    /// Seals `plaintext` into a complete file.
+    pub fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let (mut sealer, mut sealed) = self.sealer();
+        sealed.extend(sealer.push(plaintext)?);
+        sealed.extend(sealer.finish()?);
+        Ok(sealed)
+    }
+
+    /// Opens a file written by [`StorageCipher::seal`] or a [`Sealer`].
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        if sealed.len() < HEADER_LEN || !is_sealed(sealed) {
+            return Err(invalid("file is not sealed with a storage encryption key"));
+        }
+        if sealed[MAGIC.len()] != VERSION {
+            return Err(invalid("unsupported sealed file version"));
+        }
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&sealed[MAGIC.len() + 1..HEADER_LEN]);
+
+        let mut plaintext = Vec::with_capacity(sealed.len());
+        let mut rest = &sealed[HEADER_LEN..];
+        let mut counter = 0u32;
+        loop {
+            if rest.len() < 4 {
+                return Err(invalid("sealed file is truncated"));
+            }
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            if rest.len() < 4 + len {
+                return Err(invalid("sealed file is truncated"));
+            }
+            let (chunk, tail) = rest[4..].split_at(len);
+            let last = tail.is_empty();
+            let opened = self
+                .0
+                .decrypt(
+                    GenericArray::from_slice(&nonce(&prefix, counter, last)),
+                    chunk,
+                )
+                .map_err(|_| invalid("sealed file failed authentication"))?;
+            plaintext.extend_from_slice(&opened);
+            if last {
+                return Ok(plaintext);
+            }
+            rest = tail;
+            counter = counter
+                .checked_add(1)
+                .ok_or_else(|| invalid("sealed file has too many chunks"))?;
+        }
+    }
+
+    /// Opens a sealed file for reading, decrypting one chunk at a time.
+    pub fn reader<R: Read + Seek>(&self, mut inner: R) -> io::Result<SealedReader<R>> {
+        let sealed_len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+        let mut header = [0u8; HEADER_LEN];
+        if sealed_len < HEADER_LEN as u64 || inner.read_exact(&mut header).is_err() {
+            return Err(invalid("file is not sealed with a storage encryption key"));
+        }
+        if !is_sealed(&header) {
+            return Err(invalid("file is not sealed with a storage encryption key"));
+        }
+        if header[MAGIC.len()] != VERSION {
+            return Err(invalid("unsupported sealed file version"));
+        }
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&header[MAGIC.len() + 1..]);
+
+        // Plaintext plus the framing of every chunk but the last; the last
+        // chunk holds 1..=CHUNK_LEN bytes unless the file is empty.
+        let data = (sealed_len - HEADER_LEN as u64)
+            .checked_sub((4 + TAG_LEN) as u64)
+            .ok_or_else(|| invalid("sealed file is truncated"))?;
+        let full_chunks = data.saturating_sub(1) / SEALED_CHUNK_LEN;
+        let last_len = data - full_chunks * SEALED_CHUNK_LEN;
+        if last_len > CHUNK_LEN as u64 {
+            return Err(invalid("sealed file is truncated"));
+        }
+        if full_chunks > u64::from(u32::MAX) {
+            return Err(invalid("sealed file has too many chunks"));
+        }
+        Ok(SealedReader {
+            inner,
+            cipher: self.clone(),
+            prefix,
+            chunks: full_chunks + 1,
+            len: full_chunks * CHUNK_LEN as u64 + last_len,
+            pos: 0,
+            loaded: None,
+            chunk: Vec::new(),
+        })
+    }
+}
+
+/// Plaintext view of a sealed file that decrypts the chunk under the cursor.
+///
+/// Each chunk is authenticated when it is read, so only the chunks a reader
+/// visits are verified.
+pub struct SealedReader<R> {
+    inner: R,
+    cipher: StorageCipher,
+    prefix: [u8; PREFIX_LEN],
+    chunks: u64,
+    len: u64,
+    pos: u64,
+    /// Index of the chunk decrypted into `chunk`.
+    loaded: Option<u64>,
+    chunk: Vec<u8>,
+}
+
+impl<R: Read + Seek> SealedReader<R> {
+    /// Plaintext size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn load(&mut self, index: u64) -> io::Result<()> {
+        self.loaded = None;
+        let last = index + 1 == self.chunks;
+        let plain_len = if last {
+            (self.len - index * CHUNK_LEN as u64) as usize
+        } else {
+            CHUNK_LEN
+        };
+        self.inner.seek(SeekFrom::Start(
+            HEADER_LEN as u64 + index * SEALED_CHUNK_LEN,
+        ))?;
+        let mut len = [0u8; 4];
+        self.inner.read_exact(&mut len)?;
+        if u32::from_be_bytes(len) as usize != plain_len + TAG_LEN {
+            return Err(invalid("sealed file has a malformed chunk"));
+        }
+        let mut sealed = vec![0u8; plain_len + TAG_LEN];
+        self.inner.read_exact(&mut sealed)?;
+        let counter =
+            u32::try_from(index).map_err(|_| invalid("sealed file has too many chunks"))?;
+        self.chunk = self
+            .cipher
+            .0
+            .decrypt(
+                GenericArray::from_slice(&nonce(&self.prefix, counter, last)),
+                sealed.as_slice(),
+            )
+            .map_err(|_| invalid("sealed file failed authentication"))?;
+        self.loaded = Some(index);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for SealedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        let index = self.pos / CHUNK_LEN as u64;
+        if self.loaded != Some(index) {
+            self.load(index)?;
+        }
+        let start = (self.pos % CHUNK_LEN as u64) as usize;
+        let n = buf.len().min(self.chunk.len() - start);
+        buf[..n].copy_from_slice(&self.chunk[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SealedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek before the start")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Incrementally seals a file in fixed-size chunks.
+pub struct Sealer {
+    cipher: StorageCipher,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    pending: Vec<u8>,
+}
+
+impl Sealer {
+    /// Buffers `plaintext` and returns the chunks that are now complete.
+    pub fn push(&mut self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        self.pending.extend_from_slice(plaintext);
+        let mut out = Vec::new();
+        // A full buffer is kept back so the final chunk is never empty unless the file is.
+        while self.pending.len() > CHUNK_LEN {
+            let chunk = self.pending.drain(..CHUNK_LEN).collect::<Vec<_>>();
+            self.seal_chunk(&chunk, false, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    /// Seals the remaining bytes as the final chunk.
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        let chunk = std::mem::take(&mut self.pending);
+        let mut out = Vec::with_capacity(chunk.len() + 4 + TAG_LEN);
+        self.seal_chunk(&chunk, true, &mut out)?;
+        Ok(out)
+    }
+
+    fn seal_chunk(&mut self, chunk: &[u8], last: bool, out: &mut Vec<u8>) -> io::Result<()> {
+        let sealed = self
+            .cipher
+            .0
+            .encrypt(
+                GenericArray::from_slice(&nonce(&self.prefix, self.counter, last)),
+                chunk,
+            )
+            .map_err(|_| invalid("failed to seal chunk"))?;
+        out.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+        out.extend_from_slice(&sealed);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| invalid("sealed file has too many chunks"))?;
+        Ok(())
+    }
+}
+
+fn nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/// Decodes standard or URL-safe base64, with or without padding.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for byte in text.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(byte: u8) -> StorageCipher {
+        StorageCipher::from_key_material(&format!("{byte:02x}").repeat(32)).expect("key")
+    }
+
+    #[test]
+    fn sealed_files_round_trip_across_chunks() {
+        let cipher = cipher(7);
+        let plaintext = (0..CHUNK_LEN * 2 + 100)
+            .map(|idx| idx as u8)
+            .collect::<Vec<_>>();
+        let sealed = cipher.seal(&plaintext).expect("seal");
+        assert!(!sealed.windows(64).any(|window| window == &plaintext[..64]));
+        assert_eq!(cipher.open(&sealed).expect("open"), plaintext);
+        assert!(cipher
+            .open(&cipher.seal(b"").expect("seal"))
+            .expect("open")
+            .is_empty());
+
+        let (mut sealer, mut streamed) = cipher.sealer();
+        for piece in plaintext.chunks(1000) {
+            streamed.extend(sealer.push(piece).expect("push"));
+        }
+        streamed.extend(sealer.finish().expect("finish"));
+        assert_eq!(cipher.open(&streamed).expect("open"), plaintext);
+    }
+
+    #[test]
+    fn tampered_truncated_or_foreign_files_fail_to_open() {
+        let cipher = cipher(7);
+        let sealed = cipher.seal(&vec![1u8; CHUNK_LEN + 10]).expect("seal");
+
+        let mut tampered = sealed.clone();
+        tampered[HEADER_LEN + 10] ^= 1;
+        assert!(cipher.open(&tampered).is_err());
+
+        let first_chunk = HEADER_LEN + 4 + CHUNK_LEN + TAG_LEN;
+        assert!(cipher.open(&sealed[..first_chunk]).is_err());
+        assert!(self::cipher(8).open(&sealed).is_err());
+        assert!(cipher.open(b"{\"text\":\"plain\"}").is_err());
+    }
+
+    #[test]
+    fn readers_decrypt_and_seek_chunk_by_chunk() {
+        let cipher = cipher(7);
+        let plaintext = (0..CHUNK_LEN * 2 + 100)
+            .map(|idx| (idx % 251) as u8)
+            .collect::<Vec<_>>();
+        let sealed = cipher.seal(&plaintext).expect("seal");
+
+        let mut reader = cipher.reader(io::Cursor::new(&sealed)).expect("reader");
+        assert_eq!(reader.len(), plaintext.len() as u64);
+        let mut opened = Vec::new();
+        reader.read_to_end(&mut opened).expect("read");
+        assert_eq!(opened, plaintext);
+
+        let offset = CHUNK_LEN + 10;
+        reader.seek(SeekFrom::Start(offset as u64)).expect("seek");
+        let mut window = [0u8; 200];
+        reader.read_exact(&mut window).expect("read across chunks");
+        assert_eq!(window[..], plaintext[offset..offset + 200]);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-1)).expect("seek"),
+            plaintext.len() as u64 - 1
+        );
+
+        let empty = cipher.seal(b"").expect("seal");
+        let mut reader = cipher.reader(io::Cursor::new(&empty)).expect("reader");
+        assert_eq!(reader.len(), 0);
+        assert_eq!(reader.read(&mut window).expect("read"), 0);
+
+        let mut tampered = sealed.clone();
+        tampered[HEADER_LEN + SEALED_CHUNK_LEN as usize + 10] ^= 1;
+        let mut reader = cipher.reader(io::Cursor::new(&tampered)).expect("reader");
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        let truncated = &sealed[..HEADER_LEN + SEALED_CHUNK_LEN as usize];
+        let mut reader = cipher.reader(io::Cursor::new(truncated)).expect("reader");
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(cipher.reader(io::Cursor::new(b"RIFF")).is_err());
+    }
+
+    #[test]
+    fn keys_parse_from_hex_or_base64_only() {
+        let hex = "00".repeat(31) + "ff";
+        assert!(StorageCipher::from_key_material(&format!("{hex}\n")).is_ok());
+        // `openssl rand -base64 32`, padded and unpadded.
+        let base64 = "q83vASNFZ4mrze8BI0VniavN7wEjRWeJq83vASNFZ4k=";
+        assert_eq!(decode_base64(base64).map(|key| key.len()), Some(32));
+        assert!(StorageCipher::from_key_material(base64).is_ok());
+        assert!(StorageCipher::from_key_material(base64.trim_end_matches('=')).is_ok());
+
+        assert!(StorageCipher::from_key_material("too short").is_err());
+        assert!(StorageCipher::from_key_material(&"zz".repeat(32)).is_err());
+        let passphrase = "correct horse battery staple 123";
+        assert_eq!(passphrase.len(), 32);
+        assert!(StorageCipher::from_key_material(passphrase).is_err());
+        assert!(!format!("{:?}", cipher(1)).contains('1'));
+    }
+}
@@ -1,10 +1,15 @@
 //! Application error types and HTTP-to-OpenAI error mapping.
 
-use axum::http::StatusCode;
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
 
+use crate::request_id;
+
+/// Seconds a client is asked to wait before retrying a `503 server_overloaded`.
+const OVERLOADED_RETRY_AFTER_SECS: u64 = 5;
+
 /// Error model used throughout request parsing, validation, and inference.
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
@@ -17,6 +22,12 @@ pub enum AppError {
         code: Option<String>,
         status: StatusCode,
     },
+    #[error("{message}")]
+    NotImplemented {
+        message: String,
+        param: String,
+        supported_models: Vec<String>,
+    },
     #[error("{0}")]
     UnsupportedMediaType(String),
     #[error("{0}")]
@@ -24,6 +35,22 @@ pub enum AppError {
     #[error("{0}")]
     Backend(String),
     #[error("{0}")]
+    Overloaded(String),
+    #[error("{message}")]
+    RateLimited {
+        message: String,
+        retry_after_secs: u64,
+    },
+    #[error("{message}")]
+    QueueFull {
+        message: String,
+        retry_after_secs: u64,
+    },
+    #[error("{0}")]
+    ModelLoading(String),
+    #[error("{0}")]
+    Timeout(String),
+    #[error("{0}")]
     Internal(String),
 }
 
@@ -47,6 +74,50 @@ impl AppError {
         }
     }
 
+    /// Creates an `invalid_request_error` payload with status `404`.
+    pub fn not_found(message: impl Into<String>, param: Option<&str>) -> Self {
+        Self::InvalidRequest {
+            message: message.into(),
+            param: param.map(ToOwned::to_owned),
+            code: Some("not_found".to_string()),
+            status: StatusCode::NOT_FOUND,
+        }
+    }
+
+    /// Creates an `invalid_request_error` payload with status `409`.
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::InvalidRequest {
+            message: message.into(),
+            param: None,
+            code: Some("conflict".to_string()),
+            status: StatusCode::CONFLICT,
+        }
+    }
+
+    /// Creates an `invalid_request_error` payload with status `413`.
+    pub fn payload_too_large(message: impl Into<String>, param: Option<&str>) -> Self {
+        Self::InvalidRequest {
+            message: message.into(),
+            param: param.map(ToOwned::to_owned),
+            code: Some("request_too_large".to_string()),
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+
+    /// Creates a `501 Not Implemented` error for a parameter the requested
+    /// model cannot honour, listing the models that can.
+    pub fn not_implemented(
+        message: impl Into<String>,
+        param: &str,
+        supported_models: Vec<String>,
+    ) -> Self {
+        Self::NotImplemented {
+            message: message.into(),
+            param: param.to_string(),
+            supported_models,
+        }
+    }
+
     /// Creates a `415 Unsupported Media Type` style error.
     pub fn unsupported_media_type(message: impl Into<String>) -> Self {
         Self::UnsupportedMediaType(message.into())
@@ -62,6 +133,38 @@ impl AppError {
         Self::Backend(message.into())
     }
 
+    /// Creates a `503 Service Unavailable` error, with `Retry-After`, when
+    /// inference capacity is saturated.
+    pub fn overloaded(message: impl Into<String>) -> Self {
+        Self::Overloaded(message.into())
+    }
+
+    /// Creates a `429 Too Many Requests` error advising a retry after `retry_after_secs`.
+    pub fn rate_limited(message: impl Into<String>, retry_after_secs: u64) -> Self {
+        Self::RateLimited {
+            message: message.into(),
+            retry_after_secs,
+        }
+    }
+
+    /// Creates a `429 Too Many Requests` error when the inference queue is at `MAX_QUEUE_DEPTH`.
+    pub fn queue_full(message: impl Into<String>, retry_after_secs: u64) -> Self {
+        Self::QueueFull {
+            message: message.into(),
+            retry_after_secs,
+        }
+    }
+
+    /// Creates a `503 Service Unavailable` error while a requested model is still loading.
+    pub fn model_loading(message: impl Into<String>) -> Self {
+        Self::ModelLoading(message.into())
+    }
+
+    /// Creates a `504 Gateway Timeout` error when a request exceeds `REQUEST_TIMEOUT_SECS`.
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::Timeout(message.into())
+    }
+
     /// Creates a generic internal server error.
     pub fn internal(message: impl Into<String>) -> Self {
         Self::Internal(message.into())
@@ -82,11 +185,15 @@ struct OpenAiError {
     param: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     code: Option<String>,
+    /// Models that support the rejected parameter, for `501` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supported_models: Option<Vec<String>>,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, payload) = match self {
+impl AppError {
+    /// Maps the error to its HTTP status and OpenAI-style payload.
+    fn into_parts(self) -> (StatusCode, OpenAiErrorPayload) {
+        match self {
             AppError::Unauthorized(message) => (
                 StatusCode::UNAUTHORIZED,
                 OpenAiErrorPayload {
@@ -95,6 +202,7 @@ impl IntoResponse for AppError {
                         error_type: "authentication_error".to_string(),
                         param: None,
                         code: Some("invalid_api_key".to_string()),
+                        supported_models: None,
                     },
                 },
             ),
@@ -111,6 +219,23 @@ impl IntoResponse for AppError {
                         error_type: "invalid_request_error".to_string(),
                         param,
                         code,
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::NotImplemented {
+                message,
+                param,
+                supported_models,
+            } => (
+                StatusCode::NOT_IMPLEMENTED,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "invalid_request_error".to_string(),
+                        param: Some(param),
+                        code: Some("unsupported_parameter".to_string()),
+                        supported_models: Some(supported_models),
                     },
                 },
             ),
@@ -122,6 +247,7 @@ impl IntoResponse for AppError {
                         error_type: "invalid_request_error".to_string(),
                         param: Some("file".to_string()),
                         code: Some("unsupported_media_type".to_string()),
+                        supported_models: None,
                     },
                 },
             ),
@@ -133,6 +259,7 @@ impl IntoResponse for AppError {
                         error_type: "invalid_request_error".to_string(),
                         param: Some("file".to_string()),
                         code: Some("invalid_multipart".to_string()),
+                        supported_models: None,
                     },
                 },
             ),
@@ -144,6 +271,67 @@ impl IntoResponse for AppError {
                         error_type: "server_error".to_string(),
                         param: None,
                         code: Some("inference_failed".to_string()),
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::Overloaded(message) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "server_error".to_string(),
+                        param: None,
+                        code: Some("server_overloaded".to_string()),
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::RateLimited { message, .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "requests".to_string(),
+                        param: None,
+                        code: Some("rate_limit_exceeded".to_string()),
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::QueueFull { message, .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "server_error".to_string(),
+                        param: None,
+                        code: Some("queue_full".to_string()),
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::ModelLoading(message) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "server_error".to_string(),
+                        param: Some("model".to_string()),
+                        code: Some("model_loading".to_string()),
+                        supported_models: None,
+                    },
+                },
+            ),
+            AppError::Timeout(message) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                OpenAiErrorPayload {
+                    error: OpenAiError {
+                        message,
+                        error_type: "server_error".to_string(),
+                        param: None,
+                        code: Some("request_timeout".to_string()),
+                        supported_models: None,
                     },
                 },
             ),
@@ -155,11 +343,43 @@ impl IntoResponse for AppError {
                         error_type: "server_error".to_string(),
                         param: None,
                         code: Some("internal_error".to_string()),
+                        supported_models: None,
                     },
                 },
             ),
-        };
+        }
+    }
+
+    /// Returns the OpenAI-style error payload as JSON, for errors reported
+    /// inside an already-started response such as an event stream.
+    pub fn into_json(self) -> serde_json::Value {
+        let (_, payload) = self.into_parts();
+        serde_json::to_value(payload).unwrap_or_default()
+    }
+}
 
-        (status, Json(payload)).into_response()
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let retry_after = match &self {
+            AppError::RateLimited {
+                retry_after_secs, ..
+            }
+            | AppError::QueueFull {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            AppError::Overloaded(_) => Some(OVERLOADED_RETRY_AFTER_SECS),
+            _ => None,
+        };
+        let (status, payload) = self.into_parts();
+        let mut body = serde_json::to_value(payload).unwrap_or_default();
+        if let Some(request_id) = request_id::current() {
+            body["error"]["request_id"] = serde_json::Value::String(request_id);
+        }
+        let mut res = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        res
     }
 }
@@ -0,0 +1,149 @@
+//! Optional fallback decoding through an external `ffmpeg` executable.
+//!
+//! With `FFMPEG_BIN` set, uploads that symphonia cannot open (containers such
+//! as MKV or AVI, formats such as WMA, and MP4s with codecs other than AAC,
+//! ALAC, FLAC, MP3, or PCM) are piped through ffmpeg,
+//! which transcodes their first audio stream to 16 kHz mono PCM, averaging
+//! its channels or keeping only the selected one. Uploads
+//! spooled to disk in plaintext are read by ffmpeg in place; in-memory and
+//! sealed uploads are written to its stdin instead of a temporary file, so
+//! they are never stored unencrypted. MP4 and MOV files whose index comes
+//! after the media data can only be read in place.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use tracing::debug;
+
+use crate::audio::Downmix;
+use crate::error::AppError;
+
+/// Extensions accepted in addition to the built-in ones when ffmpeg is configured.
+pub const FFMPEG_EXTENSIONS: &[&str] = &["3gp", "aac", "avi", "mkv", "wma"];
+
+/// Returns the lowercased extension of `filename` when only ffmpeg can decode it.
+pub fn ffmpeg_extension(filename: &str) -> Option<String> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    let extension = extension.trim().to_ascii_lowercase();
+    FFMPEG_EXTENSIONS
+        .contains(&extension.as_str())
+        .then_some(extension)
+}
+
+/// Media handed to ffmpeg.
+pub enum FfmpegInput<'a> {
+    /// A plaintext file ffmpeg can open and seek in.
+    File(&'a Path),
+    /// Bytes streamed to ffmpeg's stdin.
+    Pipe(Box<dyn Read + Send + 'a>),
+}
+
+/// Transcodes `input` to 16 kHz mono samples with the executable at `ffmpeg_bin`.
+///
+/// With `max_samples` set, ffmpeg stops after that much audio.
+pub fn transcode_to_mono_16khz_f32(
+    ffmpeg_bin: &str,
+    input: FfmpegInput<'_>,
+    downmix: Downmix,
+    max_samples: Option<usize>,
+) -> Result<Vec<f32>, AppError> {
+    let (source, mut reader) = match input {
+        FfmpegInput::File(path) => (path.as_os_str().to_owned(), None),
+        FfmpegInput::Pipe(reader) => ("pipe:0".into(), Some(reader)),
+    };
+    let mut child = Command::new(ffmpeg_bin)
+        .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-i"])
+        .arg(&source)
+        .args(["-map", "0:a:0"])
+        .args(downmix_args(downmix))
+        .args(duration_args(max_samples))
+        .args(["-ar", "16000", "-f", "s16le", "pipe:1"])
+        .stdin(if reader.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            AppError::internal(format!(
+                "failed to start ffmpeg at {ffmpeg_bin:?}: {err}; check FFMPEG_BIN"
+            ))
+        })?;
+
+    let output = std::thread::scope(|scope| {
+        if let (Some(reader), Some(mut stdin)) = (reader.as_mut(), child.stdin.take()) {
+            // Fed from another thread so a full stdout pipe cannot stall the write.
+            scope.spawn(move || {
+                // ffmpeg closes stdin early once it has what it needs; that is not an error.
+                let _ = std::io::copy(reader, &mut stdin).and_then(|_| stdin.flush());
+            });
+        }
+        child.wait_with_output()
+    })
+    .map_err(|err| AppError::internal(format!("ffmpeg process failed: {err}")))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!(status = %output.status, stderr = %stderr.trim(), "ffmpeg could not decode upload");
+        return Err(AppError::unsupported_media_type(format!(
+            "failed to decode media with ffmpeg: {}",
+            stderr
+                .lines()
+                .last()
+                .unwrap_or("no audio stream found")
+                .trim()
+        )));
+    }
+    let samples = output
+        .stdout
+        .chunks_exact(2)
+        .take(max_samples.unwrap_or(usize::MAX))
+        .map(|pair| f32::from(i16::from_le_bytes([pair[0], pair[1]])) / 32_768.0)
+        .collect::<Vec<_>>();
+    if samples.is_empty() {
+        return Err(AppError::unsupported_media_type(
+            "decoded audio is empty after processing",
+        ));
+    }
+    Ok(samples)
+}
+
+/// ffmpeg arguments reducing the audio to mono with `downmix`.
+fn downmix_args(downmix: Downmix) -> Vec<String> {
+    match downmix {
+        Downmix::Average => vec!["-ac".into(), "1".into()],
+        Downmix::Channel(channel) => vec!["-af".into(), format!("pan=mono|c0=c{channel}")],
+    }
+}
+
+/// ffmpeg arguments ending the output after `max_samples` samples at 16 kHz.
+fn duration_args(max_samples: Option<usize>) -> Vec<String> {
+    match max_samples {
+        Some(samples) => vec!["-t".into(), format!("{}", samples as f64 / 16_000.0)],
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_container_extensions_route_to_ffmpeg() {
+        assert_eq!(ffmpeg_extension("Clip.AVI").as_deref(), Some("avi"));
+        assert_eq!(ffmpeg_extension("talk.mkv").as_deref(), Some("mkv"));
+        assert_eq!(ffmpeg_extension("voice.wav"), None);
+        assert_eq!(ffmpeg_extension("noext"), None);
+    }
+
+    #[test]
+    fn missing_ffmpeg_binary_is_reported() {
+        let input = FfmpegInput::Pipe(Box::new(&b"data"[..]));
+        let err = transcode_to_mono_16khz_f32("/nonexistent/ffmpeg", input, Downmix::Average, None)
+            .expect_err("missing binary");
+        assert!(err.to_string().contains("FFMPEG_BIN"));
+    }
+}
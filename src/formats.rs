@@ -1,8 +1,13 @@
 //! Helpers for OpenAI-compatible response formatting.
 
 use std::fmt;
+use std::io::Write;
 
-use crate::backend::TranscriptSegment;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::backend::{TranscriptResult, TranscriptSegment};
 use crate::error::AppError;
 
 /// Output format accepted by `response_format` in audio endpoints.
@@ -50,71 +55,452 @@ impl fmt::Display for ResponseFormat {
     }
 }
 
+/// Video frame rates accepted for SMPTE subtitle timecodes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FrameRate {
+    Fps23_976,
+    Fps24,
+    Fps25,
+    Fps29_97,
+    Fps30,
+    Fps50,
+    Fps59_94,
+    Fps60,
+}
+
+impl FrameRate {
+    /// Parses the `frame_rate` form field.
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        match raw.trim() {
+            "23.976" => Ok(Self::Fps23_976),
+            "24" => Ok(Self::Fps24),
+            "25" => Ok(Self::Fps25),
+            "29.97" => Ok(Self::Fps29_97),
+            "30" => Ok(Self::Fps30),
+            "50" => Ok(Self::Fps50),
+            "59.94" => Ok(Self::Fps59_94),
+            "60" => Ok(Self::Fps60),
+            other => Err(AppError::invalid_request(
+                format!(
+                    "invalid frame_rate={other:?}; expected one of 23.976,24,25,29.97,30,50,59.94,60"
+                ),
+                Some("frame_rate"),
+                Some("invalid_frame_rate"),
+            )),
+        }
+    }
+
+    /// Returns the value accepted by [`FrameRate::parse`].
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fps23_976 => "23.976",
+            Self::Fps24 => "24",
+            Self::Fps25 => "25",
+            Self::Fps29_97 => "29.97",
+            Self::Fps30 => "30",
+            Self::Fps50 => "50",
+            Self::Fps59_94 => "59.94",
+            Self::Fps60 => "60",
+        }
+    }
+
+    /// Frames counted per timecode second.
+    fn nominal(self) -> u64 {
+        match self {
+            Self::Fps23_976 | Self::Fps24 => 24,
+            Self::Fps25 => 25,
+            Self::Fps29_97 | Self::Fps30 => 30,
+            Self::Fps50 => 50,
+            Self::Fps59_94 | Self::Fps60 => 60,
+        }
+    }
+
+    /// Whether frames actually run at 1000/1001 of the nominal rate (NTSC).
+    fn fractional(self) -> bool {
+        matches!(self, Self::Fps23_976 | Self::Fps29_97 | Self::Fps59_94)
+    }
+}
+
+/// How cue times are written in SRT and WebVTT output.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SubtitleTiming {
+    /// Milliseconds, as the formats specify.
+    #[default]
+    Millis,
+    /// SMPTE timecode, `HH:MM:SS:FF` or `HH:MM:SS;FF` when drop-frame.
+    Smpte { rate: FrameRate, drop_frame: bool },
+}
+
+impl SubtitleTiming {
+    /// SMPTE timing at `rate`; drop-frame counting only exists for 29.97 and 59.94 fps.
+    pub fn smpte(rate: FrameRate, drop_frame: bool) -> Result<Self, AppError> {
+        if drop_frame && !matches!(rate, FrameRate::Fps29_97 | FrameRate::Fps59_94) {
+            return Err(AppError::invalid_request(
+                format!(
+                    "drop_frame requires frame_rate 29.97 or 59.94, not {}",
+                    rate.as_str()
+                ),
+                Some("drop_frame"),
+                Some("invalid_timecode"),
+            ));
+        }
+        Ok(Self::Smpte { rate, drop_frame })
+    }
+}
+
 /// Normalizes transcript text by collapsing all whitespace runs to one space.
 pub fn normalize_text(raw: &str) -> String {
     raw.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-/// Converts transcript segments to SRT subtitle text.
-pub fn segments_to_srt(segments: &[TranscriptSegment]) -> String {
-    let mut lines = Vec::new();
-    for (idx, seg) in segments.iter().enumerate() {
-        if seg.text.trim().is_empty() {
-            continue;
+/// Marks stripped from the start of lines by
+/// [`TextNormalization::strip_leading_punctuation`]: dashes and ellipses
+/// whisper uses for speaker turns and continuations, and stray separators.
+/// Opening quotes, brackets, `¿`, and `¡` are kept.
+const LEADING_PUNCTUATION: &[char] = &[
+    '-', '\u{2013}', '\u{2014}', '.', '\u{2026}', ',', ';', ':', '\u{3001}', '\u{3002}', '\u{ff0c}',
+];
+
+/// How response text is normalized; the default collapses every whitespace
+/// run to one space, like [`normalize_text`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct TextNormalization {
+    /// Keep line breaks, and put each segment on its own line in `text`.
+    pub keep_newlines: bool,
+    /// Keep runs of spaces and tabs instead of collapsing them.
+    pub keep_spaces: bool,
+    /// Compose characters to Unicode NFC.
+    pub nfc: bool,
+    /// Drop dashes, ellipses, and separators at the start of each line.
+    pub strip_leading_punctuation: bool,
+}
+
+impl TextNormalization {
+    /// Normalizes one piece of text.
+    pub fn normalize(&self, raw: &str) -> String {
+        if *self == Self::default() {
+            return normalize_text(raw);
         }
-        lines.push((idx + 1).to_string());
-        lines.push(format!(
-            "{} --> {}",
-            srt_timestamp(seg.start_secs),
-            srt_timestamp(seg.end_secs)
-        ));
-        lines.push(seg.text.trim().to_string());
-        lines.push(String::new());
+        let composed;
+        let raw = if self.nfc {
+            composed = raw.nfc().collect::<String>();
+            composed.as_str()
+        } else {
+            raw
+        };
+        let lines = if self.keep_newlines {
+            raw.lines().collect::<Vec<_>>()
+        } else {
+            vec![raw]
+        };
+        let lines = lines.into_iter().filter_map(|line| {
+            let line = match self.keep_spaces {
+                true => line.replace(['\n', '\r'], " ").trim().to_string(),
+                false => normalize_text(line),
+            };
+            let line = match self.strip_leading_punctuation {
+                true => line.trim_start_matches(LEADING_PUNCTUATION).trim_start(),
+                false => line.as_str(),
+            };
+            (!line.is_empty()).then(|| line.to_string())
+        });
+        lines.collect::<Vec<_>>().join("\n")
     }
 
-    let out = lines.join("\n");
-    if out.is_empty() {
-        "\n".to_string()
-    } else {
-        format!("{}\n", out.trim_end())
+    /// Builds transcript text from segments.
+    pub fn join_segments(&self, segments: &[TranscriptSegment]) -> String {
+        let separator = if self.keep_newlines { "\n" } else { " " };
+        let joined = segments
+            .iter()
+            .map(|seg| seg.text.trim())
+            .collect::<Vec<_>>()
+            .join(separator);
+        self.normalize(&joined)
+    }
+
+    /// Normalizes segment texts and rebuilds `text` from them; a no-op for the default.
+    pub fn apply(&self, result: &mut TranscriptResult) {
+        if *self == Self::default() || result.segments.is_empty() {
+            return;
+        }
+        for seg in &mut result.segments {
+            seg.text = self.normalize(&seg.text);
+        }
+        result.segments.retain(|seg| !seg.text.is_empty());
+        result.text = self.join_segments(&result.segments);
     }
 }
 
-/// Converts transcript segments to WebVTT subtitle text.
-pub fn segments_to_vtt(segments: &[TranscriptSegment]) -> String {
-    let mut lines = vec!["WEBVTT".to_string(), String::new()];
+/// Corrects impossible segment timings so subtitle tools accept the output.
+///
+/// Times are clamped to `[0, audio_secs]`, a segment starting before the
+/// previous one ended is moved to start there, and an end before its start is
+/// raised to the start. Non-finite times count as `0`. Returns how many
+/// segments were changed.
+pub fn sanitize_timestamps(segments: &mut [TranscriptSegment], audio_secs: f64) -> usize {
+    let audio_secs = audio_secs.max(0.0);
+    let clamp = |secs: f64, min: f64| {
+        let secs = if secs.is_finite() { secs } else { 0.0 };
+        secs.clamp(min, audio_secs)
+    };
+    let mut corrected = 0;
+    let mut prev_end = 0.0;
+    for seg in segments {
+        let start = clamp(seg.start_secs, prev_end);
+        let end = clamp(seg.end_secs, start);
+        if start != seg.start_secs || end != seg.end_secs {
+            corrected += 1;
+            seg.start_secs = start;
+            seg.end_secs = end;
+            for word in &mut seg.words {
+                word.start_secs = word.start_secs.clamp(start, end);
+                word.end_secs = word.end_secs.clamp(word.start_secs, end);
+            }
+        }
+        prev_end = end;
+    }
+    corrected
+}
+
+/// Segments shorter than this are merged into the next one.
+const MERGE_MIN_SEGMENT_SECS: f64 = 1.5;
+/// Merging stops before a segment would grow past this length.
+const MERGE_MAX_SEGMENT_SECS: f64 = 12.0;
+/// Segments separated by a longer pause are never merged.
+const MERGE_MAX_GAP_SECS: f64 = 1.0;
+
+/// Merges choppy adjacent segments into sentence-sized ones.
+///
+/// A segment is joined with the next when it is shorter than
+/// `MERGE_MIN_SEGMENT_SECS` or does not end a sentence, unless the pause
+/// between them exceeds `MERGE_MAX_GAP_SECS` or the merged segment would be
+/// longer than `MERGE_MAX_SEGMENT_SECS`.
+pub fn merge_segments(segments: Vec<TranscriptSegment>) -> Vec<TranscriptSegment> {
+    let mut merged: Vec<TranscriptSegment> = Vec::with_capacity(segments.len());
     for seg in segments {
-        if seg.text.trim().is_empty() {
+        let Some(current) = merged.last_mut() else {
+            merged.push(seg);
+            continue;
+        };
+        let choppy = current.end_secs - current.start_secs < MERGE_MIN_SEGMENT_SECS
+            || !ends_sentence(&current.text);
+        let fits = seg.start_secs - current.end_secs <= MERGE_MAX_GAP_SECS
+            && seg.end_secs - current.start_secs <= MERGE_MAX_SEGMENT_SECS;
+        if !(choppy && fits) {
+            merged.push(seg);
             continue;
         }
-        lines.push(format!(
-            "{} --> {}",
-            vtt_timestamp(seg.start_secs),
-            vtt_timestamp(seg.end_secs)
-        ));
-        lines.push(seg.text.trim().to_string());
-        lines.push(String::new());
+
+        let token_count = current.token_count + seg.token_count;
+        current.avg_logprob = match (current.avg_logprob, seg.avg_logprob) {
+            (Some(a), Some(b)) if token_count > 0 => Some(
+                (a * current.token_count as f64 + b * seg.token_count as f64) / token_count as f64,
+            ),
+            (Some(a), Some(b)) => Some((a + b) / 2.0),
+            (a, b) => a.or(b),
+        };
+        current.token_count = token_count;
+        // Keep the more cautious estimate, so no-speech filters still see it.
+        current.no_speech_prob = match (current.no_speech_prob, seg.no_speech_prob) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        current.tokens.extend_from_slice(&seg.tokens);
+        current.token_logprobs.extend(seg.token_logprobs);
+        current.words.extend(seg.words);
+        current.end_secs = current.end_secs.max(seg.end_secs);
+        let needs_space = match (current.text.chars().last(), seg.text.chars().next()) {
+            (Some(a), Some(b)) => {
+                !(a.is_whitespace() || b.is_whitespace() || is_cjk(a) || is_cjk(b))
+            }
+            _ => false,
+        };
+        if needs_space {
+            current.text.push(' ');
+        }
+        current.text.push_str(&seg.text);
+    }
+    merged
+}
+
+/// Ratio of the UTF-8 length of `text` to its zlib-compressed length, as in
+/// the reference Whisper output; repetitive (often hallucinated) text scores
+/// above about 2.4.
+pub fn compression_ratio(text: &str) -> f64 {
+    if text.is_empty() {
+        return 0.0;
     }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(text.as_bytes())
+        .and_then(|()| encoder.finish())
+        .map_or(0, |bytes| bytes.len());
+    if compressed == 0 {
+        return 0.0;
+    }
+    text.len() as f64 / compressed as f64
+}
+
+/// Returns `true` when `text` ends with sentence-final punctuation, allowing
+/// trailing quotes and brackets.
+pub fn ends_sentence(text: &str) -> bool {
+    text.trim_end()
+        .trim_end_matches(['"', '\'', '”', '’', ')', ']', '」', '』'])
+        .ends_with(['.', '!', '?', '…', '。', '！', '？'])
+}
 
-    format!("{}\n", lines.join("\n").trim_end())
+/// Scripts written without spaces between words.
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}' | '\u{ff00}'..='\u{ffef}')
 }
 
-fn srt_timestamp(seconds: f64) -> String {
-    let ms = seconds_to_millis(seconds);
-    let h = ms / 3_600_000;
-    let m = (ms % 3_600_000) / 60_000;
-    let s = (ms % 60_000) / 1_000;
-    let frac = ms % 1_000;
-    format!("{h:02}:{m:02}:{s:02},{frac:03}")
+/// Converts transcript segments to SRT subtitle text.
+pub fn segments_to_srt(segments: &[TranscriptSegment], timing: SubtitleTiming) -> String {
+    CueWriter::srt(timing).write_all(segments)
+}
+
+/// Converts transcript segments to WebVTT subtitle text.
+pub fn segments_to_vtt(segments: &[TranscriptSegment], timing: SubtitleTiming) -> String {
+    CueWriter::vtt(timing).write_all(segments)
 }
 
-fn vtt_timestamp(seconds: f64) -> String {
-    let ms = seconds_to_millis(seconds);
-    let h = ms / 3_600_000;
-    let m = (ms % 3_600_000) / 60_000;
-    let s = (ms % 60_000) / 1_000;
-    let frac = ms % 1_000;
-    format!("{h:02}:{m:02}:{s:02}.{frac:03}")
+/// Writes SRT or WebVTT subtitles a few segments at a time.
+///
+/// [`header`](Self::header), then every [`write`](Self::write), then
+/// [`finish`](Self::finish) add up to the whole file, so a streamed response
+/// matches the buffered one. Segments with no text get no cue, but still
+/// count towards SRT cue numbers.
+#[derive(Debug)]
+pub struct CueWriter {
+    vtt: bool,
+    timing: SubtitleTiming,
+    segments: usize,
+    cues: usize,
+}
+
+impl CueWriter {
+    /// Writes SRT cues.
+    pub fn srt(timing: SubtitleTiming) -> Self {
+        Self {
+            vtt: false,
+            timing,
+            segments: 0,
+            cues: 0,
+        }
+    }
+
+    /// Writes WebVTT cues.
+    pub fn vtt(timing: SubtitleTiming) -> Self {
+        Self {
+            vtt: true,
+            ..Self::srt(timing)
+        }
+    }
+
+    /// Text before the first cue.
+    pub fn header(&self) -> &'static str {
+        if self.vtt {
+            "WEBVTT\n"
+        } else {
+            ""
+        }
+    }
+
+    /// Cues for the next `segments`, each preceded by the blank line that
+    /// separates it from the header or the previous cue.
+    pub fn write(&mut self, segments: &[TranscriptSegment]) -> String {
+        let mut out = String::new();
+        for seg in segments {
+            self.segments += 1;
+            let text = seg.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if self.vtt || self.cues > 0 {
+                out.push('\n');
+            }
+            self.cues += 1;
+            let separator = if self.vtt { '.' } else { ',' };
+            if !self.vtt {
+                out.push_str(&format!("{}\n", self.segments));
+            }
+            out.push_str(&format!(
+                "{} --> {}\n{text}\n",
+                cue_timestamp(seg.start_secs, self.timing, separator),
+                cue_timestamp(seg.end_secs, self.timing, separator)
+            ));
+        }
+        out
+    }
+
+    /// Text after the last cue: an SRT file with no cues is a single newline.
+    pub fn finish(&self) -> &'static str {
+        if self.vtt || self.cues > 0 {
+            ""
+        } else {
+            "\n"
+        }
+    }
+
+    fn write_all(mut self, segments: &[TranscriptSegment]) -> String {
+        let cues = self.write(segments);
+        format!("{}{cues}{}", self.header(), self.finish())
+    }
+}
+
+/// Formats a cue time; `separator` precedes the milliseconds (`,` in SRT, `.` in WebVTT).
+fn cue_timestamp(seconds: f64, timing: SubtitleTiming, separator: char) -> String {
+    match timing {
+        SubtitleTiming::Millis => {
+            let ms = seconds_to_millis(seconds);
+            let h = ms / 3_600_000;
+            let m = (ms % 3_600_000) / 60_000;
+            let s = (ms % 60_000) / 1_000;
+            let frac = ms % 1_000;
+            format!("{h:02}:{m:02}:{s:02}{separator}{frac:03}")
+        }
+        SubtitleTiming::Smpte { rate, drop_frame } => smpte_timecode(seconds, rate, drop_frame),
+    }
+}
+
+/// Formats the frame nearest to `seconds` as an SMPTE timecode.
+///
+/// Non-drop timecode counts every frame, so at 29.97 fps it falls behind the
+/// clock by 3.6 seconds an hour. Drop-frame timecode skips frame numbers 0
+/// and 1 (0 to 3 at 59.94 fps) at the start of every minute not divisible by
+/// ten to stay in step.
+fn smpte_timecode(seconds: f64, rate: FrameRate, drop_frame: bool) -> String {
+    let nominal = rate.nominal();
+    let fps = match rate.fractional() {
+        true => nominal as f64 * 1000.0 / 1001.0,
+        false => nominal as f64,
+    };
+    let mut frame = if seconds > 0.0 {
+        (seconds * fps).round() as u64
+    } else {
+        0
+    };
+    let separator = if drop_frame {
+        let dropped = nominal / 15;
+        let per_minute = nominal * 60 - dropped;
+        let per_ten_minutes = per_minute * 10 + dropped;
+        let (tens, rest) = (frame / per_ten_minutes, frame % per_ten_minutes);
+        frame += dropped * 9 * tens;
+        if rest > dropped {
+            frame += dropped * ((rest - dropped) / per_minute);
+        }
+        ';'
+    } else {
+        ':'
+    };
+    let ff = frame % nominal;
+    let total_secs = frame / nominal;
+    let (h, m, s) = (
+        total_secs / 3_600,
+        (total_secs % 3_600) / 60,
+        total_secs % 60,
+    );
+    format!("{h:02}:{m:02}:{s:02}{separator}{ff:02}")
 }
 
 fn seconds_to_millis(seconds: f64) -> u64 {
@@ -128,6 +514,26 @@ fn seconds_to_millis(seconds: f64) -> u64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn sanitize_timestamps_fixes_inverted_overlapping_and_late_segments() {
+        let mut segments = vec![
+            segment(0.0, 2.0, "a"),
+            segment(1.5, 3.0, "b"),
+            segment(4.0, 3.5, "c"),
+            segment(f64::NAN, 9.0, "d"),
+            segment(4.5, 5.0, "e"),
+        ];
+        assert_eq!(sanitize_timestamps(&mut segments, 6.0), 4);
+        let times = segments
+            .iter()
+            .map(|seg| (seg.start_secs, seg.end_secs))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [(0.0, 2.0), (2.0, 3.0), (4.0, 4.0), (4.0, 6.0), (6.0, 6.0)]
+        );
+    }
+
     #[test]
     fn response_format_parse() {
         assert!(matches!(
@@ -144,4 +550,158 @@ mod tests {
             "hello world again"
         );
     }
+
+    #[test]
+    fn text_normalization_options_keep_layout() {
+        let lyrics = [
+            segment(0.0, 2.0, " - Roses  are red,\n"),
+            segment(2.0, 4.0, " ...violets\tare blue"),
+            segment(4.0, 5.0, " Cafe\u{301}"),
+        ];
+        assert_eq!(
+            TextNormalization::default().join_segments(&lyrics),
+            "- Roses are red, ...violets are blue Cafe\u{301}"
+        );
+
+        let layout = TextNormalization {
+            keep_newlines: true,
+            keep_spaces: true,
+            nfc: true,
+            strip_leading_punctuation: true,
+        };
+        assert_eq!(
+            layout.join_segments(&lyrics),
+            "Roses  are red,\nviolets\tare blue\nCaf\u{e9}"
+        );
+        assert_eq!(layout.normalize("\u{bf}Qu\u{e9}?"), "\u{bf}Qu\u{e9}?");
+
+        let mut result = TranscriptResult {
+            text: String::new(),
+            language: None,
+            segments: lyrics.to_vec(),
+            no_speech: None,
+            language_probs: Vec::new(),
+            usage: None,
+            warnings: Vec::new(),
+        };
+        layout.apply(&mut result);
+        assert_eq!(result.segments[1].text, "violets\tare blue");
+        assert_eq!(result.text, "Roses  are red,\nviolets\tare blue\nCaf\u{e9}");
+    }
+
+    fn segment(start_secs: f64, end_secs: f64, text: &str) -> TranscriptSegment {
+        TranscriptSegment {
+            avg_logprob: Some(-0.5),
+            token_count: 2,
+            ..TranscriptSegment::new(start_secs, end_secs, text)
+        }
+    }
+
+    #[test]
+    fn compression_ratio_flags_repetitive_text() {
+        assert_eq!(compression_ratio(""), 0.0);
+        assert!(compression_ratio("The quick brown fox jumps over the lazy dog.") < 1.5);
+        assert!(compression_ratio(&"Thank you. ".repeat(20)) > 2.4);
+    }
+
+    #[test]
+    fn merge_segments_joins_fragments_into_sentences() {
+        let merged = merge_segments(vec![
+            segment(0.0, 0.8, " So"),
+            segment(0.8, 2.5, " we went to"),
+            segment(2.6, 4.0, " the market."),
+            segment(4.1, 6.0, " It was closed."),
+            segment(9.0, 10.0, " Later"),
+            segment(12.5, 13.0, " that day."),
+        ]);
+        let texts = merged
+            .iter()
+            .map(|seg| seg.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                " So we went to the market.",
+                " It was closed.",
+                " Later",
+                " that day."
+            ]
+        );
+        assert_eq!(merged[0].start_secs, 0.0);
+        assert_eq!(merged[0].end_secs, 4.0);
+        assert_eq!(merged[0].token_count, 6);
+        assert_eq!(merged[0].avg_logprob, Some(-0.5));
+
+        let mut first = segment(4.5, 5.0, " and");
+        first.no_speech_prob = Some(0.1);
+        first.tokens = vec![1, 2];
+        let mut second = segment(5.0, 6.0, " then.");
+        second.no_speech_prob = Some(0.3);
+        second.tokens = vec![3];
+        let joined = merge_segments(vec![first, second]);
+        assert_eq!(joined[0].no_speech_prob, Some(0.3));
+        assert_eq!(joined[0].tokens, vec![1, 2, 3]);
+
+        let cjk = merge_segments(vec![segment(0.0, 0.5, "你好"), segment(0.5, 1.0, "世界。")]);
+        assert_eq!(cjk[0].text, "你好世界。");
+        assert!(ends_sentence("He said \"stop.\""));
+    }
+
+    #[test]
+    fn subtitles_use_smpte_timecodes_when_requested() {
+        let segments = vec![segment(1.48, 2.0, " Hi.")];
+        let timing = SubtitleTiming::smpte(FrameRate::Fps25, false).expect("timing");
+        assert_eq!(
+            segments_to_srt(&segments, timing),
+            "1\n00:00:01:12 --> 00:00:02:00\nHi.\n"
+        );
+        assert_eq!(
+            segments_to_vtt(&segments, SubtitleTiming::Millis),
+            "WEBVTT\n\n00:00:01.480 --> 00:00:02.000\nHi.\n"
+        );
+        assert!(SubtitleTiming::smpte(FrameRate::Fps25, true).is_err());
+    }
+
+    #[test]
+    fn cue_writers_add_up_to_the_whole_file() {
+        let segments = vec![
+            segment(0.0, 1.0, " One."),
+            segment(1.0, 2.0, " "),
+            segment(2.0, 3.0, " Three."),
+        ];
+        for vtt in [false, true] {
+            let writer = || match vtt {
+                false => CueWriter::srt(SubtitleTiming::Millis),
+                true => CueWriter::vtt(SubtitleTiming::Millis),
+            };
+            let whole = writer().write_all(&segments);
+            let mut pieces = writer();
+            let mut streamed = pieces.header().to_string();
+            for seg in &segments {
+                streamed.push_str(&pieces.write(std::slice::from_ref(seg)));
+            }
+            streamed.push_str(pieces.finish());
+            assert_eq!(streamed, whole);
+        }
+        assert!(segments_to_srt(&segments, SubtitleTiming::Millis).contains("\n\n3\n"));
+        assert_eq!(segments_to_srt(&[], SubtitleTiming::Millis), "\n");
+        assert_eq!(segments_to_vtt(&[], SubtitleTiming::Millis), "WEBVTT\n");
+    }
+
+    #[test]
+    fn drop_frame_timecode_skips_frame_numbers_each_minute() {
+        let at = |frame: f64, rate: FrameRate, drop_frame: bool| {
+            let fps = match rate {
+                FrameRate::Fps29_97 => 30_000.0 / 1001.0,
+                _ => 60_000.0 / 1001.0,
+            };
+            smpte_timecode(frame / fps, rate, drop_frame)
+        };
+        assert_eq!(at(1_799.0, FrameRate::Fps29_97, true), "00:00:59;29");
+        assert_eq!(at(1_800.0, FrameRate::Fps29_97, true), "00:01:00;02");
+        assert_eq!(at(17_982.0, FrameRate::Fps29_97, true), "00:10:00;00");
+        assert_eq!(at(107_892.0, FrameRate::Fps29_97, true), "01:00:00;00");
+        assert_eq!(at(107_892.0, FrameRate::Fps29_97, false), "00:59:56:12");
+        assert_eq!(at(3_600.0, FrameRate::Fps59_94, true), "00:01:00;04");
+    }
 }
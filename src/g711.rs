@@ -0,0 +1,319 @@
+//! G.711 mu-law and A-law decoding for telephony recordings.
+//!
+//! SIP and voicemail systems store 8 kHz G.711 audio as WAV files whose `fmt`
+//! chunk lacks the size field symphonia requires, as Sun `.au` files, or as
+//! headerless `.ul`/`.al` dumps; none of these open in symphonia. Uploads are
+//! sniffed here first: G.711 audio is expanded to linear samples and returned
+//! at its own rate, which the caller resamples to 16 kHz like any other codec.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::audio::Downmix;
+use crate::error::AppError;
+
+/// WAV format tag of A-law audio.
+const WAVE_FORMAT_ALAW: u16 = 0x0006;
+/// WAV format tag of mu-law audio.
+const WAVE_FORMAT_MULAW: u16 = 0x0007;
+/// WAV format tag whose real format is in the first bytes of a sub-format GUID.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+/// `.au` encoding of 8-bit mu-law.
+const AU_ENCODING_MULAW: u32 = 1;
+/// `.au` encoding of 8-bit A-law.
+const AU_ENCODING_ALAW: u32 = 27;
+/// Sample rate of headerless G.711, as used on telephone lines.
+const TELEPHONY_SAMPLE_RATE: u32 = 8_000;
+/// Extensions of headerless mu-law files.
+const RAW_MULAW_EXTENSIONS: &[&str] = &["ul", "ulaw", "mulaw"];
+/// Extensions of headerless A-law files.
+const RAW_ALAW_EXTENSIONS: &[&str] = &["al", "alaw"];
+
+/// G.711 companding law.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Law {
+    MuLaw,
+    ALaw,
+}
+
+impl Law {
+    /// Expands one companded byte to a sample in `[-1.0, 1.0]`.
+    pub fn expand(self, byte: u8) -> f32 {
+        let linear = match self {
+            Self::MuLaw => mulaw_to_linear(byte),
+            Self::ALaw => alaw_to_linear(byte),
+        };
+        f32::from(linear) / 32_768.0
+    }
+}
+
+/// G.711 audio found in an upload.
+struct G711Stream {
+    law: Law,
+    channels: usize,
+    sample_rate: u32,
+    /// Bytes of audio data, or `None` when it runs to the end of the file.
+    data_len: Option<u64>,
+}
+
+/// Decodes `source` if it holds G.711 audio, returning mono samples and their rate.
+///
+/// Returns `Ok(None)`, with `source` rewound, for anything else.
+pub fn decode<R: Read + Seek>(
+    source: &mut R,
+    extension_hint: &str,
+    downmix: Downmix,
+) -> Result<Option<(Vec<f32>, u32)>, AppError> {
+    let stream = sniff(source, extension_hint).map_err(|err| {
+        AppError::unsupported_media_type(format!("failed to read G.711 header: {err}"))
+    })?;
+    let Some(stream) = stream else {
+        source
+            .seek(SeekFrom::Start(0))
+            .map_err(|err| AppError::internal(format!("failed to rewind upload: {err}")))?;
+        return Ok(None);
+    };
+    if stream.channels == 0 || stream.sample_rate == 0 {
+        return Err(AppError::unsupported_media_type(
+            "G.711 header declares no channels or a zero sample rate",
+        ));
+    }
+
+    let mut data = Vec::new();
+    let read = match stream.data_len {
+        Some(len) => source.take(len).read_to_end(&mut data),
+        None => source.read_to_end(&mut data),
+    };
+    read.map_err(|err| {
+        AppError::unsupported_media_type(format!("failed to read G.711 audio: {err}"))
+    })?;
+    let frames = data.len() / stream.channels * stream.channels;
+    let interleaved: Vec<f32> = data[..frames]
+        .iter()
+        .map(|&byte| stream.law.expand(byte))
+        .collect();
+    let mut mono = Vec::with_capacity(frames / stream.channels);
+    downmix.apply(&interleaved, stream.channels, &mut mono)?;
+    Ok(Some((mono, stream.sample_rate)))
+}
+
+/// Reads the header of a G.711 WAV or `.au` file, or recognizes a headerless
+/// file by its extension, leaving `source` at the start of the audio data.
+fn sniff<R: Read + Seek>(source: &mut R, extension_hint: &str) -> io::Result<Option<G711Stream>> {
+    let extension = extension_hint.to_ascii_lowercase();
+    let raw_law = if RAW_MULAW_EXTENSIONS.contains(&extension.as_str()) {
+        Some(Law::MuLaw)
+    } else if RAW_ALAW_EXTENSIONS.contains(&extension.as_str()) {
+        Some(Law::ALaw)
+    } else {
+        None
+    };
+    if let Some(law) = raw_law {
+        return Ok(Some(G711Stream {
+            law,
+            channels: 1,
+            sample_rate: TELEPHONY_SAMPLE_RATE,
+            data_len: None,
+        }));
+    }
+
+    let mut magic = [0u8; 12];
+    if read_up_to(source, &mut magic)? < magic.len() {
+        return Ok(None);
+    }
+    if &magic[..4] == b".snd" {
+        return sniff_au(source, &magic);
+    }
+    if &magic[..4] == b"RIFF" && &magic[8..] == b"WAVE" {
+        return sniff_wav(source);
+    }
+    Ok(None)
+}
+
+/// Parses a Sun `.au` header; `head` holds its first 12 bytes.
+fn sniff_au<R: Read + Seek>(source: &mut R, head: &[u8; 12]) -> io::Result<Option<G711Stream>> {
+    let mut rest = [0u8; 12];
+    source.read_exact(&mut rest)?;
+    let be = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let (offset, size, encoding) = (be(&head[4..8]), be(&head[8..12]), be(&rest[..4]));
+    let law = match encoding {
+        AU_ENCODING_MULAW => Law::MuLaw,
+        AU_ENCODING_ALAW => Law::ALaw,
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported .au encoding {other}; only mu-law (1) and A-law (27) are"),
+            ))
+        }
+    };
+    source.seek(SeekFrom::Start(u64::from(offset.max(24))))?;
+    Ok(Some(G711Stream {
+        law,
+        channels: be(&rest[8..12]) as usize,
+        sample_rate: be(&rest[4..8]),
+        data_len: (size != u32::MAX).then_some(u64::from(size)),
+    }))
+}
+
+/// Walks the chunks of a WAV file; `None` unless its format is G.711.
+fn sniff_wav<R: Read + Seek>(source: &mut R) -> io::Result<Option<G711Stream>> {
+    let mut format = None;
+    loop {
+        let mut header = [0u8; 8];
+        if read_up_to(source, &mut header)? < header.len() {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        match &header[..4] {
+            b"fmt " => {
+                let mut fmt = vec![0u8; len as usize];
+                source.read_exact(&mut fmt)?;
+                if fmt.len() < 16 {
+                    return Ok(None);
+                }
+                let le16 = |at: usize| u16::from_le_bytes([fmt[at], fmt[at + 1]]);
+                let mut tag = le16(0);
+                if tag == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+                    tag = le16(24);
+                }
+                let law = match tag {
+                    WAVE_FORMAT_MULAW => Law::MuLaw,
+                    WAVE_FORMAT_ALAW => Law::ALaw,
+                    _ => return Ok(None),
+                };
+                format = Some((
+                    law,
+                    usize::from(le16(2)),
+                    u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+                ));
+                if len % 2 == 1 {
+                    source.seek(SeekFrom::Current(1))?;
+                }
+            }
+            b"data" => {
+                let Some((law, channels, sample_rate)) = format else {
+                    return Ok(None);
+                };
+                return Ok(Some(G711Stream {
+                    law,
+                    channels,
+                    sample_rate,
+                    // Streaming writers leave the size at 0 or u32::MAX.
+                    data_len: (len != 0 && len != u32::MAX).then_some(u64::from(len)),
+                }));
+            }
+            _ => {
+                source.seek(SeekFrom::Current(i64::from(len) + i64::from(len % 2)))?;
+            }
+        }
+    }
+}
+
+/// Fills as much of `buf` as the source has; returns the number of bytes read.
+fn read_up_to<R: Read>(source: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match source.read(&mut buf[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Expands a mu-law byte to 16-bit linear PCM (ITU-T G.711).
+fn mulaw_to_linear(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = i16::from(byte & 0x0f);
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    if byte & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Expands an A-law byte to 16-bit linear PCM (ITU-T G.711).
+fn alaw_to_linear(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = i16::from(byte & 0x0f);
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 8,
+        _ => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+    if byte & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A 16-byte-`fmt` WAV as written by many telephony systems.
+    fn g711_wav(tag: u16, data: &[u8]) -> Vec<u8> {
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(4 + 8 + 16 + 8 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&tag.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&8_000u32.to_le_bytes());
+        wav.extend_from_slice(&8_000u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&8u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(data);
+        wav
+    }
+
+    #[test]
+    fn expands_reference_codewords() {
+        assert_eq!(mulaw_to_linear(0xff), 0);
+        assert_eq!(mulaw_to_linear(0x80), 32_124);
+        assert_eq!(mulaw_to_linear(0x00), -32_124);
+        assert_eq!(alaw_to_linear(0xd5), 8);
+        assert_eq!(alaw_to_linear(0xaa), 32_256);
+        assert_eq!(alaw_to_linear(0x2a), -32_256);
+    }
+
+    #[test]
+    fn decodes_wav_au_and_headerless_g711() {
+        let mut wav = Cursor::new(g711_wav(WAVE_FORMAT_MULAW, &[0x80, 0xff, 0x00]));
+        let (samples, rate) = decode(&mut wav, "wav", Downmix::Average)
+            .expect("decode")
+            .expect("g711");
+        assert_eq!(rate, 8_000);
+        assert_eq!(samples.len(), 3);
+        assert!(samples[0] > 0.98 && samples[1] == 0.0 && samples[2] < -0.98);
+
+        let mut au = b".snd".to_vec();
+        for field in [24u32, 2, AU_ENCODING_ALAW, 8_000, 1] {
+            au.extend_from_slice(&field.to_be_bytes());
+        }
+        au.extend_from_slice(&[0xaa, 0xd5]);
+        let (samples, _) = decode(&mut Cursor::new(au), "au", Downmix::Average)
+            .expect("decode")
+            .expect("g711");
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0] > 0.98);
+
+        let (samples, rate) = decode(&mut Cursor::new(vec![0xffu8; 80]), "ul", Downmix::Average)
+            .expect("decode")
+            .expect("g711");
+        assert_eq!((samples.len(), rate), (80, 8_000));
+
+        let mut pcm = Cursor::new(g711_wav(1, &[0, 0]));
+        assert!(decode(&mut pcm, "wav", Downmix::Average)
+            .expect("decode")
+            .is_none());
+        assert_eq!(pcm.position(), 0);
+    }
+}
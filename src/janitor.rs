@@ -0,0 +1,296 @@
+//! Removal of temporary files that crashed or killed processes leave behind.
+//!
+//! Model downloads, spooled uploads, and storage writes all go to a temporary
+//! file that is renamed or deleted once the work finishes, so a crash strands
+//! it. The janitor sweeps once at startup, before any model is downloaded,
+//! and then every `JANITOR_INTERVAL_SECS`, removing:
+//!
+//! - `.part` files and directories under `WHISPER_CACHE_DIR`,
+//! - spooled uploads (`.tmp*`) in `UPLOAD_SPILL_DIR`,
+//! - `.part` values in directory-backed `ASYNC_JOB_DIR`,
+//!   `TRANSCRIPT_ARCHIVE_DIR`, and `DEBUG_TRACE_DIR`,
+//! - debug traces older than `DEBUG_TRACE_RETENTION_SECS`.
+//!
+//! Each periodic sweep also drops async jobs past `ASYNC_JOB_RETENTION_SECS`
+//! and reports the store's evictions as the `async_jobs_evicted` metric,
+//! tagged by reason.
+//!
+//! Temporary files count as abandoned only after [`STALE_AFTER`] without a
+//! write, so work in progress, including in other processes sharing the
+//! directories, is left alone. Removed files and reclaimed bytes are logged
+//! and counted in the `janitor_files_removed` and `janitor_bytes_reclaimed`
+//! metrics, tagged by kind.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::api::AppState;
+use crate::config::AppConfig;
+use crate::jobs::unix_secs;
+use crate::storage::Storage;
+
+/// How long a temporary file must go unmodified before it counts as abandoned.
+pub const STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// Files removed and bytes freed by a sweep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reclaimed {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Reclaimed {
+    fn add(&mut self, other: Reclaimed) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+/// What one sweep removed, by kind.
+#[derive(Debug, Default)]
+pub struct Sweep {
+    /// Abandoned model downloads.
+    pub downloads: Reclaimed,
+    /// Abandoned spooled uploads.
+    pub spool: Reclaimed,
+    /// Partially written job, archive, and trace values.
+    pub storage: Reclaimed,
+    /// Expired debug traces; their size is not known, so `bytes` stays 0.
+    pub traces: Reclaimed,
+}
+
+impl Sweep {
+    fn kinds(&self) -> [(&'static str, Reclaimed); 4] {
+        [
+            ("download", self.downloads),
+            ("spool", self.spool),
+            ("storage", self.storage),
+            ("trace", self.traces),
+        ]
+    }
+}
+
+/// Removes abandoned temporary files and expired debug traces once.
+///
+/// Failures are logged and the rest of the sweep carries on.
+pub fn sweep(cfg: &AppConfig) -> Sweep {
+    let mut sweep = Sweep {
+        downloads: or_warn(
+            "download",
+            remove_stale(Path::new(&cfg.whisper_cache_dir), is_partial, STALE_AFTER),
+        ),
+        ..Sweep::default()
+    };
+    if let Some(dir) = &cfg.upload_spill_dir {
+        sweep.spool = or_warn(
+            "spool",
+            remove_stale(Path::new(dir), is_spooled_upload, STALE_AFTER),
+        );
+    }
+    let storages = [
+        &cfg.async_job_storage,
+        &cfg.transcript_archive_storage,
+        &cfg.debug_trace_storage,
+    ];
+    for storage in storages.into_iter().flatten() {
+        sweep
+            .storage
+            .add(or_warn("storage", storage.remove_abandoned(STALE_AFTER)));
+    }
+    if let (Some(storage), Some(retention)) = (&cfg.debug_trace_storage, cfg.debug_trace_retention)
+    {
+        sweep.traces = or_warn("trace", expire(storage.as_ref(), retention));
+    }
+    sweep
+}
+
+/// Reports the startup sweep and sweeps again every `JANITOR_INTERVAL_SECS`.
+pub fn spawn(state: Arc<AppState>, startup: Sweep) {
+    tokio::spawn(async move {
+        report(&state, &startup);
+        let mut ticker = tokio::time::interval(state.cfg.janitor_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes at once; the startup sweep covered it.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let sweeping = Arc::clone(&state);
+            match tokio::task::spawn_blocking(move || sweep(&sweeping.cfg)).await {
+                Ok(sweep) => report(&state, &sweep),
+                Err(err) => warn!(error = %err, "janitor sweep panicked"),
+            }
+            state.jobs.expire();
+            report_evictions(&state);
+        }
+    });
+}
+
+fn report(state: &AppState, sweep: &Sweep) {
+    for (kind, reclaimed) in sweep.kinds() {
+        if reclaimed.files == 0 {
+            continue;
+        }
+        info!(
+            kind,
+            files = reclaimed.files,
+            bytes = reclaimed.bytes,
+            "janitor removed abandoned files"
+        );
+        let tags = [("kind", kind)];
+        state
+            .metrics
+            .count("janitor_files_removed", reclaimed.files, &tags);
+        state
+            .metrics
+            .count("janitor_bytes_reclaimed", reclaimed.bytes, &tags);
+    }
+}
+
+fn report_evictions(state: &AppState) {
+    for (reason, jobs) in state.jobs.take_evictions().reasons() {
+        if jobs == 0 {
+            continue;
+        }
+        info!(reason, jobs, "evicted finished async jobs");
+        state
+            .metrics
+            .count("async_jobs_evicted", jobs, &[("reason", reason)]);
+    }
+}
+
+fn or_warn(kind: &str, result: io::Result<Reclaimed>) -> Reclaimed {
+    result.unwrap_or_else(|err| {
+        warn!(kind, error = %err, "janitor sweep failed");
+        Reclaimed::default()
+    })
+}
+
+/// Whether `name` is an in-progress model download.
+fn is_partial(name: &str) -> bool {
+    name.ends_with(".part")
+}
+
+/// Whether `name` is an upload spooled by `tempfile`.
+fn is_spooled_upload(name: &str) -> bool {
+    name.starts_with(".tmp")
+}
+
+/// Removes entries under `dir` whose name passes `is_debris` and that nothing
+/// has written to for `stale_after`; directories are measured by their newest
+/// file. Other directories are searched recursively; symlinks are not followed.
+pub fn remove_stale(
+    dir: &Path,
+    is_debris: fn(&str) -> bool,
+    stale_after: Duration,
+) -> io::Result<Reclaimed> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Reclaimed::default()),
+        Err(err) => return Err(err),
+    };
+    let now = SystemTime::now();
+    let mut reclaimed = Reclaimed::default();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let debris = entry.file_name().to_str().is_some_and(is_debris);
+        if !debris {
+            if file_type.is_dir() {
+                reclaimed.add(remove_stale(&path, is_debris, stale_after)?);
+            }
+            continue;
+        }
+        let (size, modified) = measure(&path)?;
+        let idle = now.duration_since(modified).unwrap_or_default();
+        if idle < stale_after {
+            continue;
+        }
+        if file_type.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        reclaimed.add(Reclaimed {
+            files: 1,
+            bytes: size,
+        });
+    }
+    Ok(reclaimed)
+}
+
+/// Total size and latest modification time of a file or directory tree.
+fn measure(path: &Path) -> io::Result<(u64, SystemTime)> {
+    let metadata = fs::symlink_metadata(path)?;
+    let mut size = metadata.len();
+    let mut modified = metadata.modified()?;
+    if metadata.is_dir() {
+        size = 0;
+        for entry in fs::read_dir(path)? {
+            let (entry_size, entry_modified) = measure(&entry?.path())?;
+            size += entry_size;
+            modified = modified.max(entry_modified);
+        }
+    }
+    Ok((size, modified))
+}
+
+/// Deletes stored values last written more than `retention` ago.
+fn expire(storage: &dyn Storage, retention: Duration) -> io::Result<Reclaimed> {
+    let cutoff = unix_secs().saturating_sub(retention.as_secs());
+    let mut reclaimed = Reclaimed::default();
+    for object in storage.list("")? {
+        if object.modified < cutoff && storage.delete(&object.key)? {
+            reclaimed.files += 1;
+        }
+    }
+    Ok(reclaimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_stale_debris_is_removed() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let nested = dir.path().join("ggml-org/whisper-vad");
+        fs::create_dir_all(nested.join("faster-whisper-small.part/encoder")).expect("mkdir");
+        fs::write(nested.join("ggml-silero.part"), [0u8; 100]).expect("write");
+        fs::write(
+            nested.join("faster-whisper-small.part/encoder/model.onnx"),
+            [0u8; 50],
+        )
+        .expect("write");
+        fs::write(nested.join("ggml-silero.bin"), [0u8; 10]).expect("write");
+        fs::write(dir.path().join("ggml-small.bin.lock"), b"pid=1").expect("write");
+
+        let fresh = remove_stale(dir.path(), is_partial, STALE_AFTER).expect("sweep");
+        assert_eq!(fresh, Reclaimed::default());
+
+        let stale = remove_stale(dir.path(), is_partial, Duration::ZERO).expect("sweep");
+        assert_eq!(
+            stale,
+            Reclaimed {
+                files: 2,
+                bytes: 150
+            }
+        );
+        assert!(!nested.join("ggml-silero.part").exists());
+        assert!(!nested.join("faster-whisper-small.part").exists());
+        assert!(nested.join("ggml-silero.bin").exists());
+        assert!(dir.path().join("ggml-small.bin.lock").exists());
+
+        let missing = dir.path().join("missing");
+        assert_eq!(
+            remove_stale(&missing, is_spooled_upload, Duration::ZERO).expect("missing"),
+            Reclaimed::default()
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Persistent spool of queued async jobs.
+//!
+//! When `ASYNC_JOB_DIR` is set, every async job is written to its storage
+//! (see `storage`) as `<job id>/job.json` (the parsed form fields) plus one
+//! `<job id>/file-<n>` per uploaded file before `202 Accepted` is returned.
+//! The manifest is removed once the job takes an inference worker and the
+//! uploads once the job finishes, so after a restart the jobs that still have
+//! a manifest are exactly the jobs that were queued but never started; the
+//! server resubmits them under their original ids. A finished job's
+//! `result.json` belongs to the job store (see `jobs`) and is left alone.
+//!
+//! With storage encryption configured, manifests and uploads are sealed. A
+//! manifest that cannot be opened with the current key fails startup instead
+//! of being discarded, so changing the key never silently drops queued jobs.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::{is_sealed, StorageCipher};
+use crate::jobs::result_key;
+use crate::storage::Storage;
+use crate::upload::UploadBody;
+
+/// Name of the manifest marking a job as queued.
+const MANIFEST: &str = "job.json";
+
+/// Form fields of a queued job, as persisted in its manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledJob {
+    pub id: String,
+    pub task: String,
+    pub created_at: u64,
+    pub model: String,
+    pub language: Option<String>,
+    pub prompt: Option<String>,
+    pub response_format: String,
+    pub temperature: Option<f32>,
+    pub max_tokens_per_segment: Option<u32>,
+    /// Beam width; absent in manifests written before beam search existed.
+    #[serde(default)]
+    pub beam_size: Option<u32>,
+    /// Greedy candidate count; absent in manifests written before `best_of` existed.
+    #[serde(default)]
+    pub best_of: Option<u32>,
+    pub merge_segments: bool,
+    /// Named `restore_punctuation` in manifests written before the rename.
+    #[serde(alias = "restore_punctuation")]
+    pub heuristic_punctuation: bool,
+    pub metadata: Option<String>,
+    /// Worker pool (`cpu` or `gpu`); absent in manifests written before routing existed.
+    #[serde(default)]
+    pub compute: Option<String>,
+    /// Whether `include[]=logprobs` was requested.
+    #[serde(default)]
+    pub include_logprobs: bool,
+    /// Whether `timestamp_granularities[]=word` was requested.
+    #[serde(default)]
+    pub word_timestamps: bool,
+    /// Whether long silences are cut before inference.
+    #[serde(default)]
+    pub vad_filter: bool,
+    /// VAD silence length and padding; absent in manifests written before presets existed.
+    #[serde(default)]
+    pub vad_min_silence_ms: Option<u32>,
+    #[serde(default)]
+    pub vad_padding_ms: Option<u32>,
+    /// Resampler profile; absent in manifests written before profiles existed.
+    #[serde(default)]
+    pub resample_quality: Option<String>,
+    /// Whether RNNoise noise suppression runs after decoding.
+    #[serde(default)]
+    pub denoise: bool,
+    /// Channel selected from multichannel audio; absent when channels are averaged.
+    #[serde(default)]
+    pub channel: Option<usize>,
+    /// SMPTE frame rate of subtitle cue times; absent for millisecond timing.
+    #[serde(default)]
+    pub frame_rate: Option<String>,
+    /// Whether SMPTE cue times use drop-frame counting.
+    #[serde(default)]
+    pub drop_frame: bool,
+    /// Label of the API key that submitted the job.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub files: Vec<SpooledFile>,
+}
+
+/// An uploaded file of a queued job; its bytes are stored as `file-<index>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledFile {
+    pub filename: String,
+    pub extension: String,
+}
+
+/// Persists queued async jobs so they survive a restart.
+pub struct JobSpool {
+    storage: Arc<dyn Storage>,
+    cipher: Option<StorageCipher>,
+}
+
+/// An upload on its way into the spool.
+enum PendingUpload {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl JobSpool {
+    /// Creates a spool keeping jobs in `storage`.
+    pub fn new(storage: Arc<dyn Storage>, cipher: Option<StorageCipher>) -> Self {
+        Self { storage, cipher }
+    }
+
+    /// Writes the uploads and then the manifest of a newly queued job.
+    ///
+    /// The manifest is written last so a crash mid-write never leaves a job
+    /// that looks resumable but is missing audio.
+    pub async fn save(&self, job: &SpooledJob, bodies: &[&UploadBody]) -> io::Result<()> {
+        let uploads = bodies
+            .iter()
+            .map(|body| match body {
+                UploadBody::Memory(bytes) => match &self.cipher {
+                    Some(cipher) => cipher.seal(bytes).map(PendingUpload::Bytes),
+                    None => Ok(PendingUpload::Bytes(bytes.clone())),
+                },
+                // Files on disk are already sealed with the same key when encryption is on.
+                UploadBody::Spooled { file, .. } => Ok(PendingUpload::File(file.path().into())),
+                UploadBody::Stored { path, .. } => Ok(PendingUpload::File(path.clone())),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut manifest = serde_json::to_vec_pretty(job)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(cipher) = &self.cipher {
+            manifest = cipher.seal(&manifest)?;
+        }
+        let storage = Arc::clone(&self.storage);
+        let id = job.id.clone();
+        tokio::task::spawn_blocking(move || {
+            for (index, upload) in uploads.iter().enumerate() {
+                match upload {
+                    PendingUpload::Bytes(bytes) => storage.put(&upload_key(&id, index), bytes)?,
+                    PendingUpload::File(path) => storage.put_file(&upload_key(&id, index), path)?,
+                }
+            }
+            storage.put(&manifest_key(&id), &manifest)
+        })
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    }
+
+    /// Marks a job as started, so it is not resubmitted after a restart.
+    pub fn mark_started(&self, id: &str) {
+        let _ = self.storage.delete(&manifest_key(id));
+    }
+
+    /// Deletes the manifest and uploads of a finished job, keeping its result.
+    pub fn remove(&self, id: &str) {
+        let finished = self
+            .storage
+            .list(&job_prefix(id))
+            .is_ok_and(|objects| objects.iter().any(|object| object.key == result_key(id)));
+        if finished {
+            let _ = self.storage.delete(&manifest_key(id));
+            let _ = self.storage.delete_prefix(&upload_prefix(id));
+        } else {
+            let _ = self.storage.delete_prefix(&job_prefix(id));
+        }
+    }
+
+    /// Deletes whatever is stored for the jobs `ids` and returns how many had
+    /// anything stored; with `dry_run` set, they are only counted.
+    pub fn purge(&self, ids: &[String], dry_run: bool) -> usize {
+        let stored = ids
+            .iter()
+            .filter(|id| {
+                self.storage
+                    .list(&job_prefix(id))
+                    .is_ok_and(|objects| objects.iter().any(|object| object.key != result_key(id)))
+            })
+            .collect::<Vec<_>>();
+        if !dry_run {
+            for id in &stored {
+                self.remove(id);
+            }
+        }
+        stored.len()
+    }
+
+    /// Loads every queued job with its uploads, oldest first.
+    ///
+    /// Started or partially written jobs are deleted. Uploads kept as local
+    /// files stay in place until [`JobSpool::remove`]; others are copied to
+    /// temporary files. Fails when a sealed manifest cannot be opened with
+    /// the configured key.
+    pub fn load_queued(&self) -> io::Result<Vec<(SpooledJob, Vec<UploadBody>)>> {
+        let ids = self
+            .storage
+            .list("")?
+            .into_iter()
+            .filter_map(|object| object.key.split_once('/').map(|(id, _)| id.to_string()))
+            .collect::<BTreeSet<_>>();
+        let mut jobs = Vec::new();
+        for id in ids {
+            match self.load_job(&id) {
+                Ok(Some(job)) => jobs.push(job),
+                Ok(None) => self.remove(&id),
+                Err(err) => return Err(err),
+            }
+        }
+        jobs.sort_by_key(|(job, _)| job.created_at);
+        Ok(jobs)
+    }
+
+    /// Loads one stored job; `None` when it holds no usable queued job.
+    fn load_job(&self, id: &str) -> io::Result<Option<(SpooledJob, Vec<UploadBody>)>> {
+        let Ok(Some(mut manifest)) = self.storage.get(&manifest_key(id)) else {
+            return Ok(None);
+        };
+        match (&self.cipher, is_sealed(&manifest)) {
+            (Some(cipher), _) => {
+                manifest = cipher
+                    .open(&manifest)
+                    .map_err(|err| io::Error::new(err.kind(), format!("job {id}: {err}")))?;
+            }
+            (None, true) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("job {id} is sealed; set the storage encryption key to resume it"),
+                ));
+            }
+            (None, false) => {}
+        }
+        let Ok(job) = serde_json::from_slice::<SpooledJob>(&manifest) else {
+            return Ok(None);
+        };
+        if job.id != id {
+            return Ok(None);
+        }
+        let bodies = (0..job.files.len())
+            .map(|index| self.load_upload(&upload_key(id, index)))
+            .collect::<io::Result<Vec<_>>>();
+        Ok(bodies.ok().map(|bodies| (job, bodies)))
+    }
+
+    /// Opens one stored upload, as sealed as it was written.
+    fn load_upload(&self, key: &str) -> io::Result<UploadBody> {
+        if let Some(path) = self.storage.local_path(key) {
+            let len = std::fs::metadata(&path)?.len() as usize;
+            return Ok(UploadBody::Stored {
+                path,
+                len,
+                cipher: self.cipher.clone(),
+            });
+        }
+        let bytes = self
+            .storage
+            .get(key)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{key} is missing")))?;
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(&bytes)?;
+        Ok(UploadBody::Spooled {
+            file,
+            len: bytes.len(),
+            cipher: self.cipher.clone(),
+        })
+    }
+}
+
+fn job_prefix(id: &str) -> String {
+    format!("{id}/")
+}
+
+fn manifest_key(id: &str) -> String {
+    format!("{id}/{MANIFEST}")
+}
+
+fn upload_key(id: &str, index: usize) -> String {
+    format!("{id}/file-{index}")
+}
+
+fn upload_prefix(id: &str) -> String {
+    format!("{id}/file-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    fn local(dir: &std::path::Path) -> Arc<dyn Storage> {
+        Arc::new(LocalStorage::new(dir))
+    }
+
+    fn job(id: &str) -> SpooledJob {
+        SpooledJob {
+            id: id.to_string(),
+            task: "transcribe".to_string(),
+            created_at: 1_700_000_000,
+            model: "whisper-1".to_string(),
+            language: Some("en".to_string()),
+            prompt: None,
+            response_format: "json".to_string(),
+            temperature: None,
+            max_tokens_per_segment: None,
+            beam_size: None,
+            best_of: None,
+            merge_segments: false,
+            heuristic_punctuation: false,
+            vad_filter: false,
+            vad_min_silence_ms: None,
+            vad_padding_ms: None,
+            resample_quality: None,
+            denoise: false,
+            channel: None,
+            metadata: Some("rec-42".to_string()),
+            compute: None,
+            include_logprobs: false,
+            word_timestamps: false,
+            frame_rate: None,
+            drop_frame: false,
+            api_key: None,
+            files: vec![SpooledFile {
+                filename: "a.wav".to_string(),
+                extension: "wav".to_string(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn only_unstarted_jobs_are_reloaded() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let spool = JobSpool::new(local(dir.path()), None);
+        let audio = UploadBody::Memory(b"RIFF".to_vec());
+        spool.save(&job("job_a"), &[&audio]).await.expect("save a");
+        spool.save(&job("job_b"), &[&audio]).await.expect("save b");
+        spool.mark_started("job_b");
+
+        let queued = spool.load_queued().expect("load");
+        assert_eq!(queued.len(), 1);
+        let (loaded, bodies) = &queued[0];
+        assert_eq!(loaded.id, "job_a");
+        assert_eq!(loaded.metadata.as_deref(), Some("rec-42"));
+        assert_eq!(bodies[0].len(), 4);
+        assert!(!dir.path().join("job_b").exists());
+
+        spool.remove("job_a");
+        assert!(spool.load_queued().expect("load").is_empty());
+    }
+
+    #[tokio::test]
+    async fn sealed_jobs_need_the_same_key() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let key = |byte| {
+            StorageCipher::from_key_material(&format!("{byte:02x}").repeat(32)).expect("key")
+        };
+        let spool = JobSpool::new(local(dir.path()), Some(key(1)));
+        let audio = UploadBody::Memory(b"RIFF".to_vec());
+        spool.save(&job("job_a"), &[&audio]).await.expect("save");
+        let stored = std::fs::read(dir.path().join("job_a").join("file-0")).expect("read");
+        assert!(is_sealed(&stored));
+
+        let queued = spool.load_queued().expect("load");
+        assert_eq!(queued[0].0.metadata.as_deref(), Some("rec-42"));
+
+        assert!(JobSpool::new(local(dir.path()), Some(key(2)))
+            .load_queued()
+            .is_err());
+        assert!(JobSpool::new(local(dir.path()), None)
+            .load_queued()
+            .is_err());
+        assert!(dir.path().join("job_a").join(MANIFEST).exists());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_uploads_reload_as_temporary_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let storage = crate::storage::SqliteStorage::open(&dir.path().join("state.db"), "jobs")
+            .expect("open");
+        let spool = JobSpool::new(Arc::new(storage), None);
+        let audio = UploadBody::Memory(b"RIFF".to_vec());
+        spool.save(&job("job_a"), &[&audio]).await.expect("save");
+
+        let queued = spool.load_queued().expect("load");
+        match &queued[0].1[0] {
+            UploadBody::Spooled { file, .. } => {
+                assert_eq!(std::fs::read(file.path()).expect("read"), b"RIFF");
+            }
+            _ => panic!("expected a temporary file"),
+        }
+        spool.remove("job_a");
+        assert!(spool.load_queued().expect("load").is_empty());
+    }
+}
@@ -0,0 +1,916 @@
+//! In-memory store for asynchronous transcription jobs.
+//!
+//! `POST /v1/audio/transcriptions?async=true` (and the translations
+//! equivalent) registers a job and returns its id immediately, so long files
+//! do not hold a connection open past proxy timeouts. Clients poll
+//! `GET /v1/audio/jobs/{id}` until the job is `completed` or `failed`.
+//! Finished jobs are kept for `ASYNC_JOB_RETENTION_SECS` and then dropped,
+//! by the next job created or the janitor's next sweep. With
+//! `ASYNC_JOB_MAX_TOTAL_BYTES` or `ASYNC_JOB_MAX_BYTES_PER_KEY` set, the
+//! oldest finished jobs are also evicted as soon as stored results (counted
+//! by their JSON size) pass the cap, overall or for the submitting key.
+//! Evictions are counted by reason for the `async_jobs_evicted` metric.
+//! Queued jobs can also be persisted to `ASYNC_JOB_DIR` (see `job_spool`);
+//! finished jobs are then written there too, as `<job id>/result.json`, and
+//! reloaded on startup until their retention runs out. Evicted, expired, and
+//! purged jobs are deleted from storage along with memory.
+//! While a job waits for a worker, its status reports `queue_position` and
+//! `estimated_start_secs` (see `admission`); while a single-file job runs, it
+//! lists the `segments` decoded so far, which the final `result` replaces. Jobs can be deleted early with
+//! `POST /admin/purge` (see `purge`).
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::admission::{insert_queue_status, QueueSpot};
+use crate::backend::{TaskKind, TranscriptSegment};
+use crate::encryption::{is_sealed, StorageCipher};
+use crate::error::AppError;
+use crate::purge::PurgeFilter;
+use crate::storage::Storage;
+
+/// Name of the stored record of a finished job, next to its spooled files.
+pub(crate) const RESULT: &str = "result.json";
+
+/// Lifecycle state of an asynchronous job.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JobStatus {
+    /// Accepted and waiting for a free inference worker.
+    Queued,
+    /// Holding an inference worker.
+    Running,
+    /// Finished with a result.
+    Completed,
+    /// Finished with an error.
+    Failed,
+}
+
+impl JobStatus {
+    /// Returns the wire-format status used in job payloads.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+struct Job {
+    task: TaskKind,
+    status: JobStatus,
+    created_at: u64,
+    metadata: Option<String>,
+    /// Label of the API key that submitted the job.
+    api_key: Option<String>,
+    finished: Option<Instant>,
+    language: Option<String>,
+    result: Option<Value>,
+    error: Option<Value>,
+    /// Place in the admission queue while every worker is busy.
+    queue_spot: Option<QueueSpot>,
+    /// JSON size of the stored result or error.
+    bytes: usize,
+    /// Position among finished jobs, oldest first.
+    finish_order: u64,
+    /// Segments decoded so far while the job runs, in time order.
+    segments: Vec<PartialSegment>,
+}
+
+/// A segment of a running job's transcript.
+struct PartialSegment {
+    start_secs: f64,
+    end_secs: f64,
+    text: String,
+}
+
+impl Job {
+    fn queued(
+        task: TaskKind,
+        created_at: u64,
+        metadata: Option<String>,
+        api_key: Option<String>,
+    ) -> Self {
+        Self {
+            task,
+            status: JobStatus::Queued,
+            created_at,
+            metadata,
+            api_key,
+            finished: None,
+            language: None,
+            result: None,
+            error: None,
+            queue_spot: None,
+            bytes: 0,
+            finish_order: 0,
+            segments: Vec::new(),
+        }
+    }
+
+    fn expired(&self, retention: Duration) -> bool {
+        self.finished.is_some_and(|at| at.elapsed() >= retention)
+    }
+}
+
+/// Finished jobs dropped before their retention ran out, or because it did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Evictions {
+    /// Jobs past `ASYNC_JOB_RETENTION_SECS`.
+    pub expired: u64,
+    /// Jobs evicted to stay under `ASYNC_JOB_MAX_TOTAL_BYTES`.
+    pub total_bytes: u64,
+    /// Jobs evicted to keep their key under `ASYNC_JOB_MAX_BYTES_PER_KEY`.
+    pub key_bytes: u64,
+}
+
+impl Evictions {
+    /// Counts by the `reason` tag of the `async_jobs_evicted` metric.
+    pub fn reasons(&self) -> [(&'static str, u64); 3] {
+        [
+            ("expired", self.expired),
+            ("total_bytes", self.total_bytes),
+            ("key_bytes", self.key_bytes),
+        ]
+    }
+}
+
+/// A finished job as written to storage.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredJob {
+    task: String,
+    status: String,
+    created_at: u64,
+    /// Unix seconds when the job finished; retention counts from here.
+    finished_at: u64,
+    metadata: Option<String>,
+    api_key: Option<String>,
+    language: Option<String>,
+    result: Option<Value>,
+    error: Option<Value>,
+}
+
+impl StoredJob {
+    fn new(job: &Job) -> Self {
+        Self {
+            task: job.task.as_str().to_string(),
+            status: job.status.as_str().to_string(),
+            created_at: job.created_at,
+            finished_at: unix_secs(),
+            metadata: job.metadata.clone(),
+            api_key: job.api_key.clone(),
+            language: job.language.clone(),
+            result: job.result.clone(),
+            error: job.error.clone(),
+        }
+    }
+
+    /// Rebuilds the in-memory job, finished `age` ago.
+    fn into_job(self, age: Duration) -> Job {
+        let task = match self.task.as_str() {
+            "translate" => TaskKind::Translate,
+            _ => TaskKind::Transcribe,
+        };
+        let mut job = Job::queued(task, self.created_at, self.metadata, self.api_key);
+        job.status = match self.status.as_str() {
+            "failed" => JobStatus::Failed,
+            _ => JobStatus::Completed,
+        };
+        job.finished = Some(Instant::now().checked_sub(age).unwrap_or_else(Instant::now));
+        job.language = self.language;
+        job.bytes = self
+            .result
+            .as_ref()
+            .or(self.error.as_ref())
+            .map_or(0, json_len);
+        job.result = self.result;
+        job.error = self.error;
+        job
+    }
+}
+
+/// Where finished jobs are persisted, sealed when encryption is configured.
+struct ResultStorage {
+    storage: Arc<dyn Storage>,
+    cipher: Option<StorageCipher>,
+}
+
+/// Tracks asynchronous jobs and their results.
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, Job>>,
+    retention: Duration,
+    max_total_bytes: Option<usize>,
+    max_bytes_per_key: Option<usize>,
+    /// Evictions since [`JobStore::take_evictions`] last ran.
+    evictions: Mutex<Evictions>,
+    finished: AtomicU64,
+    storage: Option<ResultStorage>,
+}
+
+impl JobStore {
+    /// Creates an empty store that keeps finished jobs for `retention`.
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            retention,
+            max_total_bytes: None,
+            max_bytes_per_key: None,
+            evictions: Mutex::default(),
+            finished: AtomicU64::new(0),
+            storage: None,
+        }
+    }
+
+    /// Persists finished jobs in `storage` so they outlive a restart.
+    pub fn with_storage(
+        mut self,
+        storage: Arc<dyn Storage>,
+        cipher: Option<StorageCipher>,
+    ) -> Self {
+        self.storage = Some(ResultStorage { storage, cipher });
+        self
+    }
+
+    /// Loads the finished jobs persisted by a previous run and returns how many.
+    ///
+    /// Jobs past their retention are deleted instead, and the byte caps are
+    /// applied as if the jobs had just finished, oldest first. Fails when a
+    /// sealed record cannot be opened with the configured key.
+    pub fn load_finished(&self) -> io::Result<usize> {
+        let Some(results) = &self.storage else {
+            return Ok(0);
+        };
+        let now = unix_secs();
+        let mut stored = Vec::new();
+        for object in results.storage.list("")? {
+            let Some((id, RESULT)) = object.key.split_once('/') else {
+                continue;
+            };
+            let Some(mut contents) = results.storage.get(&object.key)? else {
+                continue;
+            };
+            match (&results.cipher, is_sealed(&contents)) {
+                (Some(cipher), _) => {
+                    contents = cipher
+                        .open(&contents)
+                        .map_err(|err| io::Error::new(err.kind(), format!("job {id}: {err}")))?;
+                }
+                (None, true) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("job {id} is sealed; set the storage encryption key to read it"),
+                    ));
+                }
+                (None, false) => {}
+            }
+            let age = serde_json::from_slice::<StoredJob>(&contents)
+                .ok()
+                .map(|job| {
+                    (
+                        Duration::from_secs(now.saturating_sub(job.finished_at)),
+                        job,
+                    )
+                })
+                .filter(|(age, _)| *age < self.retention);
+            match age {
+                Some((age, job)) => stored.push((id.to_string(), age, job)),
+                None => {
+                    let _ = results.storage.delete(&object.key);
+                }
+            }
+        }
+        stored.sort_by_key(|(_, age, _)| std::cmp::Reverse(*age));
+        let loaded = stored.len();
+        for (id, age, job) in stored {
+            let Ok(mut jobs) = self.jobs.lock() else {
+                break;
+            };
+            jobs.insert(id.clone(), job.into_job(age));
+            let evicted = self.settle(&mut jobs, &id);
+            drop(jobs);
+            self.delete_stored(&evicted);
+        }
+        Ok(loaded)
+    }
+
+    /// Caps the bytes of stored results, overall and per API key.
+    pub fn with_byte_caps(
+        mut self,
+        max_total_bytes: Option<usize>,
+        max_bytes_per_key: Option<usize>,
+    ) -> Self {
+        self.max_total_bytes = max_total_bytes;
+        self.max_bytes_per_key = max_bytes_per_key;
+        self
+    }
+
+    /// Registers a queued job for `task` with the client's `metadata` and returns its id.
+    ///
+    /// `api_key` is the label of the submitting key, used to find its jobs when purging.
+    pub fn create(
+        &self,
+        task: TaskKind,
+        metadata: Option<String>,
+        api_key: Option<String>,
+    ) -> Result<String, AppError> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .map_err(|_| AppError::internal("job store lock poisoned"))?;
+        let expired = self.remove_expired(&mut jobs);
+
+        let id = new_id("job_");
+        jobs.insert(
+            id.clone(),
+            Job::queued(task, unix_secs(), metadata, api_key),
+        );
+        drop(jobs);
+        self.delete_stored(&expired);
+        Ok(id)
+    }
+
+    /// Re-registers a queued job loaded from the spool under its original id.
+    pub fn restore(
+        &self,
+        id: &str,
+        task: TaskKind,
+        created_at: u64,
+        metadata: Option<String>,
+        api_key: Option<String>,
+    ) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(
+                id.to_string(),
+                Job::queued(task, created_at, metadata, api_key),
+            );
+        }
+    }
+
+    /// Records where a queued job waits for a worker.
+    ///
+    /// A batch job keeps the spot of its file that is furthest ahead.
+    pub fn set_queue_spot(&self, id: &str, spot: QueueSpot) {
+        self.update(id, |job| {
+            if job
+                .queue_spot
+                .as_ref()
+                .and_then(QueueSpot::status)
+                .is_none()
+            {
+                job.queue_spot = Some(spot);
+            }
+        });
+    }
+
+    /// Marks a job as holding an inference worker.
+    pub fn set_running(&self, id: &str) {
+        self.update(id, |job| {
+            job.status = JobStatus::Running;
+            job.queue_spot = None;
+        });
+    }
+
+    /// Adds a segment decoded while the job runs; fallback passes may still
+    /// revise it before the job completes.
+    pub fn add_segment(&self, id: &str, segment: &TranscriptSegment) {
+        self.update(id, |job| {
+            if job.finished.is_some() {
+                return;
+            }
+            let at = job
+                .segments
+                .partition_point(|earlier| earlier.start_secs <= segment.start_secs);
+            job.segments.insert(
+                at,
+                PartialSegment {
+                    start_secs: segment.start_secs,
+                    end_secs: segment.end_secs,
+                    text: segment.text.clone(),
+                },
+            );
+        });
+    }
+
+    /// Stores the formatted result of a finished job.
+    pub fn complete(&self, id: &str, language: Option<String>, result: Value) {
+        self.finish(id, |job| {
+            job.status = JobStatus::Completed;
+            job.language = language;
+            job.bytes = json_len(&result);
+            job.result = Some(result);
+        });
+    }
+
+    /// Stores the error of a failed job.
+    pub fn fail(&self, id: &str, err: AppError) {
+        self.finish(id, |job| {
+            job.status = JobStatus::Failed;
+            let error = err.into_json()["error"].take();
+            job.bytes = json_len(&error);
+            job.error = Some(error);
+        });
+    }
+
+    /// Drops finished jobs past their retention.
+    pub fn expire(&self) {
+        let expired = match self.jobs.lock() {
+            Ok(mut jobs) => self.remove_expired(&mut jobs),
+            Err(_) => return,
+        };
+        self.delete_stored(&expired);
+    }
+
+    /// Returns the evictions since the last call and resets them.
+    pub fn take_evictions(&self) -> Evictions {
+        self.evictions
+            .lock()
+            .map(|mut evictions| std::mem::take(&mut *evictions))
+            .unwrap_or_default()
+    }
+
+    /// Deletes the jobs matching `filter` and returns their ids.
+    ///
+    /// Queued and running jobs are deleted too; as their id is gone, their
+    /// result is dropped when they finish. With `dry_run` set, matching jobs
+    /// are only listed.
+    pub fn purge(&self, filter: &PurgeFilter, dry_run: bool) -> Vec<String> {
+        let Ok(mut jobs) = self.jobs.lock() else {
+            return Vec::new();
+        };
+        let ids = jobs
+            .iter()
+            .filter(|(_, job)| filter.matches(job.api_key.as_deref(), job.created_at))
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        if !dry_run {
+            for id in &ids {
+                jobs.remove(id);
+            }
+            drop(jobs);
+            self.delete_stored(&ids);
+        }
+        ids
+    }
+
+    /// Whether `id` is still tracked, i.e. was neither purged nor expired.
+    pub fn contains(&self, id: &str) -> bool {
+        self.jobs.lock().is_ok_and(|jobs| jobs.contains_key(id))
+    }
+
+    /// Returns the job payload when the key labeled `api_key` submitted it.
+    ///
+    /// Jobs of other keys look missing, so a tenant cannot even tell they exist.
+    pub fn get_for(&self, id: &str, api_key: Option<&str>) -> Option<Value> {
+        let owned = self
+            .jobs
+            .lock()
+            .ok()?
+            .get(id)
+            .is_some_and(|job| job.api_key.as_deref() == api_key);
+        owned.then(|| self.get(id)).flatten()
+    }
+
+    /// Returns the job payload served by `GET /v1/audio/jobs/{id}`.
+    pub fn get(&self, id: &str) -> Option<Value> {
+        let jobs = self.jobs.lock().ok()?;
+        let job = jobs.get(id)?;
+        if job.expired(self.retention) {
+            return None;
+        }
+        let mut body = json!({
+            "id": id,
+            "object": "audio.job",
+            "task": job.task.as_str(),
+            "status": job.status.as_str(),
+            "created_at": job.created_at,
+        });
+        if let Some(metadata) = &job.metadata {
+            body["metadata"] = json!(metadata);
+        }
+        if let Some(status) = job.queue_spot.as_ref().and_then(QueueSpot::status) {
+            if job.status == JobStatus::Queued {
+                insert_queue_status(&mut body, status);
+            }
+        }
+        if !job.segments.is_empty() {
+            body["segments"] = job
+                .segments
+                .iter()
+                .enumerate()
+                .map(|(idx, segment)| {
+                    json!({
+                        "id": idx,
+                        "start": segment.start_secs,
+                        "end": segment.end_secs,
+                        "text": segment.text,
+                    })
+                })
+                .collect();
+        }
+        if let Some(language) = &job.language {
+            body["language"] = json!(language);
+        }
+        if let Some(result) = &job.result {
+            body["result"] = result.clone();
+        }
+        if let Some(error) = &job.error {
+            body["error"] = error.clone();
+        }
+        Some(body)
+    }
+
+    /// Finishes job `id` with `apply` and persists it.
+    fn finish(&self, id: &str, apply: impl FnOnce(&mut Job)) {
+        let Ok(mut jobs) = self.jobs.lock() else {
+            return;
+        };
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        apply(job);
+        job.segments = Vec::new();
+        job.finished = Some(Instant::now());
+        let record = self.storage.is_some().then(|| StoredJob::new(job));
+        let evicted = self.settle(&mut jobs, id);
+        drop(jobs);
+        if let Some(record) = record {
+            self.store(id, &record);
+        }
+        self.delete_stored(&evicted);
+    }
+
+    /// Orders the just-finished job `id` after all others, then evicts the
+    /// oldest finished jobs until the byte caps hold again and returns their ids.
+    fn settle(&self, jobs: &mut HashMap<String, Job>, id: &str) -> Vec<String> {
+        let Some(job) = jobs.get_mut(id) else {
+            return Vec::new();
+        };
+        job.finish_order = self.finished.fetch_add(1, Ordering::Relaxed);
+        let api_key = job.api_key.clone();
+
+        let mut evicted = Vec::new();
+        if let Some(cap) = self.max_bytes_per_key {
+            evicted.extend(evict_oldest(jobs, cap, |job| job.api_key == api_key));
+        }
+        let key_bytes = evicted.len() as u64;
+        if let Some(cap) = self.max_total_bytes {
+            evicted.extend(evict_oldest(jobs, cap, |_| true));
+        }
+        self.count(Evictions {
+            key_bytes,
+            total_bytes: evicted.len() as u64 - key_bytes,
+            ..Evictions::default()
+        });
+        evicted
+    }
+
+    /// Removes finished jobs past their retention and returns their ids.
+    fn remove_expired(&self, jobs: &mut HashMap<String, Job>) -> Vec<String> {
+        let expired = jobs
+            .iter()
+            .filter(|(_, job)| job.expired(self.retention))
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for id in &expired {
+            jobs.remove(id);
+        }
+        self.count(Evictions {
+            expired: expired.len() as u64,
+            ..Evictions::default()
+        });
+        expired
+    }
+
+    /// Writes the record of a finished job to storage.
+    fn store(&self, id: &str, record: &StoredJob) {
+        let Some(results) = &self.storage else {
+            return;
+        };
+        let written = serde_json::to_vec(record)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            .and_then(|contents| match &results.cipher {
+                Some(cipher) => cipher.seal(&contents),
+                None => Ok(contents),
+            })
+            .and_then(|contents| results.storage.put(&result_key(id), &contents));
+        if let Err(err) = written {
+            warn!(job_id = id, error = %err, "failed to persist async job result");
+        }
+    }
+
+    /// Deletes the stored records of jobs no longer kept.
+    fn delete_stored(&self, ids: &[String]) {
+        let Some(results) = &self.storage else {
+            return;
+        };
+        for id in ids {
+            // The whole prefix, so a directory-backed store drops the job's directory.
+            let _ = results.storage.delete_prefix(&format!("{id}/"));
+        }
+    }
+
+    fn count(&self, evicted: Evictions) {
+        if evicted == Evictions::default() {
+            return;
+        }
+        if let Ok(mut evictions) = self.evictions.lock() {
+            evictions.expired += evicted.expired;
+            evictions.total_bytes += evicted.total_bytes;
+            evictions.key_bytes += evicted.key_bytes;
+        }
+    }
+
+    fn update(&self, id: &str, apply: impl FnOnce(&mut Job)) {
+        if let Some(job) = self
+            .jobs
+            .lock()
+            .ok()
+            .as_mut()
+            .and_then(|jobs| jobs.get_mut(id))
+        {
+            apply(job);
+        }
+    }
+}
+
+/// Removes the oldest finished jobs matching `selected` until their stored
+/// bytes fit in `cap`, and returns their ids.
+fn evict_oldest(
+    jobs: &mut HashMap<String, Job>,
+    cap: usize,
+    selected: impl Fn(&Job) -> bool,
+) -> Vec<String> {
+    let mut finished = jobs
+        .iter()
+        .filter(|(_, job)| selected(job))
+        .filter(|(_, job)| job.finished.is_some())
+        .map(|(id, job)| (job.finish_order, job.bytes, id.clone()))
+        .collect::<Vec<_>>();
+    let mut stored = finished.iter().map(|(_, bytes, _)| bytes).sum::<usize>();
+    finished.sort_unstable_by_key(|(order, _, _)| *order);
+    let mut evicted = Vec::new();
+    for (_, bytes, id) in finished {
+        if stored <= cap {
+            break;
+        }
+        jobs.remove(&id);
+        stored -= bytes;
+        evicted.push(id);
+    }
+    evicted
+}
+
+fn json_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+/// Storage key of a finished job's record.
+pub(crate) fn result_key(id: &str) -> String {
+    format!("{id}/{RESULT}")
+}
+
+/// Builds an unguessable id from per-process random hasher keys.
+pub(crate) fn new_id(prefix: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut id = String::from(prefix);
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
+
+/// Current time as Unix seconds.
+pub(crate) fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admission::QueueTracker;
+    use crate::storage::LocalStorage;
+    #[cfg(feature = "sqlite")]
+    use crate::storage::SqliteStorage;
+
+    #[test]
+    fn jobs_move_through_lifecycle() {
+        let store = JobStore::new(Duration::from_secs(60));
+        let id = store
+            .create(TaskKind::Transcribe, Some("rec-42".to_string()), None)
+            .expect("create");
+        assert!(id.starts_with("job_"));
+        let job = store.get(&id).expect("job");
+        assert_eq!(job["status"], "queued");
+        assert_eq!(job["metadata"], "rec-42");
+
+        let queue = QueueTracker::new(1);
+        let _ahead = queue.join();
+        let ticket = queue.join();
+        store.set_queue_spot(&id, ticket.spot());
+        assert_eq!(store.get(&id).expect("job")["queue_position"], 2);
+        drop(ticket);
+        assert!(store.get(&id).expect("job").get("queue_position").is_none());
+
+        store.set_running(&id);
+        assert_eq!(store.get(&id).expect("job")["status"], "running");
+
+        store.complete(&id, Some("en".to_string()), json!({"text": "hi"}));
+        let job = store.get(&id).expect("job");
+        assert_eq!(job["status"], "completed");
+        assert_eq!(job["language"], "en");
+        assert_eq!(job["result"]["text"], "hi");
+    }
+
+    #[test]
+    fn running_jobs_list_the_segments_decoded_so_far() {
+        let store = JobStore::new(Duration::from_secs(60));
+        let id = store
+            .create(TaskKind::Transcribe, None, None)
+            .expect("create");
+        store.set_running(&id);
+        assert!(store.get(&id).expect("job").get("segments").is_none());
+
+        let segment = |start_secs: f64, text: &str| {
+            TranscriptSegment::new(start_secs, start_secs + 1.0, text)
+        };
+        // Parallel chunking windows may finish out of order.
+        store.add_segment(&id, &segment(30.0, "later"));
+        store.add_segment(&id, &segment(0.0, "first"));
+        let job = store.get(&id).expect("job");
+        assert_eq!(job["segments"][0]["text"], "first");
+        assert_eq!(job["segments"][1]["id"], 1);
+        assert_eq!(job["segments"][1]["start"], 30.0);
+
+        store.complete(&id, None, json!({"text": "first later"}));
+        store.add_segment(&id, &segment(60.0, "too late"));
+        assert!(store.get(&id).expect("job").get("segments").is_none());
+    }
+
+    #[test]
+    fn failed_jobs_report_error_and_expire() {
+        let store = JobStore::new(Duration::ZERO);
+        let id = store
+            .create(TaskKind::Translate, None, None)
+            .expect("create");
+        store.fail(&id, AppError::backend("boom"));
+        assert!(store.get(&id).is_none());
+
+        let store = JobStore::new(Duration::from_secs(60));
+        let id = store
+            .create(TaskKind::Translate, None, None)
+            .expect("create");
+        store.fail(&id, AppError::backend("boom"));
+        let job = store.get(&id).expect("job");
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"]["message"], "boom");
+    }
+
+    #[test]
+    fn oldest_finished_jobs_are_evicted_over_the_byte_caps() {
+        let result = json!({"text": "x".repeat(100)});
+        let size = json_len(&result);
+        let store =
+            JobStore::new(Duration::from_secs(60)).with_byte_caps(Some(3 * size), Some(size));
+        let submit = |key: &str| {
+            let id = store
+                .create(TaskKind::Transcribe, None, Some(key.to_string()))
+                .expect("create");
+            store.complete(&id, None, result.clone());
+            id
+        };
+
+        let first_a = submit("team-a");
+        let second_a = submit("team-a");
+        assert!(store.get(&first_a).is_none(), "over team-a's cap");
+        assert!(store.get(&second_a).is_some());
+
+        let queued = store
+            .create(TaskKind::Transcribe, None, Some("team-b".to_string()))
+            .expect("create");
+        let b = submit("team-b");
+        let c = submit("team-c");
+        let d = submit("team-d");
+        assert!(store.get(&second_a).is_none(), "oldest over the total cap");
+        assert!([&b, &c, &d].iter().all(|id| store.get(id).is_some()));
+        assert!(store.contains(&queued), "unfinished jobs are never evicted");
+        assert_eq!(
+            store.take_evictions(),
+            Evictions {
+                expired: 0,
+                total_bytes: 1,
+                key_bytes: 1,
+            }
+        );
+        assert_eq!(store.take_evictions(), Evictions::default());
+
+        let store = JobStore::new(Duration::ZERO);
+        let id = store
+            .create(TaskKind::Transcribe, None, None)
+            .expect("create");
+        store.complete(&id, None, result);
+        store.expire();
+        assert!(!store.contains(&id));
+        assert_eq!(store.take_evictions().expired, 1);
+    }
+
+    #[test]
+    fn purge_deletes_matching_jobs_including_running_ones() {
+        let store = JobStore::new(Duration::from_secs(60));
+        let mine = store
+            .create(TaskKind::Transcribe, None, Some("team-a".to_string()))
+            .expect("create");
+        let theirs = store
+            .create(TaskKind::Transcribe, None, Some("team-b".to_string()))
+            .expect("create");
+        store.set_running(&mine);
+        let filter = PurgeFilter {
+            api_key: Some("team-a".to_string()),
+            ..PurgeFilter::default()
+        };
+
+        assert_eq!(store.purge(&filter, true), std::slice::from_ref(&mine));
+        assert!(store.contains(&mine));
+        assert_eq!(store.purge(&filter, false), std::slice::from_ref(&mine));
+        assert!(store.get(&mine).is_none());
+        store.complete(&mine, None, json!({"text": "late"}));
+        assert!(!store.contains(&mine));
+        assert!(store.contains(&theirs));
+    }
+
+    /// Finishes jobs in a store on `storage`, then reloads them in a new one.
+    fn finished_jobs_survive_a_restart(storage: Arc<dyn Storage>) {
+        let store = JobStore::new(Duration::from_secs(60)).with_storage(Arc::clone(&storage), None);
+        let done = store
+            .create(TaskKind::Translate, Some("rec-42".to_string()), None)
+            .expect("create");
+        store.complete(&done, Some("de".to_string()), json!({"text": "hallo"}));
+        let failed = store
+            .create(TaskKind::Transcribe, None, Some("team-a".to_string()))
+            .expect("create");
+        store.fail(&failed, AppError::backend("boom"));
+        let purged = store
+            .create(TaskKind::Transcribe, None, Some("team-b".to_string()))
+            .expect("create");
+        store.complete(&purged, None, json!({"text": "gone"}));
+        let filter = PurgeFilter {
+            api_key: Some("team-b".to_string()),
+            ..PurgeFilter::default()
+        };
+        store.purge(&filter, false);
+        let running = store
+            .create(TaskKind::Transcribe, None, None)
+            .expect("create");
+        store.set_running(&running);
+
+        let restarted = JobStore::new(Duration::from_secs(60)).with_storage(storage, None);
+        assert_eq!(restarted.load_finished().expect("load"), 2);
+        let job = restarted.get(&done).expect("completed job");
+        assert_eq!(job["status"], "completed");
+        assert_eq!(job["task"], "translate");
+        assert_eq!(job["metadata"], "rec-42");
+        assert_eq!(job["language"], "de");
+        assert_eq!(job["result"]["text"], "hallo");
+        let job = restarted.get(&failed).expect("failed job");
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"]["message"], "boom");
+        assert!(restarted.get(&purged).is_none());
+        assert!(restarted.get(&running).is_none());
+
+        let expired = JobStore::new(Duration::ZERO).with_storage(
+            Arc::clone(&restarted.storage.as_ref().expect("storage").storage),
+            None,
+        );
+        assert_eq!(expired.load_finished().expect("load"), 0);
+        assert_eq!(restarted.load_finished().expect("load"), 0);
+    }
+
+    #[test]
+    fn finished_jobs_survive_a_restart_in_local_storage() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        finished_jobs_survive_a_restart(Arc::new(LocalStorage::new(dir.path())));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn finished_jobs_survive_a_restart_in_sqlite_storage() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let storage = SqliteStorage::open(&dir.path().join("state.db"), "jobs").expect("open");
+        finished_jobs_survive_a_restart(Arc::new(storage));
+    }
+}
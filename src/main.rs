@@ -3,22 +3,66 @@
 //! This crate is a binary (not a library), so this file wires modules together,
 //! starts the Axum server, and handles graceful shutdown signals.
 
+mod admin;
+mod admission;
 mod api;
+mod archive;
 mod audio;
 mod backend;
+mod build_info;
+mod chunking;
 mod config;
+mod debug_trace;
+mod demo;
+mod denoise;
+mod encryption;
 mod error;
+mod ffmpeg;
 mod formats;
+mod g711;
+mod janitor;
+mod job_spool;
+mod jobs;
+mod memory_budget;
+mod metrics;
+mod model_bundle;
+mod model_registry;
 mod model_store;
+mod openapi;
+#[cfg(feature = "opus")]
+mod opus;
+mod pagination;
+mod presets;
+mod punctuation;
+mod purge;
+mod rate_limit;
+mod request_id;
+mod rtp;
+mod self_test;
+mod server;
+mod sniff;
+mod startup;
+mod storage;
+mod streaming;
+mod tls;
+#[cfg(feature = "tts")]
+mod tts;
+mod upload;
+mod webm;
 
 use std::sync::Arc;
 
 use tracing::info;
 
-use crate::api::{build_router, AppState};
+use crate::api::{build_router, resume_spooled_jobs, AppState};
 use crate::backend::build_backend;
-use crate::config::{AppConfig, MAX_WHISPER_PARALLELISM};
-use crate::model_store::ensure_model_ready;
+use crate::config::{AppConfig, Command, MAX_WHISPER_PARALLELISM};
+use crate::error::AppError;
+use crate::model_registry::{load_cpu_pool, load_extra_models, load_model};
+use crate::model_store::{
+    ensure_aux_models_ready, ensure_model_ready, ensure_model_ready_with_progress,
+};
+use crate::startup::{Phase, StartupStatus};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -31,29 +75,133 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init();
 
     let mut cfg = AppConfig::from_args()?;
-    ensure_model_ready(&mut cfg)?;
-    let backend = build_backend(&cfg)?;
-    let state = Arc::new(AppState::new(cfg.clone(), backend));
-
-    let app = build_router(state);
+    if let Some(command) = cfg.command.clone() {
+        return run_command(cfg, command);
+    }
+    // Loaded before the model so a bad certificate fails startup quickly.
+    let tls = tls::load_acceptor(&cfg)?;
+    // Before any download, so space held by abandoned ones is freed first.
+    let startup_sweep = janitor::sweep(&cfg);
 
+    // Bound before the model loads, so probes can see a slow startup.
     let addr = format!("{}:{}", cfg.host, cfg.port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!(
+        host = %cfg.host,
+        port = cfg.port,
+        "listening; loading the model before serving requests"
+    );
+    let status = Arc::new(StartupStatus::new(cfg.api_model_alias.clone()));
+    let loading = Arc::clone(&status);
+    let load = async move {
+        let (cfg, backend, cpu_pool, extra_models) = tokio::task::spawn_blocking(move || {
+            ensure_model_ready_with_progress(&mut cfg, Some(&loading.progress))?;
+            for path in ensure_aux_models_ready(&cfg)? {
+                info!(path = %path.to_string_lossy(), "auxiliary model ready");
+            }
+            #[cfg(feature = "tts")]
+            crate::model_store::ensure_voice_ready(&mut cfg)?;
+            loading.set_phase(Phase::Loading);
+            let backend = build_backend(&cfg)?;
+            let cpu_pool = load_cpu_pool(&cfg, load_model)?;
+            let extra_models = load_extra_models(&cfg)?;
+            if cfg.startup_self_test {
+                loading.set_phase(Phase::SelfTest);
+            }
+            Ok::<_, AppError>((cfg, backend, cpu_pool, extra_models))
+        })
+        .await
+        .map_err(|err| AppError::internal(format!("model loading panicked: {err}")))??;
+        if cfg.startup_self_test {
+            self_test::run(backend.as_ref()).await?;
+            for extra in extra_models.values() {
+                self_test::run(extra.as_ref()).await?;
+            }
+        }
+        Ok::<_, AppError>((cfg, backend, cpu_pool, extra_models))
+    };
+    let (cfg, backend, cpu_pool, extra_models) =
+        startup::serve_until(&listener, tls.clone(), status, load).await?;
+
+    let state = Arc::new(
+        AppState::new(cfg.clone(), backend)
+            .with_cpu_pool(cpu_pool)
+            .with_extra_models(extra_models),
+    );
+    let finished = state.jobs.load_finished().map_err(|err| {
+        AppError::internal(format!(
+            "failed to read finished jobs from ASYNC_JOB_DIR: {err}"
+        ))
+    })?;
+    if finished > 0 {
+        info!(
+            jobs = finished,
+            "loaded finished async jobs from ASYNC_JOB_DIR"
+        );
+    }
+    let resumed = resume_spooled_jobs(&state)?;
+    if resumed > 0 {
+        info!(
+            jobs = resumed,
+            "resumed queued async jobs from ASYNC_JOB_DIR"
+        );
+    }
+
+    janitor::spawn(Arc::clone(&state), startup_sweep);
+    if let (Some(port), Some(router)) = (cfg.rtp_port, &state.rtp) {
+        let socket = tokio::net::UdpSocket::bind((cfg.host.as_str(), port)).await?;
+        info!(host = %cfg.host, port, "receiving RTP audio for streaming sessions");
+        tokio::spawn(Arc::clone(router).serve(socket));
+    }
+
+    let app = build_router(Arc::clone(&state));
 
     info!(
         host = %cfg.host,
         port = cfg.port,
+        version = build_info::VERSION,
+        git_commit = build_info::GIT_COMMIT,
         model = %cfg.whisper_model,
         backend = ?cfg.backend_kind,
         acceleration = %cfg.acceleration_kind.as_str(),
         whisper_parallelism = cfg.whisper_parallelism,
+        max_connections = cfg.max_connections,
+        tls = tls.is_some(),
+        http2 = cfg.http2,
+        statsd_addr = cfg.statsd_addr.as_deref().unwrap_or("disabled"),
         max_whisper_parallelism = MAX_WHISPER_PARALLELISM,
         "starting whisper-openai-server"
     );
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    server::serve(listener, app, state, tls, shutdown_signal()).await?;
+    Ok(())
+}
+
+/// Runs a maintenance subcommand instead of the server.
+fn run_command(mut cfg: AppConfig, command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::ExportModelBundle { output, revision } => {
+            ensure_model_ready(&mut cfg)?;
+            let manifest = model_bundle::export_bundle(&cfg, &revision, &output)?;
+            info!(
+                bundle = %output.to_string_lossy(),
+                model = %manifest.filename,
+                size_bytes = manifest.size_bytes,
+                sha256 = %manifest.sha256,
+                "exported model bundle"
+            );
+        }
+        Command::ImportModelBundle { bundle } => {
+            let (path, manifest) = model_bundle::import_bundle(&cfg, &bundle)?;
+            info!(
+                path = %path.to_string_lossy(),
+                hf_repo = %manifest.hf_repo,
+                revision = %manifest.revision,
+                sha256 = %manifest.sha256,
+                "imported model bundle"
+            );
+        }
+    }
     Ok(())
 }
 
@@ -0,0 +1,217 @@
+//! Memory-based admission control.
+//!
+//! Each request reserves memory against `MEMORY_BUDGET_MB` before its upload
+//! is decoded, sized from the upload with [`estimated_samples`], and adjusts
+//! the reservation to its decoded sample count once that is known, holding it
+//! until inference finishes. Requests that do not fit wait (up to
+//! `MAX_QUEUE_WAIT_SECS`) for other requests to finish; a request larger than
+//! the whole budget is rejected. The budget covers per-request buffers only,
+//! not the loaded model weights.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::AppError;
+
+const KIB: usize = 1024;
+/// Decoded 16 kHz samples assumed per byte of compressed audio before
+/// decoding: 32 kbps, a low bitrate for speech, so most uploads are
+/// overestimated rather than under.
+const COMPRESSED_SAMPLES_PER_BYTE: usize = 4;
+/// Samples per byte of uncompressed audio: 8-bit 8 kHz G.711 is the densest
+/// common case, and 16-bit PCM at 16 kHz or more yields fewer.
+const PCM_SAMPLES_PER_BYTE: usize = 2;
+
+/// Shared budget of request memory, tracked in KiB.
+pub struct MemoryBudget {
+    kib: Arc<Semaphore>,
+    capacity_kib: u32,
+}
+
+/// Memory held by one request; released when dropped.
+pub struct MemoryReservation {
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl MemoryReservation {
+    /// Reservation used when no budget is configured.
+    pub fn unlimited() -> Self {
+        Self { permit: None }
+    }
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `megabytes` MiB.
+    pub fn new(megabytes: u32) -> Self {
+        let capacity_kib = megabytes.saturating_mul(1024);
+        Self {
+            kib: Arc::new(Semaphore::new(capacity_kib as usize)),
+            capacity_kib,
+        }
+    }
+
+    /// Reserves an estimate of `bytes`, capped at the whole budget so a
+    /// generous estimate delays the request instead of rejecting it.
+    pub async fn reserve_estimate(
+        &self,
+        bytes: usize,
+        max_wait: Option<Duration>,
+    ) -> Result<MemoryReservation, AppError> {
+        let kib = to_kib(bytes).min(self.capacity_kib);
+        Ok(MemoryReservation {
+            permit: Some(self.acquire(kib, max_wait).await?),
+        })
+    }
+
+    /// Resizes `reservation` to `bytes` once the real need is known.
+    ///
+    /// Shrinking returns the difference at once. Growing takes the difference
+    /// if it is free; otherwise the reservation is released and `bytes` is
+    /// waited for as a whole, so two requests each holding part of what they
+    /// need never wait on each other.
+    pub async fn resize(
+        &self,
+        reservation: &mut MemoryReservation,
+        bytes: usize,
+        max_wait: Option<Duration>,
+    ) -> Result<(), AppError> {
+        let Some(permit) = reservation.permit.as_mut() else {
+            return Ok(());
+        };
+        let kib = self.checked_kib(bytes)? as usize;
+        let held = permit.num_permits();
+        if kib <= held {
+            drop(permit.split(held - kib));
+            return Ok(());
+        }
+        let extra = u32::try_from(kib - held).unwrap_or(u32::MAX);
+        if let Ok(extra) = Arc::clone(&self.kib).try_acquire_many_owned(extra) {
+            permit.merge(extra);
+            return Ok(());
+        }
+        reservation.permit = None;
+        reservation.permit = Some(self.acquire(kib as u32, max_wait).await?);
+        Ok(())
+    }
+
+    /// Converts `bytes` to KiB, rejecting requests larger than the whole budget.
+    fn checked_kib(&self, bytes: usize) -> Result<u32, AppError> {
+        let kib = to_kib(bytes);
+        if kib > self.capacity_kib {
+            return Err(AppError::payload_too_large(
+                format!(
+                    "request needs about {} MiB of memory, more than the server budget of {} MiB",
+                    kib / 1024,
+                    self.capacity_kib / 1024
+                ),
+                Some("file"),
+            ));
+        }
+        Ok(kib)
+    }
+
+    async fn acquire(
+        &self,
+        kib: u32,
+        max_wait: Option<Duration>,
+    ) -> Result<OwnedSemaphorePermit, AppError> {
+        let acquire = Arc::clone(&self.kib).acquire_many_owned(kib);
+        match max_wait {
+            Some(max_wait) => tokio::time::timeout(max_wait, acquire).await.map_err(|_| {
+                AppError::overloaded(format!(
+                    "request waited more than {}s for memory budget; retry later",
+                    max_wait.as_secs_f64()
+                ))
+            })?,
+            None => acquire.await,
+        }
+        .map_err(|_| AppError::internal("memory budget closed"))
+    }
+}
+
+fn to_kib(bytes: usize) -> u32 {
+    u32::try_from((bytes + KIB - 1) / KIB).unwrap_or(u32::MAX)
+}
+
+/// Estimated memory for a request: the upload plus its decoded `f32` samples.
+pub fn request_memory_cost(upload_bytes: usize, samples: usize) -> usize {
+    upload_bytes + samples * std::mem::size_of::<f32>()
+}
+
+/// Decoded samples expected from an `upload_bytes` upload with `extension`,
+/// before it is decoded.
+pub fn estimated_samples(upload_bytes: usize, extension: &str) -> usize {
+    let per_byte = match extension {
+        "wav" | "au" | "ul" | "ulaw" | "mulaw" | "al" | "alaw" => PCM_SAMPLES_PER_BYTE,
+        _ => COMPRESSED_SAMPLES_PER_BYTE,
+    };
+    upload_bytes.saturating_mul(per_byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAIT: Option<Duration> = Some(Duration::from_millis(10));
+
+    #[tokio::test]
+    async fn reservations_wait_for_budget_and_reject_oversized_requests() {
+        let budget = MemoryBudget::new(1);
+        let held = budget
+            .reserve_estimate(768 * KIB, None)
+            .await
+            .expect("first reservation");
+
+        let err = budget
+            .reserve_estimate(512 * KIB, WAIT)
+            .await
+            .err()
+            .expect("budget exhausted");
+        assert!(matches!(err, AppError::Overloaded(_)));
+
+        drop(held);
+        let mut reservation = budget
+            .reserve_estimate(512 * KIB, WAIT)
+            .await
+            .expect("budget released");
+
+        let err = budget
+            .resize(&mut reservation, 2 * 1024 * KIB, None)
+            .await
+            .expect_err("larger than budget");
+        assert!(matches!(err, AppError::InvalidRequest { .. }));
+    }
+
+    #[tokio::test]
+    async fn estimates_are_capped_and_resized_to_the_decoded_size() {
+        let budget = MemoryBudget::new(1);
+        let mut first = budget
+            .reserve_estimate(4 * 1024 * KIB, WAIT)
+            .await
+            .expect("estimate capped at the budget");
+        budget
+            .resize(&mut first, 256 * KIB, WAIT)
+            .await
+            .expect("shrink");
+        let mut second = budget
+            .reserve_estimate(512 * KIB, WAIT)
+            .await
+            .expect("room freed by the shrink");
+
+        // 256 KiB are still free, so growing by that much does not wait.
+        budget
+            .resize(&mut second, 768 * KIB, WAIT)
+            .await
+            .expect("grow into free room");
+        let err = budget
+            .resize(&mut first, 512 * KIB, WAIT)
+            .await
+            .expect_err("no room to grow");
+        assert!(matches!(err, AppError::Overloaded(_)));
+
+        assert_eq!(estimated_samples(1000, "mp3"), 4000);
+        assert_eq!(estimated_samples(1000, "wav"), 2000);
+    }
+}
@@ -0,0 +1,280 @@
+//! Request metrics with optional StatsD/DogStatsD push export.
+//!
+//! Observations are formatted as StatsD lines and handed to a background task
+//! that batches them into UDP packets. When `STATSD_ADDR` is not configured,
+//! recording is a no-op. What each inference worker is running right now is
+//! instead exposed as Prometheus gauges by `GET /metrics`, rendered here.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::backend::ActiveInference;
+use crate::config::AppConfig;
+
+/// Maximum StatsD payload size per UDP packet (safe for a 1500-byte MTU).
+const STATSD_MAX_PACKET_BYTES: usize = 1432;
+/// How often buffered StatsD lines are flushed when packets are not full.
+const STATSD_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Pending StatsD lines kept before new observations are dropped.
+const STATSD_QUEUE_CAPACITY: usize = 8192;
+
+/// Shared metrics recorder.
+pub struct Metrics {
+    statsd: Option<StatsdSink>,
+}
+
+struct StatsdSink {
+    prefix: String,
+    tags: bool,
+    tx: mpsc::Sender<String>,
+}
+
+impl Metrics {
+    /// Creates a recorder, starting the StatsD exporter task when configured.
+    pub fn new(cfg: &AppConfig) -> Self {
+        let Some(addr) = cfg.statsd_addr.clone() else {
+            return Self { statsd: None };
+        };
+        let (tx, rx) = mpsc::channel(STATSD_QUEUE_CAPACITY);
+        tokio::spawn(run_statsd_exporter(addr, rx));
+        Self::with_statsd_sender(cfg.statsd_prefix.clone(), cfg.statsd_tags, tx)
+    }
+
+    fn with_statsd_sender(prefix: String, tags: bool, tx: mpsc::Sender<String>) -> Self {
+        Self {
+            statsd: Some(StatsdSink { prefix, tags, tx }),
+        }
+    }
+
+    /// Increments a counter by one.
+    pub fn incr(&self, name: &str, tags: &[(&str, &str)]) {
+        self.count(name, 1, tags);
+    }
+
+    /// Increments a counter by `value`.
+    pub fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        if let Some(sink) = &self.statsd {
+            sink.send(format_statsd_line(
+                &sink.prefix,
+                name,
+                &value.to_string(),
+                "c",
+                tags,
+                sink.tags,
+            ));
+        }
+    }
+
+    /// Records a duration observation in milliseconds.
+    pub fn timing(&self, name: &str, elapsed: Duration, tags: &[(&str, &str)]) {
+        if let Some(sink) = &self.statsd {
+            let ms = format!("{:.3}", elapsed.as_secs_f64() * 1000.0);
+            sink.send(format_statsd_line(
+                &sink.prefix,
+                name,
+                &ms,
+                "ms",
+                tags,
+                sink.tags,
+            ));
+        }
+    }
+}
+
+impl StatsdSink {
+    fn send(&self, line: String) {
+        // Metrics must never apply backpressure to request handling.
+        let _ = self.tx.try_send(line);
+    }
+}
+
+/// Formats one StatsD line, appending DogStatsD `|#tag:value` tags when enabled.
+fn format_statsd_line(
+    prefix: &str,
+    name: &str,
+    value: &str,
+    kind: &str,
+    tags: &[(&str, &str)],
+    with_tags: bool,
+) -> String {
+    let mut line = if prefix.is_empty() {
+        format!("{name}:{value}|{kind}")
+    } else {
+        format!("{prefix}.{name}:{value}|{kind}")
+    };
+    if with_tags && !tags.is_empty() {
+        let tags = tags
+            .iter()
+            .map(|(k, v)| format!("{k}:{v}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        line.push_str("|#");
+        line.push_str(&tags);
+    }
+    line
+}
+
+/// Workers of one loaded model, labelled in the Prometheus output.
+pub struct WorkerPool {
+    /// Model id clients request.
+    pub model: String,
+    /// `default`, or `cpu` for the `WHISPER_CPU_WORKERS` pool.
+    pub pool: &'static str,
+    /// What each worker is running, indexed by worker.
+    pub workers: Vec<Option<ActiveInference>>,
+}
+
+/// Gauges rendered per worker: name, help text, and the value of a worker.
+type WorkerGaugeSpec = (
+    &'static str,
+    &'static str,
+    fn(Option<&ActiveInference>) -> f64,
+);
+
+const WORKER_GAUGES: [WorkerGaugeSpec; 3] = [
+    (
+        "whisper_worker_busy",
+        "Whether the worker is running a request (1) or idle (0).",
+        |active| if active.is_some() { 1.0 } else { 0.0 },
+    ),
+    (
+        "whisper_worker_request_elapsed_seconds",
+        "Seconds since the worker started its current request; 0 when idle.",
+        |active| active.map_or(0.0, |active| active.elapsed.as_secs_f64()),
+    ),
+    (
+        "whisper_worker_request_progress_percent",
+        "Progress of the current inference pass reported by whisper.cpp; 0 when idle.",
+        |active| active.map_or(0.0, |active| f64::from(active.progress_percent)),
+    ),
+];
+
+/// Renders per-worker gauges in the Prometheus text exposition format.
+pub fn render_worker_gauges(pools: &[WorkerPool]) -> String {
+    let mut out = String::new();
+    for (name, help, value) in WORKER_GAUGES {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        for pool in pools {
+            for (worker, active) in pool.workers.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{model=\"{}\",pool=\"{}\",worker=\"{worker}\"}} {}",
+                    escape_label(&pool.model),
+                    pool.pool,
+                    value(active.as_ref())
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn run_statsd_exporter(addr: String, mut rx: mpsc::Receiver<String>) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!(error = %err, "failed to bind statsd socket; metrics export disabled");
+            return;
+        }
+    };
+    if let Err(err) = socket.connect(&addr).await {
+        warn!(error = %err, statsd_addr = %addr, "failed to resolve statsd address; metrics export disabled");
+        return;
+    }
+
+    let mut buffer = String::new();
+    let mut ticker = tokio::time::interval(STATSD_FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            line = rx.recv() => {
+                let Some(line) = line else {
+                    flush_statsd(&socket, &mut buffer).await;
+                    return;
+                };
+                if !buffer.is_empty() && buffer.len() + 1 + line.len() > STATSD_MAX_PACKET_BYTES {
+                    flush_statsd(&socket, &mut buffer).await;
+                }
+                if !buffer.is_empty() {
+                    buffer.push('\n');
+                }
+                buffer.push_str(&line);
+            }
+            _ = ticker.tick() => flush_statsd(&socket, &mut buffer).await,
+        }
+    }
+}
+
+async fn flush_statsd(socket: &UdpSocket, buffer: &mut String) {
+    if buffer.is_empty() {
+        return;
+    }
+    // UDP send failures (for example no listener) are expected and not actionable.
+    let _ = socket.send(buffer.as_bytes()).await;
+    buffer.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statsd_line_includes_prefix_and_dogstatsd_tags() {
+        let tags = [("endpoint", "/v1/models"), ("status", "200")];
+        assert_eq!(
+            format_statsd_line("whisper", "requests", "1", "c", &tags, true),
+            "whisper.requests:1|c|#endpoint:/v1/models,status:200"
+        );
+        assert_eq!(
+            format_statsd_line("", "requests", "1", "c", &tags, false),
+            "requests:1|c"
+        );
+    }
+
+    #[test]
+    fn worker_gauges_use_the_prometheus_text_format() {
+        let pools = [WorkerPool {
+            model: "whisper-1".to_string(),
+            pool: "default",
+            workers: vec![
+                Some(ActiveInference {
+                    elapsed: Duration::from_millis(12_500),
+                    progress_percent: 40,
+                }),
+                None,
+            ],
+        }];
+        let text = render_worker_gauges(&pools);
+        assert!(text.contains("# TYPE whisper_worker_busy gauge\n"));
+        assert!(text.contains(
+            "whisper_worker_busy{model=\"whisper-1\",pool=\"default\",worker=\"1\"} 0\n"
+        ));
+        assert!(text.contains(
+            "whisper_worker_request_elapsed_seconds{model=\"whisper-1\",pool=\"default\",worker=\"0\"} 12.5\n"
+        ));
+        assert!(text.contains(
+            "whisper_worker_request_progress_percent{model=\"whisper-1\",pool=\"default\",worker=\"0\"} 40\n"
+        ));
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+
+    #[test]
+    fn timings_are_sent_in_milliseconds() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let metrics = Metrics::with_statsd_sender("wos".to_string(), false, tx);
+        metrics.timing("decode_duration", Duration::from_millis(250), &[]);
+        assert_eq!(rx.try_recv().unwrap(), "wos.decode_duration:250.000|ms");
+    }
+}
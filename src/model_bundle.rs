@@ -0,0 +1,250 @@
+//! Model bundles for moving a Whisper model into an air-gapped environment.
+//!
+//! `export-model-bundle` writes the configured model and a `manifest.json`
+//! describing it (size, SHA-256, and Hugging Face source) into one tar
+//! archive, with the manifest first. `import-model-bundle` reads the manifest,
+//! streams the model through [`install_verified`], and places it in
+//! `WHISPER_CACHE_DIR` under its original file name, where the server finds it
+//! with `WHISPER_AUTO_DOWNLOAD=false`.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::build_info;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::jobs::unix_secs;
+use crate::model_store::{digest_file, install_verified, ModelDigest};
+
+/// Name of the manifest entry, which precedes the model in the archive.
+const MANIFEST_NAME: &str = "manifest.json";
+/// Bundle layout version; bumped when the manifest changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+/// Largest manifest entry read before the bundle is rejected.
+const MAX_MANIFEST_BYTES: u64 = 64 * 1024;
+
+/// Metadata stored alongside the model in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    /// File name of the model, which is also its archive entry name.
+    pub filename: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+    /// Hugging Face repository and revision the model came from.
+    pub hf_repo: String,
+    pub revision: String,
+    /// Server version that wrote the bundle.
+    pub exported_by: String,
+    /// Unix seconds when the bundle was written.
+    pub created_at: u64,
+}
+
+impl BundleManifest {
+    fn digest(&self) -> ModelDigest {
+        ModelDigest {
+            size_bytes: self.size_bytes,
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+/// Writes the model at `cfg.whisper_model` into a bundle at `output`.
+///
+/// The bundle is written to a `.part` sibling and renamed once complete.
+pub fn export_bundle(
+    cfg: &AppConfig,
+    revision: &str,
+    output: &Path,
+) -> Result<BundleManifest, AppError> {
+    let model_path = Path::new(&cfg.whisper_model);
+    let filename = model_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            AppError::internal(format!("model path {:?} has no file name", model_path))
+        })?;
+    let digest = digest_file(model_path)?;
+    let manifest = BundleManifest {
+        format_version: FORMAT_VERSION,
+        filename: filename.to_string(),
+        size_bytes: digest.size_bytes,
+        sha256: digest.sha256,
+        hf_repo: cfg.whisper_hf_repo.clone(),
+        revision: revision.to_string(),
+        exported_by: build_info::VERSION.to_string(),
+        created_at: unix_secs(),
+    };
+
+    let tmp_path = output.with_extension("part");
+    let written =
+        write_bundle(&tmp_path, &manifest, model_path).and_then(|()| fs::rename(&tmp_path, output));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(AppError::internal(format!(
+            "failed to write model bundle {:?}: {err}",
+            output
+        )));
+    }
+    Ok(manifest)
+}
+
+fn write_bundle(path: &Path, manifest: &BundleManifest, model_path: &Path) -> std::io::Result<()> {
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    let mut builder = tar::Builder::new(BufWriter::new(File::create(path)?));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at);
+    builder.append_data(&mut header, MANIFEST_NAME, manifest_json.as_slice())?;
+
+    let mut model = File::open(model_path)?;
+    let size = model.metadata()?.len();
+    if size != manifest.size_bytes {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "model changed while the bundle was written",
+        ));
+    }
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at);
+    builder.append_data(&mut header, &manifest.filename, &mut model)?;
+
+    let mut out = builder.into_inner()?;
+    out.flush()?;
+    out.get_ref().sync_all()
+}
+
+/// Verifies the bundle at `bundle` and installs its model into `WHISPER_CACHE_DIR`.
+///
+/// Returns the installed model path and the bundle's manifest.
+pub fn import_bundle(
+    cfg: &AppConfig,
+    bundle: &Path,
+) -> Result<(PathBuf, BundleManifest), AppError> {
+    let invalid =
+        |detail: String| AppError::internal(format!("invalid model bundle {:?}: {detail}", bundle));
+    let file = File::open(bundle).map_err(|err| {
+        AppError::internal(format!("failed to open model bundle {:?}: {err}", bundle))
+    })?;
+    let mut archive = tar::Archive::new(file);
+    let mut entries = archive.entries().map_err(|err| invalid(err.to_string()))?;
+
+    let mut entry = entries
+        .next()
+        .ok_or_else(|| invalid("archive is empty".to_string()))?
+        .map_err(|err| invalid(err.to_string()))?;
+    if entry_name(&entry).as_deref() != Some(MANIFEST_NAME) {
+        return Err(invalid(format!("first entry is not {MANIFEST_NAME}")));
+    }
+    if entry.size() > MAX_MANIFEST_BYTES {
+        return Err(invalid(format!("{MANIFEST_NAME} is too large")));
+    }
+    let mut manifest_json = Vec::new();
+    entry
+        .read_to_end(&mut manifest_json)
+        .map_err(|err| invalid(err.to_string()))?;
+    let manifest: BundleManifest = serde_json::from_slice(&manifest_json)
+        .map_err(|err| invalid(format!("unreadable {MANIFEST_NAME}: {err}")))?;
+    if manifest.format_version != FORMAT_VERSION {
+        return Err(invalid(format!(
+            "unsupported format_version {} (expected {FORMAT_VERSION})",
+            manifest.format_version
+        )));
+    }
+    if !is_plain_file_name(&manifest.filename) {
+        return Err(invalid(format!(
+            "model filename {:?} is not a plain file name",
+            manifest.filename
+        )));
+    }
+
+    let mut entry = entries
+        .next()
+        .ok_or_else(|| invalid(format!("model {:?} is missing", manifest.filename)))?
+        .map_err(|err| invalid(err.to_string()))?;
+    if entry_name(&entry).as_deref() != Some(manifest.filename.as_str()) {
+        return Err(invalid(format!(
+            "second entry is not the model {:?}",
+            manifest.filename
+        )));
+    }
+    let target_path = Path::new(&cfg.whisper_cache_dir).join(&manifest.filename);
+    install_verified(&mut entry, &manifest.digest(), &target_path)?;
+    Ok((target_path, manifest))
+}
+
+fn entry_name<R: Read>(entry: &tar::Entry<R>) -> Option<String> {
+    entry
+        .path()
+        .ok()
+        .and_then(|path| path.to_str().map(str::to_string))
+}
+
+/// Whether `name` names a file directly inside a directory, with no path parts.
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && Path::new(name).file_name().and_then(|file| file.to_str()) == Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CliArgs;
+    use clap::Parser;
+
+    fn cfg_with_cache(model: &Path, cache_dir: &Path) -> AppConfig {
+        let args = CliArgs::parse_from(["whisper-openai-server"]);
+        let mut cfg = AppConfig::from_cli_args(args).expect("config");
+        cfg.whisper_model = model.to_string_lossy().to_string();
+        cfg.whisper_cache_dir = cache_dir.to_string_lossy().to_string();
+        cfg
+    }
+
+    #[test]
+    fn bundles_round_trip_and_reject_tampering() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let model = dir.path().join("ggml-tiny.bin");
+        std::fs::write(&model, b"model weights").expect("write");
+        let bundle = dir.path().join("tiny.tar");
+        let offline = dir.path().join("offline");
+        let cfg = cfg_with_cache(&model, &offline);
+
+        let exported = export_bundle(&cfg, "abc123", &bundle).expect("export");
+        assert_eq!(exported.size_bytes, 13);
+        assert_eq!(exported.revision, "abc123");
+        let (installed, imported) = import_bundle(&cfg, &bundle).expect("import");
+        assert_eq!(imported, exported);
+        assert_eq!(installed, offline.join("ggml-tiny.bin"));
+        assert_eq!(std::fs::read(&installed).expect("read"), b"model weights");
+
+        // Flip a byte of the model payload; the install must be refused.
+        std::fs::remove_file(&installed).expect("remove");
+        let mut bytes = std::fs::read(&bundle).expect("read bundle");
+        let offset = bytes
+            .windows(13)
+            .position(|window| window == b"model weights")
+            .expect("payload");
+        bytes[offset] ^= 0xff;
+        std::fs::write(&bundle, bytes).expect("write bundle");
+        let err = import_bundle(&cfg, &bundle).expect_err("tampered");
+        assert!(err.to_string().contains("failed verification"), "{err}");
+        assert!(!installed.exists());
+        assert!(!offline.join("ggml-tiny.part").exists());
+    }
+
+    #[test]
+    fn only_plain_file_names_are_installed() {
+        assert!(is_plain_file_name("ggml-small.bin"));
+        for name in ["", ".", "..", "../ggml-small.bin", "/etc/passwd", "a/b.bin"] {
+            assert!(!is_plain_file_name(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! On-demand loading of additional Whisper model sizes.
+//!
+//! Requests naming `whisper-<size>` (for example `whisper-large-v3`) trigger a
+//! background download and load of that size when `WHISPER_ON_DEMAND_MODELS`
+//! is enabled. Until the model is ready, callers receive a `503` describing
+//! download progress so clients can retry.
+//!
+//! Models listed in `WHISPER_EXTRA_MODELS` are instead loaded at startup,
+//! each into its own worker pool, and served under their configured ids.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::backend::{build_backend, CpuPool, DedicatedPool, Transcriber};
+use crate::config::{AccelerationKind, AppConfig, ModelWorkers, WhisperModelSize};
+use crate::error::AppError;
+use crate::model_store::{ensure_model_ready_with_progress, DownloadProgress};
+
+/// Prefix shared by on-demand model ids (`whisper-small`, `whisper-large-v3`, ...).
+pub const ON_DEMAND_MODEL_PREFIX: &str = "whisper-";
+
+/// Loads a model for `cfg`, reporting download progress.
+pub(crate) type ModelLoader =
+    fn(AppConfig, &DownloadProgress) -> Result<Arc<dyn Transcriber>, AppError>;
+
+/// Lazily loaded Whisper backends keyed by model size.
+pub struct OnDemandModels {
+    base_cfg: AppConfig,
+    entries: Arc<Mutex<HashMap<WhisperModelSize, Entry>>>,
+    loader: ModelLoader,
+}
+
+enum Entry {
+    Loading(Arc<DownloadProgress>),
+    Ready(Arc<dyn Transcriber>),
+    Failed(String),
+}
+
+impl OnDemandModels {
+    /// Creates an empty registry deriving per-size configuration from `cfg`.
+    pub fn new(cfg: &AppConfig) -> Self {
+        Self::with_loader(cfg, load_model)
+    }
+
+    /// Creates a registry that loads models with `loader` instead of whisper-rs.
+    pub(crate) fn with_loader(cfg: &AppConfig, loader: ModelLoader) -> Self {
+        Self {
+            base_cfg: cfg.clone(),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            loader,
+        }
+    }
+
+    /// Returns the backend for `size`, starting a background load on first use.
+    ///
+    /// While loading, returns a `model_loading` error with progress. A failed
+    /// load is reported once and then cleared so the next request retries.
+    pub fn get_or_load(
+        &self,
+        model_id: &str,
+        size: WhisperModelSize,
+    ) -> Result<Arc<dyn Transcriber>, AppError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| AppError::internal("on-demand model registry lock poisoned"))?;
+        match entries.get(&size) {
+            Some(Entry::Ready(backend)) => return Ok(Arc::clone(backend)),
+            Some(Entry::Loading(progress)) => {
+                return Err(loading_error(model_id, progress));
+            }
+            Some(Entry::Failed(_)) => {
+                let Some(Entry::Failed(message)) = entries.remove(&size) else {
+                    unreachable!("entry checked above");
+                };
+                return Err(AppError::backend(format!(
+                    "failed to load model {model_id:?}: {message}"
+                )));
+            }
+            None => {}
+        }
+
+        let progress = Arc::new(DownloadProgress::default());
+        entries.insert(size, Entry::Loading(Arc::clone(&progress)));
+        drop(entries);
+
+        let cfg = on_demand_config(&self.base_cfg, size);
+        let limits = on_demand_workers(&self.base_cfg, size);
+        let max_queue_depth = self.base_cfg.max_queue_depth;
+        let entries = Arc::clone(&self.entries);
+        let loader = self.loader;
+        let id = model_id.to_string();
+        info!(model = %id, path = %cfg.whisper_model, "loading on-demand model");
+        tokio::task::spawn_blocking(move || {
+            let entry = match loader(cfg, &progress) {
+                Ok(backend) => {
+                    info!(model = %id, "on-demand model ready");
+                    Entry::Ready(match limits {
+                        Some(limits) => {
+                            Arc::new(DedicatedPool::new(backend, limits, max_queue_depth))
+                        }
+                        None => backend,
+                    })
+                }
+                Err(err) => {
+                    warn!(model = %id, error = %err, "on-demand model load failed");
+                    Entry::Failed(err.to_string())
+                }
+            };
+            if let Ok(mut entries) = entries.lock() {
+                entries.insert(size, entry);
+            }
+        });
+
+        Err(loading_error(model_id, &DownloadProgress::default()))
+    }
+
+    /// Returns the loaded models with their ids.
+    pub fn ready(&self) -> Vec<(String, Arc<dyn Transcriber>)> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .filter_map(|(size, entry)| match entry {
+                Entry::Ready(backend) => Some((model_id(*size), Arc::clone(backend))),
+                Entry::Loading(_) | Entry::Failed(_) => None,
+            })
+            .collect()
+    }
+
+    /// Reports each requested model as `loading` (with download progress), `ready`, or `failed`.
+    pub fn status(&self) -> Vec<Value> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let mut models = entries
+            .iter()
+            .map(|(size, entry)| {
+                let id = model_id(*size);
+                match entry {
+                    Entry::Loading(progress) => {
+                        let (downloaded, total) = progress.snapshot();
+                        json!({
+                            "id": id,
+                            "status": "loading",
+                            "downloaded_bytes": downloaded,
+                            "total_bytes": (total > 0).then_some(total),
+                        })
+                    }
+                    Entry::Ready(_) => json!({"id": id, "status": "ready"}),
+                    Entry::Failed(message) => {
+                        json!({"id": id, "status": "failed", "error": message})
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        models.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+        models
+    }
+}
+
+/// Parses `whisper-<size>` model ids; `whisper-1` and unknown sizes return `None`.
+pub fn parse_on_demand_model_id(model_id: &str) -> Option<WhisperModelSize> {
+    let size = model_id.strip_prefix(ON_DEMAND_MODEL_PREFIX)?;
+    WhisperModelSize::from_str(size, true).ok()
+}
+
+/// Formats the `whisper-<size>` id of an on-demand model.
+fn model_id(size: WhisperModelSize) -> String {
+    size.to_possible_value()
+        .map(|value| format!("{ON_DEMAND_MODEL_PREFIX}{}", value.get_name()))
+        .unwrap_or_default()
+}
+
+/// Derives the configuration for `size` from the server configuration: one
+/// worker, unless `WHISPER_MODEL_WORKERS` gives the model a pool of its own.
+fn on_demand_config(base: &AppConfig, size: WhisperModelSize) -> AppConfig {
+    let mut cfg = base.clone();
+    cfg.use_model_size(size);
+    cfg.whisper_parallelism = on_demand_workers(base, size).map_or(1, |limits| limits.workers);
+    cfg.whisper_cpu_affinity = None;
+    cfg
+}
+
+/// The `WHISPER_MODEL_WORKERS` entry naming the on-demand model of `size`.
+fn on_demand_workers(base: &AppConfig, size: WhisperModelSize) -> Option<ModelWorkers> {
+    base.model_workers
+        .iter()
+        .find(|(id, _)| parse_on_demand_model_id(id) == Some(size))
+        .map(|(_, limits)| *limits)
+}
+
+/// Loads every `WHISPER_EXTRA_MODELS` entry, keyed by its model id.
+///
+/// A size is resolved and downloaded like `WHISPER_MODEL_SIZE`; anything else
+/// is a model file that must already exist. Models named in
+/// `WHISPER_MODEL_WORKERS` get that many workers and a [`DedicatedPool`].
+pub fn load_extra_models(
+    cfg: &AppConfig,
+) -> Result<HashMap<String, Arc<dyn Transcriber>>, AppError> {
+    let mut models = HashMap::new();
+    for (id, source) in &cfg.extra_models {
+        let mut model_cfg = extra_model_config(cfg, source)?;
+        let limits = cfg.model_workers(id);
+        if let Some(limits) = limits {
+            model_cfg.whisper_parallelism = limits.workers;
+        }
+        info!(model = %id, path = %model_cfg.whisper_model, workers = model_cfg.whisper_parallelism, "loading extra model");
+        let backend = load_model(model_cfg, &DownloadProgress::default())?;
+        let backend: Arc<dyn Transcriber> = match limits {
+            Some(limits) => Arc::new(DedicatedPool::new(backend, limits, cfg.max_queue_depth)),
+            None => backend,
+        };
+        models.insert(id.clone(), backend);
+    }
+    Ok(models)
+}
+
+fn extra_model_config(base: &AppConfig, source: &str) -> Result<AppConfig, AppError> {
+    let mut cfg = base.clone();
+    cfg.whisper_cpu_affinity = None;
+    if let Ok(size) = WhisperModelSize::from_str(source, true) {
+        cfg.use_model_size(size);
+        return Ok(cfg);
+    }
+    if !std::path::Path::new(source).is_file() {
+        return Err(AppError::internal(format!(
+            "WHISPER_EXTRA_MODELS names {source:?}, which is neither a model size nor an existing file"
+        )));
+    }
+    cfg.whisper_model = source.to_string();
+    cfg.whisper_model_explicit = true;
+    Ok(cfg)
+}
+
+/// Loads a CPU-only copy of the model described by `cfg` with
+/// `WHISPER_CPU_WORKERS` workers; `None` when no CPU pool is configured.
+pub(crate) fn load_cpu_pool(
+    cfg: &AppConfig,
+    loader: ModelLoader,
+) -> Result<Option<Arc<dyn Transcriber>>, AppError> {
+    let Some(workers) = cfg.whisper_cpu_workers else {
+        return Ok(None);
+    };
+    let mut cpu_cfg = cfg.clone();
+    cpu_cfg.acceleration_kind = AccelerationKind::None;
+    cpu_cfg.acceleration_explicit = true;
+    cpu_cfg.whisper_parallelism = workers;
+    cpu_cfg.whisper_cpu_affinity = None;
+    let backend = loader(cpu_cfg, &DownloadProgress::default())?;
+    Ok(Some(Arc::new(CpuPool(backend))))
+}
+
+/// Resolves (downloading if needed) and loads the model described by `cfg`.
+pub(crate) fn load_model(
+    mut cfg: AppConfig,
+    progress: &DownloadProgress,
+) -> Result<Arc<dyn Transcriber>, AppError> {
+    ensure_model_ready_with_progress(&mut cfg, Some(progress))?;
+    build_backend(&cfg)
+}
+
+fn loading_error(model_id: &str, progress: &DownloadProgress) -> AppError {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let detail = match progress.snapshot() {
+        (0, _) => "preparing".to_string(),
+        (downloaded, 0) => format!("downloaded {:.1} MiB", downloaded as f64 / MIB),
+        (downloaded, total) => format!(
+            "downloaded {:.1} of {:.1} MiB, {}%",
+            downloaded as f64 / MIB,
+            total as f64 / MIB,
+            downloaded * 100 / total
+        ),
+    };
+    AppError::model_loading(format!(
+        "model {model_id:?} is loading ({detail}); retry shortly"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn on_demand_ids_map_to_model_sizes() {
+        assert_eq!(
+            parse_on_demand_model_id("whisper-large-v3"),
+            Some(WhisperModelSize::LargeV3)
+        );
+        assert_eq!(
+            parse_on_demand_model_id("whisper-tiny.en"),
+            Some(WhisperModelSize::TinyEn)
+        );
+        assert_eq!(
+            parse_on_demand_model_id("whisper-turbo"),
+            Some(WhisperModelSize::Turbo)
+        );
+        assert_eq!(parse_on_demand_model_id("whisper-1"), None);
+        assert_eq!(parse_on_demand_model_id("large-v3"), None);
+    }
+}
@@ -1,25 +1,440 @@
 //! Model path resolution and optional Hugging Face download support.
 //!
 //! This module guarantees that `cfg.whisper_model` points to a readable local
-//! file before backend initialization.
+//! file before backend initialization. The Whisper model, Piper voices, and
+//! auxiliary models (`WHISPER_AUX_MODELS`, such as VAD or diarization models)
+//! are all fetched through [`ensure_artifact`], so they share the same
+//! lock-file coordination between processes, `.part` download with
+//! verification before the atomic rename, and offline behavior when
+//! `WHISPER_AUTO_DOWNLOAD=false`. Throttled (`429`) and transiently failing
+//! downloads are retried with jittered exponential backoff that honors
+//! `Retry-After`, so replicas starting together spread out their retries.
+//!
+//! Models that ship as several files (CTranslate2 directories, ONNX encoder
+//! and decoder pairs, CoreML bundles) are fetched as a directory through
+//! [`ensure_directory_artifact`]: the repository listing names every file with
+//! its size and, for LFS files, its SHA-256; the files are downloaded into a
+//! `.part` sibling directory, verified, and the directory is renamed into
+//! place whole, so a partially downloaded model is never picked up.
 
+use std::collections::hash_map::RandomState;
 use std::fs::{self, File, OpenOptions};
-use std::io::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use reqwest::blocking::{Client, Response};
+use reqwest::header::RETRY_AFTER;
 use reqwest::StatusCode;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
 
 use crate::config::AppConfig;
 use crate::error::AppError;
 
 const LOCK_TIMEOUT: Duration = Duration::from_secs(120);
 const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Requests made for one file before a throttled or failing download gives up.
+const DOWNLOAD_ATTEMPTS: u32 = 6;
+/// Backoff before the first retry; doubled for each further attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+/// Longest wait between attempts, even when `Retry-After` asks for more.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(120);
+/// File written last into a downloaded model directory, listing its files.
+const DIRECTORY_MANIFEST: &str = ".download-manifest.json";
+
+/// Byte counters for an in-flight model download, readable from other threads.
+#[derive(Debug, Default)]
+pub struct DownloadProgress {
+    downloaded: AtomicU64,
+    total: AtomicU64,
+}
+
+impl DownloadProgress {
+    /// Returns `(downloaded_bytes, total_bytes)`; the total is 0 until known.
+    pub fn snapshot(&self) -> (u64, u64) {
+        (
+            self.downloaded.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Size and SHA-256 (lowercase hex) identifying the contents of a model file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelDigest {
+    pub size_bytes: u64,
+    pub sha256: String,
+}
+
+/// A file in a Hugging Face repository cached at `target_path`.
+#[derive(Debug, Clone)]
+pub struct Artifact<'a> {
+    /// What the file is, used in logs and errors (for example `"whisper model"`).
+    pub kind: &'a str,
+    pub hf_repo: &'a str,
+    pub hf_filename: &'a str,
+    pub target_path: PathBuf,
+    /// Configuration to check when the file is missing upstream.
+    pub not_found_hint: &'a str,
+}
+
+/// Ensures `artifact` is cached locally, downloading it when `auto_download` allows.
+///
+/// Concurrent callers (including other processes) serialize on a sibling
+/// `.lock` file, and only one downloads; the rest find the finished file.
+pub fn ensure_artifact(
+    artifact: &Artifact,
+    auto_download: bool,
+    hf_token: Option<&str>,
+    progress: Option<&DownloadProgress>,
+) -> Result<PathBuf, AppError> {
+    let target_path = &artifact.target_path;
+    if model_file_exists(&target_path.to_string_lossy()) {
+        return Ok(target_path.clone());
+    }
+    if !auto_download {
+        return Err(AppError::internal(format!(
+            "{} not found at {:?}; download it there or enable WHISPER_AUTO_DOWNLOAD",
+            artifact.kind, target_path
+        )));
+    }
+
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent).map_err(|err| {
+            AppError::internal(format!(
+                "failed to create {} cache directory {:?}: {err}",
+                artifact.kind, parent
+            ))
+        })?;
+    }
+
+    let lock_path = lock_path_for(target_path);
+    let _guard = acquire_lock(&lock_path)?;
+
+    if model_file_exists(&target_path.to_string_lossy()) {
+        return Ok(target_path.clone());
+    }
+
+    info!(
+        target = "whisper_openai_server::model_store",
+        kind = artifact.kind,
+        repo = %artifact.hf_repo,
+        filename = %artifact.hf_filename,
+        destination = %target_path.to_string_lossy(),
+        "starting download"
+    );
+    download_to_path(
+        &hf_resolve_url(artifact.hf_repo, artifact.hf_filename),
+        hf_token,
+        target_path,
+        artifact.not_found_hint,
+        progress,
+    )?;
+    Ok(target_path.clone())
+}
+
+/// A directory of files in a Hugging Face repository cached at `target_dir`.
+#[derive(Debug, Clone)]
+pub struct DirectoryArtifact<'a> {
+    /// What the model is, used in logs and errors.
+    pub kind: &'a str,
+    pub hf_repo: &'a str,
+    /// Directory within the repository; empty for the repository root.
+    pub hf_dir: &'a str,
+    pub target_dir: PathBuf,
+    /// Configuration to check when the directory is missing upstream.
+    pub not_found_hint: &'a str,
+}
+
+/// A file of a multi-file model, relative to the model directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RepoFile {
+    path: String,
+    size_bytes: u64,
+    /// Known for LFS files, which hold the weights; small text files only
+    /// have their size checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// Ensures the multi-file model `artifact` is cached as a directory,
+/// downloading it when `auto_download` allows.
+///
+/// A directory written by this function is reused when every file in its
+/// manifest is still present at its recorded size. A non-empty directory
+/// without a manifest was placed there by hand and is used as-is.
+pub fn ensure_directory_artifact(
+    artifact: &DirectoryArtifact,
+    auto_download: bool,
+    hf_token: Option<&str>,
+    progress: Option<&DownloadProgress>,
+) -> Result<PathBuf, AppError> {
+    let target_dir = &artifact.target_dir;
+    if directory_ready(target_dir)? {
+        return Ok(target_dir.clone());
+    }
+    if !auto_download {
+        return Err(AppError::internal(format!(
+            "{} not found at {:?}; download it there or enable WHISPER_AUTO_DOWNLOAD",
+            artifact.kind, target_dir
+        )));
+    }
+    if let Some(parent) = target_dir.parent() {
+        fs::create_dir_all(parent).map_err(|err| {
+            AppError::internal(format!(
+                "failed to create {} cache directory {:?}: {err}",
+                artifact.kind, parent
+            ))
+        })?;
+    }
+
+    let _guard = acquire_lock(&lock_path_for(target_dir))?;
+    if directory_ready(target_dir)? {
+        return Ok(target_dir.clone());
+    }
+
+    let files = list_repo_dir(artifact, hf_token)?;
+    info!(
+        target = "whisper_openai_server::model_store",
+        kind = artifact.kind,
+        repo = %artifact.hf_repo,
+        dir = %artifact.hf_dir,
+        files = files.len(),
+        destination = %target_dir.to_string_lossy(),
+        "starting download"
+    );
+    let hf_dir = artifact.hf_dir.trim_matches('/');
+    materialize_directory(target_dir, &files, |file, path| {
+        let hf_filename = if hf_dir.is_empty() {
+            file.path.clone()
+        } else {
+            format!("{hf_dir}/{}", file.path)
+        };
+        download_to_path(
+            &hf_resolve_url(artifact.hf_repo, &hf_filename),
+            hf_token,
+            path,
+            artifact.not_found_hint,
+            progress,
+        )
+    })?;
+    Ok(target_dir.clone())
+}
+
+/// Whether `dir` holds a usable model directory.
+///
+/// Fails when a downloaded directory no longer matches its manifest, rather
+/// than silently downloading over files someone changed.
+fn directory_ready(dir: &Path) -> Result<bool, AppError> {
+    let has_files = fs::read_dir(dir)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    if !has_files {
+        return Ok(false);
+    }
+    let manifest = match fs::read(dir.join(DIRECTORY_MANIFEST)) {
+        Ok(manifest) => manifest,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+        Err(err) => {
+            return Err(AppError::internal(format!(
+                "failed to read {DIRECTORY_MANIFEST} in {:?}: {err}",
+                dir
+            )))
+        }
+    };
+    let files: Vec<RepoFile> = serde_json::from_slice(&manifest).map_err(|err| {
+        AppError::internal(format!("invalid {DIRECTORY_MANIFEST} in {:?}: {err}", dir))
+    })?;
+    for file in &files {
+        let size = fs::metadata(dir.join(&file.path))
+            .map(|meta| meta.len())
+            .ok();
+        if size != Some(file.size_bytes) {
+            return Err(AppError::internal(format!(
+                "model directory {:?} no longer matches its download: {:?} is missing or changed; remove the directory to download it again",
+                dir, file.path
+            )));
+        }
+    }
+    Ok(true)
+}
+
+/// Lists the files under `artifact.hf_dir`, relative to it.
+fn list_repo_dir(
+    artifact: &DirectoryArtifact,
+    hf_token: Option<&str>,
+) -> Result<Vec<RepoFile>, AppError> {
+    #[derive(Deserialize)]
+    struct TreeEntry {
+        #[serde(rename = "type")]
+        kind: String,
+        path: String,
+        #[serde(default)]
+        size: u64,
+        lfs: Option<LfsPointer>,
+    }
+    #[derive(Deserialize)]
+    struct LfsPointer {
+        oid: String,
+    }
+
+    let hf_dir = artifact.hf_dir.trim_matches('/');
+    let url = hf_tree_url(artifact.hf_repo, hf_dir);
+    let client = Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|err| AppError::internal(format!("failed to create HTTP client: {err}")))?;
+    let response = send_with_retries(&client, &url, hf_token)?;
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::NOT_FOUND => {
+            return Err(AppError::internal(format!(
+                "{} not found at {url}; {}",
+                artifact.kind, artifact.not_found_hint
+            )))
+        }
+        status => {
+            return Err(AppError::internal(format!(
+                "listing {} files failed from {url} with HTTP status {status}",
+                artifact.kind
+            )))
+        }
+    }
+    let entries: Vec<TreeEntry> = serde_json::from_reader(response)
+        .map_err(|err| AppError::internal(format!("unreadable file listing from {url}: {err}")))?;
+
+    let prefix = if hf_dir.is_empty() {
+        String::new()
+    } else {
+        format!("{hf_dir}/")
+    };
+    let files = entries
+        .into_iter()
+        .filter(|entry| entry.kind == "file")
+        .filter_map(|entry| {
+            let path = entry.path.strip_prefix(&prefix)?.to_string();
+            Some(RepoFile {
+                path,
+                size_bytes: entry.size,
+                sha256: entry.lfs.map(|lfs| lfs.oid),
+            })
+        })
+        .filter(|file| !file.path.starts_with('.'))
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        return Err(AppError::internal(format!(
+            "{} at {url} has no files; {}",
+            artifact.kind, artifact.not_found_hint
+        )));
+    }
+    Ok(files)
+}
+
+/// Builds `target_dir` from `files`, each written by `fetch` to the path it is given.
+///
+/// Files land in a `.part` sibling directory and are checked against their
+/// listed size and SHA-256; the manifest is written last and the directory
+/// renamed into place only when everything matched. The caller must hold the
+/// download lock for `target_dir`.
+fn materialize_directory(
+    target_dir: &Path,
+    files: &[RepoFile],
+    mut fetch: impl FnMut(&RepoFile, &Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let staging = target_dir.with_file_name(format!(
+        "{}.part",
+        target_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("model")
+    ));
+    // Left behind by an interrupted download; the lock says nobody owns it.
+    let _ = fs::remove_dir_all(&staging);
+    let built = build_directory(&staging, files, &mut fetch).and_then(|()| {
+        fs::rename(&staging, target_dir).map_err(|err| {
+            AppError::internal(format!(
+                "failed to move model directory from {:?} to {:?}: {err}",
+                staging, target_dir
+            ))
+        })
+    });
+    if built.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    built
+}
+
+fn build_directory(
+    staging: &Path,
+    files: &[RepoFile],
+    fetch: &mut impl FnMut(&RepoFile, &Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    for file in files {
+        if !is_relative_model_path(&file.path) {
+            return Err(AppError::internal(format!(
+                "refusing model file {:?} outside its directory",
+                file.path
+            )));
+        }
+        let path = staging.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                AppError::internal(format!(
+                    "failed to create model directory {:?}: {err}",
+                    parent
+                ))
+            })?;
+        }
+        fetch(file, &path)?;
+        let actual = digest_file(&path)?;
+        let sha_matches = file
+            .sha256
+            .as_ref()
+            .map_or(true, |sha256| *sha256 == actual.sha256);
+        if actual.size_bytes != file.size_bytes || !sha_matches {
+            return Err(AppError::internal(format!(
+                "model file {:?} failed verification: expected {} bytes with sha256 {}, got {} bytes with sha256 {}",
+                file.path,
+                file.size_bytes,
+                file.sha256.as_deref().unwrap_or("(unlisted)"),
+                actual.size_bytes,
+                actual.sha256
+            )));
+        }
+    }
+    let manifest = serde_json::to_vec_pretty(files)
+        .map_err(|err| AppError::internal(format!("failed to encode model manifest: {err}")))?;
+    fs::write(staging.join(DIRECTORY_MANIFEST), manifest).map_err(|err| {
+        AppError::internal(format!(
+            "failed to write {DIRECTORY_MANIFEST} in {:?}: {err}",
+            staging
+        ))
+    })
+}
+
+/// Whether `path` stays inside the directory it is joined to.
+fn is_relative_model_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
 
 /// Ensures a local Whisper model file exists, downloading from Hugging Face if needed.
 pub fn ensure_model_ready(cfg: &mut AppConfig) -> Result<(), AppError> {
+    ensure_model_ready_with_progress(cfg, None)
+}
+
+/// Same as [`ensure_model_ready`], reporting download bytes into `progress`.
+pub fn ensure_model_ready_with_progress(
+    cfg: &mut AppConfig,
+    progress: Option<&DownloadProgress>,
+) -> Result<(), AppError> {
     if model_file_exists(&cfg.whisper_model) {
         return Ok(());
     }
@@ -31,12 +446,79 @@ pub fn ensure_model_ready(cfg: &mut AppConfig) -> Result<(), AppError> {
         )));
     }
 
-    let target_path = model_target_path(cfg);
-    if model_file_exists(&target_path.to_string_lossy()) {
-        cfg.whisper_model = target_path.to_string_lossy().to_string();
-        return Ok(());
-    }
+    let artifact = Artifact {
+        kind: "whisper model",
+        hf_repo: &cfg.whisper_hf_repo,
+        hf_filename: &cfg.whisper_hf_filename,
+        target_path: model_target_path(cfg),
+        not_found_hint: "verify WHISPER_HF_REPO and WHISPER_HF_FILENAME",
+    };
+    let target_path = ensure_artifact(&artifact, true, cfg.hf_token.as_deref(), progress)?;
+    cfg.whisper_model = target_path.to_string_lossy().to_string();
+    Ok(())
+}
+
+/// Ensures every `WHISPER_AUX_MODELS` file is cached, returning their local paths.
+///
+/// Files are stored as `<WHISPER_CACHE_DIR>/<repo>/<filename>`. An entry
+/// ending in `/` names a directory of the repository (`/` alone for all of
+/// it), which is cached as a directory at the same place.
+pub fn ensure_aux_models_ready(cfg: &AppConfig) -> Result<Vec<PathBuf>, AppError> {
+    cfg.aux_models
+        .iter()
+        .map(|(repo, filename)| {
+            if filename.ends_with('/') {
+                let artifact = DirectoryArtifact {
+                    kind: "auxiliary model",
+                    hf_repo: repo,
+                    hf_dir: filename,
+                    target_dir: aux_model_path(&cfg.whisper_cache_dir, repo, filename),
+                    not_found_hint: "verify WHISPER_AUX_MODELS",
+                };
+                return ensure_directory_artifact(
+                    &artifact,
+                    cfg.whisper_auto_download,
+                    cfg.hf_token.as_deref(),
+                    None,
+                );
+            }
+            let artifact = Artifact {
+                kind: "auxiliary model",
+                hf_repo: repo,
+                hf_filename: filename,
+                target_path: aux_model_path(&cfg.whisper_cache_dir, repo, filename),
+                not_found_hint: "verify WHISPER_AUX_MODELS",
+            };
+            ensure_artifact(
+                &artifact,
+                cfg.whisper_auto_download,
+                cfg.hf_token.as_deref(),
+                None,
+            )
+        })
+        .collect()
+}
+
+/// Computes the size and SHA-256 of the file at `path`.
+pub fn digest_file(path: &Path) -> Result<ModelDigest, AppError> {
+    let mut file = File::open(path)
+        .map_err(|err| AppError::internal(format!("failed to open model {:?}: {err}", path)))?;
+    let mut writer = HashingWriter::new(std::io::sink());
+    std::io::copy(&mut file, &mut writer)
+        .map_err(|err| AppError::internal(format!("failed to read model {:?}: {err}", path)))?;
+    Ok(writer.finish())
+}
 
+/// Installs the model read from `reader` at `target_path` if it matches `expected`.
+///
+/// As with downloads, the file is written to a `.part` sibling while holding
+/// the download lock and renamed into place only once its size and SHA-256
+/// match, so a truncated or corrupt copy never looks like a usable model.
+pub fn install_verified(
+    reader: &mut impl Read,
+    expected: &ModelDigest,
+    target_path: &Path,
+) -> Result<(), AppError> {
     if let Some(parent) = target_path.parent() {
         fs::create_dir_all(parent).map_err(|err| {
             AppError::internal(format!(
@@ -45,29 +527,116 @@ pub fn ensure_model_ready(cfg: &mut AppConfig) -> Result<(), AppError> {
             ))
         })?;
     }
+    let _guard = acquire_lock(&lock_path_for(target_path))?;
 
-    let lock_path = lock_path_for(&target_path);
-    let _guard = acquire_lock(&lock_path)?;
+    let tmp_path = target_path.with_extension("part");
+    let out = File::create(&tmp_path).map_err(|err| {
+        AppError::internal(format!(
+            "failed to create temporary model file {:?}: {err}",
+            tmp_path
+        ))
+    })?;
+    let mut writer = HashingWriter::new(out);
+    let copied = std::io::copy(reader, &mut writer).and_then(|_| writer.inner.sync_all());
+    if let Err(err) = copied {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(AppError::internal(format!(
+            "failed writing model to {:?}: {err}",
+            tmp_path
+        )));
+    }
+    let actual = writer.finish();
+    if actual != *expected {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(AppError::internal(format!(
+            "model for {:?} failed verification: expected {} bytes with sha256 {}, got {} bytes with sha256 {}",
+            target_path, expected.size_bytes, expected.sha256, actual.size_bytes, actual.sha256
+        )));
+    }
 
-    if model_file_exists(&target_path.to_string_lossy()) {
-        cfg.whisper_model = target_path.to_string_lossy().to_string();
+    fs::rename(&tmp_path, target_path).map_err(|err| {
+        AppError::internal(format!(
+            "failed to move model from {:?} to {:?}: {err}",
+            tmp_path, target_path
+        ))
+    })
+}
+
+/// Cache location of an auxiliary model, namespaced by repository.
+pub fn aux_model_path(cache_dir: &str, repo: &str, filename: &str) -> PathBuf {
+    Path::new(cache_dir)
+        .join(repo.trim_matches('/'))
+        .join(filename.trim_matches('/'))
+}
+
+/// Ensures the configured Piper voice (`.onnx` model plus `.onnx.json` config)
+/// exists locally, downloading it from Hugging Face if needed.
+#[cfg(feature = "tts")]
+pub fn ensure_voice_ready(cfg: &mut AppConfig) -> Result<(), AppError> {
+    if cfg.tts_voice.ends_with(".onnx") {
+        if model_file_exists(&cfg.tts_voice)
+            && model_file_exists(&format!("{}.json", cfg.tts_voice))
+        {
+            cfg.tts_voice_model = cfg.tts_voice.clone();
+            return Ok(());
+        }
+        return Err(AppError::internal(format!(
+            "piper voice not found at {:?}; both the .onnx model and its .onnx.json config are required",
+            cfg.tts_voice
+        )));
+    }
+
+    let target_path = Path::new(&cfg.tts_voices_dir).join(format!("{}.onnx", cfg.tts_voice));
+    let config_path = PathBuf::from(format!("{}.json", target_path.to_string_lossy()));
+    let voice_exists = || {
+        model_file_exists(&target_path.to_string_lossy())
+            && model_file_exists(&config_path.to_string_lossy())
+    };
+    if voice_exists() {
+        cfg.tts_voice_model = target_path.to_string_lossy().to_string();
         return Ok(());
     }
 
-    info!(
-        target = "whisper_openai_server::model_store",
-        repo = %cfg.whisper_hf_repo,
-        filename = %cfg.whisper_hf_filename,
-        size = ?cfg.whisper_model_size,
-        destination = %target_path.to_string_lossy(),
-        "starting whisper model download"
-    );
+    if !cfg.whisper_auto_download {
+        return Err(AppError::internal(format!(
+            "piper voice {:?} not found in {:?}; set TTS_VOICE to an existing .onnx file or enable WHISPER_AUTO_DOWNLOAD",
+            cfg.tts_voice, cfg.tts_voices_dir
+        )));
+    }
 
-    download_model_to_path(cfg, &target_path)?;
-    cfg.whisper_model = target_path.to_string_lossy().to_string();
+    let hf_path = piper_voice_hf_path(&cfg.tts_voice)?;
+    // The config is fetched first so a present `.onnx` always has its config.
+    for (suffix, path) in [(".onnx.json", &config_path), (".onnx", &target_path)] {
+        let hf_filename = format!("{hf_path}{suffix}");
+        let artifact = Artifact {
+            kind: "piper voice",
+            hf_repo: &cfg.tts_hf_repo,
+            hf_filename: &hf_filename,
+            target_path: path.clone(),
+            not_found_hint: "verify TTS_VOICE and TTS_HF_REPO",
+        };
+        ensure_artifact(&artifact, true, cfg.hf_token.as_deref(), None)?;
+    }
+
+    cfg.tts_voice_model = target_path.to_string_lossy().to_string();
     Ok(())
 }
 
+/// Maps a Piper voice name such as `en_US-lessac-medium` to its path in the
+/// `rhasspy/piper-voices` repository layout (`en/en_US/lessac/medium/...`).
+#[cfg(feature = "tts")]
+fn piper_voice_hf_path(voice: &str) -> Result<String, AppError> {
+    let mut parts = voice.splitn(3, '-');
+    let (Some(locale), Some(name), Some(quality)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(AppError::internal(format!(
+            "invalid piper voice name {voice:?}; expected <locale>-<name>-<quality>, for example en_US-lessac-medium"
+        )));
+    };
+    let family = locale.split('_').next().unwrap_or(locale);
+    Ok(format!("{family}/{locale}/{name}/{quality}/{voice}"))
+}
+
 fn model_file_exists(path: &str) -> bool {
     fs::metadata(path)
         .map(|meta| meta.is_file() && meta.len() > 0)
@@ -121,23 +690,23 @@ fn acquire_lock(path: &Path) -> Result<LockGuard, AppError> {
     }
 }
 
-fn download_model_to_path(cfg: &AppConfig, target_path: &Path) -> Result<(), AppError> {
-    let url = hf_resolve_url(&cfg.whisper_hf_repo, &cfg.whisper_hf_filename);
-    let client = reqwest::blocking::Client::builder()
+/// Downloads `url` into `target_path` via a temporary `.part` file.
+///
+/// `not_found_hint` is appended to 404 errors so callers can point at the
+/// configuration values that produced the URL.
+fn download_to_path(
+    url: &str,
+    hf_token: Option<&str>,
+    target_path: &Path,
+    not_found_hint: &str,
+    progress: Option<&DownloadProgress>,
+) -> Result<(), AppError> {
+    let client = Client::builder()
         .timeout(Duration::from_secs(600))
         .build()
         .map_err(|err| AppError::internal(format!("failed to create HTTP client: {err}")))?;
 
-    let mut request = client.get(&url);
-    if let Some(token) = cfg.hf_token.as_deref() {
-        request = request.bearer_auth(token);
-    }
-
-    let mut response = request.send().map_err(|err| {
-        AppError::internal(format!(
-            "failed to download model from {url}: {err}; check network connectivity"
-        ))
-    })?;
+    let mut response = send_with_retries(&client, url, hf_token)?;
 
     if !response.status().is_success() {
         return match response.status() {
@@ -146,7 +715,10 @@ fn download_model_to_path(cfg: &AppConfig, target_path: &Path) -> Result<(), App
                 response.status()
             ))),
             StatusCode::NOT_FOUND => Err(AppError::internal(format!(
-                "model not found at {url}; verify WHISPER_HF_REPO and WHISPER_HF_FILENAME"
+                "model not found at {url}; {not_found_hint}"
+            ))),
+            StatusCode::TOO_MANY_REQUESTS => Err(AppError::internal(format!(
+                "Hugging Face is still rate limiting model download from {url} after {DOWNLOAD_ATTEMPTS} attempts; set HF_TOKEN for a higher limit or retry later"
             ))),
             status => Err(AppError::internal(format!(
                 "model download failed from {url} with HTTP status {status}"
@@ -161,7 +733,17 @@ fn download_model_to_path(cfg: &AppConfig, target_path: &Path) -> Result<(), App
             tmp_path
         ))
     })?;
-    std::io::copy(&mut response, &mut out).map_err(|err| {
+    if let Some(progress) = progress {
+        progress.downloaded.store(0, Ordering::Relaxed);
+        progress
+            .total
+            .store(response.content_length().unwrap_or(0), Ordering::Relaxed);
+    }
+    let mut writer = ProgressWriter {
+        inner: &mut out,
+        progress,
+    };
+    std::io::copy(&mut response, &mut writer).map_err(|err| {
         AppError::internal(format!(
             "failed writing downloaded model to {:?}: {err}",
             tmp_path
@@ -192,6 +774,171 @@ fn download_model_to_path(cfg: &AppConfig, target_path: &Path) -> Result<(), App
     Ok(())
 }
 
+/// Requests `url`, retrying throttled (`429`) and transient failures.
+///
+/// Waits follow `Retry-After` when the server sends it and exponential backoff
+/// otherwise, with random jitter added so processes throttled at the same
+/// moment do not all retry at the same moment. The last response is returned
+/// even when it is still an error status, for the caller to report.
+fn send_with_retries(
+    client: &Client,
+    url: &str,
+    hf_token: Option<&str>,
+) -> Result<Response, AppError> {
+    let mut attempt = 1;
+    loop {
+        let mut request = client.get(url);
+        if let Some(token) = hf_token {
+            request = request.bearer_auth(token);
+        }
+        let (retry_after, reason) = match request.send() {
+            Ok(response) if !is_retryable_status(response.status()) => return Ok(response),
+            Ok(response) if attempt >= DOWNLOAD_ATTEMPTS => return Ok(response),
+            Ok(response) => (
+                retry_after(&response),
+                format!("HTTP status {}", response.status()),
+            ),
+            Err(err) if (err.is_connect() || err.is_timeout()) && attempt < DOWNLOAD_ATTEMPTS => {
+                (None, err.to_string())
+            }
+            Err(err) => {
+                return Err(AppError::internal(format!(
+                    "failed to download model from {url}: {err}; check network connectivity"
+                )))
+            }
+        };
+        let delay = retry_delay(attempt, retry_after, jitter_fraction());
+        warn!(
+            target = "whisper_openai_server::model_store",
+            %url,
+            attempt,
+            max_attempts = DOWNLOAD_ATTEMPTS,
+            delay_ms = delay.as_millis() as u64,
+            reason = %reason,
+            "model download failed; retrying"
+        );
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Whether a download answered with `status` may succeed if repeated.
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// The `Retry-After` delay of `response`, when given in seconds.
+///
+/// The HTTP-date form is not parsed; such responses fall back to backoff.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Wait before retry number `attempt` (from 1), given `jitter` in `[0, 1)`.
+///
+/// Without `Retry-After`, the wait is drawn from the upper half of the
+/// exponential backoff. With it, up to half the backoff is added on top of the
+/// requested wait to spread replicas apart. Either way the wait is capped at
+/// [`RETRY_MAX_DELAY`].
+fn retry_delay(attempt: u32, retry_after: Option<Duration>, jitter: f64) -> Duration {
+    let backoff = RETRY_BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(RETRY_MAX_DELAY);
+    let spread = backoff.mul_f64(jitter / 2.0);
+    let delay = match retry_after {
+        Some(retry_after) => retry_after + spread,
+        None => backoff / 2 + spread,
+    };
+    delay.min(RETRY_MAX_DELAY)
+}
+
+/// A random fraction in `[0, 1)` from per-process random hasher keys.
+fn jitter_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Writer adapter that counts bytes into an optional [`DownloadProgress`].
+struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: Option<&'a DownloadProgress>,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(progress) = self.progress {
+            progress
+                .downloaded
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer adapter that hashes and counts the bytes passed through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    size_bytes: u64,
+}
+
+impl<W> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size_bytes: 0,
+        }
+    }
+
+    fn finish(self) -> ModelDigest {
+        let sha256 = self
+            .hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        ModelDigest {
+            size_bytes: self.size_bytes,
+            sha256,
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size_bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 fn hf_resolve_url(repo: &str, filename: &str) -> String {
     format!(
         "https://huggingface.co/{}/resolve/main/{}",
@@ -200,6 +947,15 @@ fn hf_resolve_url(repo: &str, filename: &str) -> String {
     )
 }
 
+/// Hugging Face API listing of every file under `dir` of `repo`.
+fn hf_tree_url(repo: &str, dir: &str) -> String {
+    format!(
+        "https://huggingface.co/api/models/{}/tree/main/{}?recursive=true",
+        repo.trim_matches('/'),
+        dir.trim_matches('/')
+    )
+}
+
 struct LockGuard {
     path: PathBuf,
 }
@@ -212,8 +968,25 @@ impl Drop for LockGuard {
 
 #[cfg(test)]
 mod tests {
-    use super::{hf_resolve_url, lock_path_for};
+    use super::{
+        aux_model_path, digest_file, directory_ready, download_to_path, ensure_artifact,
+        hf_resolve_url, lock_path_for, materialize_directory, retry_delay, Artifact, RepoFile,
+        RETRY_MAX_DELAY,
+    };
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
     use std::path::Path;
+    use std::time::Duration;
+
+    #[cfg(feature = "tts")]
+    #[test]
+    fn piper_voice_path_follows_repo_layout() {
+        assert_eq!(
+            super::piper_voice_hf_path("en_US-lessac-medium").unwrap(),
+            "en/en_US/lessac/medium/en_US-lessac-medium"
+        );
+        assert!(super::piper_voice_hf_path("lessac").is_err());
+    }
 
     #[test]
     fn resolve_url_normalizes_edges() {
@@ -223,6 +996,134 @@ mod tests {
         );
     }
 
+    #[test]
+    fn artifacts_resolve_from_cache_and_respect_offline_mode() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let target_path = aux_model_path(
+            &dir.path().to_string_lossy(),
+            "ggml-org/whisper-vad",
+            "ggml-silero-v5.1.2.bin",
+        );
+        assert!(target_path.ends_with("ggml-org/whisper-vad/ggml-silero-v5.1.2.bin"));
+        let artifact = Artifact {
+            kind: "VAD model",
+            hf_repo: "ggml-org/whisper-vad",
+            hf_filename: "ggml-silero-v5.1.2.bin",
+            target_path: target_path.clone(),
+            not_found_hint: "verify WHISPER_AUX_MODELS",
+        };
+
+        let err = ensure_artifact(&artifact, false, None, None).expect_err("offline miss");
+        assert!(err.to_string().contains("VAD model not found"));
+
+        std::fs::create_dir_all(target_path.parent().expect("parent")).expect("mkdir");
+        std::fs::write(&target_path, b"weights").expect("write");
+        assert_eq!(
+            ensure_artifact(&artifact, false, None, None).expect("cached"),
+            target_path
+        );
+    }
+
+    #[test]
+    fn model_directories_appear_only_when_every_file_verifies() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let weights = dir.path().join("weights");
+        std::fs::write(&weights, b"weights").expect("write");
+        let files = vec![
+            RepoFile {
+                path: "config.json".to_string(),
+                size_bytes: 2,
+                sha256: None,
+            },
+            RepoFile {
+                path: "encoder/model.onnx".to_string(),
+                size_bytes: 7,
+                sha256: Some(digest_file(&weights).expect("digest").sha256),
+            },
+        ];
+        let target_dir = dir.path().join("faster-whisper-small");
+        let fetch = |payload: &'static [u8]| {
+            move |file: &RepoFile, path: &Path| {
+                let body: &[u8] = if file.path == "config.json" {
+                    b"{}"
+                } else {
+                    payload
+                };
+                std::fs::write(path, body).map_err(|err| super::AppError::internal(err.to_string()))
+            }
+        };
+
+        let err = materialize_directory(&target_dir, &files, fetch(b"WEIGHTS"))
+            .expect_err("corrupt file");
+        assert!(err.to_string().contains("failed verification"), "{err}");
+        assert!(!target_dir.exists());
+        assert!(!dir.path().join("faster-whisper-small.part").exists());
+
+        materialize_directory(&target_dir, &files, fetch(b"weights")).expect("materialize");
+        assert!(directory_ready(&target_dir).expect("ready"));
+        assert_eq!(
+            std::fs::read(target_dir.join("encoder/model.onnx")).expect("read"),
+            b"weights"
+        );
+
+        std::fs::write(target_dir.join("config.json"), b"{\"x\": 1}").expect("edit");
+        assert!(directory_ready(&target_dir).is_err());
+
+        let escaping = vec![RepoFile {
+            path: "../escape.bin".to_string(),
+            size_bytes: 7,
+            sha256: None,
+        }];
+        let other_dir = dir.path().join("other");
+        assert!(materialize_directory(&other_dir, &escaping, fetch(b"weights")).is_err());
+        assert!(!directory_ready(&other_dir).expect("missing"));
+    }
+
+    #[test]
+    fn retry_delay_backs_off_and_honors_retry_after() {
+        let secs =
+            |attempt, retry_after, jitter| retry_delay(attempt, retry_after, jitter).as_secs_f64();
+        assert_eq!(secs(1, None, 0.0), 1.0);
+        assert_eq!(secs(1, None, 0.5), 1.5);
+        assert_eq!(secs(3, None, 0.0), 4.0);
+        assert_eq!(secs(3, Some(Duration::from_secs(30)), 0.0), 30.0);
+        assert_eq!(secs(3, Some(Duration::from_secs(30)), 0.5), 32.0);
+        assert_eq!(retry_delay(40, None, 0.0), RETRY_MAX_DELAY / 2);
+        assert!(retry_delay(40, None, 0.999) < RETRY_MAX_DELAY);
+        assert_eq!(
+            retry_delay(1, Some(Duration::from_secs(3600)), 0.0),
+            RETRY_MAX_DELAY
+        );
+    }
+
+    #[test]
+    fn throttled_downloads_are_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/model.bin", listener.local_addr().expect("addr"));
+        let server = std::thread::spawn(move || {
+            for response in [
+                "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                "HTTP/1.1 200 OK\r\ncontent-length: 7\r\nconnection: close\r\n\r\nweights",
+            ] {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).expect("read");
+                    assert!(read > 0, "request ended early");
+                    request.extend_from_slice(&buf[..read]);
+                }
+                stream.write_all(response.as_bytes()).expect("write");
+            }
+        });
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let target_path = dir.path().join("model.bin");
+        download_to_path(&url, None, &target_path, "", None).expect("download");
+        server.join().expect("server");
+        assert_eq!(std::fs::read(&target_path).expect("read"), b"weights");
+    }
+
     #[test]
     fn lock_path_uses_sibling_file() {
         let path = Path::new("/tmp/ggml-small.bin");
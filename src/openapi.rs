@@ -0,0 +1,468 @@
+//! OpenAPI 3.1 description of the HTTP API, served at `GET /openapi.json`.
+//!
+//! The document is assembled by hand so it can describe the multipart forms,
+//! alternate response formats, and OpenAI-style error payloads exactly as the
+//! handlers implement them. Keep it in step with `api.rs` when adding fields.
+
+use serde_json::{json, Value};
+
+use crate::build_info::VERSION;
+use crate::config::AppConfig;
+
+/// Builds the OpenAPI document for this server's configuration.
+pub fn spec(cfg: &AppConfig) -> Value {
+    let mut models = cfg.accepted_model_ids();
+    models.extend(cfg.model_aliases.iter().map(|(alias, _)| alias.clone()));
+
+    let mut spec = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "whisper-openai-server",
+            "version": VERSION,
+            "description": "OpenAI-compatible speech-to-text API backed by whisper.cpp.",
+        },
+        "paths": {
+            "/v1/audio/transcriptions": {
+                "post": audio_operation(
+                    "createTranscription",
+                    "Transcribes audio in its spoken language.",
+                    &models,
+                ),
+            },
+            "/v1/audio/translations": {
+                "post": audio_operation(
+                    "createTranslation",
+                    "Translates speech into English text.",
+                    &models,
+                ),
+            },
+            "/v1/audio/language": {
+                "post": {
+                    "operationId": "detectLanguage",
+                    "summary": "Detects the spoken language without transcribing.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {"schema": audio_form_schema(&models)},
+                        },
+                    },
+                    "responses": {
+                        "200": json_response("Detected language.", "LanguageDetection"),
+                        "400": error_response("Invalid form field or audio."),
+                        "401": error_response("Missing or invalid API key."),
+                        "413": error_response("Upload or decoded audio too large."),
+                        "503": error_response("Server busy or model loading; retry later."),
+                    },
+                },
+            },
+            "/v1/models": {
+                "get": {
+                    "operationId": "listModels",
+                    "summary": "Lists the model ids accepted by the audio endpoints.",
+                    "responses": {
+                        "200": json_response("Accepted models.", "ModelList"),
+                        "401": error_response("Missing or invalid API key."),
+                    },
+                },
+            },
+            "/v1/audio/jobs/{id}": {
+                "get": {
+                    "operationId": "getAudioJob",
+                    "summary": "Reports the status and result of an async transcription job.",
+                    "parameters": [path_param("id", "Job id returned by `?async=true`.")],
+                    "responses": {
+                        "200": json_response("Job status.", "AudioJob"),
+                        "401": error_response("Missing or invalid API key."),
+                        "404": error_response("Unknown or expired job."),
+                    },
+                },
+            },
+            "/v1/audio/continuations/{token}": {
+                "get": {
+                    "operationId": "getTranscriptContinuation",
+                    "summary": "Returns the next page of a transcript split by `max_chars`.",
+                    "parameters": [path_param("token", "`continuation_token` from the previous page.")],
+                    "responses": {
+                        "200": json_response("Next transcript page.", "VerboseTranscription"),
+                        "401": error_response("Missing or invalid API key."),
+                        "404": error_response("Unknown or expired token."),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer"},
+            },
+        },
+    });
+    if !cfg.api_keys.is_empty() {
+        spec["security"] = json!([{"bearerAuth": []}]);
+    }
+    spec
+}
+
+/// Operation object shared by the transcription and translation endpoints.
+fn audio_operation(operation_id: &str, summary: &str, models: &[String]) -> Value {
+    json!({
+        "operationId": operation_id,
+        "summary": summary,
+        "parameters": [{
+            "name": "async",
+            "in": "query",
+            "required": false,
+            "description": "Queue the request and return `202` with a job to poll.",
+            "schema": {"type": "boolean", "default": false},
+        }],
+        "requestBody": {
+            "required": true,
+            "content": {
+                "multipart/form-data": {
+                    "schema": audio_form_schema(models),
+                    "encoding": {"file": {"contentType": "audio/*, video/*, application/octet-stream"}},
+                },
+            },
+        },
+        "responses": {
+            "200": {
+                "description": "Transcript in the requested `response_format`.",
+                "content": {
+                    "application/json": {
+                        "schema": {
+                            "oneOf": [
+                                {"$ref": "#/components/schemas/Transcription"},
+                                {"$ref": "#/components/schemas/VerboseTranscription"},
+                                {"$ref": "#/components/schemas/BatchTranscription"},
+                            ],
+                        },
+                    },
+                    "text/plain": {"schema": {"type": "string"}},
+                    "application/x-subrip": {"schema": {"type": "string"}},
+                    "text/vtt": {"schema": {"type": "string"}},
+                    "text/event-stream": {
+                        "schema": {"type": "string"},
+                        "description": "Server-sent transcript deltas when `stream=true`.",
+                    },
+                },
+            },
+            "202": json_response("Async job accepted.", "AudioJob"),
+            "400": error_response("Invalid form field or audio."),
+            "401": error_response("Missing or invalid API key."),
+            "413": error_response("Upload or decoded audio too large."),
+            "429": {
+                "description": "Rate limit exceeded or inference queue full (`MAX_QUEUE_DEPTH`).",
+                "headers": {"Retry-After": {"schema": {"type": "integer"}}},
+                "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}},
+            },
+            "501": error_response("The model cannot honour a requested parameter."),
+            "503": error_response("Server busy or model loading; retry later."),
+            "504": error_response("Request exceeded `REQUEST_TIMEOUT_SECS`."),
+        },
+    })
+}
+
+/// Multipart form fields read by `parse_audio_form`.
+fn audio_form_schema(models: &[String]) -> Value {
+    json!({
+        "type": "object",
+        "required": ["file"],
+        "properties": {
+            "file": {
+                "description": "Audio file; repeat the field to transcribe several files in one request.",
+                "type": "string",
+                "format": "binary",
+            },
+            "model": {"type": "string", "default": "whisper-1", "examples": models},
+            "language": {"type": "string", "description": "ISO-639-1 code of the spoken language, e.g. `en`; for translations this is the source language, not the output."},
+            "source_language": {"type": "string", "description": "Same as `language`; if both are sent they must match."},
+            "prompt": {"type": "string", "description": "Text to guide the model's style."},
+            "response_format": {
+                "type": "string",
+                "enum": ["json", "text", "srt", "verbose_json", "vtt"],
+                "default": "json",
+            },
+            "temperature": {"type": "number", "minimum": 0.0, "maximum": 1.0},
+            "max_tokens_per_segment": {"type": "integer", "minimum": 1, "maximum": 224},
+            "beam_size": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 8,
+                "description": "Beam width; decodes with beam search instead of greedy sampling.",
+            },
+            "best_of": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 8,
+                "description": "Candidates sampled per segment at temperatures above 0; the most likely is kept.",
+            },
+            "stream": {"type": "boolean", "default": false},
+            "merge_segments": {"type": "boolean"},
+            "heuristic_punctuation": {"type": "boolean"},
+            "vad_filter": {"type": "boolean"},
+            "resample_quality": {"type": "string", "enum": ["fast", "balanced", "high"]},
+            "preset": {
+                "type": "string",
+                "description": "Named bundle of decode options (`meeting`, `voicemail`, `dictation`, `subtitles`, or one from `PRESETS_FILE`); explicit fields take precedence.",
+            },
+            "denoise": {
+                "type": "boolean",
+                "description": "Suppress background noise with RNNoise before inference; needs a server built with the `denoise` feature.",
+            },
+            "channel": {
+                "description": "Channel number from 0 to transcribe, or `average` to mix all channels.",
+                "oneOf": [
+                    {"type": "integer", "minimum": 0},
+                    {"type": "string", "enum": ["average"]},
+                ],
+            },
+            "metadata": {"type": "string", "maxLength": 4096},
+            "max_chars": {"type": "integer", "minimum": 1},
+            "overflow": {"type": "string", "enum": ["split", "truncate"], "default": "split"},
+            "debug_trace": {
+                "type": "boolean",
+                "description": "Store a debug trace of the request, fetched from `GET /admin/traces/{trace_id}` with the id in the `x-debug-trace-id` response header; needs `DEBUG_TRACE_DIR`.",
+            },
+            "compute": {"type": "string", "enum": ["cpu", "gpu"]},
+            "duration": {
+                "type": "number",
+                "exclusiveMinimum": 0.0,
+                "maximum": 30.0,
+                "description": "Seconds inspected by `/v1/audio/language`; ignored elsewhere.",
+            },
+            "include[]": {
+                "type": "array",
+                "items": {"type": "string", "enum": ["logprobs"]},
+                "description": "`logprobs` adds token log-probabilities to json and verbose_json responses.",
+            },
+            "timestamp_granularities[]": {
+                "type": "array",
+                "items": {"type": "string", "enum": ["segment", "word"]},
+                "description": "`word` requires verbose_json and is rejected unless the model's capabilities include word timestamps.",
+            },
+            "timecode": {
+                "type": "string",
+                "enum": ["ms", "smpte"],
+                "default": "ms",
+                "description": "`smpte` writes srt and vtt cue times as frame-based timecode; requires `frame_rate`.",
+            },
+            "frame_rate": {
+                "type": "string",
+                "enum": ["23.976", "24", "25", "29.97", "30", "50", "59.94", "60"],
+            },
+            "drop_frame": {
+                "type": "boolean",
+                "default": false,
+                "description": "Drop-frame timecode; only valid at 29.97 and 59.94 fps.",
+            },
+        },
+    })
+}
+
+fn schemas() -> Value {
+    json!({
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "required": ["message", "type"],
+                    "properties": {
+                        "message": {"type": "string"},
+                        "type": {"type": "string"},
+                        "param": {"type": "string"},
+                        "code": {"type": "string"},
+                        "request_id": {"type": "string"},
+                        "supported_models": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Models that support the rejected parameter (`501` only).",
+                        },
+                    },
+                },
+            },
+        },
+        "Transcription": {
+            "type": "object",
+            "required": ["text"],
+            "properties": {
+                "text": {"type": "string"},
+                "language": {"type": "string"},
+                "avg_logprob": {"type": "number"},
+                "confidence": {"type": "number", "minimum": 0.0, "maximum": 1.0},
+                "no_speech": {"type": "boolean"},
+                "no_speech_reason": {"type": "string", "enum": ["silent_audio", "no_speech_detected"]},
+                "warnings": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["code", "message"],
+                        "properties": {
+                            "code": {
+                                "type": "string",
+                                "enum": ["audio_clipped", "fallback_decode", "low_language_confidence", "segments_truncated"],
+                            },
+                            "message": {"type": "string"},
+                        },
+                    },
+                },
+                "metadata": {"type": "string"},
+                "continuation_token": {"type": "string"},
+                "truncated": {"type": "boolean"},
+            },
+        },
+        "VerboseTranscription": {
+            "allOf": [
+                {"$ref": "#/components/schemas/Transcription"},
+                {
+                    "type": "object",
+                    "properties": {
+                        "task": {"type": "string", "enum": ["transcribe", "translate"]},
+                        "segments": {
+                            "type": "array",
+                            "items": {"$ref": "#/components/schemas/Segment"},
+                        },
+                        "words": {
+                            "type": "array",
+                            "description": "Present with `timestamp_granularities[]=word`.",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "word": {"type": "string"},
+                                    "start": {"type": "number"},
+                                    "end": {"type": "number"},
+                                },
+                            },
+                        },
+                        "language_probabilities": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "language": {"type": "string"},
+                                    "probability": {"type": "number"},
+                                },
+                            },
+                        },
+                    },
+                },
+            ],
+        },
+        "Segment": {
+            "type": "object",
+            "required": ["id", "start", "end", "text"],
+            "properties": {
+                "id": {"type": "integer"},
+                "start": {"type": "number"},
+                "end": {"type": "number"},
+                "text": {"type": "string"},
+                "avg_logprob": {"type": "number"},
+            },
+        },
+        "BatchTranscription": {
+            "type": "object",
+            "properties": {
+                "object": {"const": "list"},
+                "data": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "description": "A transcript plus `index` and `filename`, or an `error` object.",
+                    },
+                },
+                "metadata": {"type": "string"},
+            },
+        },
+        "AudioJob": {
+            "type": "object",
+            "required": ["id", "object", "task", "status", "created_at"],
+            "properties": {
+                "id": {"type": "string"},
+                "object": {"const": "audio.job"},
+                "task": {"type": "string", "enum": ["transcribe", "translate"]},
+                "status": {"type": "string", "enum": ["queued", "running", "completed", "failed"]},
+                "created_at": {"type": "integer"},
+                "metadata": {"type": "string"},
+                "language": {"type": "string"},
+                "queue_position": {"type": "integer", "minimum": 1},
+                "estimated_start_secs": {"type": "integer"},
+                "result": {"description": "The synchronous response body once completed."},
+                "error": {"$ref": "#/components/schemas/Error/properties/error"},
+            },
+        },
+        "LanguageDetection": {
+            "type": "object",
+            "required": ["language"],
+            "properties": {
+                "language": {"type": ["string", "null"]},
+                "probability": {"type": "number"},
+                "language_probabilities": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "language": {"type": "string"},
+                            "probability": {"type": "number"},
+                        },
+                    },
+                },
+                "no_speech": {"type": "boolean"},
+                "no_speech_reason": {"type": "string", "enum": ["silent_audio"]},
+                "metadata": {"type": "string"},
+            },
+        },
+        "ModelList": {
+            "type": "object",
+            "properties": {
+                "object": {"const": "list"},
+                "data": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "object"],
+                        "properties": {
+                            "id": {"type": "string"},
+                            "object": {"const": "model"},
+                            "owned_by": {"type": "string"},
+                            "alias_for": {"type": "string"},
+                            "capabilities": {
+                                "type": "object",
+                                "properties": {
+                                    "word_timestamps": {"type": "boolean"},
+                                    "streaming": {"type": "boolean"},
+                                    "diarization": {"type": "boolean"},
+                                    "translation_targets": {"type": "array", "items": {"type": "string"}},
+                                    "max_parallelism": {"type": "integer"},
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn json_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": {"schema": {"$ref": format!("#/components/schemas/{schema}")}},
+        },
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(description, "Error")
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": {"type": "string"},
+    })
+}
@@ -0,0 +1,88 @@
+//! Opus decoding, compiled with the `opus` feature (links libopus).
+//!
+//! symphonia demuxes Opus from Ogg and WebM but cannot decode it, so those
+//! packets are decoded here. libopus decodes straight to 16 kHz, folding in
+//! the resampling the other codecs go through afterwards, and averages stereo
+//! to mono itself; a selected channel is picked from a stereo decode instead.
+
+use std::convert::TryFrom;
+
+use audiopus::coder::Decoder;
+use audiopus::packet::Packet;
+use audiopus::{Channels, MutSignals, SampleRate};
+
+use crate::audio::Downmix;
+use crate::error::AppError;
+
+/// Samples in the longest Opus packet (120 ms) at 16 kHz.
+const MAX_PACKET_SAMPLES: usize = 1_920;
+/// Ratio of Opus' 48 kHz reference rate, in which pre-skip is counted, to 16 kHz.
+const PRE_SKIP_DIVISOR: usize = 3;
+
+/// Decodes one Opus stream to 16 kHz mono samples.
+pub struct OpusDecoder {
+    decoder: Decoder,
+    /// Channel picked from a stereo decode; `None` when libopus downmixes.
+    channel: Option<usize>,
+    /// Encoder priming samples still to drop from the start of the stream.
+    skip: usize,
+    frame: Vec<f32>,
+}
+
+impl OpusDecoder {
+    /// Creates a decoder; `header` is the stream's `OpusHead`, when known.
+    pub fn new(header: Option<&[u8]>, downmix: Downmix) -> Result<Self, AppError> {
+        let header = header.filter(|header| header.len() >= 12 && header.starts_with(b"OpusHead"));
+        let channels = header.map_or(1, |header| usize::from(header[9]));
+        downmix.check(channels)?;
+        let channel = match downmix {
+            Downmix::Channel(channel) if channels == 2 => Some(channel),
+            Downmix::Channel(_) if channels > 2 => {
+                return Err(AppError::unsupported_media_type(
+                    "channel selection is not supported for Opus with more than two channels",
+                ));
+            }
+            _ => None,
+        };
+        let (layout, width) = if channel.is_some() {
+            (Channels::Stereo, 2)
+        } else {
+            (Channels::Mono, 1)
+        };
+        let decoder = Decoder::new(SampleRate::Hz16000, layout)
+            .map_err(|err| AppError::internal(format!("failed to create Opus decoder: {err}")))?;
+        let skip = header.map_or(0, |header| {
+            usize::from(u16::from_le_bytes([header[10], header[11]])) / PRE_SKIP_DIVISOR
+        });
+        Ok(Self {
+            decoder,
+            channel,
+            skip,
+            frame: vec![0.0; MAX_PACKET_SAMPLES * width],
+        })
+    }
+
+    /// Decodes `packet` and appends its samples to `out`; damaged packets are skipped.
+    pub fn decode(&mut self, packet: &[u8], out: &mut Vec<f32>) {
+        let Ok(packet) = Packet::try_from(packet) else {
+            return;
+        };
+        let Ok(signals) = MutSignals::try_from(&mut self.frame[..]) else {
+            return;
+        };
+        let Ok(len) = self.decoder.decode_float(Some(packet), signals, false) else {
+            return;
+        };
+        let skipped = self.skip.min(len);
+        self.skip -= skipped;
+        match self.channel {
+            Some(channel) => out.extend(
+                self.frame[skipped * 2..len * 2]
+                    .iter()
+                    .skip(channel)
+                    .step_by(2),
+            ),
+            None => out.extend_from_slice(&self.frame[skipped..len]),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Size-limited JSON transcripts.
+//!
+//! A client that sets the `max_chars` form field receives `json` or
+//! `verbose_json` transcripts whose `text` is at most that many characters.
+//! With `overflow=split` (the default) the transcript is divided into pages on
+//! segment boundaries; each page but the last carries a `continuation_token`
+//! for `GET /v1/audio/continuations/{token}`, which returns the next page.
+//! With `overflow=truncate` only the first page is returned, marked
+//! `"truncated": true`. Stored pages expire after `ASYNC_JOB_RETENTION_SECS`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::jobs::new_id;
+
+/// What to do with text beyond `max_chars`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Overflow {
+    /// Return the rest as continuation pages.
+    #[default]
+    Split,
+    /// Drop the rest.
+    Truncate,
+}
+
+impl Overflow {
+    /// Parses the `overflow` form field.
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "split" => Ok(Self::Split),
+            "truncate" => Ok(Self::Truncate),
+            _ => Err(AppError::invalid_request(
+                format!("invalid overflow={raw:?}; expected split or truncate"),
+                Some("overflow"),
+                Some("invalid_overflow"),
+            )),
+        }
+    }
+}
+
+/// Continuation pages waiting to be fetched.
+pub struct ContinuationStore {
+    pages: Mutex<HashMap<String, (Instant, Value)>>,
+    retention: Duration,
+}
+
+impl ContinuationStore {
+    /// Creates an empty store that keeps pages for `retention`.
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            pages: Mutex::new(HashMap::new()),
+            retention,
+        }
+    }
+
+    /// Limits `body` to `max_chars` of text, storing later pages when splitting.
+    pub fn limit(&self, body: Value, max_chars: usize, overflow: Overflow) -> Value {
+        let mut pages = split_pages(&body, max_chars);
+        if pages.len() <= 1 {
+            return body;
+        }
+        if overflow == Overflow::Truncate {
+            let mut first = pages.swap_remove(0);
+            first["truncated"] = json!(true);
+            return first;
+        }
+
+        let Ok(mut stored) = self.pages.lock() else {
+            return body;
+        };
+        let retention = self.retention;
+        stored.retain(|_, (created, _)| created.elapsed() < retention);
+        let mut next_token: Option<String> = None;
+        while let Some(mut page) = pages.pop() {
+            if let Some(token) = next_token.take() {
+                page["continuation_token"] = json!(token);
+            }
+            if pages.is_empty() {
+                return page;
+            }
+            let token = new_id("cont_");
+            stored.insert(token.clone(), (Instant::now(), page));
+            next_token = Some(token);
+        }
+        body
+    }
+
+    /// Returns the page stored under `token`, if it has not expired.
+    pub fn get(&self, token: &str) -> Option<Value> {
+        let pages = self.pages.lock().ok()?;
+        let (created, page) = pages.get(token)?;
+        (created.elapsed() < self.retention).then(|| page.clone())
+    }
+}
+
+/// A piece of transcript text, with the segment it came from for `verbose_json`.
+struct Piece {
+    text: String,
+    segment: Option<Value>,
+}
+
+/// Splits `body` into pages whose `text` is at most `max_chars` characters.
+///
+/// Pages break between segments where possible; longer segments are split
+/// between words (keeping their timings) and longer words between characters.
+fn split_pages(body: &Value, max_chars: usize) -> Vec<Value> {
+    let max_chars = max_chars.max(1);
+    let text = body["text"].as_str().unwrap_or_default();
+    if text.chars().count() <= max_chars {
+        return vec![body.clone()];
+    }
+
+    let segments = body["segments"].as_array();
+    let mut pieces = Vec::new();
+    match segments {
+        Some(segments) if !segments.is_empty() => {
+            for segment in segments {
+                let segment_text = segment["text"].as_str().unwrap_or_default().trim();
+                for chunk in split_text(segment_text, max_chars) {
+                    let mut part = segment.clone();
+                    part["text"] = json!(chunk);
+                    pieces.push(Piece {
+                        text: chunk,
+                        segment: Some(part),
+                    });
+                }
+            }
+        }
+        _ => pieces.extend(split_text(text, max_chars).into_iter().map(|chunk| Piece {
+            text: chunk,
+            segment: None,
+        })),
+    }
+
+    let mut base = body.clone();
+    if let Some(fields) = base.as_object_mut() {
+        fields.remove("text");
+        fields.remove("segments");
+    }
+    let new_page = |texts: &[String], page_segments: Vec<Value>| {
+        let mut page = base.clone();
+        page["text"] = json!(texts.join(" "));
+        if segments.is_some() {
+            page["segments"] = json!(page_segments);
+        }
+        page
+    };
+
+    let mut pages = Vec::new();
+    let (mut texts, mut page_segments, mut len) = (Vec::new(), Vec::new(), 0usize);
+    for piece in pieces {
+        let piece_len = piece.text.chars().count();
+        let joined_len = if texts.is_empty() {
+            piece_len
+        } else {
+            len + 1 + piece_len
+        };
+        if joined_len > max_chars && !texts.is_empty() {
+            pages.push(new_page(&texts, std::mem::take(&mut page_segments)));
+            texts.clear();
+            len = piece_len;
+        } else {
+            len = joined_len;
+        }
+        texts.push(piece.text);
+        page_segments.extend(piece.segment);
+    }
+    if !texts.is_empty() {
+        pages.push(new_page(&texts, page_segments));
+    }
+    pages
+}
+
+/// Splits `text` into chunks of at most `max_chars` characters between words.
+fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+    for word in text.split_whitespace() {
+        let word_chars = word.chars().collect::<Vec<_>>();
+        for part in word_chars.chunks(max_chars) {
+            let part_len = part.len();
+            if current_len > 0 && current_len + 1 + part_len > max_chars {
+                chunks.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            if current_len > 0 {
+                current.push(' ');
+                current_len += 1;
+            }
+            current.extend(part);
+            current_len += part_len;
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Value {
+        json!({
+            "task": "transcribe",
+            "language": "en",
+            "text": "one two three four five six",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.0, "text": "one two three"},
+                {"id": 1, "start": 1.0, "end": 2.0, "text": "four five six"},
+            ],
+        })
+    }
+
+    #[test]
+    fn split_pages_follow_continuation_tokens() {
+        let store = ContinuationStore::new(Duration::from_secs(60));
+        let first = store.limit(transcript(), 13, Overflow::Split);
+        assert_eq!(first["text"], "one two three");
+        assert_eq!(first["segments"].as_array().expect("segments").len(), 1);
+        assert_eq!(first["language"], "en");
+
+        let token = first["continuation_token"].as_str().expect("token");
+        let second = store.get(token).expect("second page");
+        assert_eq!(second["text"], "four five six");
+        assert_eq!(second["segments"][0]["id"], 1);
+        assert!(second.get("continuation_token").is_none());
+    }
+
+    #[test]
+    fn long_segments_split_between_words_and_truncate_marks_result() {
+        let store = ContinuationStore::new(Duration::from_secs(60));
+        let first = store.limit(transcript(), 8, Overflow::Truncate);
+        assert_eq!(first["text"], "one two");
+        assert_eq!(first["segments"][0]["start"], 0.0);
+        assert_eq!(first["truncated"], true);
+        assert!(first.get("continuation_token").is_none());
+
+        let short = store.limit(transcript(), 100, Overflow::Split);
+        assert_eq!(short, transcript());
+        assert_eq!(split_text("abcdefgh", 3), ["abc", "def", "gh"]);
+    }
+}
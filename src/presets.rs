@@ -0,0 +1,212 @@
+//! Named bundles of decode options selected with the `preset` form field.
+//!
+//! A preset fills in the options a request leaves unset, so a client can ask
+//! for `preset=voicemail` instead of tuning VAD, segmentation, and sampling
+//! itself. Options set explicitly in the request still win, and options a
+//! preset leaves unset fall back to the server defaults. The built-in presets
+//! can be replaced, and new ones added, with a JSON file named by `PRESETS_FILE`.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::audio::ResampleQuality;
+use crate::config::{parse_decoders, parse_max_tokens_per_segment};
+use crate::denoise;
+
+/// Decode options applied by a preset; unset fields keep the server default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub temperature: Option<f32>,
+    pub max_tokens_per_segment: Option<u32>,
+    /// Beam width, overriding `WHISPER_BEAM_SIZE`.
+    pub beam_size: Option<u32>,
+    /// Greedy candidate count, overriding `WHISPER_BEST_OF`.
+    pub best_of: Option<u32>,
+    pub merge_segments: Option<bool>,
+    /// Also accepted under its former name, `restore_punctuation`.
+    #[serde(alias = "restore_punctuation")]
+    pub heuristic_punctuation: Option<bool>,
+    pub vad_filter: Option<bool>,
+    /// Shortest silence cut by the VAD filter, overriding `VAD_MIN_SILENCE_MS`.
+    pub vad_min_silence_ms: Option<u32>,
+    /// Audio kept around speech by the VAD filter, overriding `VAD_PADDING_MS`.
+    pub vad_padding_ms: Option<u32>,
+    pub resample_quality: Option<ResampleQuality>,
+    /// RNNoise noise suppression; only accepted with the `denoise` feature.
+    pub denoise: Option<bool>,
+}
+
+impl Preset {
+    /// Checks that every option is in the range the matching request field accepts.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                return Err(format!("temperature={temperature} is outside [0.0, 1.0]"));
+            }
+        }
+        if let Some(tokens) = self.max_tokens_per_segment {
+            parse_max_tokens_per_segment(&tokens.to_string())
+                .map_err(|expected| format!("max_tokens_per_segment={tokens}: {expected}"))?;
+        }
+        if let Some(beam_size) = self.beam_size {
+            parse_decoders(&beam_size.to_string())
+                .map_err(|expected| format!("beam_size={beam_size}: {expected}"))?;
+        }
+        if let Some(best_of) = self.best_of {
+            parse_decoders(&best_of.to_string())
+                .map_err(|expected| format!("best_of={best_of}: {expected}"))?;
+        }
+        if let Some(ms) = self.vad_min_silence_ms {
+            if !(100..=60_000).contains(&ms) {
+                return Err(format!("vad_min_silence_ms={ms} is outside [100, 60000]"));
+            }
+        }
+        if let Some(ms) = self.vad_padding_ms {
+            if ms > 10_000 {
+                return Err(format!("vad_padding_ms={ms} is above 10000"));
+            }
+        }
+        if self.denoise == Some(true) && !denoise::AVAILABLE {
+            return Err("denoise requires a server built with the `denoise` feature".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The presets available without `PRESETS_FILE`.
+pub fn builtin_presets() -> BTreeMap<String, Preset> {
+    let presets = [
+        (
+            // Long recordings with several speakers and pauses between turns.
+            "meeting",
+            Preset {
+                merge_segments: Some(true),
+                heuristic_punctuation: Some(true),
+                vad_filter: Some(true),
+                vad_min_silence_ms: Some(2_000),
+                resample_quality: Some(ResampleQuality::Balanced),
+                ..Preset::default()
+            },
+        ),
+        (
+            // Short, often quiet telephone messages with dead air around them.
+            "voicemail",
+            Preset {
+                temperature: Some(0.0),
+                merge_segments: Some(true),
+                vad_filter: Some(true),
+                vad_min_silence_ms: Some(500),
+                vad_padding_ms: Some(300),
+                ..Preset::default()
+            },
+        ),
+        (
+            // A single close-miked speaker whose pauses are part of the text.
+            "dictation",
+            Preset {
+                temperature: Some(0.0),
+                merge_segments: Some(true),
+                heuristic_punctuation: Some(true),
+                vad_filter: Some(false),
+                ..Preset::default()
+            },
+        ),
+        (
+            // Short segments that fit on screen, timed against the original audio.
+            "subtitles",
+            Preset {
+                max_tokens_per_segment: Some(32),
+                merge_segments: Some(false),
+                vad_filter: Some(true),
+                vad_min_silence_ms: Some(1_000),
+                resample_quality: Some(ResampleQuality::High),
+                ..Preset::default()
+            },
+        ),
+    ];
+    presets
+        .into_iter()
+        .map(|(name, preset)| (name.to_string(), preset))
+        .collect()
+}
+
+/// Returns the built-in presets, with those defined in the JSON object at
+/// `file` added or replacing built-ins of the same name.
+pub fn load_presets(file: Option<&str>) -> Result<BTreeMap<String, Preset>, String> {
+    let mut presets = builtin_presets();
+    let Some(path) = file.map(str::trim).filter(|path| !path.is_empty()) else {
+        return Ok(presets);
+    };
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read PRESETS_FILE {path:?}: {err}"))?;
+    let defined: BTreeMap<String, Preset> = serde_json::from_str(&contents)
+        .map_err(|err| format!("invalid PRESETS_FILE {path:?}: {err}"))?;
+    for (name, preset) in defined {
+        if name.trim().is_empty() || name.trim() != name {
+            return Err(format!(
+                "invalid preset name {name:?} in PRESETS_FILE: expected a non-empty name without surrounding whitespace"
+            ));
+        }
+        preset
+            .validate()
+            .map_err(|err| format!("invalid preset {name:?} in PRESETS_FILE: {err}"))?;
+        presets.insert(name, preset);
+    }
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        let presets = builtin_presets();
+        assert_eq!(
+            presets.keys().collect::<Vec<_>>(),
+            ["dictation", "meeting", "subtitles", "voicemail"]
+        );
+        for (name, preset) in &presets {
+            assert_eq!(preset.validate(), Ok(()), "{name}");
+        }
+    }
+
+    #[test]
+    fn presets_file_adds_and_replaces_presets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("presets.json");
+        std::fs::write(
+            &path,
+            r#"{"podcast": {"resample_quality": "high", "vad_filter": false},
+                "meeting": {"vad_filter": false}}"#,
+        )
+        .expect("write");
+        let presets = load_presets(path.to_str()).expect("load");
+        assert_eq!(
+            presets["podcast"].resample_quality,
+            Some(ResampleQuality::High)
+        );
+        assert_eq!(
+            presets["meeting"],
+            Preset {
+                vad_filter: Some(false),
+                ..Preset::default()
+            }
+        );
+        assert!(presets.contains_key("voicemail"));
+
+        for invalid in [
+            r#"{"loud": {"temperature": 2.0}}"#,
+            r#"{"fast": {"beam": 4}}"#,
+            r#"{"wide": {"beam_size": 16}}"#,
+            r#"{"picky": {"best_of": 0}}"#,
+            r#"{"tight": {"vad_min_silence_ms": 10}}"#,
+            r#"{" spaced ": {}}"#,
+        ] {
+            std::fs::write(&path, invalid).expect("write");
+            assert!(load_presets(path.to_str()).is_err(), "{invalid}");
+        }
+    }
+}
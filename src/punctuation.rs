@@ -0,0 +1,203 @@
+//! Heuristic punctuation and casing for English transcripts.
+//!
+//! The small `.en` models often return lowercase, unpunctuated runs of words.
+//! With `heuristic_punctuation` enabled, a few rules guess where sentences
+//! end; there is no language model behind them. A sentence ends wherever the
+//! speaker pauses for `SENTENCE_PAUSE_SECS`, unless the word before the pause
+//! rarely ends one (an article or conjunction, say), and at the end of the
+//! transcript. It gets `?` when it opens with an auxiliary verb, or with a
+//! question word not followed by a subject ("when did" but not "when we").
+//! The first word of every sentence and the pronoun "I" are capitalized. Text
+//! that already ends in punctuation is left alone, so well-punctuated output
+//! from larger models passes through intact.
+
+use crate::backend::TranscriptSegment;
+use crate::formats::ends_sentence;
+
+/// Pause after a segment, in seconds, treated as the end of a sentence.
+const SENTENCE_PAUSE_SECS: f64 = 0.6;
+
+/// Verbs that open a yes/no question when they start a sentence.
+const AUXILIARIES: &[&str] = &[
+    "are", "can", "could", "did", "do", "does", "is", "shall", "should", "was", "were", "will",
+    "would",
+];
+
+/// Words that open a question when they start a sentence, unless a subject
+/// follows them, as in "when we got there".
+const QUESTION_WORDS: &[&str] = &[
+    "how", "what", "when", "where", "which", "who", "whom", "whose", "why",
+];
+
+/// Subjects that make a sentence opened by a question word a statement.
+const SUBJECTS: &[&str] = &["he", "i", "it", "she", "there", "they", "we", "you"];
+
+/// Words a sentence rarely ends with, so a pause after them is mid-sentence.
+const CONTINUATION_WORDS: &[&str] = &[
+    "a", "an", "and", "because", "but", "if", "my", "nor", "of", "or", "our", "than", "the",
+    "their", "your",
+];
+
+/// Guesses sentence punctuation and casing in `segments`, in place.
+pub fn guess_punctuation(segments: &mut [TranscriptSegment]) {
+    let mut sentence_start = true;
+    let mut question = false;
+    // Set after a question word opens a sentence, until the next word is seen.
+    let mut subject_ends_question = false;
+    for idx in 0..segments.len() {
+        let next_start = segments.get(idx + 1).map(|next| next.start_secs);
+        let seg = &mut segments[idx];
+        let mut words = seg
+            .text
+            .split_whitespace()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        for word in &mut words {
+            let bare = bare_word(word);
+            if std::mem::take(&mut subject_ends_question) && SUBJECTS.contains(&bare.as_str()) {
+                question = false;
+            }
+            if is_pronoun_i(word) || sentence_start {
+                capitalize(word);
+            }
+            if sentence_start {
+                subject_ends_question = QUESTION_WORDS.contains(&bare.as_str());
+                question = subject_ends_question || AUXILIARIES.contains(&bare.as_str());
+            }
+            sentence_start = ends_sentence(word);
+        }
+
+        let last = words.last_mut().expect("segment has words");
+        let ends = match next_start {
+            None => true,
+            Some(start) => {
+                start - seg.end_secs >= SENTENCE_PAUSE_SECS
+                    && !CONTINUATION_WORDS.contains(&bare_word(last).as_str())
+            }
+        };
+        if ends && !sentence_start && !last.ends_with([',', ';', ':', '-', '—']) {
+            last.push(if question { '?' } else { '.' });
+            sentence_start = true;
+            subject_ends_question = false;
+        }
+        let leading = if seg.text.starts_with(char::is_whitespace) {
+            " "
+        } else {
+            ""
+        };
+        seg.text = format!("{leading}{}", words.join(" "));
+    }
+}
+
+/// `word` in lowercase, without surrounding punctuation.
+fn bare_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Matches "i" and its contractions ("i'm", "i'll", ...).
+fn is_pronoun_i(word: &str) -> bool {
+    let bare = word.trim_end_matches(|c: char| !c.is_alphanumeric());
+    bare == "i" || bare.starts_with("i'") || bare.starts_with("i’")
+}
+
+/// Uppercases the first letter of `word`, skipping leading quotes or brackets.
+fn capitalize(word: &mut String) {
+    if let Some((idx, c)) = word.char_indices().find(|(_, c)| c.is_alphanumeric()) {
+        if c.is_lowercase() {
+            let upper = c.to_uppercase().collect::<String>();
+            word.replace_range(idx..idx + c.len_utf8(), &upper);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_sentence_ends_and_casing() {
+        let mut segments = vec![
+            TranscriptSegment::new(0.0, 1.0, " so i think we"),
+            TranscriptSegment::new(1.1, 2.0, " should go"),
+            TranscriptSegment::new(3.0, 4.0, " what do you think"),
+            TranscriptSegment::new(5.0, 6.0, " i'm not sure, really"),
+        ];
+        guess_punctuation(&mut segments);
+        let texts = segments
+            .iter()
+            .map(|seg| seg.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                " So I think we",
+                " should go.",
+                " What do you think?",
+                " I'm not sure, really."
+            ]
+        );
+    }
+
+    #[test]
+    fn pauses_after_words_that_cannot_end_a_sentence_are_mid_sentence() {
+        let mut segments = vec![
+            TranscriptSegment::new(0.0, 1.0, " we went to the"),
+            TranscriptSegment::new(2.0, 3.0, " market and"),
+            TranscriptSegment::new(4.0, 5.0, " it was closed"),
+            TranscriptSegment::new(6.0, 7.0, " so we left"),
+        ];
+        guess_punctuation(&mut segments);
+        let texts = segments
+            .iter()
+            .map(|seg| seg.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                " We went to the",
+                " market and",
+                " it was closed.",
+                " So we left."
+            ]
+        );
+    }
+
+    #[test]
+    fn question_words_followed_by_a_subject_open_statements() {
+        let mut segments = vec![
+            TranscriptSegment::new(0.0, 2.0, " when we got there it was closed"),
+            TranscriptSegment::new(3.0, 4.0, " when did you get there"),
+            TranscriptSegment::new(5.0, 6.0, " is it raining"),
+            TranscriptSegment::new(7.0, 7.5, " why"),
+        ];
+        guess_punctuation(&mut segments);
+        let texts = segments
+            .iter()
+            .map(|seg| seg.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                " When we got there it was closed.",
+                " When did you get there?",
+                " Is it raining?",
+                " Why?"
+            ]
+        );
+    }
+
+    #[test]
+    fn punctuated_text_is_left_alone() {
+        let mut segments = vec![
+            TranscriptSegment::new(0.0, 1.0, " Hello there. How are you?"),
+            TranscriptSegment::new(2.0, 3.0, " Fine,"),
+        ];
+        guess_punctuation(&mut segments);
+        assert_eq!(segments[0].text, " Hello there. How are you?");
+        assert_eq!(segments[1].text, " Fine,");
+    }
+}
@@ -0,0 +1,131 @@
+//! Deleting stored request data on demand, such as for GDPR erasure requests.
+//!
+//! `POST /admin/purge` removes what the server keeps about requests made with
+//! one API key, within a time range, or both: async job results, uploads of
+//! queued jobs in `ASYNC_JOB_DIR`, transcripts in `TRANSCRIPT_ARCHIVE_DIR`, and
+//! debug traces in `DEBUG_TRACE_DIR`.
+//! Jobs that are still queued or running are soft-deleted: they vanish from
+//! `GET /v1/audio/jobs/{id}` at once and their result is discarded, not
+//! stored or archived, when they finish. Every purge, dry runs included, is
+//! logged and appended to `PURGE_AUDIT_LOG` as one JSON line.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Seconds in a UTC day.
+const DAY_SECS: u64 = 86_400;
+
+/// Which stored artifacts a purge removes; unset fields match everything.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct PurgeFilter {
+    /// Label of the API key the requests were made with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Earliest creation time to purge, in Unix seconds (inclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<u64>,
+    /// Creation time to purge up to, in Unix seconds (exclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<u64>,
+}
+
+impl PurgeFilter {
+    /// Whether the filter would match every artifact.
+    pub fn is_empty(&self) -> bool {
+        self.api_key.is_none() && self.from.is_none() && self.to.is_none()
+    }
+
+    /// Whether an artifact created at `created_at` with `api_key` is purged.
+    pub fn matches(&self, api_key: Option<&str>, created_at: u64) -> bool {
+        self.api_key
+            .as_deref()
+            .map_or(true, |label| api_key == Some(label))
+            && self.from.map_or(true, |from| created_at >= from)
+            && self.to.map_or(true, |to| created_at < to)
+    }
+
+    /// Whether anything created on the UTC day starting at `day_start` can match.
+    pub fn covers_day(&self, day_start: u64) -> bool {
+        self.from.map_or(true, |from| from < day_start + DAY_SECS)
+            && self.to.map_or(true, |to| to > day_start)
+    }
+}
+
+/// Opens the JSON-lines audit log at `path` for appending, creating it if needed.
+///
+/// Opened before anything is deleted, so an unwritable log stops the purge.
+pub fn open_audit_log(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// Appends one audit entry as a single line.
+pub fn write_audit_entry(log: &mut File, entry: &Value) -> io::Result<()> {
+    let mut line =
+        serde_json::to_vec(entry).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    line.push(b'\n');
+    log.write_all(&line)?;
+    log.sync_data()
+}
+
+/// Parses a `YYYY-MM-DD` directory name into the Unix seconds its UTC day starts at.
+pub(crate) fn parse_day(name: &str) -> Option<u64> {
+    let mut parts = name.splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days-from-civil conversion from Howard Hinnant's date algorithms.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days).ok().map(|days| days * DAY_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::utc_date;
+
+    #[test]
+    fn filters_match_key_and_half_open_range() {
+        let filter = PurgeFilter {
+            api_key: Some("team-a".to_string()),
+            from: Some(100),
+            to: Some(200),
+        };
+        assert!(filter.matches(Some("team-a"), 100));
+        assert!(!filter.matches(Some("team-a"), 200));
+        assert!(!filter.matches(Some("team-b"), 150));
+        assert!(!filter.matches(None, 150));
+        assert!(PurgeFilter::default().matches(None, 0));
+        assert!(PurgeFilter::default().is_empty());
+
+        assert!(filter.covers_day(0));
+        assert!(!filter.covers_day(DAY_SECS));
+    }
+
+    #[test]
+    fn day_directories_parse_back_to_their_start() {
+        for secs in [0, 951_782_400, 1_735_603_200] {
+            let (year, month, day) = utc_date(secs);
+            assert_eq!(
+                parse_day(&format!("{year:04}-{month:02}-{day:02}")),
+                Some(secs)
+            );
+        }
+        assert_eq!(parse_day("notes"), None);
+        assert_eq!(parse_day("2024-13-01"), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Per-client rate limiting for the `/v1` API.
+//!
+//! Clients are identified by their API key when the request carries a valid
+//! one, otherwise by their IP address. Each client gets a token bucket that
+//! refills at `RATE_LIMIT_REQUESTS_PER_MINUTE` and a cap of
+//! `RATE_LIMIT_CONCURRENT_REQUESTS` requests in flight; individual API keys
+//! may override either limit. Requests over a limit receive an OpenAI-style
+//! `429` with a `Retry-After` header.
+//!
+//! A request's concurrency slot lasts until its response body is sent or, for
+//! handlers that keep working after responding, until they drop the
+//! [`HeldPermit`] taken from the request extensions.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+
+use crate::api::{AppState, AuthenticatedKey};
+use crate::error::AppError;
+
+/// Client entries kept before idle ones are pruned.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Limits applied to one client; `None` leaves that dimension unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientLimits {
+    pub per_minute: Option<u32>,
+    pub max_concurrent: Option<usize>,
+}
+
+struct ClientState {
+    tokens: f64,
+    capacity: f64,
+    refilled: Instant,
+    in_flight: usize,
+}
+
+impl ClientState {
+    /// Tops up the bucket for the time elapsed and returns the available tokens.
+    fn refill(&mut self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.refilled = now;
+        self.tokens
+    }
+}
+
+/// Shared per-client request counters.
+#[derive(Default)]
+pub struct RateLimiter {
+    clients: Arc<Mutex<HashMap<String, ClientState>>>,
+}
+
+/// One admitted request; releases its concurrency slot when dropped.
+pub struct RateLimitPermit {
+    key: String,
+    clients: Arc<Mutex<HashMap<String, ClientState>>>,
+}
+
+/// Shares a request's permit with its handler through the request extensions,
+/// so a WebSocket session keeps its slot after the upgrade response is sent.
+#[derive(Clone)]
+pub struct HeldPermit {
+    _permit: Arc<RateLimitPermit>,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            if let Some(client) = clients.get_mut(&self.key) {
+                client.in_flight = client.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Admits one request for `key` under `limits` or returns a `429` error.
+    pub fn admit(&self, key: &str, limits: ClientLimits) -> Result<RateLimitPermit, AppError> {
+        let mut clients = self
+            .clients
+            .lock()
+            .map_err(|_| AppError::internal("rate limiter lock poisoned"))?;
+        let now = Instant::now();
+        if clients.len() >= PRUNE_THRESHOLD && !clients.contains_key(key) {
+            clients
+                .retain(|_, client| client.in_flight > 0 || client.refill(now) < client.capacity);
+        }
+        let capacity = limits.per_minute.map_or(0.0, f64::from);
+        let client = clients.entry(key.to_string()).or_insert(ClientState {
+            tokens: capacity,
+            capacity,
+            refilled: now,
+            in_flight: 0,
+        });
+        client.capacity = capacity;
+
+        if let Some(max_concurrent) = limits.max_concurrent {
+            if client.in_flight >= max_concurrent {
+                return Err(AppError::rate_limited(
+                    format!("rate limit reached: {max_concurrent} concurrent requests in flight"),
+                    1,
+                ));
+            }
+        }
+        if let Some(per_minute) = limits.per_minute {
+            let tokens = client.refill(now);
+            if tokens < 1.0 {
+                let refill_per_sec = f64::from(per_minute) / 60.0;
+                let retry_after = ((1.0 - tokens) / refill_per_sec).ceil() as u64;
+                return Err(AppError::rate_limited(
+                    format!("rate limit reached: {per_minute} requests per minute"),
+                    retry_after.max(1),
+                ));
+            }
+            client.tokens -= 1.0;
+        }
+        client.in_flight += 1;
+        Ok(RateLimitPermit {
+            key: key.to_string(),
+            clients: Arc::clone(&self.clients),
+        })
+    }
+}
+
+/// Middleware enforcing the configured rate limits on each request.
+///
+/// The concurrency slot is held until the response body finishes, so
+/// server-sent event streams count as in flight for their whole duration, and
+/// for as long as the handler keeps the [`HeldPermit`].
+pub async fn enforce_rate_limit(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let (key, limits) = client_key(&state, &req);
+    let Some(limiter) = state
+        .rate_limiter
+        .as_ref()
+        .filter(|_| limits != ClientLimits::default())
+    else {
+        return next.run(req).await;
+    };
+    let permit = match limiter.admit(&key, limits) {
+        Ok(permit) => Arc::new(permit),
+        Err(err) => {
+            state.metrics.incr("rate_limited", &[]);
+            return err.into_response();
+        }
+    };
+    req.extensions_mut().insert(HeldPermit {
+        _permit: Arc::clone(&permit),
+    });
+    next.run(req).await.map(|body| {
+        Body::new(PermitBody {
+            inner: body,
+            _permit: permit,
+        })
+    })
+}
+
+/// Identifies the client by its API key label, falling back to its IP address,
+/// and returns the limits that apply to it.
+fn client_key(state: &AppState, req: &Request) -> (String, ClientLimits) {
+    let defaults = ClientLimits {
+        per_minute: state.cfg.rate_limit_requests_per_minute,
+        max_concurrent: state.cfg.rate_limit_concurrent_requests,
+    };
+    if let Some(AuthenticatedKey(label)) = req.extensions().get::<AuthenticatedKey>() {
+        if let Some(api_key) = state.cfg.api_keys.iter().find(|key| &key.label == label) {
+            let limits = ClientLimits {
+                per_minute: api_key.requests_per_minute.or(defaults.per_minute),
+                max_concurrent: api_key.concurrent_requests.or(defaults.max_concurrent),
+            };
+            return (format!("key:{label}"), limits);
+        }
+    }
+    let key = match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    };
+    (key, defaults)
+}
+
+/// Response body that holds a rate-limit permit until it is fully sent.
+struct PermitBody {
+    inner: Body,
+    _permit: Arc<RateLimitPermit>,
+}
+
+impl HttpBody for PermitBody {
+    type Data = axum::body::Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_over_per_minute_limit_are_rejected() {
+        let limiter = RateLimiter::default();
+        let limits = ClientLimits {
+            per_minute: Some(2),
+            max_concurrent: None,
+        };
+        let _a = limiter.admit("ip:1", limits).expect("first");
+        let _b = limiter.admit("ip:1", limits).expect("second");
+        let err = limiter.admit("ip:1", limits).err().expect("third rejected");
+        assert!(matches!(
+            err,
+            AppError::RateLimited {
+                retry_after_secs: 30,
+                ..
+            }
+        ));
+        limiter
+            .admit("ip:2", limits)
+            .expect("other client unaffected");
+    }
+
+    #[test]
+    fn concurrency_slots_are_released_on_drop() {
+        let limiter = RateLimiter::default();
+        let limits = ClientLimits {
+            per_minute: None,
+            max_concurrent: Some(1),
+        };
+        let held = limiter.admit("key:a", limits).expect("first");
+        assert!(limiter.admit("key:a", limits).is_err());
+        drop(held);
+        limiter.admit("key:a", limits).expect("slot released");
+    }
+}
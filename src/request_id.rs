@@ -0,0 +1,73 @@
+//! Per-request correlation ids.
+//!
+//! Every request gets an id: the client's `x-request-id` header when it is a
+//! short printable token, otherwise a fresh `req_...` id. The id is attached
+//! to the request's tracing span, echoed in the `x-request-id` response
+//! header, and added to error payloads as `error.request_id`, so a client
+//! report can be matched to server logs.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{field, info_span, Instrument};
+
+use crate::jobs::new_id;
+
+/// Header carrying the request id in both directions.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied id that is propagated rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Returns the id of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware assigning each request an id and a `request` tracing span.
+///
+/// The span's `api_key` field is filled in later by `identify_api_key`.
+pub async fn assign_request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map_or_else(|| new_id("req_"), ToOwned::to_owned);
+    let header_value = HeaderValue::from_str(&id).ok();
+    if let Some(value) = &header_value {
+        req.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value.clone());
+    }
+
+    let span = info_span!("request", request_id = %id, api_key = field::Empty);
+    let mut res = REQUEST_ID.scope(id, next.run(req).instrument(span)).await;
+    if let Some(value) = header_value {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    res
+}
+
+/// Accepts short ids of visible ASCII characters, so they are safe to log and echo.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_ids_must_be_short_and_printable() {
+        assert!(is_valid_request_id("trace-123:abc"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id(&"x".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,345 @@
+//! RTP audio for streaming sessions (`RTP_PORT`).
+//!
+//! A `/v1/audio/stream` session opened with `encoding=rtp` is given a token
+//! in its `session.created` message. RTP has no header field to carry one, so
+//! the sender first sends a datagram holding just the token from the address
+//! it will send media from, the way SIP endpoints latch onto symmetric RTP.
+//! Packets from that address then feed the session, and its transcripts go
+//! back over the WebSocket. Sending the token again from another address moves
+//! the session there, such as after a NAT rebinding.
+//!
+//! Payload types 0 (PCMU) and 8 (PCMA) are G.711 at 8 kHz, and the session's
+//! Opus payload type (111 unless the client names another) is Opus, which
+//! needs the `opus` feature. Other payload types, such as DTMF events and
+//! comfort noise, are skipped. Packets arriving after a newer one are dropped,
+//! and lost packets are not concealed.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::audio::resample_linear;
+#[cfg(feature = "opus")]
+use crate::audio::Downmix;
+#[cfg(not(feature = "opus"))]
+use crate::audio::OPUS_UNAVAILABLE;
+use crate::error::AppError;
+use crate::g711::Law;
+use crate::jobs::new_id;
+#[cfg(feature = "opus")]
+use crate::opus::OpusDecoder;
+
+/// Largest datagram read; RTP packets stay below the path MTU.
+const MAX_DATAGRAM_LEN: usize = 2_048;
+/// Packets queued per session while it waits for inference, about 20 seconds
+/// of 20 ms packets; newer packets are dropped beyond it.
+const SESSION_QUEUE_PACKETS: usize = 1_024;
+/// Sample rate of G.711 payloads.
+const G711_SAMPLE_RATE: u32 = 8_000;
+/// Sample rate sessions decode to.
+const SESSION_SAMPLE_RATE: u32 = 16_000;
+/// Static payload type of G.711 mu-law.
+const PAYLOAD_PCMU: u8 = 0;
+/// Static payload type of G.711 A-law.
+const PAYLOAD_PCMA: u8 = 8;
+/// Opus payload type used when the client names none, as in most SDP offers.
+pub const DEFAULT_OPUS_PAYLOAD_TYPE: u8 = 111;
+/// Payload types assigned by signaling rather than by the RTP profile.
+pub const DYNAMIC_PAYLOAD_TYPES: RangeInclusive<u8> = 96..=127;
+
+/// The parts of an RTP packet a session reads.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RtpPacket<'a> {
+    pub payload_type: u8,
+    pub sequence: u16,
+    pub payload: &'a [u8],
+}
+
+impl<'a> RtpPacket<'a> {
+    /// Parses an RTP version 2 packet, skipping CSRCs, the header extension,
+    /// and padding; `None` for anything else.
+    pub fn parse(datagram: &'a [u8]) -> Option<Self> {
+        if datagram.len() < 12 || datagram[0] >> 6 != 2 {
+            return None;
+        }
+        let mut end = datagram.len();
+        if datagram[0] & 0x20 != 0 {
+            end = end.checked_sub(usize::from(datagram[end - 1]))?;
+        }
+        let mut start = 12 + 4 * usize::from(datagram[0] & 0x0f);
+        if datagram[0] & 0x10 != 0 {
+            let words = datagram.get(start + 2..start + 4)?;
+            start += 4 + 4 * usize::from(u16::from_be_bytes([words[0], words[1]]));
+        }
+        Some(Self {
+            payload_type: datagram[1] & 0x7f,
+            sequence: u16::from_be_bytes([datagram[2], datagram[3]]),
+            payload: datagram.get(start..end)?,
+        })
+    }
+}
+
+/// Decodes the RTP packets of one session to 16 kHz audio.
+pub struct RtpStream {
+    opus_payload_type: u8,
+    /// Sequence number of the newest packet decoded.
+    last_sequence: Option<u16>,
+    #[cfg(feature = "opus")]
+    opus: Option<OpusDecoder>,
+    /// Whether the missing Opus decoder was already reported.
+    #[cfg(not(feature = "opus"))]
+    opus_reported: bool,
+}
+
+impl RtpStream {
+    /// Decodes a stream carrying Opus as `opus_payload_type`.
+    pub fn new(opus_payload_type: u8) -> Self {
+        Self {
+            opus_payload_type,
+            last_sequence: None,
+            #[cfg(feature = "opus")]
+            opus: None,
+            #[cfg(not(feature = "opus"))]
+            opus_reported: false,
+        }
+    }
+
+    /// Decodes `datagram` and appends its audio to `window`.
+    pub fn push(&mut self, datagram: &[u8], window: &mut Vec<f32>) -> Result<(), AppError> {
+        let Some(packet) = RtpPacket::parse(datagram) else {
+            return Ok(());
+        };
+        if let Some(last) = self.last_sequence {
+            // Sequence numbers wrap, so "older" means up to half the range behind.
+            if packet.sequence.wrapping_sub(last) as i16 <= 0 {
+                return Ok(());
+            }
+        }
+        self.last_sequence = Some(packet.sequence);
+        let law = match packet.payload_type {
+            PAYLOAD_PCMU => Law::MuLaw,
+            PAYLOAD_PCMA => Law::ALaw,
+            payload_type if payload_type == self.opus_payload_type => {
+                return self.decode_opus(packet.payload, window);
+            }
+            _ => return Ok(()),
+        };
+        let samples = packet
+            .payload
+            .iter()
+            .map(|&byte| law.expand(byte))
+            .collect::<Vec<_>>();
+        window.extend(resample_linear(
+            &samples,
+            G711_SAMPLE_RATE,
+            SESSION_SAMPLE_RATE,
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "opus")]
+    fn decode_opus(&mut self, payload: &[u8], window: &mut Vec<f32>) -> Result<(), AppError> {
+        let decoder = match &mut self.opus {
+            Some(decoder) => decoder,
+            None => self.opus.insert(OpusDecoder::new(None, Downmix::Average)?),
+        };
+        decoder.decode(payload, window);
+        Ok(())
+    }
+
+    /// Reports the missing decoder once, then skips Opus packets.
+    #[cfg(not(feature = "opus"))]
+    fn decode_opus(&mut self, _payload: &[u8], _window: &mut Vec<f32>) -> Result<(), AppError> {
+        if std::mem::replace(&mut self.opus_reported, true) {
+            return Ok(());
+        }
+        Err(AppError::unsupported_media_type(OPUS_UNAVAILABLE))
+    }
+}
+
+/// Hands datagrams received on `RTP_PORT` to the sessions their senders are bound to.
+#[derive(Default)]
+pub struct RtpRouter {
+    routes: Mutex<Routes>,
+}
+
+#[derive(Default)]
+struct Routes {
+    /// Packet queue of each open session, by token.
+    sessions: HashMap<String, mpsc::Sender<Vec<u8>>>,
+    /// Token each sending address is bound to.
+    senders: HashMap<SocketAddr, String>,
+}
+
+impl RtpRouter {
+    /// Opens a session; packets from the address its token is sent from are
+    /// queued on the returned subscription.
+    pub fn register(self: &Arc<Self>) -> RtpSubscription {
+        let token = new_id("rtp_");
+        let (tx, packets) = mpsc::channel(SESSION_QUEUE_PACKETS);
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.sessions.insert(token.clone(), tx);
+        }
+        RtpSubscription {
+            token,
+            packets,
+            router: Arc::clone(self),
+        }
+    }
+
+    /// Receives datagrams on `socket` for as long as the server runs.
+    pub async fn serve(self: Arc<Self>, socket: UdpSocket) {
+        let mut buf = vec![0; MAX_DATAGRAM_LEN];
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, from)) => self.route(from, &buf[..len]),
+                Err(err) => debug!(error = %err, "failed to receive RTP datagram"),
+            }
+        }
+    }
+
+    /// Binds `from` to a session when `datagram` is its token, and otherwise
+    /// queues the datagram for the session `from` is bound to.
+    fn route(&self, from: SocketAddr, datagram: &[u8]) {
+        let Ok(mut routes) = self.routes.lock() else {
+            return;
+        };
+        let token = std::str::from_utf8(datagram)
+            .map(str::trim)
+            .ok()
+            .filter(|token| routes.sessions.contains_key(*token));
+        if let Some(token) = token {
+            routes.senders.retain(|_, bound| bound != token);
+            routes.senders.insert(from, token.to_string());
+            debug!(%from, "RTP sender bound to a streaming session");
+            return;
+        }
+        let Some(tx) = routes
+            .senders
+            .get(&from)
+            .and_then(|token| routes.sessions.get(token))
+        else {
+            return;
+        };
+        if tx.try_send(datagram.to_vec()).is_err() {
+            debug!(%from, "RTP session is behind; packet dropped");
+        }
+    }
+}
+
+/// A session's registration with the [`RtpRouter`], removed on drop.
+pub struct RtpSubscription {
+    /// Sent by the RTP sender to bind its address to the session.
+    pub token: String,
+    /// Datagrams from the bound sender.
+    pub packets: mpsc::Receiver<Vec<u8>>,
+    router: Arc<RtpRouter>,
+}
+
+impl Drop for RtpSubscription {
+    fn drop(&mut self) {
+        if let Ok(mut routes) = self.router.routes.lock() {
+            routes.sessions.remove(&self.token);
+            routes.senders.retain(|_, bound| *bound != self.token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(payload_type: u8, sequence: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, payload_type];
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&[0; 8]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn packets_parse_past_csrcs_extensions_and_padding() {
+        let plain = packet(PAYLOAD_PCMU, 7, &[1, 2, 3]);
+        assert_eq!(
+            RtpPacket::parse(&plain),
+            Some(RtpPacket {
+                payload_type: PAYLOAD_PCMU,
+                sequence: 7,
+                payload: &[1, 2, 3],
+            })
+        );
+
+        // One CSRC, a one-word extension, and two bytes of padding.
+        let mut full = vec![0xb1, 0x80 | PAYLOAD_PCMA, 0, 9];
+        full.extend_from_slice(&[0; 8]);
+        full.extend_from_slice(&[0; 4]);
+        full.extend_from_slice(&[0xbe, 0xde, 0, 1, 0, 0, 0, 0]);
+        full.extend_from_slice(&[4, 5, 0, 2]);
+        let parsed = RtpPacket::parse(&full).expect("packet");
+        assert_eq!(parsed.payload_type, PAYLOAD_PCMA);
+        assert_eq!(parsed.payload, &[4, 5]);
+
+        assert_eq!(RtpPacket::parse(b"rtp_0123456789abcdef"), None);
+        assert_eq!(RtpPacket::parse(&plain[..11]), None);
+    }
+
+    #[test]
+    fn streams_drop_late_packets_and_skip_other_payload_types() {
+        let mut stream = RtpStream::new(DEFAULT_OPUS_PAYLOAD_TYPE);
+        let mut window = Vec::new();
+        stream
+            .push(&packet(PAYLOAD_PCMU, u16::MAX, &[0xff; 80]), &mut window)
+            .unwrap();
+        assert_eq!(window.len(), 160);
+        stream
+            .push(
+                &packet(PAYLOAD_PCMU, u16::MAX - 1, &[0xff; 80]),
+                &mut window,
+            )
+            .unwrap();
+        stream
+            .push(&packet(101, 0, &[1, 2, 3, 4]), &mut window)
+            .unwrap();
+        assert_eq!(window.len(), 160);
+        // The sequence number wrapped past the DTMF event.
+        stream
+            .push(&packet(PAYLOAD_PCMA, 1, &[0xd5; 80]), &mut window)
+            .unwrap();
+        assert_eq!(window.len(), 320);
+    }
+
+    #[test]
+    fn senders_bind_to_a_session_by_sending_its_token() {
+        let router = Arc::new(RtpRouter::default());
+        let mut session = router.register();
+        let sender: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let moved: SocketAddr = "192.0.2.1:4002".parse().unwrap();
+
+        router.route(sender, &packet(PAYLOAD_PCMU, 1, &[0]));
+        assert!(session.packets.try_recv().is_err());
+        router.route(sender, session.token.as_bytes());
+        router.route(sender, &packet(PAYLOAD_PCMU, 2, &[0]));
+        assert_eq!(
+            session.packets.try_recv().ok(),
+            Some(packet(PAYLOAD_PCMU, 2, &[0]))
+        );
+
+        router.route(moved, format!("{}\n", session.token).as_bytes());
+        router.route(sender, &packet(PAYLOAD_PCMU, 3, &[0]));
+        router.route(moved, &packet(PAYLOAD_PCMU, 4, &[0]));
+        assert_eq!(
+            session.packets.try_recv().ok(),
+            Some(packet(PAYLOAD_PCMU, 4, &[0]))
+        );
+        assert!(session.packets.try_recv().is_err());
+
+        drop(session);
+        let routes = router.routes.lock().unwrap();
+        assert!(routes.sessions.is_empty() && routes.senders.is_empty());
+    }
+}
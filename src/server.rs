@@ -0,0 +1,394 @@
+//! HTTP accept loop with connection admission control and timeouts.
+//!
+//! This replaces `axum::serve` so the server can cap concurrent connections
+//! (`MAX_CONNECTIONS`) and bound how long slow clients may hold them. Connections
+//! over the cap receive a minimal `503` and are closed immediately instead of
+//! holding file descriptors and memory; while many such responses are still
+//! being written, further rejected connections are closed without one. Header, body-read, body-write, and idle
+//! timeouts come from the `HTTP_*_TIMEOUT_SECS` settings.
+//!
+//! Each connection speaks HTTP/1.1 or HTTP/2, detected from its first bytes
+//! (h2c with prior knowledge) or negotiated through ALPN when TLS is
+//! configured. `HTTP2_MAX_CONCURRENT_STREAMS` and the keep-alive settings let
+//! batch clients multiplex many small requests over a few connections.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::extract::ConnectInfo;
+use axum::Router;
+use futures_util::FutureExt;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Semaphore};
+use tokio::time::Sleep;
+use tower_http::add_extension::AddExtension;
+use tower_http::timeout::RequestBodyTimeoutLayer;
+use tracing::{debug, error, warn};
+
+use crate::api::AppState;
+use crate::error::AppError;
+use crate::tls::{self, TlsAcceptor};
+
+/// How long a rejected connection may take to receive its `503` before it is dropped.
+const REJECT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most rejected connections still being sent their `503`; beyond this, rejected
+/// connections are closed without a response so a flood cannot pile up sockets.
+const MAX_PENDING_REJECTIONS: usize = 64;
+
+type ConnectionService = TowerToHyperService<AddExtension<Router, ConnectInfo<SocketAddr>>>;
+
+/// Serves `app` on `listener` until `shutdown` resolves, then drains open connections.
+///
+/// With `tls` set, connections are served over TLS after the handshake.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    state: Arc<AppState>,
+    tls: Option<TlsAcceptor>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let cfg = &state.cfg;
+    let connection_slots = Arc::new(Semaphore::new(cfg.max_connections));
+    let rejection_slots = Arc::new(Semaphore::new(MAX_PENDING_REJECTIONS));
+    let app = app.layer(RequestBodyTimeoutLayer::new(cfg.http_read_timeout));
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(cfg.http_header_timeout)
+        .keep_alive(cfg.http_keepalive)
+        .http2()
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(cfg.http2_max_concurrent_streams)
+        .keep_alive_interval(cfg.http2_keepalive_interval)
+        .keep_alive_timeout(cfg.http2_keepalive_timeout);
+    let builder = if cfg.http2 {
+        builder
+    } else {
+        builder.http1_only()
+    };
+    let handshake_timeout = cfg.http_header_timeout;
+    let idle_timeout = cfg.http_idle_timeout;
+    let write_timeout = cfg.http_write_timeout;
+
+    let (signal_tx, signal_rx) = watch::channel(());
+    let signal_tx = Arc::new(signal_tx);
+    tokio::spawn(async move {
+        shutdown.await;
+        drop(signal_rx);
+    });
+    let (close_tx, close_rx) = watch::channel(());
+
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    accept_error(err).await;
+                    continue;
+                }
+            },
+            _ = signal_tx.closed() => break,
+        };
+
+        let Ok(permit) = Arc::clone(&connection_slots).try_acquire_owned() else {
+            state.metrics.incr("connections_rejected", &[]);
+            // A plaintext 503 would be unreadable to a client expecting TLS.
+            if tls.is_none() {
+                if let Ok(rejecting) = Arc::clone(&rejection_slots).try_acquire_owned() {
+                    let max_connections = state.cfg.max_connections;
+                    tokio::spawn(async move {
+                        let _rejecting = rejecting;
+                        reject_connection(stream, max_connections).await;
+                    });
+                }
+            }
+            continue;
+        };
+
+        let service =
+            TowerToHyperService::new(AddExtension::new(app.clone(), ConnectInfo(remote_addr)));
+        let builder = builder.clone();
+        let signal_tx = Arc::clone(&signal_tx);
+        let close_rx = close_rx.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let io = ActivityIo::new(stream, write_timeout);
+            let connection = Connection {
+                builder,
+                service,
+                activity: io.activity(),
+                idle_timeout,
+                remote_addr,
+            };
+            match tls {
+                None => connection.run(io, &signal_tx).await,
+                Some(acceptor) => {
+                    match tokio::time::timeout(handshake_timeout, tls::accept(&acceptor, io)).await
+                    {
+                        Ok(Ok(io)) => connection.run(io, &signal_tx).await,
+                        Ok(Err(err)) => {
+                            debug!(%remote_addr, error = %err, "TLS handshake failed");
+                        }
+                        Err(_) => debug!(%remote_addr, "TLS handshake timed out"),
+                    }
+                }
+            }
+            drop(close_rx);
+        });
+    }
+
+    drop(close_rx);
+    drop(listener);
+    close_tx.closed().await;
+    Ok(())
+}
+
+/// An accepted connection waiting to be served.
+struct Connection {
+    builder: Builder<TokioExecutor>,
+    service: ConnectionService,
+    activity: Activity,
+    idle_timeout: Duration,
+    remote_addr: SocketAddr,
+}
+
+impl Connection {
+    /// Serves HTTP on `io` until the client leaves, the connection idles out,
+    /// or `shutdown` closes, in which case in-flight requests finish first.
+    async fn run<I>(self, io: I, shutdown: &watch::Sender<()>)
+    where
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let Self {
+            builder,
+            service,
+            activity,
+            idle_timeout,
+            remote_addr,
+        } = self;
+        let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+        tokio::pin!(conn);
+        let signal_closed = shutdown.closed().fuse();
+        tokio::pin!(signal_closed);
+        let mut closing = false;
+
+        loop {
+            let idle_deadline = activity.last() + idle_timeout;
+            tokio::select! {
+                result = conn.as_mut() => {
+                    if let Err(err) = result {
+                        debug!(%remote_addr, error = %err, "connection ended with error");
+                    }
+                    break;
+                }
+                _ = &mut signal_closed => {
+                    closing = true;
+                    conn.as_mut().graceful_shutdown();
+                }
+                _ = tokio::time::sleep_until(idle_deadline.into()), if !closing => {
+                    if activity.last().elapsed() >= idle_timeout {
+                        debug!(%remote_addr, "closing idle connection");
+                        closing = true;
+                        conn.as_mut().graceful_shutdown();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Shared timestamp of the last socket read or write on a connection.
+#[derive(Clone)]
+struct Activity {
+    started: Instant,
+    elapsed_ms: Arc<AtomicU64>,
+}
+
+impl Activity {
+    fn touch(&self) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.elapsed_ms.store(elapsed, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.started + Duration::from_millis(self.elapsed_ms.load(Ordering::Relaxed))
+    }
+}
+
+/// Socket wrapper recording activity for the idle timeout and failing writes
+/// that stall (the client stopped reading) for longer than the write timeout.
+struct ActivityIo {
+    inner: TcpStream,
+    activity: Activity,
+    write_timeout: Duration,
+    write_stall: Option<Pin<Box<Sleep>>>,
+}
+
+impl ActivityIo {
+    fn new(inner: TcpStream, write_timeout: Duration) -> Self {
+        Self {
+            inner,
+            activity: Activity {
+                started: Instant::now(),
+                elapsed_ms: Arc::new(AtomicU64::new(0)),
+            },
+            write_timeout,
+            write_stall: None,
+        }
+    }
+
+    fn activity(&self) -> Activity {
+        self.activity.clone()
+    }
+
+    /// Records write progress, or starts/polls the stall timer while the socket is full.
+    fn track_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        if poll.is_pending() {
+            let timeout = self.write_timeout;
+            let stall = self
+                .write_stall
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+            if stall.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "client stopped reading the response",
+                )));
+            }
+            return poll;
+        }
+        self.write_stall = None;
+        if matches!(poll, Poll::Ready(Ok(written)) if written > 0) {
+            self.activity.touch();
+        }
+        poll
+    }
+}
+
+impl AsyncRead for ActivityIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if buf.filled().len() > filled {
+            self.activity.touch();
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for ActivityIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.track_write(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.track_write(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Logs an accept failure, backing off when the process is out of file descriptors.
+pub(crate) async fn accept_error(err: io::Error) {
+    if matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    ) {
+        return;
+    }
+    error!(error = %err, "accept error");
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
+
+/// Answers a connection over the limit with an OpenAI-style `503` and closes it.
+async fn reject_connection(mut stream: TcpStream, max_connections: usize) {
+    let body = AppError::overloaded(format!(
+        "server is at its limit of {max_connections} concurrent connections; retry later"
+    ))
+    .into_json()
+    .to_string();
+    let response = format!(
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-type: application/json\r\ncontent-length: {}\r\nretry-after: 1\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let write = async {
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    };
+    if let Err(err) = tokio::time::timeout(REJECT_WRITE_TIMEOUT, write)
+        .await
+        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+    {
+        warn!(error = %err, "failed to send connection-limit response");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rejected_connections_receive_503_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.expect("connect");
+            let mut response = String::new();
+            stream
+                .read_to_string(&mut response)
+                .await
+                .expect("read response");
+            response
+        });
+
+        let (stream, _) = listener.accept().await.expect("accept");
+        reject_connection(stream, 1).await;
+
+        let response = client.await.expect("client task");
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("\"code\":\"server_overloaded\""));
+    }
+}
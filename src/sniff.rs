@@ -0,0 +1,190 @@
+//! Upload type detection from the leading bytes of the file.
+//!
+//! The filename extension is only a claim: a WAV saved as `audio.dat` is
+//! still WAV, and a web page saved as `audio.mp3` is not audio at all.
+//! [`resolve_extension`] checks the container magic bytes and picks the
+//! decoder hint from the content when it is recognized, falling back to the
+//! extension for formats without a header (raw G.711) or a reliable
+//! signature. Recognized non-audio content is rejected with an error naming
+//! what the file actually is.
+
+use tracing::debug;
+
+use crate::audio::{validate_extension, SUPPORTED_EXTENSIONS};
+use crate::error::AppError;
+use crate::ffmpeg::{ffmpeg_extension, FFMPEG_EXTENSIONS};
+
+/// Bytes of an upload needed to recognize its type.
+pub const HEAD_BYTES: usize = 64;
+
+/// What the leading bytes of an upload identify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Content {
+    /// An audio or video container, named by its canonical extension.
+    Media(&'static str),
+    /// Something that is not media, described for the error message.
+    Other(&'static str),
+}
+
+/// Identifies a container or common non-audio file from its first bytes.
+fn sniff(head: &[u8]) -> Option<Content> {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    let media = if starts(b"RIFF") && at(8, b"WAVE") {
+        "wav"
+    } else if starts(b"RIFF") && at(8, b"AVI ") {
+        "avi"
+    } else if starts(b"fLaC") {
+        "flac"
+    } else if starts(b"OggS") {
+        "ogg"
+    } else if starts(&[0x1a, 0x45, 0xdf, 0xa3]) {
+        "webm"
+    } else if at(4, b"ftyp") {
+        "mp4"
+    } else if starts(b".snd") {
+        "au"
+    } else if starts(&[0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11]) {
+        "wma"
+    } else if starts(b"ID3") {
+        "mp3"
+    } else if head.len() >= 2 && head[0] == 0xff && head[1] & 0xf6 == 0xf0 {
+        // ADTS sync word with layer bits 00; MPEG audio frames use 01-11.
+        "aac"
+    } else if head.len() >= 2 && head[0] == 0xff && head[1] & 0xe0 == 0xe0 && head[1] & 0x06 != 0 {
+        "mp3"
+    } else {
+        return sniff_other(head).map(Content::Other);
+    };
+    Some(Content::Media(media))
+}
+
+/// Recognizes files commonly uploaded by mistake.
+fn sniff_other(head: &[u8]) -> Option<&'static str> {
+    let kinds: [(&[u8], &str); 6] = [
+        (b"%PDF", "a PDF document"),
+        (b"\x89PNG", "a PNG image"),
+        (b"\xff\xd8\xff", "a JPEG image"),
+        (b"GIF8", "a GIF image"),
+        (b"PK\x03\x04", "a ZIP archive"),
+        (b"\x1f\x8b", "a gzip archive"),
+    ];
+    if let Some((_, kind)) = kinds.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(kind);
+    }
+    let text = head
+        .iter()
+        .skip_while(|byte| byte.is_ascii_whitespace())
+        .take(16)
+        .map(|byte| byte.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    if text.starts_with(b"<!doctype html") || text.starts_with(b"<html") {
+        return Some("an HTML page");
+    }
+    if text.starts_with(b"<?xml") {
+        return Some("an XML document");
+    }
+    if text.starts_with(b"{") || text.starts_with(b"[") {
+        return Some("JSON");
+    }
+    None
+}
+
+/// The container an extension claims, for comparison with sniffed content.
+fn container_of(extension: &str) -> &str {
+    match extension {
+        "mpga" | "mpeg" => "mp3",
+        "m4a" | "m4v" | "mov" | "3gp" => "mp4",
+        "mkv" => "webm",
+        other => other,
+    }
+}
+
+/// Picks the decoder hint for an upload named `filename` starting with `head`.
+///
+/// The extension is kept when the content agrees with it or cannot be
+/// identified; recognized media under the wrong (or no) extension is decoded
+/// as what it is. `ffmpeg` enables the extensions only ffmpeg can decode.
+pub fn resolve_extension(filename: &str, head: &[u8], ffmpeg: bool) -> Result<String, AppError> {
+    let claimed = match ffmpeg_extension(filename) {
+        Some(extension) if ffmpeg => Some(extension),
+        _ => validate_extension(filename).ok(),
+    };
+
+    match sniff(head) {
+        Some(Content::Other(kind)) => Err(AppError::unsupported_media_type(format!(
+            "uploaded file {filename:?} is {kind}, not audio"
+        ))),
+        Some(Content::Media(container)) => {
+            if let Some(claimed) = claimed.filter(|ext| container_of(ext) == container) {
+                return Ok(claimed);
+            }
+            let decodable = SUPPORTED_EXTENSIONS.contains(&container)
+                || (ffmpeg && FFMPEG_EXTENSIONS.contains(&container));
+            if !decodable {
+                return Err(AppError::unsupported_media_type(format!(
+                    "uploaded file {filename:?} contains .{container} media, which requires FFMPEG_BIN to decode"
+                )));
+            }
+            debug!(
+                filename,
+                content = container,
+                "upload extension does not match its content; decoding by content"
+            );
+            Ok(container.to_string())
+        }
+        None => match claimed {
+            Some(claimed) => Ok(claimed),
+            None => validate_extension(filename),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(filename: &str, head: &[u8]) -> Result<String, String> {
+        resolve_extension(filename, head, false).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn content_decides_the_decoder_hint() {
+        let wav = b"RIFF\x24\x00\x00\x00WAVEfmt ";
+        assert_eq!(resolve("audio.dat", wav).as_deref(), Ok("wav"));
+        assert_eq!(resolve("recording", wav).as_deref(), Ok("wav"));
+        assert_eq!(resolve("voicemail.ul", wav).as_deref(), Ok("wav"));
+        assert_eq!(resolve("song.mpga", b"ID3\x04").as_deref(), Ok("mpga"));
+        assert_eq!(
+            resolve("clip.mov", b"\0\0\0\x20ftypqt  ").as_deref(),
+            Ok("mov")
+        );
+        assert_eq!(
+            resolve("clip.mp3", b"\0\0\0\x20ftypM4A ").as_deref(),
+            Ok("mp4")
+        );
+        assert_eq!(
+            resolve("talk.mp3", &[0xff, 0xfb, 0x90]).as_deref(),
+            Ok("mp3")
+        );
+
+        // Raw G.711 has no header, so the extension stands.
+        assert_eq!(resolve("call.ul", &[0x7f; 16]).as_deref(), Ok("ul"));
+    }
+
+    #[test]
+    fn mislabeled_files_get_accurate_errors() {
+        let err = resolve("audio.wav", b"%PDF-1.7").expect_err("pdf");
+        assert!(err.contains("is a PDF document, not audio"), "{err}");
+        let err = resolve("audio.mp3", b"\n  <!DOCTYPE html>").expect_err("html");
+        assert!(err.contains("an HTML page"), "{err}");
+        let err = resolve("audio.wav", b"RIFF\0\0\0\0AVI LIST").expect_err("avi");
+        assert!(err.contains("requires FFMPEG_BIN"), "{err}");
+        assert_eq!(
+            resolve_extension("audio.wav", b"RIFF\0\0\0\0AVI LIST", true).ok(),
+            Some("avi".to_string())
+        );
+        let err = resolve("notes.txt", b"hello").expect_err("unknown");
+        assert!(err.contains("unsupported file extension .txt"), "{err}");
+    }
+}
@@ -0,0 +1,242 @@
+//! Health probes answered while the server starts.
+//!
+//! Downloading the model, loading it into every worker, and the startup
+//! self-test can take minutes, and a probe that cannot connect looks like a
+//! dead process. The listener is therefore bound first and served by
+//! [`serve_until`] until the full router is built: `/health/live` answers
+//! `200`, `/health/ready` answers `503` with the model's `status`
+//! (`downloading` with byte progress, `loading`, or `self_test`), and every
+//! other route answers `503 model_loading` with `Retry-After`. Each
+//! connection is closed after one response, so clients reconnect to the full
+//! router once it takes over.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use hyper::server::conn::http1;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use hyper_util::service::TowerToHyperService;
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tracing::debug;
+
+use crate::api::health_live;
+use crate::error::AppError;
+use crate::model_store::DownloadProgress;
+use crate::server::accept_error;
+use crate::tls::{self, TlsAcceptor};
+
+/// Seconds clients are asked to wait before retrying a request during startup.
+const RETRY_AFTER_SECS: u64 = 5;
+/// Longest a startup connection may take, TLS handshake included.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What startup is doing with the default model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Phase {
+    /// Resolving the model file, downloading it when it is not cached.
+    #[default]
+    Downloading,
+    /// Loading the model into the inference workers.
+    Loading,
+    /// Running the startup self-test.
+    SelfTest,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Downloading => "downloading",
+            Self::Loading => "loading",
+            Self::SelfTest => "self_test",
+        }
+    }
+}
+
+/// Startup progress of the default model, shared with the probes.
+#[derive(Debug)]
+pub struct StartupStatus {
+    model_id: String,
+    phase: Mutex<Phase>,
+    /// Bytes of the default model downloaded so far.
+    pub progress: DownloadProgress,
+}
+
+impl StartupStatus {
+    /// Tracks the startup of the model served as `model_id`.
+    pub fn new(model_id: impl Into<String>) -> Self {
+        Self {
+            model_id: model_id.into(),
+            phase: Mutex::default(),
+            progress: DownloadProgress::default(),
+        }
+    }
+
+    /// Records that startup moved on to `phase`.
+    pub fn set_phase(&self, phase: Phase) {
+        if let Ok(mut current) = self.phase.lock() {
+            *current = phase;
+        }
+    }
+
+    fn phase(&self) -> Phase {
+        self.phase.lock().map(|phase| *phase).unwrap_or_default()
+    }
+
+    /// The `model` object of the readiness body.
+    fn model_json(&self) -> serde_json::Value {
+        let phase = self.phase();
+        let mut model = json!({"id": self.model_id, "status": phase.as_str()});
+        if phase == Phase::Downloading {
+            let (downloaded, total) = self.progress.snapshot();
+            model["downloaded_bytes"] = json!(downloaded);
+            model["total_bytes"] = json!((total > 0).then_some(total));
+        }
+        model
+    }
+}
+
+/// Routes answered until the full router is ready.
+fn startup_router(status: Arc<StartupStatus>) -> Router {
+    Router::new()
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(startup_ready))
+        .fallback(starting)
+        .with_state(status)
+}
+
+async fn startup_ready(State(status): State<Arc<StartupStatus>>) -> Response {
+    let body = json!({"status": "starting", "model": status.model_json()});
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+}
+
+async fn starting(State(status): State<Arc<StartupStatus>>) -> Response {
+    let mut res = AppError::model_loading(format!(
+        "the server is starting; model {} is {}",
+        status.model_id,
+        status.phase().as_str()
+    ))
+    .into_response();
+    res.headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+    res
+}
+
+/// Answers connections on `listener` with the startup routes until `work`
+/// completes, and returns its output.
+pub async fn serve_until<T>(
+    listener: &TcpListener,
+    tls: Option<TlsAcceptor>,
+    status: Arc<StartupStatus>,
+    work: impl Future<Output = T>,
+) -> T {
+    let app = startup_router(status);
+    tokio::pin!(work);
+    loop {
+        let stream = tokio::select! {
+            output = &mut work => return output,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    accept_error(err).await;
+                    continue;
+                }
+            },
+        };
+        let app = app.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let served = tokio::time::timeout(CONNECTION_TIMEOUT, async move {
+                match tls {
+                    None => serve_connection(stream, app).await,
+                    Some(acceptor) => match tls::accept(&acceptor, stream).await {
+                        Ok(io) => serve_connection(io, app).await,
+                        Err(err) => debug!(error = %err, "TLS handshake failed"),
+                    },
+                }
+            })
+            .await;
+            if served.is_err() {
+                debug!("startup connection timed out");
+            }
+        });
+    }
+}
+
+/// Serves one request on `io` and closes it.
+async fn serve_connection<I>(io: I, app: Router)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let served = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .keep_alive(false)
+        .serve_connection(TokioIo::new(io), TowerToHyperService::new(app))
+        .await;
+    if let Err(err) = served {
+        debug!(error = %err, "startup connection ended with error");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+            .await
+            .expect("write request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("read response");
+        response
+    }
+
+    #[tokio::test]
+    async fn readiness_is_503_until_the_model_is_loaded() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let status = Arc::new(StartupStatus::new("whisper-1"));
+        let (loaded_tx, loaded_rx) = tokio::sync::oneshot::channel::<&str>();
+        let probing = Arc::clone(&status);
+        let probes = tokio::spawn(async move {
+            let downloading = get(addr, "/health/ready").await;
+            probing.set_phase(Phase::Loading);
+            let loading = get(addr, "/health/ready").await;
+            let live = get(addr, "/health/live").await;
+            let request = get(addr, "/v1/models").await;
+            loaded_tx.send("backend").expect("send");
+            (downloading, loading, live, request)
+        });
+
+        let loaded = serve_until(&listener, None, status, loaded_rx).await;
+        assert_eq!(loaded, Ok("backend"));
+        let (downloading, loading, live, request) = probes.await.expect("probes");
+        assert!(downloading.starts_with("HTTP/1.1 503"), "{downloading}");
+        assert!(
+            downloading.contains(r#""status":"downloading""#),
+            "{downloading}"
+        );
+        assert!(downloading.contains(r#""downloaded_bytes":0"#));
+        assert!(loading.starts_with("HTTP/1.1 503"), "{loading}");
+        assert!(loading.contains(r#""status":"loading""#), "{loading}");
+        assert!(live.starts_with("HTTP/1.1 200"), "{live}");
+        assert!(request.starts_with("HTTP/1.1 503"), "{request}");
+        assert!(request.to_ascii_lowercase().contains("retry-after: 5"));
+        assert!(request.contains(r#""code":"model_loading""#), "{request}");
+    }
+}
@@ -0,0 +1,521 @@
+//! Pluggable key-value storage behind the async job spool and results, the
+//! transcript archive, and debug traces.
+//!
+//! `ASYNC_JOB_DIR` and `TRANSCRIPT_ARCHIVE_DIR` each name a storage
+//! location: a directory, stored as plain files, or `sqlite:<file>`, stored
+//! as rows of a SQLite database (built with the `sqlite` feature). Both can
+//! share one database; each uses its own namespace. Keys are `/`-separated
+//! relative paths such as `job_abc/job.json`, so the local layout is the same
+//! as before storage was pluggable. Callers seal values themselves when
+//! storage encryption is configured.
+//!
+//! Implementations are synchronous; async callers run them on the blocking
+//! thread pool. Other backends (object stores, Redis) implement [`Storage`]
+//! and get a scheme in [`StorageLocation::parse`].
+
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::janitor::{remove_stale, Reclaimed};
+
+/// Suffix of values being written; they are renamed into place once complete.
+const PARTIAL_SUFFIX: &str = ".part";
+/// Location prefix selecting the SQLite backend.
+const SQLITE_SCHEME: &str = "sqlite:";
+
+/// A stored value's key and when it was last written, in Unix seconds.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StoredObject {
+    pub key: String,
+    pub modified: u64,
+}
+
+/// Byte values stored under `/`-separated keys.
+pub trait Storage: Send + Sync + fmt::Debug {
+    /// Stores `value` under `key`, replacing any previous value atomically.
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()>;
+
+    /// Stores `value` under `key`, failing with
+    /// [`io::ErrorKind::AlreadyExists`] instead when `key` holds a value.
+    fn put_new(&self, key: &str, value: &[u8]) -> io::Result<()>;
+
+    /// Stores the contents of the file at `source` under `key`.
+    fn put_file(&self, key: &str, source: &Path) -> io::Result<()> {
+        self.put(key, &std::fs::read(source)?)
+    }
+
+    /// Returns the value stored under `key`, or `None` when there is none.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Path of the value under `key` when it is kept as a local file, so
+    /// large values can be read without loading them into memory.
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Deletes the value under `key`; returns whether there was one.
+    fn delete(&self, key: &str) -> io::Result<bool>;
+
+    /// Deletes every value whose key starts with `prefix`; returns how many.
+    fn delete_prefix(&self, prefix: &str) -> io::Result<usize> {
+        let mut deleted = 0;
+        for object in self.list(prefix)? {
+            deleted += usize::from(self.delete(&object.key)?);
+        }
+        Ok(deleted)
+    }
+
+    /// Lists the values whose key starts with `prefix`, in key order.
+    fn list(&self, prefix: &str) -> io::Result<Vec<StoredObject>>;
+
+    /// Deletes partial writes left by a crash that nothing has touched for
+    /// `stale_after`. Backends that write atomically have none.
+    fn remove_abandoned(&self, _stale_after: Duration) -> io::Result<Reclaimed> {
+        Ok(Reclaimed::default())
+    }
+}
+
+/// Where a feature keeps its data, as configured by a `*_DIR` setting.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StorageLocation {
+    /// Plain files under a directory.
+    Local(PathBuf),
+    /// Rows of a SQLite database file.
+    Sqlite(PathBuf),
+}
+
+impl StorageLocation {
+    /// Parses a directory path or `sqlite:<file>` (`sqlite://<file>` also works).
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("storage location is empty".to_string());
+        }
+        match value.strip_prefix(SQLITE_SCHEME) {
+            Some(path) => {
+                let path = path.strip_prefix("//").unwrap_or(path);
+                if path.is_empty() {
+                    return Err(format!("{value:?} names no database file"));
+                }
+                Ok(Self::Sqlite(PathBuf::from(path)))
+            }
+            None => Ok(Self::Local(PathBuf::from(value))),
+        }
+    }
+
+    /// Opens the storage for `namespace`, which keeps features sharing a
+    /// database apart; local directories are already separate.
+    pub fn open(&self, namespace: &str) -> io::Result<Arc<dyn Storage>> {
+        match self {
+            Self::Local(dir) => Ok(Arc::new(LocalStorage::new(dir))),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => Ok(Arc::new(SqliteStorage::open(path, namespace)?)),
+            #[cfg(not(feature = "sqlite"))]
+            Self::Sqlite(path) => {
+                let _ = namespace;
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "cannot open {}: SQLite storage needs a server built with the `sqlite` feature",
+                        path.display()
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Stores each value as a file under a root directory.
+#[derive(Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Creates storage rooted at `root`; directories are created on first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid storage key {key:?}"),
+            ));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// Writes to a sibling `.part` file and renames it, so readers never see
+    /// a partially written value.
+    fn write_with(&self, key: &str, write: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+        let (path, partial) = self.partial_path(key)?;
+        write(&partial)?;
+        std::fs::rename(&partial, &path)
+    }
+
+    /// Path of `key` and of the `.part` file written before it, with their
+    /// directory created.
+    fn partial_path(&self, key: &str) -> io::Result<(PathBuf, PathBuf)> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(PARTIAL_SUFFIX);
+        Ok((path, PathBuf::from(partial)))
+    }
+
+    fn collect(&self, dir: &Path, key: &str, objects: &mut Vec<StoredObject>) -> io::Result<()> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+                continue;
+            };
+            let child = if key.is_empty() {
+                name.clone()
+            } else {
+                format!("{key}/{name}")
+            };
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                self.collect(&entry.path(), &child, objects)?;
+            } else if !name.ends_with(PARTIAL_SUFFIX) {
+                let modified = metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs());
+                objects.push(StoredObject {
+                    key: child,
+                    modified,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        self.write_with(key, |path| std::fs::write(path, value))
+    }
+
+    /// Links the `.part` file into place, which unlike a rename fails when
+    /// the key exists.
+    fn put_new(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        let (path, partial) = self.partial_path(key)?;
+        std::fs::write(&partial, value)?;
+        let linked = std::fs::hard_link(&partial, &path);
+        std::fs::remove_file(&partial)?;
+        linked
+    }
+
+    fn remove_abandoned(&self, stale_after: Duration) -> io::Result<Reclaimed> {
+        remove_stale(
+            &self.root,
+            |name| name.ends_with(PARTIAL_SUFFIX),
+            stale_after,
+        )
+    }
+
+    fn put_file(&self, key: &str, source: &Path) -> io::Result<()> {
+        self.write_with(key, |path| std::fs::copy(source, path).map(drop))
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)?) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        self.path(key).ok()
+    }
+
+    fn delete(&self, key: &str) -> io::Result<bool> {
+        match std::fs::remove_file(self.path(key)?) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Removes a whole directory at once when `prefix` names one (`job_abc/`).
+    fn delete_prefix(&self, prefix: &str) -> io::Result<usize> {
+        let objects = self.list(prefix)?;
+        match prefix.strip_suffix('/') {
+            Some(dir) => match std::fs::remove_dir_all(self.path(dir)?) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            },
+            None => {
+                for object in &objects {
+                    self.delete(&object.key)?;
+                }
+            }
+        }
+        Ok(objects.len())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<StoredObject>> {
+        // Start from the deepest directory the prefix names instead of the root.
+        let (dir, key) = match prefix.rfind('/') {
+            Some(slash) => (self.path(&prefix[..slash])?, &prefix[..slash]),
+            None => (self.root.clone(), ""),
+        };
+        let mut objects = Vec::new();
+        self.collect(&dir, key, &mut objects)?;
+        objects.retain(|object| object.key.starts_with(prefix));
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+/// Stores values as rows of an `objects` table in a SQLite database.
+///
+/// One connection is shared behind a mutex; the database runs in WAL mode so
+/// another process can read it while the server writes.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStorage {
+    path: PathBuf,
+    namespace: String,
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    /// Opens (creating if needed) the database at `path` for `namespace`.
+    pub fn open(path: &Path, namespace: &str) -> io::Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(sqlite_error)?;
+        connection
+            .pragma_update(None, "journal_mode", "WAL")
+            .map_err(sqlite_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS objects (
+                    namespace TEXT NOT NULL,
+                    key TEXT NOT NULL,
+                    value BLOB NOT NULL,
+                    modified INTEGER NOT NULL,
+                    PRIMARY KEY (namespace, key)
+                )",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            namespace: namespace.to_string(),
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> io::Result<std::sync::MutexGuard<'_, rusqlite::Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "sqlite connection lock poisoned"))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl fmt::Debug for SqliteStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteStorage")
+            .field("path", &self.path)
+            .field("namespace", &self.namespace)
+            .finish()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO objects (namespace, key, value, modified)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![self.namespace, key, value, crate::jobs::unix_secs() as i64],
+            )
+            .map(drop)
+            .map_err(sqlite_error)
+    }
+
+    fn put_new(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        let inserted = self
+            .connection()?
+            .execute(
+                "INSERT OR IGNORE INTO objects (namespace, key, value, modified)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![self.namespace, key, value, crate::jobs::unix_secs() as i64],
+            )
+            .map_err(sqlite_error)?;
+        if inserted == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("storage key {key:?} already holds a value"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+
+        self.connection()?
+            .query_row(
+                "SELECT value FROM objects WHERE namespace = ?1 AND key = ?2",
+                rusqlite::params![self.namespace, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<bool> {
+        self.connection()?
+            .execute(
+                "DELETE FROM objects WHERE namespace = ?1 AND key = ?2",
+                rusqlite::params![self.namespace, key],
+            )
+            .map(|deleted| deleted > 0)
+            .map_err(sqlite_error)
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> io::Result<usize> {
+        self.connection()?
+            .execute(
+                "DELETE FROM objects
+                 WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2",
+                rusqlite::params![self.namespace, prefix],
+            )
+            .map_err(sqlite_error)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<StoredObject>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT key, modified FROM objects
+                 WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2
+                 ORDER BY key",
+            )
+            .map_err(sqlite_error)?;
+        let rows = statement
+            .query_map(rusqlite::params![self.namespace, prefix], |row| {
+                Ok(StoredObject {
+                    key: row.get(0)?,
+                    modified: row.get::<_, i64>(1)?.max(0) as u64,
+                })
+            })
+            .map_err(sqlite_error)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(sqlite_error)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(storage: &dyn Storage) {
+        storage.put("job_a/job.json", b"{}").expect("put");
+        storage.put("job_a/file-0", b"RIFF").expect("put");
+        storage.put_new("job_b/job.json", b"{}").expect("put new");
+        assert_eq!(
+            storage.get("job_a/file-0").expect("get").as_deref(),
+            Some(&b"RIFF"[..])
+        );
+        assert_eq!(storage.get("job_c/job.json").expect("get"), None);
+        let exists = storage
+            .put_new("job_b/job.json", b"[]")
+            .expect_err("put new");
+        assert_eq!(exists.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            storage.get("job_b/job.json").expect("get").as_deref(),
+            Some(&b"{}"[..])
+        );
+
+        let keys = |prefix| {
+            storage
+                .list(prefix)
+                .expect("list")
+                .into_iter()
+                .map(|object| object.key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys("job_a/"), ["job_a/file-0", "job_a/job.json"]);
+        assert_eq!(keys("").len(), 3);
+
+        assert!(storage.delete("job_b/job.json").expect("delete"));
+        assert!(!storage.delete("job_b/job.json").expect("delete"));
+        assert_eq!(storage.delete_prefix("job_a/").expect("delete prefix"), 2);
+        assert!(keys("").is_empty());
+    }
+
+    #[test]
+    fn local_storage_keeps_values_as_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let storage = LocalStorage::new(dir.path());
+        exercise(&storage);
+        assert!(!dir.path().join("job_a").exists());
+        assert!(storage.put("../escape", b"x").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_storage_separates_namespaces() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("state.db");
+        exercise(&SqliteStorage::open(&path, "jobs").expect("open"));
+
+        let jobs = SqliteStorage::open(&path, "jobs").expect("open");
+        let archive = SqliteStorage::open(&path, "archive").expect("open");
+        jobs.put("a", b"1").expect("put");
+        assert_eq!(archive.get("a").expect("get"), None);
+        assert_eq!(archive.list("").expect("list"), []);
+    }
+
+    #[test]
+    fn locations_parse_directories_and_sqlite_files() {
+        assert_eq!(
+            StorageLocation::parse("/var/lib/jobs"),
+            Ok(StorageLocation::Local(PathBuf::from("/var/lib/jobs")))
+        );
+        assert_eq!(
+            StorageLocation::parse("sqlite:///var/lib/state.db"),
+            Ok(StorageLocation::Sqlite(PathBuf::from("/var/lib/state.db")))
+        );
+        assert_eq!(
+            StorageLocation::parse("sqlite:state.db"),
+            Ok(StorageLocation::Sqlite(PathBuf::from("state.db")))
+        );
+        assert!(StorageLocation::parse("sqlite:").is_err());
+    }
+}
@@ -0,0 +1,933 @@
+//! WebSocket streaming transcription (`GET /v1/audio/stream`).
+//!
+//! Clients push audio as binary messages and receive incremental transcript
+//! messages. A [`StreamSession`] keeps a rolling window of uncommitted 16 kHz
+//! audio: the window is re-transcribed as it grows (`transcript.partial`) and
+//! finalized (`transcript.final`) when it reaches whisper's 30 second context,
+//! when the client sends `{"type":"commit"}`, or when the stream closes.
+//!
+//! WebM/Opus, as recorded by browsers' `MediaRecorder`, is demuxed and decoded
+//! as it arrives, whichever byte offsets the chunks are cut at. Raw G.711
+//! (`pcm_mulaw`, `pcm_alaw`), as carried by SIP media streams, is expanded
+//! byte by byte. Other encoded streams are decoded on a blocking thread that
+//! reads each message's bytes as they arrive, so the decoder keeps its state
+//! between messages instead of starting over.
+//!
+//! With `encoding=rtp` the audio arrives instead as RTP packets on
+//! `RTP_PORT`, routed to the session by [`crate::rtp`].
+//!
+//! With `task=translate` the same windows are translated into English, so a
+//! session can caption multilingual speech live.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Extension, Query, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::json;
+use symphonia::core::io::MediaSource;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::api::{
+    acquire_inference_slot, parse_compute, require_auth, resolve_backend, resolve_source_language,
+    unsupported_by_model, AppState,
+};
+#[cfg(feature = "opus")]
+use crate::audio::Downmix;
+#[cfg(not(feature = "opus"))]
+use crate::audio::OPUS_UNAVAILABLE;
+use crate::audio::{decode_incrementally, is_silent, resample_linear, SUPPORTED_EXTENSIONS};
+use crate::backend::{TaskKind, TranscribeRequest, Transcriber};
+use crate::error::AppError;
+use crate::g711::Law;
+#[cfg(feature = "opus")]
+use crate::opus::OpusDecoder;
+use crate::rate_limit::HeldPermit;
+use crate::rtp::{RtpStream, RtpSubscription, DEFAULT_OPUS_PAYLOAD_TYPE, DYNAMIC_PAYLOAD_TYPES};
+use crate::webm::WebmDemuxer;
+
+/// Sample rate of the rolling window fed to whisper.
+const WINDOW_SAMPLE_RATE: usize = 16_000;
+/// New audio required before the window is re-transcribed as a partial result.
+const PARTIAL_INTERVAL_SAMPLES: usize = 2 * WINDOW_SAMPLE_RATE;
+/// Window length at which audio is finalized (whisper's context length).
+const MAX_WINDOW_SAMPLES: usize = 30 * WINDOW_SAMPLE_RATE;
+/// Upper bound on encoded bytes per session; they are kept so the decoder can
+/// seek back within what has arrived, such as to a header at the start.
+const MAX_ENCODED_BUFFER_BYTES: usize = 25 * 1024 * 1024;
+/// Matroska codec id of Opus audio.
+const OPUS_CODEC_ID: &str = "A_OPUS";
+
+/// Query parameters accepted when opening a stream.
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    #[serde(default = "default_model")]
+    model: String,
+    language: Option<String>,
+    /// Same as `language`; both name the spoken language.
+    source_language: Option<String>,
+    /// `transcribe` (the default) or `translate` into English.
+    task: Option<String>,
+    /// `pcm_s16le` (raw little-endian mono), `pcm_mulaw` or `pcm_alaw` (raw
+    /// G.711 mono), `rtp` (packets on `RTP_PORT`), or a container extension
+    /// such as `webm`.
+    #[serde(default = "default_encoding")]
+    encoding: String,
+    /// Sample rate of raw PCM and G.711 input; ignored for encoded input.
+    #[serde(default = "default_sample_rate")]
+    sample_rate: u32,
+    /// Dynamic RTP payload type of Opus packets with `encoding=rtp`.
+    opus_payload_type: Option<u8>,
+    /// Worker pool (`cpu` or `gpu`), replaced by the API key's policy when set.
+    compute: Option<String>,
+}
+
+/// Decode options fixed for the lifetime of a session.
+struct SessionOptions {
+    task: TaskKind,
+    language: Option<String>,
+}
+
+fn parse_task(raw: Option<&str>) -> Result<TaskKind, AppError> {
+    match raw.map(|task| task.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("transcribe") => Ok(TaskKind::Transcribe),
+        Some("translate") => Ok(TaskKind::Translate),
+        Some(other) => Err(AppError::invalid_request(
+            format!("invalid task={other:?}; expected transcribe or translate"),
+            Some("task"),
+            Some("invalid_task"),
+        )),
+    }
+}
+
+fn default_model() -> String {
+    "whisper-1".to_string()
+}
+
+fn default_encoding() -> String {
+    StreamEncoding::PCM_S16LE.to_string()
+}
+
+fn default_sample_rate() -> u32 {
+    WINDOW_SAMPLE_RATE as u32
+}
+
+/// How binary messages from the client are interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEncoding {
+    /// Raw little-endian 16-bit mono PCM at the given sample rate.
+    PcmS16le { sample_rate: u32 },
+    /// Raw 8-bit G.711 mono audio at the given sample rate.
+    G711 { law: Law, sample_rate: u32 },
+    /// RTP packets carrying G.711, or Opus as the given payload type.
+    Rtp { opus_payload_type: u8 },
+    /// A continuous encoded stream in the container named by the extension.
+    Encoded { extension: String },
+}
+
+impl StreamEncoding {
+    const PCM_S16LE: &'static str = "pcm_s16le";
+    const PCM_MULAW: &'static str = "pcm_mulaw";
+    const PCM_ALAW: &'static str = "pcm_alaw";
+    const RTP: &'static str = "rtp";
+
+    fn parse(encoding: &str, sample_rate: u32) -> Result<Self, AppError> {
+        let encoding = encoding.trim().to_ascii_lowercase();
+        let raw = match encoding.as_str() {
+            Self::PCM_S16LE => Some(None),
+            Self::PCM_MULAW => Some(Some(Law::MuLaw)),
+            Self::PCM_ALAW => Some(Some(Law::ALaw)),
+            _ => None,
+        };
+        if let Some(law) = raw {
+            if !(8_000..=192_000).contains(&sample_rate) {
+                return Err(AppError::invalid_request(
+                    format!("invalid sample_rate={sample_rate}; expected 8000-192000"),
+                    Some("sample_rate"),
+                    Some("invalid_sample_rate"),
+                ));
+            }
+            return Ok(match law {
+                Some(law) => Self::G711 { law, sample_rate },
+                None => Self::PcmS16le { sample_rate },
+            });
+        }
+        if encoding == Self::RTP {
+            return Ok(Self::Rtp {
+                opus_payload_type: DEFAULT_OPUS_PAYLOAD_TYPE,
+            });
+        }
+        if SUPPORTED_EXTENSIONS.contains(&encoding.as_str()) {
+            return Ok(Self::Encoded {
+                extension: encoding,
+            });
+        }
+        Err(AppError::invalid_request(
+            format!(
+                "invalid encoding={encoding:?}; expected {}, {}, {}, {}, or one of {}",
+                Self::PCM_S16LE,
+                Self::PCM_MULAW,
+                Self::PCM_ALAW,
+                Self::RTP,
+                SUPPORTED_EXTENSIONS.join(",")
+            ),
+            Some("encoding"),
+            Some("invalid_encoding"),
+        ))
+    }
+}
+
+/// Rolling audio buffer for one streaming connection.
+pub struct StreamSession {
+    encoding: StreamEncoding,
+    /// Uncommitted 16 kHz audio awaiting finalization.
+    window: Vec<f32>,
+    /// Stream time in seconds at which `window` starts.
+    window_start_secs: f64,
+    /// Window length when the last partial was emitted.
+    partial_at: usize,
+    /// Odd trailing byte of a PCM chunk, carried into the next chunk.
+    pcm_carry: Option<u8>,
+    /// WebM bytes held until the codec is known, in case it is not Opus.
+    encoded: Vec<u8>,
+    /// Decoder of other encoded input, started by the first chunk.
+    decoder: Option<EncodedStream>,
+    /// Incremental decoder for `webm` input, dropped if the audio is not Opus.
+    webm: Option<WebmStream>,
+    /// Decoder of `rtp` input.
+    rtp: Option<RtpStream>,
+}
+
+impl StreamSession {
+    /// Creates an empty session for `encoding`.
+    pub fn new(encoding: StreamEncoding) -> Self {
+        let webm =
+            matches!(&encoding, StreamEncoding::Encoded { extension } if extension == "webm")
+                .then(WebmStream::default);
+        let rtp = match encoding {
+            StreamEncoding::Rtp { opus_payload_type } => Some(RtpStream::new(opus_payload_type)),
+            _ => None,
+        };
+        Self {
+            encoding,
+            window: Vec::new(),
+            window_start_secs: 0.0,
+            partial_at: 0,
+            pcm_carry: None,
+            encoded: Vec::new(),
+            decoder: None,
+            webm,
+            rtp,
+        }
+    }
+
+    /// Appends a binary audio chunk to the window.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), AppError> {
+        match self.encoding.clone() {
+            StreamEncoding::PcmS16le { sample_rate } => {
+                let mut bytes = Vec::with_capacity(chunk.len() + 1);
+                bytes.extend(self.pcm_carry.take());
+                bytes.extend_from_slice(chunk);
+                if bytes.len() % 2 == 1 {
+                    self.pcm_carry = bytes.pop();
+                }
+                let samples = bytes
+                    .chunks_exact(2)
+                    .map(|pair| f32::from(i16::from_le_bytes([pair[0], pair[1]])) / 32_768.0)
+                    .collect::<Vec<_>>();
+                self.window.extend(resample_linear(
+                    &samples,
+                    sample_rate,
+                    WINDOW_SAMPLE_RATE as u32,
+                ));
+            }
+            StreamEncoding::G711 { law, sample_rate } => {
+                let samples = chunk
+                    .iter()
+                    .map(|&byte| law.expand(byte))
+                    .collect::<Vec<_>>();
+                self.window.extend(resample_linear(
+                    &samples,
+                    sample_rate,
+                    WINDOW_SAMPLE_RATE as u32,
+                ));
+            }
+            StreamEncoding::Rtp { .. } => {
+                if let Some(rtp) = &mut self.rtp {
+                    rtp.push(chunk, &mut self.window)?;
+                }
+            }
+            StreamEncoding::Encoded { extension } => {
+                let received =
+                    self.encoded.len() + self.decoder.as_ref().map_or(0, EncodedStream::len);
+                if received + chunk.len() > MAX_ENCODED_BUFFER_BYTES {
+                    return Err(AppError::invalid_request(
+                        "encoded stream exceeded 25 MiB; use encoding=pcm_s16le for long sessions",
+                        Some("encoding"),
+                        Some("stream_buffer_full"),
+                    ));
+                }
+                if let Some(webm) = &mut self.webm {
+                    if webm.push(chunk, &mut self.window)? {
+                        // Kept until the codec is known, in case it is not Opus.
+                        match webm.demuxer.track() {
+                            None => self.encoded.extend_from_slice(chunk),
+                            Some(_) => self.encoded = Vec::new(),
+                        }
+                        return Ok(());
+                    }
+                    self.webm = None;
+                }
+                let decoder = self
+                    .decoder
+                    .get_or_insert_with(|| EncodedStream::start(extension));
+                if !self.encoded.is_empty() {
+                    decoder.push(&std::mem::take(&mut self.encoded));
+                }
+                decoder.push(chunk);
+                decoder.drain(&mut self.window)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the encoded input and moves the rest of its audio into the window.
+    pub async fn finish(&mut self) -> Result<(), AppError> {
+        match self.decoder.take() {
+            Some(decoder) => decoder.finish(&mut self.window).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether enough new audio arrived to re-transcribe the window.
+    pub fn partial_due(&self) -> bool {
+        self.window.len() >= self.partial_at + PARTIAL_INTERVAL_SAMPLES
+    }
+
+    /// Returns whether the window reached whisper's context length.
+    pub fn window_full(&self) -> bool {
+        self.window.len() >= MAX_WINDOW_SAMPLES
+    }
+
+    /// Returns the current window and its stream start time, marking it as partially emitted.
+    pub fn partial_window(&mut self) -> (f64, Vec<f32>) {
+        self.partial_at = self.window.len();
+        (self.window_start_secs, self.window.clone())
+    }
+
+    /// Removes and returns the window (at most [`MAX_WINDOW_SAMPLES`]) for finalization.
+    pub fn take_window(&mut self) -> (f64, Vec<f32>) {
+        let take = self.window.len().min(MAX_WINDOW_SAMPLES);
+        let committed = self.window.drain(..take).collect::<Vec<_>>();
+        let start = self.window_start_secs;
+        self.window_start_secs += committed.len() as f64 / WINDOW_SAMPLE_RATE as f64;
+        self.partial_at = 0;
+        (start, committed)
+    }
+}
+
+/// An encoded stream decoded on a blocking thread as its bytes arrive.
+struct EncodedStream {
+    bytes: Arc<StreamBytes>,
+    samples: Receiver<Result<Vec<f32>, AppError>>,
+    task: JoinHandle<()>,
+}
+
+impl EncodedStream {
+    /// Starts decoding a stream in the container named by `extension`.
+    fn start(extension: String) -> Self {
+        let bytes = Arc::new(StreamBytes::default());
+        let reader = StreamReader {
+            bytes: Arc::clone(&bytes),
+            pos: 0,
+        };
+        let (sender, samples) = mpsc::channel();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut emit = |decoded| {
+                let _ = sender.send(Ok(decoded));
+            };
+            if let Err(err) = decode_incrementally(Box::new(reader), &extension, &mut emit) {
+                let _ = sender.send(Err(err));
+            }
+        });
+        Self {
+            bytes,
+            samples,
+            task,
+        }
+    }
+
+    /// Encoded bytes received so far.
+    fn len(&self) -> usize {
+        self.bytes.state.lock().map_or(0, |state| state.data.len())
+    }
+
+    fn push(&self, chunk: &[u8]) {
+        if let Ok(mut state) = self.bytes.state.lock() {
+            state.data.extend_from_slice(chunk);
+            self.bytes.arrived.notify_one();
+        }
+    }
+
+    /// Moves the samples decoded so far into `window`.
+    fn drain(&self, window: &mut Vec<f32>) -> Result<(), AppError> {
+        while let Ok(decoded) = self.samples.try_recv() {
+            window.extend(decoded?);
+        }
+        Ok(())
+    }
+
+    /// Ends the stream and waits for the decoder to pass on the rest.
+    async fn finish(mut self, window: &mut Vec<f32>) -> Result<(), AppError> {
+        self.bytes.close();
+        if let Err(err) = (&mut self.task).await {
+            return Err(AppError::internal(format!("stream decoder failed: {err}")));
+        }
+        self.drain(window)
+    }
+}
+
+impl Drop for EncodedStream {
+    fn drop(&mut self) {
+        self.bytes.close();
+    }
+}
+
+/// Encoded bytes shared between a session and its decoder thread.
+#[derive(Default)]
+struct StreamBytes {
+    state: Mutex<StreamState>,
+    arrived: Condvar,
+}
+
+#[derive(Default)]
+struct StreamState {
+    data: Vec<u8>,
+    /// No more bytes will arrive.
+    closed: bool,
+}
+
+impl StreamBytes {
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        self.arrived.notify_one();
+    }
+}
+
+/// Blocking reader over the bytes of a stream that waits for more to arrive.
+struct StreamReader {
+    bytes: Arc<StreamBytes>,
+    pos: usize,
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let poisoned = || io::Error::new(io::ErrorKind::Other, "stream buffer poisoned");
+        let mut state = self.bytes.state.lock().map_err(|_| poisoned())?;
+        while self.pos >= state.data.len() && !state.closed {
+            state = self.bytes.arrived.wait(state).map_err(|_| poisoned())?;
+        }
+        let available = state.data.get(self.pos..).unwrap_or_default();
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Seek for StreamReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => usize::try_from(offset).ok(),
+            SeekFrom::Current(delta) => isize::try_from(delta)
+                .ok()
+                .and_then(|delta| self.pos.checked_add_signed(delta)),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the end of a live stream is unknown",
+                ))
+            }
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek before the start")
+        })?;
+        Ok(self.pos as u64)
+    }
+}
+
+impl MediaSource for StreamReader {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Incremental demuxing and decoding of a WebM stream carrying Opus.
+#[derive(Default)]
+struct WebmStream {
+    demuxer: WebmDemuxer,
+    #[cfg(feature = "opus")]
+    decoder: Option<OpusDecoder>,
+}
+
+impl WebmStream {
+    /// Demuxes `chunk` and appends its decoded audio to `window`.
+    ///
+    /// Returns `false` once the audio track turns out not to be Opus, leaving
+    /// the stream to the buffered re-decoding path.
+    fn push(&mut self, chunk: &[u8], window: &mut Vec<f32>) -> Result<bool, AppError> {
+        let frames = self.demuxer.push(chunk)?;
+        match self.demuxer.track() {
+            None => Ok(true),
+            Some(track) if track.codec_id == OPUS_CODEC_ID => {
+                self.decode(frames, window)?;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+        }
+    }
+
+    #[cfg(feature = "opus")]
+    fn decode(&mut self, frames: Vec<Vec<u8>>, window: &mut Vec<f32>) -> Result<(), AppError> {
+        let decoder = match &mut self.decoder {
+            Some(decoder) => decoder,
+            None => {
+                let header = self
+                    .demuxer
+                    .track()
+                    .map(|track| track.codec_private.as_slice());
+                self.decoder
+                    .insert(OpusDecoder::new(header, Downmix::Average)?)
+            }
+        };
+        for frame in frames {
+            decoder.decode(&frame, window);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "opus"))]
+    fn decode(&mut self, _frames: Vec<Vec<u8>>, _window: &mut Vec<f32>) -> Result<(), AppError> {
+        Err(AppError::unsupported_media_type(OPUS_UNAVAILABLE))
+    }
+}
+
+/// Upgrades `GET /v1/audio/stream` to a WebSocket transcription session.
+pub async fn audio_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<StreamParams>,
+    permit: Option<Extension<HeldPermit>>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let api_key = require_auth(&state.cfg, &headers)?;
+    let mut encoding = StreamEncoding::parse(&params.encoding, params.sample_rate)?;
+    let rtp = match &mut encoding {
+        StreamEncoding::Rtp { opus_payload_type } => {
+            let Some(router) = &state.rtp else {
+                return Err(AppError::invalid_request(
+                    "encoding=rtp requires the server to be started with RTP_PORT",
+                    Some("encoding"),
+                    Some("rtp_unavailable"),
+                ));
+            };
+            if let Some(payload_type) = params.opus_payload_type {
+                if !DYNAMIC_PAYLOAD_TYPES.contains(&payload_type) {
+                    return Err(AppError::invalid_request(
+                        format!("invalid opus_payload_type={payload_type}; expected 96-127"),
+                        Some("opus_payload_type"),
+                        Some("invalid_opus_payload_type"),
+                    ));
+                }
+                *opus_payload_type = payload_type;
+            }
+            Some(router.register())
+        }
+        _ => None,
+    };
+    let task = parse_task(params.task.as_deref())?;
+    let language = resolve_source_language(params.language, params.source_language)?;
+    let compute = match api_key.and_then(|key| key.compute) {
+        Some(compute) => Some(compute),
+        None => params.compute.as_deref().map(parse_compute).transpose()?,
+    };
+    let backend = resolve_backend(&state, &params.model, compute)?;
+    if !backend.capabilities().streaming {
+        return Err(unsupported_by_model(
+            &state,
+            &params.model,
+            "streaming",
+            "model",
+            |supported| supported.streaming,
+        ));
+    }
+    if task == TaskKind::Translate && !backend.capabilities().translation_targets.contains(&"en") {
+        return Err(unsupported_by_model(
+            &state,
+            &params.model,
+            "translation into English",
+            "task",
+            |supported| supported.translation_targets.contains(&"en"),
+        ));
+    }
+    let options = SessionOptions { task, language };
+    Ok(upgrade.on_upgrade(move |socket| async move {
+        // The session counts against the client's concurrency limit until the
+        // socket closes, not just while the handshake is answered.
+        let _permit = permit;
+        run_session(
+            socket,
+            state,
+            backend,
+            StreamSession::new(encoding),
+            options,
+            rtp,
+        )
+        .await
+    }))
+}
+
+async fn run_session(
+    mut socket: WebSocket,
+    state: Arc<AppState>,
+    backend: Arc<dyn Transcriber>,
+    mut session: StreamSession,
+    options: SessionOptions,
+    mut rtp: Option<RtpSubscription>,
+) {
+    let mut created = json!({
+        "type": "session.created",
+        "sample_rate": WINDOW_SAMPLE_RATE,
+        "task": options.task.as_str(),
+    });
+    if let Some(rtp) = &rtp {
+        created["rtp"] = json!({"token": rtp.token, "port": state.cfg.rtp_port});
+    }
+    if socket
+        .send(Message::Text(created.to_string()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        let message = match &mut rtp {
+            Some(rtp) => tokio::select! {
+                message = socket.recv() => message,
+                Some(packet) = rtp.packets.recv() => Some(Ok(Message::Binary(packet))),
+            },
+            None => socket.recv().await,
+        };
+        let Some(Ok(message)) = message else {
+            break;
+        };
+        let outcome = match message {
+            Message::Binary(chunk) => match session.push(&chunk) {
+                Ok(()) if session.window_full() => {
+                    finalize(&state, &backend, &mut session, &options, &mut socket).await
+                }
+                Ok(()) if session.partial_due() => {
+                    partial(&state, &backend, &mut session, &options, &mut socket).await
+                }
+                Ok(()) => Ok(()),
+                Err(err) => Err(err),
+            },
+            Message::Text(text) => match control_type(&text).as_deref() {
+                Some("commit") => {
+                    finalize(&state, &backend, &mut session, &options, &mut socket).await
+                }
+                Some("close") => break,
+                _ => Err(AppError::invalid_request(
+                    format!("unsupported control message {text:?}; expected commit or close"),
+                    None,
+                    Some("invalid_control_message"),
+                )),
+            },
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => Ok(()),
+        };
+        if let Err(err) = outcome {
+            let mut payload = err.into_json();
+            payload["type"] = json!("error");
+            if socket
+                .send(Message::Text(payload.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    if let Err(err) = session.finish().await {
+        debug!(error = %err, "encoded stream ended without decodable audio");
+    }
+    while !session.window.is_empty() {
+        if finalize(&state, &backend, &mut session, &options, &mut socket)
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+fn control_type(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    value["type"].as_str().map(ToOwned::to_owned)
+}
+
+async fn partial(
+    state: &AppState,
+    backend: &Arc<dyn Transcriber>,
+    session: &mut StreamSession,
+    options: &SessionOptions,
+    socket: &mut WebSocket,
+) -> Result<(), AppError> {
+    let (start, window) = session.partial_window();
+    send_transcript(
+        state,
+        backend,
+        "transcript.partial",
+        start,
+        window,
+        options,
+        socket,
+    )
+    .await
+}
+
+async fn finalize(
+    state: &AppState,
+    backend: &Arc<dyn Transcriber>,
+    session: &mut StreamSession,
+    options: &SessionOptions,
+    socket: &mut WebSocket,
+) -> Result<(), AppError> {
+    let (start, window) = session.take_window();
+    if window.is_empty() {
+        return Ok(());
+    }
+    send_transcript(
+        state,
+        backend,
+        "transcript.final",
+        start,
+        window,
+        options,
+        socket,
+    )
+    .await
+}
+
+async fn send_transcript(
+    state: &AppState,
+    backend: &Arc<dyn Transcriber>,
+    kind: &str,
+    start_secs: f64,
+    window: Vec<f32>,
+    options: &SessionOptions,
+    socket: &mut WebSocket,
+) -> Result<(), AppError> {
+    let end_secs = start_secs + window.len() as f64 / WINDOW_SAMPLE_RATE as f64;
+    let (text, detected_language) = if is_silent(&window) {
+        (String::new(), options.language.clone())
+    } else {
+        let _slot = acquire_inference_slot(state, backend.as_ref()).await?;
+        let inference_started = Instant::now();
+        let result = backend
+            .transcribe(TranscribeRequest {
+                task: options.task,
+                audio_16khz_mono_f32: window,
+                language: options.language.clone(),
+                prompt: None,
+                temperature: None,
+                max_tokens_per_segment: state.cfg.whisper_max_tokens_per_segment,
+                beam_size: state.cfg.whisper_beam_size,
+                best_of: state.cfg.whisper_best_of,
+                language_probs: false,
+                deadline: state
+                    .cfg
+                    .request_timeout
+                    .map(|timeout| Instant::now() + timeout),
+            })
+            .await?;
+        state.metrics.timing(
+            "inference_duration",
+            inference_started.elapsed(),
+            &[("task", "stream")],
+        );
+        (result.text, result.language)
+    };
+
+    let message = json!({
+        "type": kind,
+        "text": text,
+        "start": start_secs,
+        "end": end_secs,
+        "language": detected_language,
+    });
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .map_err(|err| AppError::internal(format!("failed to send stream message: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcm_bytes(samples: usize) -> Vec<u8> {
+        (0..samples)
+            .flat_map(|idx| ((idx % 100) as i16 * 100).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn pcm_chunks_carry_odd_bytes_between_pushes() {
+        let mut session = StreamSession::new(StreamEncoding::PcmS16le {
+            sample_rate: 16_000,
+        });
+        let bytes = pcm_bytes(3);
+        session.push(&bytes[..3]).unwrap();
+        session.push(&bytes[3..]).unwrap();
+        assert_eq!(session.window.len(), 3);
+        assert_eq!(session.window[1], 100.0 / 32_768.0);
+    }
+
+    #[test]
+    fn windows_roll_forward_when_finalized() {
+        let mut session = StreamSession::new(StreamEncoding::PcmS16le {
+            sample_rate: 16_000,
+        });
+        session.push(&pcm_bytes(PARTIAL_INTERVAL_SAMPLES)).unwrap();
+        assert!(session.partial_due());
+        let (start, window) = session.partial_window();
+        assert_eq!((start, window.len()), (0.0, PARTIAL_INTERVAL_SAMPLES));
+        assert!(!session.partial_due());
+
+        session.push(&pcm_bytes(MAX_WINDOW_SAMPLES)).unwrap();
+        assert!(session.window_full());
+        let (start, window) = session.take_window();
+        assert_eq!((start, window.len()), (0.0, MAX_WINDOW_SAMPLES));
+        let (start, window) = session.take_window();
+        assert_eq!((start, window.len()), (30.0, PARTIAL_INTERVAL_SAMPLES));
+    }
+
+    #[test]
+    fn encoding_parser_validates_names_and_rates() {
+        assert_eq!(
+            StreamEncoding::parse("PCM_S16LE", 48_000).unwrap(),
+            StreamEncoding::PcmS16le {
+                sample_rate: 48_000
+            }
+        );
+        assert!(matches!(
+            StreamEncoding::parse("webm", 0).unwrap(),
+            StreamEncoding::Encoded { .. }
+        ));
+        assert!(StreamEncoding::parse("pcm_s16le", 1).is_err());
+        assert_eq!(
+            StreamEncoding::parse("pcm_mulaw", 8_000).unwrap(),
+            StreamEncoding::G711 {
+                law: Law::MuLaw,
+                sample_rate: 8_000
+            }
+        );
+        assert_eq!(
+            StreamEncoding::parse("RTP", 0).unwrap(),
+            StreamEncoding::Rtp {
+                opus_payload_type: DEFAULT_OPUS_PAYLOAD_TYPE
+            }
+        );
+        assert!(StreamEncoding::parse("opus", 16_000).is_err());
+    }
+
+    #[test]
+    fn task_defaults_to_transcription() {
+        assert_eq!(parse_task(None).unwrap(), TaskKind::Transcribe);
+        assert_eq!(parse_task(Some("")).unwrap(), TaskKind::Transcribe);
+        assert_eq!(parse_task(Some("Translate")).unwrap(), TaskKind::Translate);
+        assert!(parse_task(Some("summarize")).is_err());
+    }
+
+    /// A minimal WebM stream with an Opus track and `packets` empty 20 ms packets.
+    fn webm_opus(packets: u8) -> Vec<u8> {
+        let element = |id: &[u8], body: &[u8]| {
+            let mut out = id.to_vec();
+            out.push(0x80 | body.len() as u8);
+            out.extend_from_slice(body);
+            out
+        };
+        let mut track = element(&[0xD7], &[1]);
+        track.extend(element(&[0x83], &[2]));
+        track.extend(element(&[0x86], b"A_OPUS"));
+        let mut bytes = element(&[0x1A, 0x45, 0xDF, 0xA3], b"");
+        bytes.extend([0x18, 0x53, 0x80, 0x67, 0xFF]);
+        bytes.extend(element(
+            &[0x16, 0x54, 0xAE, 0x6B],
+            &element(&[0xAE], &track),
+        ));
+        bytes.extend([0x1F, 0x43, 0xB6, 0x75, 0xFF]);
+        for packet in 0..packets {
+            bytes.extend(element(&[0xA3], &[0x81, 0, packet, 0x80, 0xF8]));
+        }
+        bytes
+    }
+
+    #[tokio::test]
+    async fn encoded_streams_decode_as_bytes_arrive() {
+        let samples = 16_000u32;
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((36 + samples * 2).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend([1, 0, 1, 0]);
+        wav.extend(16_000u32.to_le_bytes());
+        wav.extend(32_000u32.to_le_bytes());
+        wav.extend([2, 0, 16, 0]);
+        wav.extend(b"data");
+        wav.extend((samples * 2).to_le_bytes());
+        wav.extend(pcm_bytes(samples as usize));
+
+        let mut session = StreamSession::new(StreamEncoding::parse("wav", 0).unwrap());
+        for piece in wav.chunks(997) {
+            session.push(piece).expect("push");
+        }
+        session.finish().await.expect("finish");
+        assert_eq!(session.window.len(), samples as usize);
+        assert_eq!(session.window[1], 100.0 / 32_768.0);
+    }
+
+    #[test]
+    fn webm_opus_streams_decode_incrementally() {
+        let mut session = StreamSession::new(StreamEncoding::parse("webm", 0).unwrap());
+        let bytes = webm_opus(3);
+        let mut results = Vec::new();
+        for piece in bytes.chunks(5) {
+            results.push(session.push(piece));
+        }
+        if cfg!(feature = "opus") {
+            assert!(results.iter().all(Result::is_ok));
+            assert!(session.encoded.is_empty());
+            assert_eq!(session.window.len(), 3 * 320);
+        } else {
+            assert!(results.iter().any(Result::is_err));
+            assert!(session.window.is_empty());
+        }
+    }
+}
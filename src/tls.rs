@@ -0,0 +1,119 @@
+//! Optional HTTPS termination, compiled with the `tls` feature.
+//!
+//! With `TLS_CERT_FILE` and `TLS_KEY_FILE` set, every accepted connection
+//! completes a TLS handshake before HTTP is served on it. ALPN offers `h2`
+//! (unless `HTTP2=false`) ahead of `http/1.1`, so HTTP/2 clients multiplex
+//! over TLS without an upgrade round trip. A build without the feature
+//! refuses to start with a certificate configured rather than silently
+//! serving plaintext.
+
+use std::io;
+
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::crypto::ring::default_provider;
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::ServerConfig;
+#[cfg(feature = "tls")]
+pub use tokio_rustls::TlsAcceptor;
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+
+/// Stands in for the acceptor in builds without the `tls` feature, where
+/// [`load_acceptor`] never returns one.
+#[cfg(not(feature = "tls"))]
+#[derive(Clone)]
+pub enum TlsAcceptor {}
+
+/// Loads the configured certificate and key, or returns `None` to serve plaintext.
+#[cfg(feature = "tls")]
+pub fn load_acceptor(cfg: &AppConfig) -> Result<Option<TlsAcceptor>, AppError> {
+    let Some(files) = &cfg.tls else {
+        return Ok(None);
+    };
+    let certs = CertificateDer::pem_file_iter(&files.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| {
+            AppError::internal(format!(
+                "failed to read TLS_CERT_FILE {:?}: {err}",
+                files.cert
+            ))
+        })?;
+    if certs.is_empty() {
+        return Err(AppError::internal(format!(
+            "TLS_CERT_FILE {:?} contains no certificates",
+            files.cert
+        )));
+    }
+    let key = PrivateKeyDer::from_pem_file(&files.key).map_err(|err| {
+        AppError::internal(format!(
+            "failed to read TLS_KEY_FILE {:?}: {err}",
+            files.key
+        ))
+    })?;
+    let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|err| AppError::internal(format!("invalid TLS certificate or key: {err}")))?;
+    config.alpn_protocols = alpn_protocols(cfg.http2);
+    Ok(Some(TlsAcceptor::from(Arc::new(config))))
+}
+
+/// Without the `tls` feature a configured certificate is a startup error.
+#[cfg(not(feature = "tls"))]
+pub fn load_acceptor(cfg: &AppConfig) -> Result<Option<TlsAcceptor>, AppError> {
+    match cfg.tls {
+        None => Ok(None),
+        Some(_) => Err(AppError::internal(
+            "TLS_CERT_FILE requires a server built with the `tls` feature",
+        )),
+    }
+}
+
+/// Completes the server side of a TLS handshake on `io`.
+#[cfg(feature = "tls")]
+pub async fn accept<IO>(
+    acceptor: &TlsAcceptor,
+    io: IO,
+) -> io::Result<tokio_rustls::server::TlsStream<IO>>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    acceptor.accept(io).await
+}
+
+#[cfg(not(feature = "tls"))]
+pub async fn accept<IO>(acceptor: &TlsAcceptor, _io: IO) -> io::Result<IO> {
+    match *acceptor {}
+}
+
+/// ALPN protocol ids offered to clients, most preferred first.
+#[cfg(feature = "tls")]
+fn alpn_protocols(http2: bool) -> Vec<Vec<u8>> {
+    let mut protocols = Vec::new();
+    if http2 {
+        protocols.push(b"h2".to_vec());
+    }
+    protocols.push(b"http/1.1".to_vec());
+    protocols
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpn_prefers_h2_unless_disabled() {
+        assert_eq!(alpn_protocols(true), [b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert_eq!(alpn_protocols(false), [b"http/1.1".to_vec()]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Text-to-speech synthesis backed by a local `piper` executable.
+//!
+//! Piper is invoked once per request with the text on stdin and raw 16-bit
+//! mono PCM read back from stdout; WAV framing is added here.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+
+/// Speech synthesizer that shells out to `piper` with a single configured voice.
+pub struct PiperTts {
+    piper_bin: String,
+    voice_model: PathBuf,
+    voice_config: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoiceConfig {
+    audio: VoiceAudioConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoiceAudioConfig {
+    sample_rate: u32,
+}
+
+/// Synthesized speech as raw little-endian 16-bit mono PCM.
+pub struct SpeechAudio {
+    /// PCM samples encoded as `s16le` bytes.
+    pub pcm_s16le: Vec<u8>,
+    /// Sample rate of `pcm_s16le` as declared by the voice config.
+    pub sample_rate: u32,
+}
+
+impl PiperTts {
+    /// Creates a synthesizer for the voice resolved by the model store.
+    pub fn new(cfg: &AppConfig) -> Self {
+        Self {
+            piper_bin: cfg.tts_piper_bin.clone(),
+            voice_model: PathBuf::from(&cfg.tts_voice_model),
+            voice_config: PathBuf::from(format!("{}.json", cfg.tts_voice_model)),
+        }
+    }
+
+    /// Synthesizes `text` at the given playback `speed` (1.0 is normal rate).
+    pub async fn synthesize(&self, text: &str, speed: f32) -> Result<SpeechAudio, AppError> {
+        let sample_rate = self.voice_sample_rate().await?;
+        let length_scale = 1.0 / speed;
+
+        let mut child = Command::new(&self.piper_bin)
+            .arg("--model")
+            .arg(&self.voice_model)
+            .arg("--config")
+            .arg(&self.voice_config)
+            .arg("--length_scale")
+            .arg(length_scale.to_string())
+            .arg("--output-raw")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| {
+                AppError::backend(format!(
+                    "failed to start piper at {:?}: {err}; set PIPER_BIN to the piper executable",
+                    self.piper_bin
+                ))
+            })?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| AppError::backend("failed to open piper stdin"))?;
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|err| AppError::backend(format!("failed to write text to piper: {err}")))?;
+        drop(stdin);
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|err| AppError::backend(format!("piper process failed: {err}")))?;
+        if !output.status.success() {
+            return Err(AppError::backend(format!(
+                "piper exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        if output.stdout.is_empty() {
+            return Err(AppError::backend("piper produced no audio"));
+        }
+
+        Ok(SpeechAudio {
+            pcm_s16le: output.stdout,
+            sample_rate,
+        })
+    }
+
+    async fn voice_sample_rate(&self) -> Result<u32, AppError> {
+        let raw = tokio::fs::read(&self.voice_config).await.map_err(|err| {
+            AppError::internal(format!(
+                "failed to read piper voice config {:?}: {err}",
+                self.voice_config
+            ))
+        })?;
+        let config: VoiceConfig = serde_json::from_slice(&raw).map_err(|err| {
+            AppError::internal(format!(
+                "invalid piper voice config {:?}: {err}",
+                self.voice_config
+            ))
+        })?;
+        Ok(config.audio.sample_rate)
+    }
+}
+
+/// Wraps raw `s16le` mono PCM in a minimal RIFF/WAVE container.
+pub fn pcm16_to_wav(pcm_s16le: &[u8], sample_rate: u32) -> Vec<u8> {
+    let data_len = pcm_s16le.len() as u32;
+    let mut out = Vec::with_capacity(44 + pcm_s16le.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    out.extend_from_slice(pcm_s16le);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_header_describes_pcm_payload() {
+        let wav = pcm16_to_wav(&[0, 0, 1, 0], 22_050);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 40);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 22_050);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 4);
+        assert_eq!(wav.len(), 48);
+    }
+}
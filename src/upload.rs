@@ -0,0 +1,271 @@
+//! Uploaded audio bodies, held in memory or spooled to disk.
+//!
+//! Uploads are capped at `MAX_UPLOAD_BYTES` while they stream in, so an
+//! oversized file is rejected without being buffered. Uploads larger than
+//! `UPLOAD_SPILL_THRESHOLD_BYTES` are written to a temporary file (in
+//! `UPLOAD_SPILL_DIR`, or the system temp directory) as they arrive, and the
+//! decoder reads them back from disk, so large files do not need their full
+//! size in RAM on top of the decoded samples. Temporary files are deleted
+//! when the upload is dropped. With storage encryption configured, spooled
+//! uploads are sealed as they are written and decrypted a chunk at a time
+//! while they are decoded, so their plaintext is never held in memory whole.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use axum::extract::multipart::{Field, MultipartError};
+use axum::http::StatusCode;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
+
+use crate::audio::{
+    decode_bytes, decode_file, decode_reader, DecodedAudio, Downmix, ResampleQuality, SharedBytes,
+};
+use crate::config::AppConfig;
+use crate::encryption::{SealedReader, Sealer, StorageCipher};
+use crate::error::AppError;
+use crate::ffmpeg::{transcode_to_mono_16khz_f32, FfmpegInput};
+use crate::sniff::HEAD_BYTES;
+
+/// Body of one uploaded file.
+pub enum UploadBody {
+    /// Upload held in memory.
+    Memory(Vec<u8>),
+    /// Upload spooled to a temporary file that is removed on drop.
+    Spooled {
+        file: NamedTempFile,
+        len: usize,
+        /// Key the file is sealed with, when storage encryption is configured.
+        cipher: Option<StorageCipher>,
+    },
+    /// Upload persisted by the async job spool, which owns and removes the file.
+    Stored {
+        path: PathBuf,
+        len: usize,
+        /// Key the file is sealed with, when storage encryption is configured.
+        cipher: Option<StorageCipher>,
+    },
+}
+
+impl UploadBody {
+    /// Size of the upload in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Memory(bytes) => bytes.len(),
+            Self::Spooled { len, .. } | Self::Stored { len, .. } => *len,
+        }
+    }
+
+    /// Returns `true` when the upload has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of the upload held in memory until decoding finishes.
+    pub fn resident_bytes(&self) -> usize {
+        match self {
+            Self::Memory(bytes) => bytes.len(),
+            Self::Spooled { .. } | Self::Stored { .. } => 0,
+        }
+    }
+
+    /// Decodes the upload to 16 kHz mono samples; blocks, so call off the async runtime.
+    ///
+    /// In-memory uploads are decoded in place rather than copied, and uploads
+    /// on disk are read incrementally, sealed ones decrypted chunk by chunk.
+    /// With `ffmpeg_bin` set, media the built-in decoders reject
+    /// is transcoded by ffmpeg from the same bytes or file instead, which
+    /// resamples with its own filter and ignores `quality`. With `max_samples`
+    /// set, only that much audio from the start is decoded.
+    pub fn decode(
+        mut self,
+        extension_hint: &str,
+        ffmpeg_bin: Option<&str>,
+        quality: ResampleQuality,
+        downmix: Downmix,
+        max_samples: Option<usize>,
+    ) -> Result<DecodedAudio, AppError> {
+        let memory = match &mut self {
+            Self::Memory(bytes) => std::mem::take(bytes),
+            Self::Spooled { .. } | Self::Stored { .. } => Vec::new(),
+        };
+        let source = match self.stored_file() {
+            None => Source::Bytes(memory.into()),
+            Some((path, Some(cipher))) => Source::Sealed(path, cipher),
+            Some((path, None)) => Source::File(path),
+        };
+
+        let decoded = match &source {
+            Source::Bytes(bytes) => {
+                decode_bytes(bytes.clone(), extension_hint, quality, downmix, max_samples)
+            }
+            Source::File(path) => decode_file(
+                open_stored(path)?,
+                extension_hint,
+                quality,
+                downmix,
+                max_samples,
+            ),
+            Source::Sealed(path, cipher) => {
+                let reader = open_sealed(path, cipher)?;
+                let len = reader.len();
+                decode_reader(reader, len, extension_hint, quality, downmix, max_samples)
+            }
+        };
+        let err = match decoded {
+            Err(err @ AppError::UnsupportedMediaType(_)) => err,
+            decoded => return decoded,
+        };
+        let Some(ffmpeg_bin) = ffmpeg_bin else {
+            return Err(err);
+        };
+        debug!(error = %err, "built-in decoders failed; falling back to ffmpeg");
+        let input = match &source {
+            Source::Bytes(bytes) => FfmpegInput::Pipe(Box::new(bytes.as_ref())),
+            Source::File(path) => FfmpegInput::File(path),
+            Source::Sealed(path, cipher) => FfmpegInput::Pipe(Box::new(open_sealed(path, cipher)?)),
+        };
+        let samples = transcode_to_mono_16khz_f32(ffmpeg_bin, input, downmix, max_samples)?;
+        Ok(DecodedAudio {
+            samples,
+            resample_elapsed: None,
+        })
+    }
+
+    /// Path of an upload on disk and the key it is sealed with.
+    fn stored_file(&self) -> Option<(&Path, Option<&StorageCipher>)> {
+        match self {
+            Self::Memory(_) => None,
+            Self::Spooled { file, cipher, .. } => Some((file.path(), cipher.as_ref())),
+            Self::Stored { path, cipher, .. } => Some((path, cipher.as_ref())),
+        }
+    }
+}
+
+/// Where an upload is decoded from.
+enum Source<'a> {
+    /// Plaintext bytes, shared between the built-in decoders and ffmpeg.
+    Bytes(SharedBytes),
+    /// An unsealed file on disk.
+    File(&'a Path),
+    /// A file on disk sealed with the storage key.
+    Sealed(&'a Path, &'a StorageCipher),
+}
+
+fn open_stored(path: &Path) -> Result<File, AppError> {
+    File::open(path)
+        .map_err(|err| AppError::internal(format!("failed to open stored upload {path:?}: {err}")))
+}
+
+/// Opens a sealed upload file for decrypting reads.
+fn open_sealed(path: &Path, cipher: &StorageCipher) -> Result<SealedReader<File>, AppError> {
+    cipher
+        .reader(open_stored(path)?)
+        .map_err(|err| AppError::internal(format!("failed to open sealed upload: {err}")))
+}
+
+/// Reads a multipart file field, spooling it to disk once it exceeds
+/// `UPLOAD_SPILL_THRESHOLD_BYTES`.
+///
+/// Returns the body with its first [`HEAD_BYTES`] bytes, kept in plaintext
+/// for type detection even when the rest is sealed on disk. `already_read`
+/// is the size of earlier files in the same request; the read stops with a
+/// `413` as soon as the total passes `MAX_UPLOAD_BYTES`.
+pub async fn read_upload(
+    mut field: Field<'_>,
+    cfg: &AppConfig,
+    already_read: usize,
+) -> Result<(UploadBody, Vec<u8>), AppError> {
+    let spill_dir = cfg.upload_spill_dir.as_deref().map(Path::new);
+    let mut head = Vec::with_capacity(HEAD_BYTES);
+    let mut buffer = Vec::new();
+    let cipher = cfg.storage_cipher.as_ref();
+    let mut spool: Option<(NamedTempFile, tokio::fs::File, Option<Sealer>)> = None;
+    let mut len = 0usize;
+
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|err| multipart_error("failed to read file bytes", err, cfg))?
+    {
+        len += chunk.len();
+        if already_read + len > cfg.max_upload_bytes {
+            return Err(upload_too_large(cfg.max_upload_bytes));
+        }
+        let missing = HEAD_BYTES - head.len();
+        head.extend_from_slice(&chunk[..missing.min(chunk.len())]);
+        if let Some((_, writer, sealer)) = spool.as_mut() {
+            match sealer {
+                Some(sealer) => {
+                    let sealed = sealer.push(&chunk).map_err(spool_error)?;
+                    writer.write_all(&sealed).await.map_err(spool_error)?;
+                }
+                None => writer.write_all(&chunk).await.map_err(spool_error)?,
+            }
+            continue;
+        }
+        buffer.extend_from_slice(&chunk);
+        if cfg
+            .upload_spill_threshold_bytes
+            .is_some_and(|threshold| len > threshold)
+        {
+            let file = match spill_dir {
+                Some(dir) => NamedTempFile::new_in(dir),
+                None => NamedTempFile::new(),
+            }
+            .map_err(spool_error)?;
+            let mut writer =
+                tokio::fs::File::from_std(file.as_file().try_clone().map_err(spool_error)?);
+            let sealer = match cipher {
+                Some(cipher) => {
+                    let (mut sealer, header) = cipher.sealer();
+                    writer.write_all(&header).await.map_err(spool_error)?;
+                    buffer = sealer.push(&buffer).map_err(spool_error)?;
+                    Some(sealer)
+                }
+                None => None,
+            };
+            writer.write_all(&buffer).await.map_err(spool_error)?;
+            buffer = Vec::new();
+            spool = Some((file, writer, sealer));
+        }
+    }
+
+    let body = match spool {
+        Some((file, mut writer, sealer)) => {
+            if let Some(sealer) = sealer {
+                let sealed = sealer.finish().map_err(spool_error)?;
+                writer.write_all(&sealed).await.map_err(spool_error)?;
+            }
+            writer.flush().await.map_err(spool_error)?;
+            UploadBody::Spooled {
+                file,
+                len,
+                cipher: cipher.cloned(),
+            }
+        }
+        None => UploadBody::Memory(buffer),
+    };
+    Ok((body, head))
+}
+
+/// `413` error for uploads over `MAX_UPLOAD_BYTES`.
+pub fn upload_too_large(max_upload_bytes: usize) -> AppError {
+    AppError::payload_too_large(
+        format!("uploaded audio exceeds the maximum upload size of {max_upload_bytes} bytes"),
+        Some("file"),
+    )
+}
+
+/// Maps a multipart read error, reporting the request body limit as `413`.
+pub fn multipart_error(context: &str, err: MultipartError, cfg: &AppConfig) -> AppError {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return upload_too_large(cfg.max_upload_bytes);
+    }
+    AppError::bad_multipart(format!("{context}: {err}"))
+}
+
+fn spool_error(err: std::io::Error) -> AppError {
+    AppError::internal(format!("failed to spool upload to disk: {err}"))
+}
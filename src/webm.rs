@@ -0,0 +1,386 @@
+//! Incremental WebM demuxing for audio streamed by browsers.
+//!
+//! `MediaRecorder` emits WebM with an unknown-size Segment and Clusters and
+//! hands it over in chunks cut at arbitrary byte positions, often in the
+//! middle of a block or a cluster header. [`WebmDemuxer`] accepts the bytes as
+//! they arrive and returns the frames of the first audio track from every
+//! block that is complete, buffering the rest until the next push. Clusters
+//! are descended into rather than read whole, so their (unknown) sizes do not
+//! matter, and elements the demuxer has no use for are skipped as they
+//! stream past.
+
+use crate::error::AppError;
+
+/// EBML header.
+const ID_EBML: u32 = 0x1A45_DFA3;
+const ID_SEGMENT: u32 = 0x1853_8067;
+const ID_CLUSTER: u32 = 0x1F43_B675;
+const ID_BLOCK_GROUP: u32 = 0xA0;
+const ID_BLOCK: u32 = 0xA1;
+const ID_SIMPLE_BLOCK: u32 = 0xA3;
+const ID_TRACKS: u32 = 0x1654_AE6B;
+const ID_TRACK_ENTRY: u32 = 0xAE;
+const ID_TRACK_NUMBER: u32 = 0xD7;
+const ID_TRACK_TYPE: u32 = 0x83;
+const ID_CODEC_ID: u32 = 0x86;
+const ID_CODEC_PRIVATE: u32 = 0x63A2;
+/// `TrackType` of audio tracks.
+const TRACK_TYPE_AUDIO: u64 = 2;
+/// Largest element buffered whole (track headers and blocks).
+const MAX_ELEMENT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// The audio track frames are taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTrack {
+    pub number: u64,
+    /// Matroska codec id, such as `A_OPUS` or `A_VORBIS`.
+    pub codec_id: String,
+    /// Codec setup data; the `OpusHead` for Opus.
+    pub codec_private: Vec<u8>,
+}
+
+/// Pulls audio frames out of a WebM byte stream as it arrives.
+#[derive(Debug, Default)]
+pub struct WebmDemuxer {
+    /// Bytes not yet parsed.
+    pending: Vec<u8>,
+    /// Bytes of a skipped element that have not arrived yet.
+    skip: u64,
+    track: Option<AudioTrack>,
+}
+
+impl WebmDemuxer {
+    /// The audio track, once the track headers have arrived.
+    pub fn track(&self) -> Option<&AudioTrack> {
+        self.track.as_ref()
+    }
+
+    /// Appends `bytes` and returns the audio frames of every block completed by them.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, AppError> {
+        let skipped = self.skip.min(bytes.len() as u64) as usize;
+        self.skip -= skipped as u64;
+        self.pending.extend_from_slice(&bytes[skipped..]);
+
+        let mut frames = Vec::new();
+        let mut pos = 0;
+        while self.skip == 0 {
+            let Some((id, size, header_len)) = element_header(&self.pending[pos..])? else {
+                break;
+            };
+            let body = pos + header_len;
+            match (id, size) {
+                (ID_SEGMENT | ID_CLUSTER | ID_BLOCK_GROUP, _) => pos = body,
+                (_, None) => {
+                    return Err(invalid(format!(
+                        "unsupported unknown-size WebM element {id:#x}"
+                    )))
+                }
+                (ID_EBML | ID_TRACKS | ID_SIMPLE_BLOCK | ID_BLOCK, Some(size)) => {
+                    if size > MAX_ELEMENT_BYTES {
+                        return Err(invalid(format!("WebM element {id:#x} is too large")));
+                    }
+                    let end = body + size as usize;
+                    if self.pending.len() < end {
+                        break;
+                    }
+                    let data = &self.pending[body..end];
+                    match id {
+                        ID_TRACKS if self.track.is_none() => self.track = Some(audio_track(data)?),
+                        ID_SIMPLE_BLOCK | ID_BLOCK => {
+                            if let Some(track) = &self.track {
+                                block_frames(data, track.number, &mut frames)?;
+                            }
+                        }
+                        _ => {}
+                    }
+                    pos = end;
+                }
+                (_, Some(size)) => {
+                    let available = (self.pending.len() - body) as u64;
+                    pos = body + size.min(available) as usize;
+                    self.skip = size.saturating_sub(available);
+                }
+            }
+        }
+        self.pending.drain(..pos);
+        Ok(frames)
+    }
+}
+
+/// Reads an element id (with its length marker) and data size from the start
+/// of `data`; `None` when the header is incomplete. The size is `None` when
+/// unknown.
+fn element_header(data: &[u8]) -> Result<Option<(u32, Option<u64>, usize)>, AppError> {
+    let Some(&first) = data.first() else {
+        return Ok(None);
+    };
+    let id_len = first.leading_zeros() as usize + 1;
+    if id_len > 4 {
+        return Err(invalid("invalid WebM element id".to_string()));
+    }
+    if data.len() < id_len {
+        return Ok(None);
+    }
+    let id = data[..id_len]
+        .iter()
+        .fold(0u32, |id, byte| (id << 8) | u32::from(*byte));
+    let Some((size, size_len)) = read_vint(&data[id_len..])? else {
+        return Ok(None);
+    };
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    Ok(Some((id, (!unknown).then_some(size), id_len + size_len)))
+}
+
+/// Reads a variable-length integer without its length marker; `None` when incomplete.
+fn read_vint(data: &[u8]) -> Result<Option<(u64, usize)>, AppError> {
+    let Some(&first) = data.first() else {
+        return Ok(None);
+    };
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return Err(invalid("invalid WebM variable-length integer".to_string()));
+    }
+    if data.len() < len {
+        return Ok(None);
+    }
+    let marker_mask = if len == 8 { 0 } else { 0xFF >> len };
+    let value = data[1..len]
+        .iter()
+        .fold(u64::from(first & marker_mask), |value, byte| {
+            (value << 8) | u64::from(*byte)
+        });
+    Ok(Some((value, len)))
+}
+
+/// Like [`read_vint`], but fails on truncated input inside a complete element.
+fn vint(data: &[u8]) -> Result<(u64, usize), AppError> {
+    read_vint(data)?.ok_or_else(|| invalid("truncated WebM element".to_string()))
+}
+
+/// Finds the first audio track in the body of a `Tracks` element.
+fn audio_track(mut data: &[u8]) -> Result<AudioTrack, AppError> {
+    while let Some((id, mut entry, rest)) = child(data)? {
+        data = rest;
+        if id != ID_TRACK_ENTRY {
+            continue;
+        }
+        let (mut number, mut kind, mut codec_id, mut codec_private) = (0, 0, None, Vec::new());
+        while let Some((id, value, rest)) = child(entry)? {
+            entry = rest;
+            match id {
+                ID_TRACK_NUMBER => number = uint(value),
+                ID_TRACK_TYPE => kind = uint(value),
+                ID_CODEC_ID => codec_id = Some(String::from_utf8_lossy(value).into_owned()),
+                ID_CODEC_PRIVATE => codec_private = value.to_vec(),
+                _ => {}
+            }
+        }
+        if let (TRACK_TYPE_AUDIO, Some(codec_id)) = (kind, codec_id) {
+            return Ok(AudioTrack {
+                number,
+                codec_id: codec_id.trim_end_matches('\0').to_string(),
+                codec_private,
+            });
+        }
+    }
+    Err(invalid("WebM stream has no audio track".to_string()))
+}
+
+/// A child element's id, body, and the bytes after it.
+type Child<'a> = (u32, &'a [u8], &'a [u8]);
+
+/// Splits the child element at the start of a complete master element body
+/// into its id, its body, and the bytes after it.
+fn child(data: &[u8]) -> Result<Option<Child<'_>>, AppError> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    let truncated = || invalid("truncated WebM element".to_string());
+    let (id, size, header_len) = element_header(data)?.ok_or_else(truncated)?;
+    let end = size
+        .and_then(|size| header_len.checked_add(usize::try_from(size).ok()?))
+        .filter(|end| *end <= data.len())
+        .ok_or_else(truncated)?;
+    Ok(Some((id, &data[header_len..end], &data[end..])))
+}
+
+/// Big-endian unsigned integer element value.
+fn uint(value: &[u8]) -> u64 {
+    value
+        .iter()
+        .take(8)
+        .fold(0, |acc, byte| (acc << 8) | u64::from(*byte))
+}
+
+/// Appends the frames of a `SimpleBlock` or `Block` on `track` to `frames`.
+fn block_frames(data: &[u8], track: u64, frames: &mut Vec<Vec<u8>>) -> Result<(), AppError> {
+    let (number, len) = vint(data)?;
+    // Timecode (2 bytes) and flags follow the track number.
+    let Some(&flags) = data.get(len + 2) else {
+        return Err(invalid("truncated WebM block".to_string()));
+    };
+    if number != track {
+        return Ok(());
+    }
+    let mut rest = &data[len + 3..];
+    let lacing = (flags >> 1) & 0b11;
+    if lacing == 0 {
+        frames.push(rest.to_vec());
+        return Ok(());
+    }
+    let (&count, tail) = rest
+        .split_first()
+        .ok_or_else(|| invalid("truncated WebM block".to_string()))?;
+    rest = tail;
+    let count = usize::from(count) + 1;
+    let mut sizes = Vec::with_capacity(count);
+    match lacing {
+        // Xiph lacing: each size is a run of 255s plus a final byte.
+        0b01 => {
+            for _ in 1..count {
+                let mut size = 0usize;
+                loop {
+                    let (&byte, tail) = rest
+                        .split_first()
+                        .ok_or_else(|| invalid("truncated WebM block".to_string()))?;
+                    rest = tail;
+                    size += usize::from(byte);
+                    if byte != 255 {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+        // EBML lacing: the first size, then signed differences.
+        0b11 => {
+            let (first, len) = vint(rest)?;
+            rest = &rest[len..];
+            let mut size = first as i64;
+            sizes.push(first as usize);
+            for _ in 2..count {
+                let (raw, len) = vint(rest)?;
+                rest = &rest[len..];
+                size += raw as i64 - ((1i64 << (7 * len - 1)) - 1);
+                if size < 0 {
+                    return Err(invalid("invalid WebM lace size".to_string()));
+                }
+                sizes.push(size as usize);
+            }
+        }
+        // Fixed-size lacing.
+        _ => sizes.extend(std::iter::repeat(rest.len() / count).take(count - 1)),
+    }
+    for size in sizes {
+        if size > rest.len() {
+            return Err(invalid("invalid WebM lace size".to_string()));
+        }
+        let (frame, tail) = rest.split_at(size);
+        frames.push(frame.to_vec());
+        rest = tail;
+    }
+    frames.push(rest.to_vec());
+    Ok(())
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::unsupported_media_type(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes an element with a one-byte size, or an unknown size for `None`.
+    fn element(id: &[u8], body: Option<&[u8]>) -> Vec<u8> {
+        let mut out = id.to_vec();
+        match body {
+            Some(body) => {
+                out.push(0x80 | body.len() as u8);
+                out.extend_from_slice(body);
+            }
+            None => out.push(0xFF),
+        }
+        out
+    }
+
+    fn stream() -> Vec<u8> {
+        let entry = |number: u8, kind: u8, codec: &[u8], private: &[u8]| {
+            let mut body = element(&[0xD7], Some(&[number]));
+            body.extend(element(&[0x83], Some(&[kind])));
+            body.extend(element(&[0x86], Some(codec)));
+            body.extend(element(&[0x63, 0xA2], Some(private)));
+            element(&[0xAE], Some(&body))
+        };
+        let mut tracks = entry(1, 1, b"V_VP8", b"");
+        tracks.extend(entry(2, 2, b"A_OPUS", b"OpusHead"));
+
+        let mut out = element(&[0x1A, 0x45, 0xDF, 0xA3], Some(b"webm"));
+        out.extend(element(&[0x18, 0x53, 0x80, 0x67], None));
+        out.extend(element(&[0x15, 0x49, 0xA9, 0x66], Some(&[0; 20])));
+        out.extend(element(&[0x16, 0x54, 0xAE, 0x6B], Some(&tracks)));
+        for cluster in 0..2u8 {
+            out.extend(element(&[0x1F, 0x43, 0xB6, 0x75], None));
+            out.extend(element(&[0xE7], Some(&[cluster])));
+            out.extend(element(&[0xA3], Some(&[0x81, 0, 0, 0x80, cluster, 1])));
+            out.extend(element(&[0xA3], Some(&[0x82, 0, 0, 0x80, cluster, 2])));
+            // Two frames with Xiph lacing.
+            let block = element(&[0xA1], Some(&[0x82, 0, 0, 0x02, 1, 2, cluster, 3, 4, 5]));
+            out.extend(element(&[0xA0], Some(&block)));
+        }
+        out
+    }
+
+    #[test]
+    fn demuxes_audio_frames_across_arbitrary_chunk_boundaries() {
+        let bytes = stream();
+        for chunk in [1, 3, 7, bytes.len()] {
+            let mut demuxer = WebmDemuxer::default();
+            let mut frames = Vec::new();
+            for piece in bytes.chunks(chunk) {
+                frames.extend(demuxer.push(piece).expect("push"));
+            }
+            assert_eq!(
+                frames,
+                [
+                    vec![0, 2],
+                    vec![0, 3],
+                    vec![4, 5],
+                    vec![1, 2],
+                    vec![1, 3],
+                    vec![4, 5]
+                ],
+                "chunk size {chunk}"
+            );
+            let track = demuxer.track().expect("track");
+            assert_eq!((track.number, track.codec_id.as_str()), (2, "A_OPUS"));
+            assert_eq!(track.codec_private, b"OpusHead");
+        }
+    }
+
+    #[test]
+    fn lacing_modes_split_frames() {
+        let mut frames = Vec::new();
+        // Fixed-size lacing: three frames of two bytes.
+        block_frames(&[0x81, 0, 0, 0x04, 2, 1, 1, 2, 2, 3, 3], 1, &mut frames).expect("fixed");
+        // EBML lacing: sizes 2, then 2 + (-1) = 1, then the rest.
+        block_frames(
+            &[0x81, 0, 0, 0x06, 2, 0x82, 0xBE, 7, 7, 8, 9, 9],
+            1,
+            &mut frames,
+        )
+        .expect("ebml");
+        assert_eq!(
+            frames,
+            [
+                vec![1, 1],
+                vec![2, 2],
+                vec![3, 3],
+                vec![7, 7],
+                vec![8],
+                vec![9, 9]
+            ]
+        );
+        assert!(block_frames(&[0x81, 0, 0, 0x02, 1, 9], 1, &mut frames).is_err());
+    }
+}